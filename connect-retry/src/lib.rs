@@ -0,0 +1,154 @@
+//! Bounded retry for connections made at service startup.
+//!
+//! Under docker-compose a service often starts before Postgres accepts
+//! connections.  Instead of exiting on the first refused connect, services
+//! wrap it in [`retry`], which tries up to `DB_CONNECT_ATTEMPTS` times with
+//! exponential backoff, logging each failure, and returns the last error
+//! once the attempts are used up.
+//!
+//! | Env var                 | Default |
+//! |-------------------------|---------|
+//! | `DB_CONNECT_ATTEMPTS`   | `10`    |
+//! | `DB_CONNECT_BACKOFF_MS` | `500`   |
+
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+use tracing::warn;
+
+/// Longest wait between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// How often and how patiently to try.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    /// Attempts in total, including the first; at least 1.
+    pub attempts: u32,
+    /// Delay before the second attempt; doubled for each further one.
+    pub base_delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            attempts: 10,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Backoff {
+    /// Build from `DB_CONNECT_ATTEMPTS` and `DB_CONNECT_BACKOFF_MS`; unset or
+    /// invalid values (and 0 attempts) keep the defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Self {
+            attempts: var("DB_CONNECT_ATTEMPTS")
+                .filter(|&n| n > 0)
+                .map_or(default.attempts, |n| n.min(u32::MAX as u64) as u32),
+            base_delay: var("DB_CONNECT_BACKOFF_MS").map_or(default.base_delay, Duration::from_millis),
+        }
+    }
+
+    /// Wait after failed attempt number `attempt` (1-based), capped at
+    /// [`MAX_DELAY`].
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_DELAY)
+    }
+}
+
+/// Run `connect` until it succeeds or `backoff.attempts` have failed, in
+/// which case the last error is returned.  `what` names the target in logs.
+pub async fn retry<T, E, F, Fut>(what: &str, backoff: &Backoff, mut connect: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match connect().await {
+            Err(e) if attempt < backoff.attempts => {
+                let delay = backoff.delay(attempt);
+                warn!(
+                    error = %e,
+                    attempt,
+                    attempts = backoff.attempts,
+                    delay_ms = delay.as_millis() as u64,
+                    "connecting to {what} failed; retrying"
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn fast(attempts: u32) -> Backoff {
+        Backoff {
+            attempts,
+            base_delay: Duration::from_millis(1),
+        }
+    }
+
+    /// A connect that fails `failures` times, then succeeds.
+    fn flaky(failures: u32, calls: &AtomicU32) -> impl FnMut() -> std::future::Ready<Result<&'static str, String>> + '_ {
+        move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            std::future::ready(if call > failures {
+                Ok("connected")
+            } else {
+                Err(format!("refused #{call}"))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn succeeds_after_transient_failures() {
+        let calls = AtomicU32::new(0);
+        let result = retry("postgres", &fast(5), flaky(3, &calls)).await;
+        assert_eq!(result, Ok("connected"));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn gives_up_with_the_last_error_after_the_cap() {
+        let calls = AtomicU32::new(0);
+        let result = retry("postgres", &fast(3), flaky(10, &calls)).await;
+        assert_eq!(result, Err("refused #3".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn single_attempt_does_not_retry() {
+        let calls = AtomicU32::new(0);
+        assert!(retry("postgres", &fast(1), flaky(1, &calls)).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let backoff = Backoff {
+            attempts: 20,
+            base_delay: Duration::from_millis(500),
+        };
+        assert_eq!(backoff.delay(1), Duration::from_millis(500));
+        assert_eq!(backoff.delay(3), Duration::from_secs(2));
+        assert_eq!(backoff.delay(10), MAX_DELAY);
+    }
+}
@@ -0,0 +1,147 @@
+//! Static bearer-token authentication for the HTTP API.
+//!
+//! When `COORDINATOR_API_TOKEN` is set every route except `/health` requires
+//! `Authorization: Bearer <token>`; otherwise auth is disabled.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Routes served without a token (liveness probes).
+const PUBLIC_PATHS: &[&str] = &["/health"];
+
+/// The expected API token, if auth is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct ApiAuth {
+    token: Option<Arc<str>>,
+}
+
+impl ApiAuth {
+    /// Require `token`; `None` or an empty token disables auth.
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            token: token.filter(|t| !t.is_empty()).map(Arc::from),
+        }
+    }
+
+    /// Build from `COORDINATOR_API_TOKEN`.  Unset means no auth.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("COORDINATOR_API_TOKEN").ok())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    fn accepts(&self, headers: &HeaderMap) -> bool {
+        let Some(expected) = &self.token else {
+            return true;
+        };
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|given| constant_time_eq(given.trim().as_bytes(), expected.as_bytes()))
+    }
+}
+
+/// Middleware rejecting requests without the configured bearer token.
+pub async fn require_token(State(auth): State<ApiAuth>, req: Request, next: Next) -> Response {
+    if PUBLIC_PATHS.contains(&req.uri().path()) || auth.accepts(req.headers()) {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({ "error": "missing or invalid bearer token" })),
+    )
+        .into_response()
+}
+
+/// Compare without short-circuiting on the first differing byte, so response
+/// timing does not reveal how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(auth: ApiAuth) -> Router {
+        Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/data/structured/:table", get(|| async { "rows" }))
+            .layer(middleware::from_fn_with_state(auth, require_token))
+    }
+
+    async fn status(app: Router, path: &str, authorization: Option<&str>) -> StatusCode {
+        let mut req = Request::builder().uri(path);
+        if let Some(value) = authorization {
+            req = req.header(header::AUTHORIZATION, value);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_rejected() {
+        let app = app(ApiAuth::new(Some("s3cret".into())));
+        assert_eq!(
+            status(app, "/data/structured/t", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_token_is_rejected() {
+        let app = app(ApiAuth::new(Some("s3cret".into())));
+        for value in ["Bearer nope", "Bearer s3cre", "Basic s3cret", "s3cret"] {
+            assert_eq!(
+                status(app.clone(), "/data/structured/t", Some(value)).await,
+                StatusCode::UNAUTHORIZED,
+                "{value}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn correct_token_is_accepted() {
+        let app = app(ApiAuth::new(Some("s3cret".into())));
+        assert_eq!(
+            status(app, "/data/structured/t", Some("Bearer s3cret")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn health_is_public_and_unset_token_disables_auth() {
+        let app_with_auth = app(ApiAuth::new(Some("s3cret".into())));
+        assert_eq!(status(app_with_auth, "/health", None).await, StatusCode::OK);
+
+        assert!(!ApiAuth::new(Some(String::new())).is_enabled());
+        let open = app(ApiAuth::new(None));
+        assert_eq!(
+            status(open, "/data/structured/t", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn constant_time_eq_matches_only_equal_bytes() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Tuned gRPC channels to the backend services.
+//!
+//! Channels stay `connect_lazy`, but with HTTP/2 keepalive pings (also
+//! while idle) and TCP keepalive, so NATs and load balancers do not silently
+//! drop idle connections and the first call after a quiet period does not
+//! fail; a connect timeout bounds how long a dead backend can stall a call.
+//!
+//! | Env var                          | Default | Meaning                                  |
+//! |----------------------------------|---------|------------------------------------------|
+//! | `BACKEND_CONNECT_TIMEOUT_MS`     | `3000`  | TCP connect timeout                      |
+//! | `BACKEND_KEEPALIVE_SECS`         | `30`    | HTTP/2 ping and TCP keepalive interval; `0` disables both |
+//! | `BACKEND_KEEPALIVE_TIMEOUT_SECS` | `10`    | Ping ack wait before the connection is dropped |
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tonic::transport::{Channel, Endpoint};
+
+/// Connection settings shared by every backend channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelConfig {
+    pub connect_timeout: Duration,
+    /// `None` disables HTTP/2 and TCP keepalive.
+    pub keepalive: Option<Duration>,
+    pub keepalive_timeout: Duration,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_millis(3000),
+            keepalive: Some(Duration::from_secs(30)),
+            keepalive_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ChannelConfig {
+    /// Build from `BACKEND_CONNECT_TIMEOUT_MS`, `BACKEND_KEEPALIVE_SECS` and
+    /// `BACKEND_KEEPALIVE_TIMEOUT_SECS`; unset or invalid values keep the
+    /// defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Self {
+            connect_timeout: var("BACKEND_CONNECT_TIMEOUT_MS")
+                .filter(|&ms| ms > 0)
+                .map_or(default.connect_timeout, Duration::from_millis),
+            keepalive: var("BACKEND_KEEPALIVE_SECS").map_or(default.keepalive, |secs| {
+                (secs > 0).then(|| Duration::from_secs(secs))
+            }),
+            keepalive_timeout: var("BACKEND_KEEPALIVE_TIMEOUT_SECS")
+                .filter(|&secs| secs > 0)
+                .map_or(default.keepalive_timeout, Duration::from_secs),
+        }
+    }
+
+    /// The endpoint for `addr` with these settings applied.
+    pub fn endpoint(&self, addr: String) -> Result<Endpoint> {
+        let endpoint = Channel::from_shared(addr)?
+            .connect_timeout(self.connect_timeout)
+            .tcp_keepalive(self.keepalive);
+        Ok(match self.keepalive {
+            Some(interval) => endpoint
+                .http2_keep_alive_interval(interval)
+                .keep_alive_timeout(self.keepalive_timeout)
+                .keep_alive_while_idle(true),
+            None => endpoint,
+        })
+    }
+
+    /// A lazily connecting channel to `addr`.
+    pub fn connect_lazy(&self, addr: String) -> Result<Channel> {
+        Ok(self.endpoint(addr)?.connect_lazy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn endpoint_keeps_the_address_and_rejects_bad_ones() {
+        let config = ChannelConfig::default();
+        let endpoint = config.endpoint("http://[::1]:50051".to_string()).unwrap();
+        assert_eq!(endpoint.uri().to_string(), "http://[::1]:50051/");
+        assert!(config.endpoint("not a uri".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn connect_timeout_is_applied() {
+        let config = ChannelConfig {
+            connect_timeout: Duration::from_millis(200),
+            ..ChannelConfig::default()
+        };
+        // A listener whose accept queue is full: further SYNs are dropped,
+        // so without the timeout the connect would hang for the OS default
+        // (minutes).  Unlike a non-routable address this doesn't depend on
+        // how the network treats it.
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = socket.listen(0).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut queued = Vec::new();
+        for _ in 0..8 {
+            match tokio::time::timeout(Duration::from_millis(100), tokio::net::TcpStream::connect(addr)).await {
+                Ok(stream) => queued.push(stream.unwrap()),
+                Err(_) => break,
+            }
+        }
+        let endpoint = config.endpoint(format!("http://{addr}")).unwrap();
+        let started = Instant::now();
+        assert!(endpoint.connect().await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+    }
+}
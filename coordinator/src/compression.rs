@@ -0,0 +1,100 @@
+//! Response compression for the HTTP API.
+//!
+//! Bodies are gzip/deflate/br encoded when the client's `Accept-Encoding`
+//! allows it.  tower-http's default predicate already leaves alone bodies
+//! under 32 bytes, images, gRPC and `text/event-stream`; `/ws/status` is
+//! routed outside the layer so the WebSocket upgrade is never touched.
+
+use tower_http::compression::CompressionLayer;
+
+/// The compression layer wrapped around the JSON routes.
+pub fn layer() -> CompressionLayer {
+    CompressionLayer::new().gzip(true).deflate(true).br(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{self, Body},
+        http::{header, Request, StatusCode},
+        response::sse::{Event, Sse},
+        routing::get,
+        Json, Router,
+    };
+    use futures::stream;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/dashboard/ticker",
+                get(|| async {
+                    let events: Vec<_> = (0..500)
+                        .map(|i| serde_json::json!({ "id": i, "message": "soil moisture WARN → CRITICAL" }))
+                        .collect();
+                    Json(serde_json::json!({ "events": events }))
+                }),
+            )
+            .route(
+                "/events",
+                get(|| async {
+                    let events = stream::iter((0..100).map(|i| {
+                        Ok::<_, std::convert::Infallible>(Event::default().data(format!("tick {i}")))
+                    }));
+                    Sse::new(events)
+                }),
+            )
+            .layer(layer())
+    }
+
+    fn get_with(path: &str, accept_encoding: Option<&str>) -> Request<Body> {
+        let mut req = Request::builder().uri(path);
+        if let Some(value) = accept_encoding {
+            req = req.header(header::ACCEPT_ENCODING, value);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn large_json_is_gzipped_when_requested() {
+        let resp = app()
+            .oneshot(get_with("/dashboard/ticker", Some("gzip")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_ENCODING], "gzip");
+
+        let compressed = body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let plain = app()
+            .oneshot(get_with("/dashboard/ticker", None))
+            .await
+            .unwrap();
+        let plain = body::to_bytes(plain.into_body(), usize::MAX).await.unwrap();
+        assert!(compressed.len() < plain.len() / 4, "{} vs {}", compressed.len(), plain.len());
+    }
+
+    #[tokio::test]
+    async fn uncompressed_without_accept_encoding() {
+        let resp = app()
+            .oneshot(get_with("/dashboard/ticker", None))
+            .await
+            .unwrap();
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+
+        let bytes = body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["events"].as_array().unwrap().len(), 500);
+    }
+
+    #[tokio::test]
+    async fn event_streams_are_not_compressed() {
+        let resp = app()
+            .oneshot(get_with("/events", Some("gzip, br")))
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/event-stream");
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! CSV rendering of time-series query results.
+//!
+//! `POST /data/timeseries/query` answers CSV instead of JSON when the request
+//! accepts `text/csv`.  Points carry different tags and fields, so the
+//! columns are the union across all returned points:
+//!
+//! ```text
+//! time,measurement,<tags, sorted>,<fields, sorted>
+//! ```
+//!
+//! with an empty cell wherever a point lacks a key.  `time` is RFC 3339 in
+//! UTC.  A tag and a field sharing a name each get their own column.
+
+use std::collections::BTreeSet;
+
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, SecondsFormat};
+use proto::influxdb_service::{field_value::Kind, DataPoint};
+
+pub const CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Whether the request's `Accept` lists `text/csv`.
+pub fn accepts_csv(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|range| {
+            let media_type = range.split(';').next().unwrap_or_default();
+            media_type.trim().eq_ignore_ascii_case("text/csv")
+        })
+}
+
+/// `points` as CSV: a header row, then one row per point.
+pub fn to_csv(points: &[DataPoint]) -> String {
+    let tags: BTreeSet<&str> = points.iter().flat_map(|p| p.tags.keys()).map(String::as_str).collect();
+    let fields: BTreeSet<&str> = points
+        .iter()
+        .flat_map(|p| p.fields.keys().chain(p.typed_fields.keys()))
+        .map(String::as_str)
+        .collect();
+
+    let mut out = String::new();
+    let header = ["time", "measurement"].into_iter().chain(tags.iter().copied()).chain(fields.iter().copied());
+    push_row(&mut out, header.map(str::to_string));
+
+    for point in points {
+        let time = DateTime::from_timestamp_nanos(point.timestamp_ns).to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        let tag_cells = tags.iter().map(|&k| point.tags.get(k).cloned().unwrap_or_default());
+        // On a key clash the typed value wins, as when the point was written.
+        let field_cells = fields.iter().map(|&k| match point.typed_fields.get(k).and_then(|v| v.kind.as_ref()) {
+            Some(kind) => kind_to_cell(kind),
+            None => point.fields.get(k).map(f64::to_string).unwrap_or_default(),
+        });
+        let row = [time, point.measurement.clone()].into_iter().chain(tag_cells).chain(field_cells);
+        push_row(&mut out, row);
+    }
+    out
+}
+
+fn kind_to_cell(kind: &Kind) -> String {
+    match kind {
+        Kind::DoubleValue(d) => d.to_string(),
+        Kind::IntValue(i) => i.to_string(),
+        Kind::UintValue(u) => u.to_string(),
+        Kind::BoolValue(b) => b.to_string(),
+        Kind::StringValue(s) => s.clone(),
+    }
+}
+
+fn push_row(out: &mut String, cells: impl Iterator<Item = String>) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_cell(out, &cell);
+    }
+    out.push_str("\r\n");
+}
+
+/// Append `cell`, quoted (RFC 4180) if it contains a delimiter, quote or
+/// line break.
+fn push_cell(out: &mut String, cell: &str) {
+    if cell.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&cell.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(cell);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use proto::influxdb_service::FieldValue;
+
+    use super::*;
+
+    fn point(ts_secs: i64, tags: &[(&str, &str)], fields: &[(&str, f64)]) -> DataPoint {
+        DataPoint {
+            measurement: "plant_telemetry".into(),
+            tags: tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            fields: fields.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            timestamp_ns: ts_secs * 1_000_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn header_is_the_union_of_keys_and_rows_fill_gaps() {
+        let mut second = point(1_704_067_202, &[("plant_id", "p2")], &[("ambient_temp_c", 21.5)]);
+        second.typed_fields.insert(
+            "note".into(),
+            FieldValue { kind: Some(Kind::StringValue("dry, \"very\"".into())) },
+        );
+        let points = [
+            point(1_704_067_201, &[("plant_id", "p1"), ("device_uid", "esp32")], &[("soil_moisture", 42.5)]),
+            second,
+        ];
+
+        let csv = to_csv(&points);
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(
+            rows,
+            [
+                "time,measurement,device_uid,plant_id,ambient_temp_c,note,soil_moisture",
+                "2024-01-01T00:00:01Z,plant_telemetry,esp32,p1,,,42.5",
+                "2024-01-01T00:00:02Z,plant_telemetry,,p2,21.5,\"dry, \"\"very\"\"\",",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn no_points_is_just_the_fixed_header() {
+        assert_eq!(to_csv(&[]), "time,measurement\r\n");
+    }
+
+    #[test]
+    fn accept_negotiation() {
+        let accept = |v: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(v));
+            accepts_csv(&headers)
+        };
+        assert!(accept("text/csv"));
+        assert!(accept("application/json;q=0.5, Text/CSV; charset=utf-8"));
+        assert!(!accept("application/json"));
+        assert!(!accept("*/*"));
+        assert!(!accepts_csv(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,177 @@
+//! Per-plant cache of dashboard responses, invalidated by the supervisor.
+//!
+//! `GET /dashboard/history/:plant_id` responses are kept for
+//! `COORDINATOR_DASHBOARD_CACHE_TTL_SECS` (default 60, `0` disables).  The
+//! supervisor notifies the Postgres channel `plant_state_changed` with the
+//! plant id when an ingest commits; [`spawn_listener`] drops that plant's
+//! entries on each notification, and everything when the listener had to
+//! reconnect and may have missed some.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Channel the supervisor notifies; must match its `STATE_CHANGED_CHANNEL`.
+pub const CHANNEL: &str = "plant_state_changed";
+
+/// Responses by plant id, then by the rest of the request (e.g. `since`).
+type Entries = HashMap<String, HashMap<String, (Instant, serde_json::Value)>>;
+
+/// Dashboard responses kept per plant.
+pub struct PlantCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+impl PlantCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Build from `COORDINATOR_DASHBOARD_CACHE_TTL_SECS`; `None` when it is
+    /// `0`.
+    pub fn from_env() -> Option<Self> {
+        let secs = std::env::var("COORDINATOR_DASHBOARD_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(60);
+        (secs > 0).then(|| Self::new(Duration::from_secs(secs)))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The response kept for `(plant_id, variant)`, if younger than the TTL.
+    pub fn get(&self, plant_id: &str, variant: &str, now: Instant) -> Option<serde_json::Value> {
+        let entries = self.lock();
+        let (at, body) = entries.get(plant_id)?.get(variant)?;
+        (now.duration_since(*at) < self.ttl).then(|| body.clone())
+    }
+
+    pub fn insert(&self, plant_id: &str, variant: &str, body: serde_json::Value, now: Instant) {
+        let mut entries = self.lock();
+        // Drop expired entries on the way, so plants never asked again
+        // don't pile up.
+        let ttl = self.ttl;
+        entries.retain(|_, variants| {
+            variants.retain(|_, (at, _)| now.duration_since(*at) < ttl);
+            !variants.is_empty()
+        });
+        entries
+            .entry(plant_id.to_string())
+            .or_default()
+            .insert(variant.to_string(), (now, body));
+    }
+
+    /// Forget everything kept for `plant_id`.
+    pub fn invalidate(&self, plant_id: &str) {
+        self.lock().remove(plant_id);
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+/// Listen on [`CHANNEL`] and invalidate `cache` per notified plant.
+pub fn spawn_listener(pool: PgPool, cache: Arc<PlantCache>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = listen(&pool, &cache).await {
+                warn!(error = %e, "dashboard cache listener failed; retrying");
+            }
+            // Notifications may have been missed while not listening.
+            cache.clear();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    })
+}
+
+async fn listen(pool: &PgPool, cache: &PlantCache) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    loop {
+        match listener.try_recv().await? {
+            Some(notification) => {
+                debug!(plant_id = notification.payload(), "plant state changed");
+                cache.invalidate(notification.payload());
+            }
+            // The connection dropped; the next call reconnects.
+            None => cache.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_until_ttl() {
+        let cache = PlantCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(cache.get("p1", "", now).is_none());
+        cache.insert("p1", "", serde_json::json!({"n": 1}), now);
+        assert_eq!(
+            cache.get("p1", "", now + Duration::from_secs(59)),
+            Some(serde_json::json!({"n": 1}))
+        );
+        assert!(cache.get("p1", "", now + Duration::from_secs(60)).is_none());
+        assert!(cache.get("p1", "since", now).is_none());
+    }
+
+    #[test]
+    fn invalidate_drops_only_that_plant() {
+        let cache = PlantCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert("p1", "", serde_json::json!(1), now);
+        cache.insert("p1", "2024-01-01T00:00:00Z", serde_json::json!(2), now);
+        cache.insert("p2", "", serde_json::json!(3), now);
+
+        cache.invalidate("p1");
+        assert!(cache.get("p1", "", now).is_none());
+        assert!(cache.get("p1", "2024-01-01T00:00:00Z", now).is_none());
+        assert!(cache.get("p2", "", now).is_some());
+
+        cache.clear();
+        assert!(cache.get("p2", "", now).is_none());
+    }
+
+    #[tokio::test]
+    async fn notification_invalidates_the_plant() {
+        let Some(url) = std::env::var("TEST_DATABASE_URL").ok() else { return };
+        let pool = PgPool::connect(&url).await.unwrap();
+        let cache = Arc::new(PlantCache::new(Duration::from_secs(60)));
+        let plant_id = uuid::Uuid::new_v4().to_string();
+        let listener = spawn_listener(pool.clone(), cache.clone());
+
+        // Keep re-seeding until the listener is up and drops the entry.
+        let invalidated = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                cache.insert(&plant_id, "", serde_json::json!({}), Instant::now());
+                sqlx::query("SELECT pg_notify($1, $2)")
+                    .bind(CHANNEL)
+                    .bind(&plant_id)
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                if cache.get(&plant_id, "", Instant::now()).is_none() {
+                    break;
+                }
+            }
+        })
+        .await;
+        listener.abort();
+        assert!(invalidated.is_ok(), "cache entry survived the notification");
+    }
+}
@@ -0,0 +1,47 @@
+//! Per-call deadlines for coordinator → backend gRPC calls.
+//!
+//! Every call carries `BACKEND_RPC_TIMEOUT_MS` (default 5000) as its
+//! `grpc-timeout`, so the backend can give up too, and is abandoned locally
+//! with `DEADLINE_EXCEEDED` (HTTP 504) once that much time has passed.
+//! A tonic backend enforcing the `grpc-timeout` answers `CANCELLED` at the
+//! same moment, racing the local timer; that answer counts as
+//! `DEADLINE_EXCEEDED` too.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tonic::{Code, Request, Status};
+
+/// Deadline used when `BACKEND_RPC_TIMEOUT_MS` is unset or invalid.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Read `BACKEND_RPC_TIMEOUT_MS`.
+pub fn timeout_from_env() -> Duration {
+    std::env::var("BACKEND_RPC_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&ms: &u64| ms > 0)
+        .map_or(DEFAULT_TIMEOUT, Duration::from_millis)
+}
+
+/// Send `message` through `call` with a `timeout` deadline.
+pub async fn call<M, T, F, Fut>(timeout: Duration, message: M, call: F) -> Result<T, Status>
+where
+    F: FnOnce(Request<M>) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let mut request = crate::request_id::outgoing(message);
+    request.set_timeout(timeout);
+    let started = tokio::time::Instant::now();
+    let expired = || {
+        Status::deadline_exceeded(format!(
+            "backend did not answer within {}ms",
+            timeout.as_millis()
+        ))
+    };
+    match tokio::time::timeout(timeout, call(request)).await {
+        Ok(Err(status)) if status.code() == Code::Cancelled && started.elapsed() >= timeout => Err(expired()),
+        Ok(result) => result,
+        Err(_) => Err(expired()),
+    }
+}
@@ -0,0 +1,231 @@
+//! Retrying coordinator → backend gRPC calls through backend restarts.
+//!
+//! Channels are `connect_lazy`, so a call made while a backend is restarting
+//! fails at once with `Unavailable`.  [`retry`] re-issues such reads (and
+//! ones that hit `DeadlineExceeded`) with jittered exponential backoff; any
+//! other status, e.g. `InvalidArgument` or `NotFound`, is returned as is.
+//!
+//! Calls that write go through [`retry_mutation`] instead, which only
+//! re-issues `Unavailable`: the request never reached the backend.  A write
+//! that missed its deadline may still have been applied, so sending it again
+//! could apply it twice.
+//!
+//! | Env var                          | Default |
+//! |----------------------------------|---------|
+//! | `COORDINATOR_GRPC_RETRIES`       | `3`     |
+//! | `COORDINATOR_GRPC_RETRY_BASE_MS` | `100`   |
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tonic::{Code, Status};
+use tracing::warn;
+
+/// Longest wait between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// How often and how patiently to retry.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further one.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Build from `COORDINATOR_GRPC_RETRIES` and
+    /// `COORDINATOR_GRPC_RETRY_BASE_MS`; unset or invalid values keep the
+    /// defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Self {
+            max_retries: var("COORDINATOR_GRPC_RETRIES").map_or(default.max_retries, |n| n as u32),
+            base_delay: var("COORDINATOR_GRPC_RETRY_BASE_MS")
+                .map_or(default.base_delay, Duration::from_millis),
+        }
+    }
+
+    /// Backoff before retry number `retry` (0-based): the exponential delay,
+    /// capped at [`MAX_DELAY`], scaled by a random factor in [0.5, 1).
+    fn delay(&self, retry: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1 << retry.min(16))
+            .min(MAX_DELAY);
+        exp.mul_f64(0.5 + jitter() / 2.0)
+    }
+}
+
+/// Whether a failed read may be re-issued.
+pub fn is_retryable(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded)
+}
+
+/// Whether a failed write may be re-issued: only when it never reached the
+/// backend.
+pub fn is_retryable_mutation(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+}
+
+/// Run the read `call` until it succeeds, fails with a non-retryable
+/// status, or the policy's retries are used up.  `call` builds a fresh
+/// request each time.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, call: F) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    retry_if(policy, is_retryable, call).await
+}
+
+/// [`retry`] for a `call` that changes backend state; see
+/// [`is_retryable_mutation`].
+pub async fn retry_mutation<T, F, Fut>(policy: &RetryPolicy, call: F) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    retry_if(policy, is_retryable_mutation, call).await
+}
+
+async fn retry_if<T, F, Fut>(
+    policy: &RetryPolicy,
+    retryable: fn(&Status) -> bool,
+    mut call: F,
+) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let mut retries = 0;
+    loop {
+        match call().await {
+            Err(status) if retryable(&status) && retries < policy.max_retries => {
+                let delay = policy.delay(retries);
+                warn!(
+                    code = ?status.code(),
+                    retry = retries + 1,
+                    delay_ms = delay.as_millis() as u64,
+                    "backend call failed; retrying"
+                );
+                tokio::time::sleep(delay).await;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// A random number in [0, 1) without pulling in an RNG crate.
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+        }
+    }
+
+    /// A call failing with `failures` in turn, then returning `Ok(attempt)`.
+    async fn scripted(attempts: &AtomicU32, failures: &[Code]) -> Result<u32, Status> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+        match failures.get(attempt as usize) {
+            Some(&code) => Err(Status::new(code, "scripted")),
+            None => Ok(attempt),
+        }
+    }
+
+    #[tokio::test]
+    async fn two_unavailable_then_success() {
+        let attempts = AtomicU32::new(0);
+        let result = retry(&fast(), || {
+            scripted(&attempts, &[Code::Unavailable, Code::Unavailable])
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_status_passes_through_immediately() {
+        for code in [Code::InvalidArgument, Code::NotFound] {
+            let attempts = AtomicU32::new(0);
+            let codes = [code];
+            let err = retry(&fast(), || scripted(&attempts, &codes))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), code);
+            assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let attempts = AtomicU32::new(0);
+        let err = retry(&fast(), || {
+            scripted(&attempts, &[Code::DeadlineExceeded; 10])
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.code(), Code::DeadlineExceeded);
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn mutation_is_retried_only_when_unavailable() {
+        let attempts = AtomicU32::new(0);
+        let result = retry_mutation(&fast(), || scripted(&attempts, &[Code::Unavailable])).await;
+        assert_eq!(result.unwrap(), 1);
+
+        // It may have been applied: not sent again.
+        let attempts = AtomicU32::new(0);
+        let err = retry_mutation(&fast(), || {
+            scripted(&attempts, &[Code::DeadlineExceeded; 10])
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.code(), Code::DeadlineExceeded);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn delay_grows_with_jitter_and_cap() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+        };
+        for _ in 0..20 {
+            let first = policy.delay(0);
+            assert!(first >= Duration::from_millis(50) && first < Duration::from_millis(100));
+            let third = policy.delay(2);
+            assert!(third >= Duration::from_millis(200) && third < Duration::from_millis(400));
+            assert!(policy.delay(12) < MAX_DELAY);
+        }
+    }
+}
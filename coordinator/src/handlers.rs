@@ -1,28 +1,45 @@
 //! Axum HTTP handlers for the coordinator service.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Body,
+    extract::{Path, Query, State, WebSocketUpgrade},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use sqlx::Row;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{StreamExt, TryStreamExt};
 use tracing::{error, info};
 
 use crate::{
+    csv, deadline,
+    grpc_retry::{retry, retry_mutation},
+    json_body::ApiJson,
     models::{
-        DataRequest, DataResponse, DeleteTimeSeriesRequest, StructuredWriteResult,
-        TimeSeriesQueryRequest, TimeSeriesWriteResult, UpdateStructuredRequest,
+        self, CountStructuredQuery, DataRequest, DataResponse, DeleteTimeSeriesRequest,
+        InvalidNames, LedgerQuery, ListStructuredQuery, TimeSeriesPoint,
+        RegisterDeviceRequest, SeverityHistoryQuery,
+        RegisterPlantRequest, RegisterPlantTypeRequest, StructuredWriteResult,
+        TimeSeriesExportQuery, TimeSeriesQueryRequest, TimeSeriesWriteResult, UpdateStructuredRequest,
     },
-    AppState,
+    request_id, AppState,
 };
 use proto::{
-    influxdb_service::{DataPoint, DeleteRequest as InfluxDeleteRequest, QueryRequest, WriteRequest},
+    influxdb_service::{
+        DataPoint, DeleteRequest as InfluxDeleteRequest, ErrorCode, ExportRequest, QueryRequest,
+        QueryResponse, WriteRequest,
+    },
     postgres_service::{
-        CreateRequest, DeleteRequest as PgDeleteRequest, ListRequest, ReadRequest, UpdateRequest,
+        BatchCreateRequest, CountRequest, CreateRequest, DeleteRequest as PgDeleteRequest, ListRequest,
+        ReadRequest, Record, UpdateRequest, UpdateResponse,
+    },
+    supervisor_service::{
+        CreateDeviceRequest, CreatePlantRequest, CreatePlantTypeRequest,
+        GetEffectiveThresholdsRequest, MetricThresholdSpec, QueryLedgerRequest,
     },
 };
 
@@ -34,14 +51,28 @@ use proto::{
 /// Forwards each kind to the appropriate backend service concurrently via gRPC.
 pub async fn post_data(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<DataRequest>,
+    ApiJson(req): ApiJson<DataRequest>,
 ) -> impl IntoResponse {
+    if let Ok(payload) = serde_json::to_value(&req) {
+        state.redactor.log_payload("POST /data", &payload);
+    }
+
     if req.structured.is_none() && req.timeseries.is_none() {
         return (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({"error": "at least one of 'structured' or 'timeseries' must be present"})),
         );
     }
+    // Checked before either write so a bad point fails the whole request.
+    if let Some(points) = &req.timeseries {
+        let names = models::validate(
+            points.iter().map(|p| p.measurement.as_str()),
+            points.iter().flat_map(TimeSeriesPoint::keys),
+        );
+        if let Err(invalid) = names {
+            return invalid_names_response(invalid);
+        }
+    }
 
     // Fan-out both calls concurrently.
     let (structured_result, timeseries_result) = tokio::join!(
@@ -54,8 +85,28 @@ pub async fn post_data(
         timeseries: timeseries_result,
     };
 
-    info!("POST /data processed");
-    (StatusCode::OK, Json(serde_json::to_value(resp).unwrap()))
+    let status = data_status(&resp);
+    info!(status = status.as_u16(), "POST /data processed");
+    (status, Json(serde_json::to_value(resp).unwrap()))
+}
+
+/// 200 when every write in `resp` succeeded, 502 when none did and 207
+/// (Multi-Status) otherwise; the body says which parts failed.
+fn data_status(resp: &DataResponse) -> StatusCode {
+    let outcomes: Vec<bool> = resp
+        .structured
+        .iter()
+        .flatten()
+        .map(|r| r.success)
+        .chain(resp.timeseries.iter().map(|t| t.success))
+        .collect();
+    if outcomes.iter().all(|&ok| ok) {
+        StatusCode::OK
+    } else if outcomes.iter().any(|&ok| ok) {
+        StatusCode::MULTI_STATUS
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
 }
 
 async fn handle_structured(
@@ -63,50 +114,114 @@ async fn handle_structured(
     records: Option<Vec<crate::models::StructuredRecord>>,
 ) -> Option<Vec<StructuredWriteResult>> {
     let records = records?;
-    let mut results = Vec::with_capacity(records.len());
-
-    for r in records {
-        let payload = r.payload.to_string();
-        let mut pg_client = state.pg_client.clone();
+    let mut results: Vec<Option<StructuredWriteResult>> = records.iter().map(|_| None).collect();
 
-        let result = pg_client
-            .create(CreateRequest {
+    for indices in group_by_table(&records) {
+        if let &[i] = indices.as_slice() {
+            let r = &records[i];
+            let request = CreateRequest {
                 table_name: r.table.clone(),
-                payload,
+                payload: r.payload.to_string(),
+                ..Default::default()
+            };
+            let result = retry_mutation(&state.grpc_retry, || {
+                let mut client = state.pg_client.clone();
+                let request = request.clone();
+                let timeout = state.rpc_timeout;
+                async move { deadline::call(timeout, request, |r| client.create(r)).await }
             })
             .await;
 
+            results[i] = Some(match result {
+                Ok(resp) => {
+                    let inner = resp.into_inner();
+                    StructuredWriteResult {
+                        table: r.table.clone(),
+                        id: if inner.success { Some(inner.id) } else { None },
+                        success: inner.success,
+                        error: if inner.error.is_empty() { None } else { Some(inner.error) },
+                    }
+                }
+                Err(e) => {
+                    error!(error = %e, "postgres create rpc failed");
+                    write_failed(&r.table, e.to_string())
+                }
+            });
+            continue;
+        }
+
+        // Several records for one table: one transactional BatchCreate.
+        let request = BatchCreateRequest {
+            requests: indices
+                .iter()
+                .map(|&i| CreateRequest {
+                    table_name: records[i].table.clone(),
+                    payload: records[i].payload.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let result = retry_mutation(&state.grpc_retry, || {
+            let mut client = state.pg_client.clone();
+            let request = request.clone();
+            let timeout = state.rpc_timeout;
+            async move { deadline::call(timeout, request, |r| client.batch_create(r)).await }
+        })
+        .await;
+
         match result {
+            Ok(resp) if resp.get_ref().success => {
+                for (&i, id) in indices.iter().zip(resp.into_inner().ids) {
+                    results[i] = Some(StructuredWriteResult {
+                        table: records[i].table.clone(),
+                        id: Some(id),
+                        success: true,
+                        error: None,
+                    });
+                }
+            }
             Ok(resp) => {
                 let inner = resp.into_inner();
-                results.push(StructuredWriteResult {
-                    table: r.table,
-                    id: if inner.success {
-                        Some(inner.id)
-                    } else {
-                        None
-                    },
-                    success: inner.success,
-                    error: if inner.error.is_empty() {
-                        None
-                    } else {
-                        Some(inner.error)
-                    },
-                });
+                let failed = inner.failed_index.map(|f| indices[f as usize]);
+                for &i in &indices {
+                    let error = match failed {
+                        Some(f) if f != i => format!("not written: record {f} in the same batch failed"),
+                        _ => inner.error.clone(),
+                    };
+                    results[i] = Some(write_failed(&records[i].table, error));
+                }
             }
             Err(e) => {
-                error!(error = %e, "postgres create rpc failed");
-                results.push(StructuredWriteResult {
-                    table: r.table,
-                    id: None,
-                    success: false,
-                    error: Some(e.to_string()),
-                });
+                error!(error = %e, "postgres batch_create rpc failed");
+                for &i in &indices {
+                    results[i] = Some(write_failed(&records[i].table, e.to_string()));
+                }
             }
         }
     }
 
-    Some(results)
+    Some(results.into_iter().flatten().collect())
+}
+
+fn write_failed(table: &str, error: String) -> StructuredWriteResult {
+    StructuredWriteResult {
+        table: table.to_string(),
+        id: None,
+        success: false,
+        error: Some(error),
+    }
+}
+
+/// Indices of `records` grouped by target table, in first-seen order.
+fn group_by_table(records: &[crate::models::StructuredRecord]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (i, r) in records.iter().enumerate() {
+        match groups.iter_mut().find(|(t, _)| *t == r.table.as_str()) {
+            Some((_, indices)) => indices.push(i),
+            None => groups.push((&r.table, vec![i])),
+        }
+    }
+    groups.into_iter().map(|(_, indices)| indices).collect()
 }
 
 async fn handle_timeseries(
@@ -121,15 +236,22 @@ async fn handle_timeseries(
             tags: p.tags,
             fields: p.fields,
             timestamp_ns: p.timestamp_ns,
+            typed_fields: Default::default(),
         })
         .collect();
 
-    let mut influx_client = state.influx_client.clone();
-    let result = influx_client
-        .write(WriteRequest {
-            points: proto_points,
-        })
-        .await;
+    let request = WriteRequest {
+        points: proto_points,
+        precision: String::new(),
+        bucket: String::new(),
+    };
+    let result = retry_mutation(&state.grpc_retry, || {
+        let mut client = state.influx_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.write(r)).await }
+    })
+    .await;
 
     match result {
         Ok(resp) => {
@@ -162,8 +284,15 @@ pub async fn get_structured(
     State(state): State<Arc<AppState>>,
     Path((table, id)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    let mut client = state.pg_client.clone();
-    match client.read(ReadRequest { id, table_name: table }).await {
+    let request = ReadRequest { id, table_name: table };
+    match retry(&state.grpc_retry, || {
+        let mut client = state.pg_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.read(r)).await }
+    })
+    .await
+    {
         Ok(resp) => {
             let inner = resp.into_inner();
             if inner.success {
@@ -176,68 +305,200 @@ pub async fn get_structured(
             }
         }
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e.to_string()})),
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
         ),
     }
 }
 
-/// GET /data/structured/:table
+/// Page size for `GET /data/structured/:table` without `limit`.
+const DEFAULT_PAGE_SIZE: u32 = 100;
+/// Largest accepted `limit`.
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// GET /data/structured/:table?limit=&offset=&filter=
+///
+/// Responds with `{records, has_more, next_offset}`; one extra row is
+/// requested to tell whether another page exists.  `filter` is a JSON object
+/// matched by containment; anything else is rejected with 400.
 pub async fn list_structured(
     State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
+    Query(params): Query<ListStructuredQuery>,
 ) -> impl IntoResponse {
-    let mut client = state.pg_client.clone();
-    match client
-        .list(ListRequest {
-            table_name: table,
-            filter: String::new(),
-            limit: 100,
-            offset: 0,
-        })
-        .await
+    let filter = match parse_filter(params.filter.as_deref()) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))),
+    };
+    let (limit, offset) = page_bounds(&params);
+    let request = ListRequest {
+        table_name: table,
+        filter,
+        limit: limit + 1,
+        offset,
+        include_deleted: false,
+        order_by: params.sort.unwrap_or_default(),
+        order_dir: params.dir.unwrap_or_default(),
+    };
+    match retry(&state.grpc_retry, || {
+        let mut client = state.pg_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.list(r)).await }
+    })
+    .await
     {
         Ok(resp) => {
             let inner = resp.into_inner();
-            (StatusCode::OK, Json(serde_json::to_value(inner.records).unwrap()))
+            (StatusCode::OK, Json(page_response(inner.records, limit, offset)))
         }
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e.to_string()})),
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
+        ),
+    }
+}
+
+/// GET /data/structured/:table/count?filter=
+///
+/// Responds with `{count}`, the number of records `list_structured` pages
+/// through for the same `filter`.
+pub async fn count_structured(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(params): Query<CountStructuredQuery>,
+) -> impl IntoResponse {
+    let filter = match parse_filter(params.filter.as_deref()) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))),
+    };
+    let request = CountRequest {
+        table_name: table,
+        filter,
+        include_deleted: false,
+    };
+    match retry(&state.grpc_retry, || {
+        let mut client = state.pg_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.count(r)).await }
+    })
+    .await
+    {
+        Ok(resp) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"count": resp.into_inner().count})),
+        ),
+        Err(e) => (
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
         ),
     }
 }
 
+/// Clamp the requested page to `limit` in 1..=[`MAX_PAGE_SIZE`] and a
+/// non-negative `offset`.
+fn page_bounds(params: &ListStructuredQuery) -> (u32, u32) {
+    let limit = params
+        .limit
+        .map_or(DEFAULT_PAGE_SIZE, |l| l.clamp(1, MAX_PAGE_SIZE as i64) as u32);
+    let offset = params.offset.unwrap_or(0).clamp(0, u32::MAX as i64) as u32;
+    (limit, offset)
+}
+
+/// Validate a `filter` query value as a JSON object and return it in the
+/// form `ListRequest.filter` expects; absent or blank means no filter.
+fn parse_filter(raw: Option<&str>) -> Result<String, String> {
+    let Some(raw) = raw.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(String::new());
+    };
+    match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(value @ serde_json::Value::Object(_)) => Ok(value.to_string()),
+        Ok(_) => Err("filter must be a JSON object".to_string()),
+        Err(e) => Err(format!("filter is not valid JSON: {e}")),
+    }
+}
+
+/// Trim the `limit + 1` rows fetched for a page down to `limit` and report
+/// whether more follow.
+fn page_response(mut records: Vec<Record>, limit: u32, offset: u32) -> serde_json::Value {
+    let has_more = records.len() > limit as usize;
+    records.truncate(limit as usize);
+    serde_json::json!({
+        "records": records,
+        "has_more": has_more,
+        "next_offset": has_more.then(|| offset.saturating_add(limit)),
+    })
+}
+
 /// PUT /data/structured/:table/:id
 pub async fn update_structured(
     State(state): State<Arc<AppState>>,
     Path((table, id)): Path<(String, String)>,
-    Json(body): Json<UpdateStructuredRequest>,
+    ApiJson(body): ApiJson<UpdateStructuredRequest>,
 ) -> impl IntoResponse {
-    let mut client = state.pg_client.clone();
-    let payload = body.payload.to_string();
-    match client
-        .update(UpdateRequest {
-            id,
-            table_name: table,
-            payload,
-        })
-        .await
+    state
+        .redactor
+        .log_payload("PUT /data/structured/:table/:id", &body.payload);
+
+    let request = UpdateRequest {
+        id,
+        table_name: table,
+        payload: body.payload.to_string(),
+        version: body.version,
+    };
+    match retry_mutation(&state.grpc_retry, || {
+        let mut client = state.pg_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.update(r)).await }
+    })
+    .await
     {
-        Ok(resp) => {
-            let inner = resp.into_inner();
-            if inner.success {
-                (StatusCode::OK, Json(serde_json::json!({"success": true})))
-            } else {
-                (
-                    StatusCode::NOT_FOUND,
-                    Json(serde_json::json!({"error": inner.error})),
-                )
-            }
-        }
+        Ok(resp) => update_response(resp.into_inner()),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e.to_string()})),
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
+        ),
+    }
+}
+
+/// PATCH /data/structured/:table/:id — shallow-merge `payload` into the
+/// record: supplied keys override, omitted keys are kept, nested objects
+/// are replaced whole.  400 unless `payload` is a JSON object.
+pub async fn patch_structured(
+    State(state): State<Arc<AppState>>,
+    Path((table, id)): Path<(String, String)>,
+    ApiJson(body): ApiJson<UpdateStructuredRequest>,
+) -> impl IntoResponse {
+    state
+        .redactor
+        .log_payload("PATCH /data/structured/:table/:id", &body.payload);
+
+    if !body.payload.is_object() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "payload must be a JSON object"})),
+        );
+    }
+    let request = UpdateRequest {
+        id,
+        table_name: table,
+        payload: body.payload.to_string(),
+        version: body.version,
+    };
+    match retry_mutation(&state.grpc_retry, || {
+        let mut client = state.pg_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.update_partial(r)).await }
+    })
+    .await
+    {
+        Ok(resp) => update_response(resp.into_inner()),
+        Err(e) => (
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
         ),
     }
 }
@@ -247,10 +508,14 @@ pub async fn delete_structured(
     State(state): State<Arc<AppState>>,
     Path((table, id)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    let mut client = state.pg_client.clone();
-    match client
-        .delete(PgDeleteRequest { id, table_name: table })
-        .await
+    let request = PgDeleteRequest { id, table_name: table };
+    match retry_mutation(&state.grpc_retry, || {
+        let mut client = state.pg_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.delete(r)).await }
+    })
+    .await
     {
         Ok(resp) => {
             let inner = resp.into_inner();
@@ -265,8 +530,8 @@ pub async fn delete_structured(
             }
         }
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e.to_string()})),
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
         )
             .into_response(),
     }
@@ -277,50 +542,239 @@ pub async fn delete_structured(
 // ------------------------------------------------------------------ //
 
 /// POST /data/timeseries/query
+///
+/// Answers CSV (see [`csv`]) instead of JSON when `Accept` lists `text/csv`;
+/// errors are JSON either way.
 pub async fn query_timeseries(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<TimeSeriesQueryRequest>,
-) -> impl IntoResponse {
-    let mut client = state.influx_client.clone();
-    match client
-        .query(QueryRequest {
-            measurement: body.measurement,
-            start: body.start,
-            stop: body.stop,
-            tag_filters: body.tag_filters,
-            limit: body.limit,
-        })
-        .await
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<TimeSeriesQueryRequest>,
+) -> Response {
+    let names = models::validate(
+        std::iter::once(&body.measurement).chain(&body.measurements).map(String::as_str),
+        body.tag_filters.keys().map(String::as_str),
+    );
+    if let Err(invalid) = names {
+        return invalid_names_response(invalid).into_response();
+    }
+    let request = QueryRequest {
+        measurement: body.measurement,
+        measurements: body.measurements,
+        start: body.start,
+        stop: body.stop,
+        tag_filters: body.tag_filters,
+        limit: body.limit,
+        aggregate_window: body.aggregate_window,
+        aggregate_fn: body.aggregate_fn,
+        after_time_ns: body.after_time_ns,
+    };
+    match retry(&state.grpc_retry, || {
+        let mut client = state.influx_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.query(r)).await }
+    })
+    .await
     {
-        Ok(resp) => {
+        Ok(resp) if resp.get_ref().success && csv::accepts_csv(&headers) => {
             let inner = resp.into_inner();
-            (StatusCode::OK, Json(serde_json::to_value(inner).unwrap()))
+            let mut response =
+                ([(header::CONTENT_TYPE, csv::CONTENT_TYPE)], csv::to_csv(&inner.points)).into_response();
+            if inner.next_cursor_ns != 0 {
+                response.headers_mut().insert(NEXT_CURSOR_HEADER, inner.next_cursor_ns.into());
+            }
+            response
         }
+        Ok(resp) => query_response(resp.into_inner()).into_response(),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({"error": e.to_string()})),
-        ),
+            grpc_status_to_http(&e),
+            Json(serde_json::json!({"error": e.message()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Carries a CSV query page's `next_cursor`; absent on the last page.
+const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// GET /data/timeseries/export?measurement=&start=&stop=&limit=&tag_filters=
+///
+/// Streams the range's raw records as newline-delimited JSON.  A query that
+/// fails before its first record is answered with an HTTP error; one that
+/// fails later can only cut the body short.
+pub async fn export_timeseries(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TimeSeriesExportQuery>,
+) -> Response {
+    let tag_filters = match parse_tag_filters(params.tag_filters.as_deref()) {
+        Ok(tag_filters) => tag_filters,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))).into_response();
+        }
+    };
+    let names = models::validate(
+        std::iter::once(params.measurement.as_str()),
+        tag_filters.keys().map(String::as_str),
+    );
+    if let Err(invalid) = names {
+        return invalid_names_response(invalid).into_response();
+    }
+    let request = ExportRequest {
+        measurement: params.measurement,
+        start: params.start,
+        stop: params.stop,
+        tag_filters,
+        limit: params.limit,
+    };
+    // No deadline: it would bound the whole export, not just its start.
+    let opened = retry(&state.grpc_retry, || {
+        let mut client = state.influx_client.clone();
+        let request = request_id::outgoing(request.clone());
+        async move { client.export(request).await }
+    })
+    .await;
+    let mut lines = match opened {
+        Ok(resp) => resp.into_inner(),
+        Err(e) => return export_error(&e),
+    };
+    let first = match lines.message().await {
+        Ok(first) => first,
+        Err(e) => return export_error(&e),
+    };
+    let body = futures::stream::iter(first.map(Ok))
+        .chain(lines)
+        .map_ok(|line| line.json + "\n");
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+fn export_error(e: &tonic::Status) -> Response {
+    error!(error = %e, "influx export failed");
+    (
+        grpc_status_to_http(e),
+        Json(serde_json::json!({"error": e.message()})),
+    )
+        .into_response()
+}
+
+/// Parse a `tag_filters` query value, a JSON object of strings; absent or
+/// blank means no filters.
+fn parse_tag_filters(raw: Option<&str>) -> Result<HashMap<String, String>, String> {
+    let Some(raw) = raw.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(HashMap::new());
+    };
+    serde_json::from_str(raw)
+        .map_err(|e| format!("tag_filters must be a JSON object of strings: {e}"))
+}
+
+/// Map an InfluxDB `QueryResponse` onto an HTTP status and body.
+///
+/// A successful query is always 200, even with no points, and carries
+/// `next_cursor` (null on the last page); a failed one is mapped from its
+/// `error_code`.
+fn query_response(inner: QueryResponse) -> (StatusCode, Json<serde_json::Value>) {
+    if inner.success {
+        let next_cursor = (inner.next_cursor_ns != 0).then_some(inner.next_cursor_ns);
+        let mut body = serde_json::to_value(inner).unwrap();
+        body["next_cursor"] = serde_json::json!(next_cursor);
+        return (StatusCode::OK, Json(body));
+    }
+    let status = match inner.error_code() {
+        ErrorCode::InvalidArgument => StatusCode::BAD_REQUEST,
+        ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::Backend | ErrorCode::Unspecified => StatusCode::BAD_GATEWAY,
+    };
+    (status, Json(serde_json::json!({"error": inner.error})))
+}
+
+/// Map a postgres-service `UpdateResponse` onto an HTTP status and body:
+/// 409 with the record's current version on a stale `version`, else 404.
+fn update_response(inner: UpdateResponse) -> (StatusCode, Json<serde_json::Value>) {
+    if inner.success {
+        (StatusCode::OK, Json(serde_json::json!({"success": true, "version": inner.version})))
+    } else if inner.conflict {
+        (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": inner.error, "current_version": inner.version})),
+        )
+    } else {
+        (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": inner.error})))
     }
 }
 
-/// DELETE /data/timeseries
+/// 400 naming the measurement or keys [`models::validate`] rejected.
+fn invalid_names_response(invalid: InvalidNames) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "error": invalid.to_string(),
+            "invalid_keys": invalid.invalid_keys,
+        })),
+    )
+}
+
+/// Map a gRPC transport/status error from a backend onto an HTTP status.
+fn grpc_status_to_http(status: &tonic::Status) -> StatusCode {
+    match status.code() {
+        tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        tonic::Code::NotFound => StatusCode::NOT_FOUND,
+        tonic::Code::AlreadyExists => StatusCode::CONFLICT,
+        tonic::Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        tonic::Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// HTTP status for a failed backend call on routes that otherwise answer
+/// 500: 504 if it timed out.
+fn rpc_failure_status(status: &tonic::Status) -> StatusCode {
+    match status.code() {
+        tonic::Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// DELETE /data/timeseries[?count=true]
+///
+/// Answers 204 on success, or 200 `{"deleted_estimate": N}` when `count=true`.
 pub async fn delete_timeseries(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<DeleteTimeSeriesRequest>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    ApiJson(body): ApiJson<DeleteTimeSeriesRequest>,
 ) -> impl IntoResponse {
-    let mut client = state.influx_client.clone();
-    match client
-        .delete(InfluxDeleteRequest {
-            measurement: body.measurement,
-            start: body.start,
-            stop: body.stop,
-            tag_filters: body.tag_filters,
-        })
-        .await
+    let with_count = params.get("count").is_some_and(|v| v == "true");
+    // An empty measurement is allowed here: it deletes across measurements.
+    if let Err(invalid) = models::validate(std::iter::empty(), body.tag_filters.keys().map(String::as_str)) {
+        return invalid_names_response(invalid).into_response();
+    }
+    let request = InfluxDeleteRequest {
+        measurement: body.measurement,
+        start: body.start,
+        stop: body.stop,
+        tag_filters: body.tag_filters,
+        confirm_full_range: body.confirm_full_range,
+        with_count,
+    };
+    match retry_mutation(&state.grpc_retry, || {
+        let mut client = state.influx_client.clone();
+        let request = request.clone();
+        let timeout = state.rpc_timeout;
+        async move { deadline::call(timeout, request, |r| client.delete(r)).await }
+    })
+    .await
     {
         Ok(resp) => {
             let inner = resp.into_inner();
-            if inner.success {
+            if inner.success && with_count {
+                (
+                    StatusCode::OK,
+                    Json(serde_json::json!({"deleted_estimate": inner.deleted_estimate})),
+                )
+                    .into_response()
+            } else if inner.success {
                 StatusCode::NO_CONTENT.into_response()
             } else {
                 (
@@ -331,7 +785,7 @@ pub async fn delete_timeseries(
             }
         }
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            rpc_failure_status(&e),
             Json(serde_json::json!({"error": e.to_string()})),
         )
             .into_response(),
@@ -339,18 +793,199 @@ pub async fn delete_timeseries(
 }
 
 // ------------------------------------------------------------------ //
-//  Health                                                             //
+//  Admin registration (database-supervisor) endpoints                 //
+// ------------------------------------------------------------------ //
+
+/// Turn a supervisor `Create*` result into `201 {"id": ...}` or a mapped error.
+fn created<T>(
+    result: Result<tonic::Response<T>, tonic::Status>,
+    id: impl FnOnce(T) -> String,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match result {
+        Ok(resp) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({"id": id(resp.into_inner())})),
+        ),
+        Err(e) => {
+            error!(error = %e, "supervisor admin rpc failed");
+            (
+                grpc_status_to_http(&e),
+                Json(serde_json::json!({"error": e.message()})),
+            )
+        }
+    }
+}
+
+/// POST /admin/plant-types
+pub async fn create_plant_type(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<RegisterPlantTypeRequest>,
+) -> impl IntoResponse {
+    let mut client = state.supervisor_client.clone();
+    let result = client
+        .create_plant_type(request_id::outgoing(CreatePlantTypeRequest {
+            name: body.name,
+            description: body.description,
+            thresholds: body
+                .thresholds
+                .into_iter()
+                .map(|t| MetricThresholdSpec {
+                    metric: t.metric,
+                    warn_min: t.warn_min,
+                    warn_max: t.warn_max,
+                    crit_min: t.crit_min,
+                    crit_max: t.crit_max,
+                    unit: t.unit,
+                    smoothing: t.smoothing,
+                    smoothing_window: t.smoothing_window,
+                    smoothing_alpha: t.smoothing_alpha,
+                    hysteresis: t.hysteresis,
+                    max_rate_per_min: t.max_rate_per_min,
+                    required: t.required,
+                })
+                .collect(),
+        }))
+        .await;
+    created(result, |r| r.id)
+}
+
+/// POST /admin/plants
+pub async fn create_plant(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<RegisterPlantRequest>,
+) -> impl IntoResponse {
+    let mut client = state.supervisor_client.clone();
+    let result = client
+        .create_plant(request_id::outgoing(CreatePlantRequest {
+            plant_type_id: body.plant_type_id,
+            display_name: body.display_name,
+            location: body.location,
+            notes: body.notes,
+            device_id: body.device_id,
+        }))
+        .await;
+    created(result, |r| r.id)
+}
+
+/// POST /admin/devices
+pub async fn create_device(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<RegisterDeviceRequest>,
+) -> impl IntoResponse {
+    let mut client = state.supervisor_client.clone();
+    let result = client
+        .create_device(request_id::outgoing(CreateDeviceRequest {
+            device_uid: body.device_uid,
+            firmware_version: body.firmware_version,
+            expected_interval_s: body.expected_interval_s,
+            interval_tolerance_pct: body.interval_tolerance_pct,
+        }))
+        .await;
+    created(result, |r| r.id)
+}
+
+// ------------------------------------------------------------------ //
+//  Live status (WebSocket)                                            //
+// ------------------------------------------------------------------ //
+
+/// GET /ws/status — stream `PlantStatusChanged.v1` events as JSON text
+/// frames.  503 when the coordinator has no `AMQP_URL`.
+pub async fn ws_status(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
+    let Some(feed) = state.status_feed.clone() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"error": "status feed disabled (AMQP_URL not set)"})),
+        )
+            .into_response();
+    };
+    ws.on_upgrade(move |socket| crate::status_feed::forward(socket, feed))
+}
+
+// ------------------------------------------------------------------ //
+//  Health and metrics                                                 //
 // ------------------------------------------------------------------ //
 
+/// GET /metrics — Prometheus text format.
+pub async fn metrics(State(state): State<Arc<AppState>>) -> String {
+    state.metrics.render()
+}
+
+/// GET /schema — JSON Schema of the public request and response bodies.
+pub async fn schema() -> Json<serde_json::Value> {
+    Json(models::json_schemas())
+}
+
 pub async fn health() -> impl IntoResponse {
     (StatusCode::OK, Json(serde_json::json!({"status": "ok"})))
 }
 
+/// GET /admin/ledger?device_uid=&plant_id=&start_ns=&stop_ns=&limit=
+pub async fn query_ledger(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<LedgerQuery>,
+) -> impl IntoResponse {
+    let mut client = state.supervisor_client.clone();
+    let result = client
+        .query_ledger(request_id::outgoing(QueryLedgerRequest {
+            device_uid: params.device_uid,
+            plant_id: params.plant_id,
+            start_ns: params.start_ns,
+            stop_ns: params.stop_ns,
+            limit: params.limit,
+        }))
+        .await;
+    match result {
+        Ok(resp) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"entries": resp.into_inner().entries})),
+        ),
+        Err(e) => {
+            error!(error = %e, "supervisor query_ledger failed");
+            (
+                grpc_status_to_http(&e),
+                Json(serde_json::json!({"error": e.message()})),
+            )
+        }
+    }
+}
+
+/// GET /admin/plants/:plant_id/thresholds — the thresholds ingest currently
+/// applies to the plant (its overrides merged over its type's).
+pub async fn effective_thresholds(
+    State(state): State<Arc<AppState>>,
+    Path(plant_id): Path<String>,
+) -> impl IntoResponse {
+    let mut client = state.supervisor_client.clone();
+    let result = client
+        .get_effective_thresholds(request_id::outgoing(GetEffectiveThresholdsRequest { plant_id }))
+        .await;
+    match result {
+        Ok(resp) => {
+            let resp = resp.into_inner();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "plant_type_id": resp.plant_type_id,
+                    "thresholds": resp.thresholds,
+                })),
+            )
+        }
+        Err(e) => {
+            error!(error = %e, "supervisor get_effective_thresholds failed");
+            (
+                grpc_status_to_http(&e),
+                Json(serde_json::json!({"error": e.message()})),
+            )
+        }
+    }
+}
+
 // ------------------------------------------------------------------ //
 //  Dashboard endpoints                                                //
 // ------------------------------------------------------------------ //
 
-/// GET /dashboard/attention — plants needing attention (WARN or CRITICAL)
+/// GET /dashboard/attention — plants needing attention (STALE, WARN or
+/// CRITICAL), most urgent first
 pub async fn dashboard_attention(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
@@ -379,9 +1014,10 @@ pub async fn dashboard_attention(
         FROM plant_current_state pcs
         JOIN plant p    ON p.id = pcs.plant_id
         JOIN plant_type pt ON pt.id = p.plant_type_id
-        WHERE pcs.severity IN ('WARN', 'CRITICAL')
+        WHERE pcs.severity IN ('STALE', 'WARN', 'CRITICAL')
           AND p.is_active = TRUE
-        ORDER BY pcs.severity DESC, pcs.updated_at DESC
+        ORDER BY CASE pcs.severity WHEN 'CRITICAL' THEN 3 WHEN 'WARN' THEN 2 ELSE 1 END DESC,
+                 pcs.updated_at DESC
     "#)
     .fetch_all(pool)
     .await;
@@ -418,6 +1054,10 @@ pub async fn dashboard_attention(
 }
 
 /// GET /dashboard/ticker?limit=N — latest ticker events
+///
+/// `occurred_at` is when the event was recorded; `occurred_at_ns` and
+/// `reading_time` are the device timestamp of the reading that raised it,
+/// null for events not raised by a reading.
 pub async fn dashboard_ticker(
     State(state): State<Arc<AppState>>,
     Query(params): Query<std::collections::HashMap<String, String>>,
@@ -442,6 +1082,7 @@ pub async fn dashboard_ticker(
         SELECT
             id,
             occurred_at,
+            occurred_at_ns,
             plant_id::text AS plant_id,
             device_uid,
             severity,
@@ -459,9 +1100,12 @@ pub async fn dashboard_ticker(
             let data: Vec<serde_json::Value> = rows
                 .iter()
                 .map(|r| {
+                    let occurred_at_ns = r.try_get::<Option<i64>, _>("occurred_at_ns").ok().flatten();
                     serde_json::json!({
                         "id":          r.try_get::<i64, _>("id").ok(),
                         "occurred_at": r.try_get::<DateTime<Utc>, _>("occurred_at").ok().map(|t| t.to_rfc3339()),
+                        "occurred_at_ns": occurred_at_ns,
+                        "reading_time": occurred_at_ns.map(rfc3339_from_ns),
                         "plant_id":    r.try_get::<Option<String>, _>("plant_id").ok().flatten(),
                         "device_uid":  r.try_get::<Option<String>, _>("device_uid").ok().flatten(),
                         "severity":    r.try_get::<String, _>("severity").ok(),
@@ -481,10 +1125,18 @@ pub async fn dashboard_ticker(
     }
 }
 
-/// GET /dashboard/edges?ttl_seconds=T — edge node online/offline status
-pub async fn dashboard_edges(
+/// RFC 3339 in UTC of a Unix-nanosecond timestamp, keeping every
+/// sub-second digit it has.
+fn rfc3339_from_ns(ns: i64) -> String {
+    DateTime::from_timestamp_nanos(ns).to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// GET /dashboard/history/:plant_id?since=RFC3339 — a plant's overall
+/// severity transitions, oldest first.
+pub async fn dashboard_history(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<std::collections::HashMap<String, String>>,
+    Path(plant_id): Path<String>,
+    Query(params): Query<SeverityHistoryQuery>,
 ) -> impl IntoResponse {
     let pool = match &state.db_pool {
         Some(p) => p,
@@ -495,29 +1147,32 @@ pub async fn dashboard_edges(
             );
         }
     };
-
-    let ttl_seconds: i64 = params
-        .get("ttl_seconds")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(300_i64);
+    let Ok(plant_uuid) = uuid::Uuid::parse_str(&plant_id) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "plant_id must be a UUID"})),
+        );
+    };
+    let since = match parse_since(params.since.as_deref()) {
+        Ok(since) => since,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))),
+    };
+    let cache_key = (plant_uuid.to_string(), since.map(|t| t.to_rfc3339()).unwrap_or_default());
+    if let Some(cache) = &state.dashboard_cache {
+        if let Some(body) = cache.get(&cache_key.0, &cache_key.1, std::time::Instant::now()) {
+            return (StatusCode::OK, Json(body));
+        }
+    }
 
     let rows = sqlx::query(r#"
-        SELECT
-            id::text AS id,
-            device_uid,
-            firmware_version,
-            last_seen_at,
-            is_active,
-            CASE
-                WHEN last_seen_at IS NULL THEN FALSE
-                WHEN last_seen_at >= NOW() - ($1 * INTERVAL '1 second') THEN TRUE
-                ELSE FALSE
-            END AS online
-        FROM device
-        WHERE is_active = TRUE
-        ORDER BY last_seen_at DESC NULLS LAST
+        SELECT occurred_at, prev_severity, new_severity, metric_severity
+        FROM plant_severity_history
+        WHERE plant_id = $1
+          AND ($2::timestamptz IS NULL OR occurred_at >= $2)
+        ORDER BY occurred_at, id
     "#)
-    .bind(ttl_seconds)
+    .bind(plant_uuid)
+    .bind(since)
     .fetch_all(pool)
     .await;
 
@@ -527,19 +1182,21 @@ pub async fn dashboard_edges(
                 .iter()
                 .map(|r| {
                     serde_json::json!({
-                        "id":               r.try_get::<String, _>("id").ok(),
-                        "device_uid":       r.try_get::<String, _>("device_uid").ok(),
-                        "firmware_version": r.try_get::<Option<String>, _>("firmware_version").ok().flatten(),
-                        "last_seen_at":     r.try_get::<Option<DateTime<Utc>>, _>("last_seen_at").ok().flatten().map(|t| t.to_rfc3339()),
-                        "is_active":        r.try_get::<bool, _>("is_active").ok(),
-                        "online":           r.try_get::<bool, _>("online").ok(),
+                        "occurred_at":     r.try_get::<DateTime<Utc>, _>("occurred_at").ok().map(|t| t.to_rfc3339()),
+                        "prev_severity":   r.try_get::<Option<String>, _>("prev_severity").ok().flatten(),
+                        "new_severity":    r.try_get::<String, _>("new_severity").ok(),
+                        "metric_severity": r.try_get::<Option<serde_json::Value>, _>("metric_severity").ok().flatten(),
                     })
                 })
                 .collect();
-            (StatusCode::OK, Json(serde_json::json!({"devices": data})))
+            let body = serde_json::json!({"plant_id": plant_id, "transitions": data});
+            if let Some(cache) = &state.dashboard_cache {
+                cache.insert(&cache_key.0, &cache_key.1, body.clone(), std::time::Instant::now());
+            }
+            (StatusCode::OK, Json(body))
         }
         Err(e) => {
-            error!(error = %e, "dashboard_edges query failed");
+            error!(error = %e, "dashboard_history query failed");
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(serde_json::json!({"error": e.to_string()})),
@@ -547,3 +1204,1031 @@ pub async fn dashboard_edges(
         }
     }
 }
+
+/// Parse an optional RFC 3339 `since` bound.
+fn parse_since(raw: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    raw.filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            DateTime::parse_from_rfc3339(s.trim())
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| format!("since must be an RFC 3339 timestamp: {e}"))
+        })
+        .transpose()
+}
+
+/// Readings kept in `plant_current_state` columns, as named in the response.
+const CURRENT_READINGS: [&str; 4] = ["soil_moisture", "ambient_light_lux", "ambient_humidity_rh", "ambient_temp_c"];
+
+/// GET /plants/:plant_id/current — an active plant's latest readings and
+/// severity from `plant_current_state`, without a time-series query.  A
+/// plant that hasn't reported yet has a null `severity` and no readings.
+pub async fn plant_current(
+    State(state): State<Arc<AppState>>,
+    Path(plant_id): Path<String>,
+) -> impl IntoResponse {
+    let pool = match &state.db_pool {
+        Some(p) => p,
+        None => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({"error": "dashboard database not configured"})),
+            );
+        }
+    };
+    let Ok(plant_uuid) = uuid::Uuid::parse_str(&plant_id) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "plant_id must be a UUID"})),
+        );
+    };
+
+    let row = sqlx::query(r#"
+        SELECT p.display_name, pcs.severity, pcs.updated_at, pcs.metric_severity,
+               pcs.soil_moisture, pcs.ambient_light_lux, pcs.ambient_humidity_rh, pcs.ambient_temp_c
+        FROM plant p
+        LEFT JOIN plant_current_state pcs ON pcs.plant_id = p.id
+        WHERE p.id = $1 AND p.is_active = TRUE
+    "#)
+    .bind(plant_uuid)
+    .fetch_optional(pool)
+    .await;
+
+    match row {
+        Ok(Some(r)) => {
+            let readings: serde_json::Map<String, serde_json::Value> = CURRENT_READINGS
+                .iter()
+                .filter_map(|&name| {
+                    let value = r.try_get::<Option<f64>, _>(name).ok().flatten()?;
+                    Some((name.to_string(), serde_json::json!(value)))
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "plant_id":        plant_uuid.to_string(),
+                    "display_name":    r.try_get::<String, _>("display_name").ok(),
+                    "severity":        r.try_get::<Option<String>, _>("severity").ok().flatten(),
+                    "updated_at":      r.try_get::<Option<DateTime<Utc>>, _>("updated_at").ok().flatten().map(|t| t.to_rfc3339()),
+                    "readings":        readings,
+                    "metric_severity": r.try_get::<Option<serde_json::Value>, _>("metric_severity").ok().flatten(),
+                })),
+            )
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("no active plant {plant_uuid}")})),
+        ),
+        Err(e) => {
+            error!(error = %e, "plant_current query failed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+        }
+    }
+}
+
+/// GET /dashboard/edges?ttl_seconds=T — edge node online/offline status
+pub async fn dashboard_edges(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    let pool = match &state.db_pool {
+        Some(p) => p,
+        None => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({"error": "dashboard database not configured"})),
+            );
+        }
+    };
+
+    let ttl_seconds: i64 = params
+        .get("ttl_seconds")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300_i64);
+
+    let rows = sqlx::query(r#"
+        SELECT
+            id::text AS id,
+            device_uid,
+            firmware_version,
+            last_seen_at,
+            is_active,
+            battery_v,
+            rssi_dbm,
+            CASE
+                WHEN last_seen_at IS NULL THEN FALSE
+                WHEN last_seen_at >= NOW() - ($1 * INTERVAL '1 second') THEN TRUE
+                ELSE FALSE
+            END AS online
+        FROM device
+        WHERE is_active = TRUE
+        ORDER BY last_seen_at DESC NULLS LAST
+    "#)
+    .bind(ttl_seconds)
+    .fetch_all(pool)
+    .await;
+
+    match rows {
+        Ok(rows) => {
+            let data: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|r| {
+                    serde_json::json!({
+                        "id":               r.try_get::<String, _>("id").ok(),
+                        "device_uid":       r.try_get::<String, _>("device_uid").ok(),
+                        "firmware_version": r.try_get::<Option<String>, _>("firmware_version").ok().flatten(),
+                        "last_seen_at":     r.try_get::<Option<DateTime<Utc>>, _>("last_seen_at").ok().flatten().map(|t| t.to_rfc3339()),
+                        "is_active":        r.try_get::<bool, _>("is_active").ok(),
+                        "online":           r.try_get::<bool, _>("online").ok(),
+                        "battery_v":        r.try_get::<Option<f64>, _>("battery_v").ok().flatten(),
+                        "rssi_dbm":         r.try_get::<Option<i32>, _>("rssi_dbm").ok().flatten(),
+                    })
+                })
+                .collect();
+            (StatusCode::OK, Json(serde_json::json!({"devices": data})))
+        }
+        Err(e) => {
+            error!(error = %e, "dashboard_edges query failed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+        }
+    }
+}
+
+/// Severities reported by `GET /dashboard/summary`, always all present.
+const SUMMARY_SEVERITIES: [&str; 4] = ["NORMAL", "WARN", "CRITICAL", "STALE"];
+
+/// GET /dashboard/summary?ttl_seconds=T — active plants per severity and
+/// active devices online/offline (seen within T seconds, default 300)
+pub async fn dashboard_summary(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    let pool = match &state.db_pool {
+        Some(p) => p,
+        None => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({"error": "dashboard database not configured"})),
+            );
+        }
+    };
+
+    let ttl_seconds: i64 = params
+        .get("ttl_seconds")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300_i64);
+
+    let severities = sqlx::query_as::<_, (String, i64)>(r#"
+        SELECT pcs.severity, COUNT(*)
+        FROM plant_current_state pcs
+        JOIN plant p ON p.id = pcs.plant_id
+        WHERE p.is_active = TRUE
+        GROUP BY pcs.severity
+    "#)
+    .fetch_all(pool)
+    .await;
+
+    let severities = match severities {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!(error = %e, "dashboard_summary severity query failed");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": e.to_string()})),
+            );
+        }
+    };
+
+    let devices = sqlx::query_as::<_, (i64, i64)>(r#"
+        SELECT
+            COUNT(*) FILTER (WHERE last_seen_at >= NOW() - ($1 * INTERVAL '1 second')),
+            COUNT(*)
+        FROM device
+        WHERE is_active = TRUE
+    "#)
+    .bind(ttl_seconds)
+    .fetch_one(pool)
+    .await;
+
+    match devices {
+        Ok((online, total)) => (StatusCode::OK, Json(summary_body(&severities, online, total))),
+        Err(e) => {
+            error!(error = %e, "dashboard_summary device query failed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+        }
+    }
+}
+
+/// The `/dashboard/summary` body for per-severity plant counts and device
+/// totals; severities without plants are reported as 0.
+fn summary_body(severities: &[(String, i64)], devices_online: i64, devices_total: i64) -> serde_json::Value {
+    let mut by_severity: serde_json::Map<String, serde_json::Value> = SUMMARY_SEVERITIES
+        .iter()
+        .map(|s| (s.to_string(), 0.into()))
+        .collect();
+    for (severity, count) in severities {
+        by_severity.insert(severity.clone(), (*count).into());
+    }
+    serde_json::json!({
+        "plants": {
+            "by_severity": by_severity,
+            "total":       severities.iter().map(|(_, n)| n).sum::<i64>(),
+        },
+        "devices": {
+            "online":  devices_online,
+            "offline": devices_total - devices_online,
+            "total":   devices_total,
+        },
+    })
+}
+
+// ------------------------------------------------------------------ //
+//  Tests                                                              //
+// ------------------------------------------------------------------ //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn structured_records_grouped_by_table_in_order() {
+        let record = |table: &str| crate::models::StructuredRecord {
+            table: table.into(),
+            payload: serde_json::json!({}),
+        };
+        let records = [record("a"), record("b"), record("a"), record("c"), record("a")];
+        assert_eq!(group_by_table(&records), vec![vec![0, 2, 4], vec![1], vec![3]]);
+    }
+
+    fn written(table: &str, success: bool) -> StructuredWriteResult {
+        StructuredWriteResult {
+            table: table.into(),
+            id: success.then(|| "1".to_string()),
+            success,
+            error: (!success).then(|| "boom".to_string()),
+        }
+    }
+
+    fn timeseries(success: bool) -> Option<TimeSeriesWriteResult> {
+        Some(TimeSeriesWriteResult { success, error: (!success).then(|| "boom".to_string()) })
+    }
+
+    #[test]
+    fn post_data_status_is_200_when_everything_succeeded() {
+        let resp = DataResponse {
+            structured: Some(vec![written("a", true), written("b", true)]),
+            timeseries: timeseries(true),
+        };
+        assert_eq!(data_status(&resp), StatusCode::OK);
+        let resp = DataResponse { structured: None, timeseries: timeseries(true) };
+        assert_eq!(data_status(&resp), StatusCode::OK);
+    }
+
+    #[test]
+    fn post_data_status_is_207_when_some_parts_failed() {
+        let resp = DataResponse {
+            structured: Some(vec![written("a", true)]),
+            timeseries: timeseries(false),
+        };
+        assert_eq!(data_status(&resp), StatusCode::MULTI_STATUS);
+        let resp = DataResponse {
+            structured: Some(vec![written("a", true), written("b", false)]),
+            timeseries: None,
+        };
+        assert_eq!(data_status(&resp), StatusCode::MULTI_STATUS);
+    }
+
+    #[test]
+    fn post_data_status_is_502_when_everything_failed() {
+        let resp = DataResponse {
+            structured: Some(vec![written("a", false), written("b", false)]),
+            timeseries: timeseries(false),
+        };
+        assert_eq!(data_status(&resp), StatusCode::BAD_GATEWAY);
+    }
+
+    fn records(n: usize) -> Vec<Record> {
+        (0..n)
+            .map(|i| Record {
+                id: i.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn list_defaults_to_first_page_of_100() {
+        assert_eq!(page_bounds(&ListStructuredQuery::default()), (100, 0));
+
+        let body = page_response(records(37), 100, 0);
+        assert_eq!(body["records"].as_array().unwrap().len(), 37);
+        assert_eq!(body["has_more"], false);
+        assert!(body["next_offset"].is_null());
+    }
+
+    #[test]
+    fn list_custom_page_reports_next_offset() {
+        let params = ListStructuredQuery {
+            limit: Some(10),
+            offset: Some(20),
+            filter: None,
+            ..Default::default()
+        };
+        assert_eq!(page_bounds(&params), (10, 20));
+
+        // 11 rows back for limit 10 means another page exists.
+        let body = page_response(records(11), 10, 20);
+        let page = body["records"].as_array().unwrap();
+        assert_eq!(page.len(), 10);
+        assert_eq!(page[9]["id"], "9");
+        assert_eq!(body["has_more"], true);
+        assert_eq!(body["next_offset"], 30);
+    }
+
+    #[test]
+    fn list_bounds_are_clamped() {
+        let params = ListStructuredQuery {
+            limit: Some(5000),
+            offset: Some(-3),
+            filter: None,
+            ..Default::default()
+        };
+        assert_eq!(page_bounds(&params), (1000, 0));
+
+        let params = ListStructuredQuery {
+            limit: Some(0),
+            offset: None,
+            filter: None,
+            ..Default::default()
+        };
+        assert_eq!(page_bounds(&params), (1, 0));
+    }
+
+    #[test]
+    fn valid_filter_passes_through() {
+        assert_eq!(parse_filter(None).unwrap(), "");
+        assert_eq!(parse_filter(Some("  ")).unwrap(), "");
+
+        let filter = parse_filter(Some(r#"{ "status": "active", "tags": {"a": 1} }"#)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&filter).unwrap();
+        assert_eq!(value, serde_json::json!({"status": "active", "tags": {"a": 1}}));
+    }
+
+    #[test]
+    fn reading_time_keeps_nanoseconds() {
+        assert_eq!(rfc3339_from_ns(1_700_000_000_123_456_789), "2023-11-14T22:13:20.123456789Z");
+        assert_eq!(rfc3339_from_ns(1_700_000_000_000_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn invalid_filter_is_rejected() {
+        assert!(parse_filter(Some("{status: active}"))
+            .unwrap_err()
+            .contains("not valid JSON"));
+        assert_eq!(
+            parse_filter(Some(r#"["status"]"#)).unwrap_err(),
+            "filter must be a JSON object"
+        );
+    }
+
+    mod slow_backend {
+        use std::sync::Mutex;
+        use std::time::Duration;
+
+        use futures::stream::BoxStream;
+        use proto::influxdb_service::{
+            influx_db_service_server::{InfluxDbService, InfluxDbServiceServer},
+            DataPoint, DeleteResponse, ExportLine, QueryTypedResponse, RenameTagRequest,
+            RenameTagResponse, WriteLineProtocolRequest, WriteResponse,
+        };
+        use proto::postgres_service::{
+            postgres_service_client::PostgresServiceClient,
+            postgres_service_server::{PostgresService, PostgresServiceServer},
+            BatchCreateResponse, BatchReadRequest, BatchReadResponse, CountResponse, CreateResponse,
+            DeleteResponse as PgDeleteResponse, ListResponse, PurgeRequest, PurgeResponse,
+            ReadResponse, RestoreRequest, RestoreResponse,
+        };
+        use proto::supervisor_service::supervisor_service_client::SupervisorServiceClient;
+        use proto::influxdb_service::influx_db_service_client::InfluxDbServiceClient;
+        use tonic::transport::{server::TcpIncoming, Channel, Server};
+        use tonic::{Request, Response, Status};
+
+        use super::*;
+        use crate::grpc_retry::RetryPolicy;
+
+        /// Answers `Query` at once, or after 2s for measurement `slow`, and
+        /// records each request's `grpc-timeout` and request id.
+        #[derive(Default, Clone)]
+        struct SlowInflux {
+            timeouts: Arc<Mutex<Vec<String>>>,
+            request_ids: Arc<Mutex<Vec<Option<String>>>>,
+        }
+
+        #[tonic::async_trait]
+        impl InfluxDbService for SlowInflux {
+            type QueryStreamStream = BoxStream<'static, Result<DataPoint, Status>>;
+            type ExportStream = BoxStream<'static, Result<ExportLine, Status>>;
+
+            async fn query(&self, request: Request<QueryRequest>) -> Result<Response<QueryResponse>, Status> {
+                let timeout = request.metadata().get("grpc-timeout").map(|v| v.to_str().unwrap().to_string());
+                self.timeouts.lock().unwrap().push(timeout.unwrap_or_default());
+                let request_id = request.extensions().get::<::request_id::RequestId>();
+                self.request_ids.lock().unwrap().push(request_id.map(|id| id.to_string()));
+                if request.get_ref().measurement == "slow" {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+                Ok(Response::new(QueryResponse { success: true, ..Default::default() }))
+            }
+
+            async fn write(&self, _: Request<WriteRequest>) -> Result<Response<WriteResponse>, Status> {
+                Err(Status::unimplemented("write"))
+            }
+            async fn write_line_protocol(
+                &self,
+                _: Request<WriteLineProtocolRequest>,
+            ) -> Result<Response<WriteResponse>, Status> {
+                Err(Status::unimplemented("write_line_protocol"))
+            }
+            async fn query_typed(&self, _: Request<QueryRequest>) -> Result<Response<QueryTypedResponse>, Status> {
+                Err(Status::unimplemented("query_typed"))
+            }
+            async fn query_stream(&self, _: Request<QueryRequest>) -> Result<Response<Self::QueryStreamStream>, Status> {
+                Err(Status::unimplemented("query_stream"))
+            }
+            /// One line per unit of `limit`, or an error for measurement
+            /// `missing`.
+            async fn export(&self, request: Request<ExportRequest>) -> Result<Response<Self::ExportStream>, Status> {
+                let req = request.into_inner();
+                if req.measurement == "missing" {
+                    let failed = futures::stream::iter([Err(Status::invalid_argument("bad range"))]);
+                    return Ok(Response::new(failed.boxed()));
+                }
+                let lines = (0..req.limit).map(|i| ExportLine { json: format!("{{\"n\":{i}}}") }).map(Ok);
+                Ok(Response::new(futures::stream::iter(lines.collect::<Vec<_>>()).boxed()))
+            }
+            async fn delete(&self, _: Request<InfluxDeleteRequest>) -> Result<Response<DeleteResponse>, Status> {
+                Err(Status::unimplemented("delete"))
+            }
+            async fn rename_tag(&self, _: Request<RenameTagRequest>) -> Result<Response<RenameTagResponse>, Status> {
+                Err(Status::unimplemented("rename_tag"))
+            }
+        }
+
+        /// Coordinator state whose influx client talks to a [`SlowInflux`]
+        /// with a 200ms deadline and no retries.
+        async fn state() -> (Arc<AppState>, SlowInflux) {
+            let mock = SlowInflux::default();
+            let seen = mock.clone();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+            tokio::spawn(
+                Server::builder()
+                    .layer(::request_id::RequestIdLayer)
+                    .add_service(InfluxDbServiceServer::new(mock))
+                    .serve_with_incoming(incoming),
+            );
+
+            let channel = |url: String| Channel::from_shared(url).unwrap().connect_lazy();
+            let state = AppState {
+                pg_client: PostgresServiceClient::new(channel("http://127.0.0.1:1".into())),
+                influx_client: InfluxDbServiceClient::new(channel(format!("http://{addr}"))),
+                supervisor_client: SupervisorServiceClient::new(channel("http://127.0.0.1:1".into())),
+                db_pool: None,
+                dashboard_cache: None,
+                redactor: Default::default(),
+                status_feed: None,
+                metrics: metrics_exporter_prometheus::PrometheusBuilder::new()
+                    .build_recorder()
+                    .handle(),
+                grpc_retry: RetryPolicy { max_retries: 0, ..Default::default() },
+                rpc_timeout: Duration::from_millis(200),
+            };
+            (Arc::new(state), seen)
+        }
+
+        async fn query(state: &Arc<AppState>, measurement: &str) -> StatusCode {
+            let body = serde_json::from_value(serde_json::json!({
+                "measurement": measurement,
+                "start": "-1h",
+                "stop": "now()",
+            }))
+            .unwrap();
+            query_timeseries(State(state.clone()), HeaderMap::new(), ApiJson(body))
+                .await
+                .into_response()
+                .status()
+        }
+
+        #[tokio::test]
+        async fn slow_backend_times_out_with_504() {
+            let (state, mock) = state().await;
+            let started = std::time::Instant::now();
+            assert_eq!(query(&state, "slow").await, StatusCode::GATEWAY_TIMEOUT);
+            assert!(started.elapsed() < Duration::from_secs(1));
+
+            // The deadline travels with the call as `grpc-timeout`.
+            let sent = mock.timeouts.lock().unwrap().clone();
+            assert_eq!(sent.len(), 1);
+            assert!(
+                ["200m", "200000u", "200000000n"].contains(&sent[0].as_str()),
+                "grpc-timeout: {sent:?}"
+            );
+        }
+
+        #[tokio::test]
+        async fn fast_backend_is_unaffected() {
+            let (state, _) = state().await;
+            assert_eq!(query(&state, "fast").await, StatusCode::OK);
+        }
+
+        async fn export(state: &Arc<AppState>, measurement: &str, limit: u32) -> axum::response::Response {
+            let params = TimeSeriesExportQuery {
+                measurement: measurement.into(),
+                start: "-1h".into(),
+                stop: "now()".into(),
+                tag_filters: Some(r#"{"plant_id":"p1"}"#.into()),
+                limit,
+            };
+            export_timeseries(State(state.clone()), Query(params)).await
+        }
+
+        #[tokio::test]
+        async fn export_streams_ndjson_lines() {
+            let (state, _) = state().await;
+            let resp = export(&state, "plant_telemetry", 3).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(std::str::from_utf8(&body).unwrap(), "{\"n\":0}\n{\"n\":1}\n{\"n\":2}\n");
+        }
+
+        #[tokio::test]
+        async fn export_failing_before_first_line_is_an_http_error() {
+            let (state, _) = state().await;
+            assert_eq!(export(&state, "missing", 3).await.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn patch_rejects_non_object_payload_before_calling_backend() {
+            let (state, _) = state().await;
+            let body = serde_json::from_value(serde_json::json!({"payload": [1, 2]})).unwrap();
+            let resp = patch_structured(
+                State(state),
+                Path(("plants".to_string(), uuid::Uuid::new_v4().to_string())),
+                ApiJson(body),
+            )
+            .await
+            .into_response();
+            // The postgres client points nowhere; reaching it would not be a 400.
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn post_data_rejects_illegal_tag_keys_before_any_write() {
+            let (state, _) = state().await;
+            let body = serde_json::from_value(serde_json::json!({
+                "structured": [{"table": "plants", "payload": {"name": "fern"}}],
+                "timeseries": [{
+                    "measurement": "plant_telemetry",
+                    "tags": {"device uid": "esp32"},
+                    "fields": {"moisture_pct": 41.5}
+                }]
+            }))
+            .unwrap();
+            let resp = post_data(State(state), ApiJson(body)).await.into_response();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["invalid_keys"], serde_json::json!(["device uid"]));
+        }
+
+        #[tokio::test]
+        async fn post_data_is_502_when_both_backends_fail() {
+            // Postgres is unreachable and the mock does not implement write.
+            let (state, _) = state().await;
+            let body = serde_json::from_value(serde_json::json!({
+                "structured": [{"table": "plants", "payload": {"name": "fern"}}],
+                "timeseries": [{"measurement": "plant_telemetry", "fields": {"moisture_pct": 41.5}}]
+            }))
+            .unwrap();
+            let resp = post_data(State(state), ApiJson(body)).await.into_response();
+            assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["structured"][0]["success"], false);
+            assert_eq!(body["timeseries"]["success"], false);
+        }
+
+        /// Takes 2s to answer `Create`, counting each one it receives.
+        #[derive(Default, Clone)]
+        struct SlowPostgres {
+            creates: Arc<std::sync::atomic::AtomicU32>,
+        }
+
+        #[tonic::async_trait]
+        impl PostgresService for SlowPostgres {
+            async fn create(&self, _: Request<CreateRequest>) -> Result<Response<CreateResponse>, Status> {
+                self.creates.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                Ok(Response::new(CreateResponse { success: true, ..Default::default() }))
+            }
+            async fn batch_create(&self, _: Request<BatchCreateRequest>) -> Result<Response<BatchCreateResponse>, Status> {
+                Err(Status::unimplemented("batch_create"))
+            }
+            async fn read(&self, _: Request<ReadRequest>) -> Result<Response<ReadResponse>, Status> {
+                Err(Status::unimplemented("read"))
+            }
+            async fn batch_read(&self, _: Request<BatchReadRequest>) -> Result<Response<BatchReadResponse>, Status> {
+                Err(Status::unimplemented("batch_read"))
+            }
+            async fn list(&self, _: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+                Err(Status::unimplemented("list"))
+            }
+            async fn count(&self, _: Request<CountRequest>) -> Result<Response<CountResponse>, Status> {
+                Err(Status::unimplemented("count"))
+            }
+            async fn update(&self, _: Request<UpdateRequest>) -> Result<Response<UpdateResponse>, Status> {
+                Err(Status::unimplemented("update"))
+            }
+            async fn update_partial(&self, _: Request<UpdateRequest>) -> Result<Response<UpdateResponse>, Status> {
+                Err(Status::unimplemented("update_partial"))
+            }
+            async fn delete(&self, _: Request<PgDeleteRequest>) -> Result<Response<PgDeleteResponse>, Status> {
+                Err(Status::unimplemented("delete"))
+            }
+            async fn restore(&self, _: Request<RestoreRequest>) -> Result<Response<RestoreResponse>, Status> {
+                Err(Status::unimplemented("restore"))
+            }
+            async fn purge(&self, _: Request<PurgeRequest>) -> Result<Response<PurgeResponse>, Status> {
+                Err(Status::unimplemented("purge"))
+            }
+        }
+
+        #[tokio::test]
+        async fn create_that_times_out_is_not_sent_again() {
+            let mock = SlowPostgres::default();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+            tokio::spawn(
+                Server::builder()
+                    .add_service(PostgresServiceServer::new(mock.clone()))
+                    .serve_with_incoming(incoming),
+            );
+            let (state, _) = state().await;
+            let mut state = Arc::try_unwrap(state).ok().unwrap();
+            state.pg_client = PostgresServiceClient::new(
+                Channel::from_shared(format!("http://{addr}")).unwrap().connect_lazy(),
+            );
+            state.grpc_retry = RetryPolicy { max_retries: 3, base_delay: Duration::from_millis(1) };
+
+            let body = serde_json::from_value(serde_json::json!({
+                "structured": [{"table": "plants", "payload": {"name": "fern"}}]
+            }))
+            .unwrap();
+            let resp = post_data(State(Arc::new(state)), ApiJson(body)).await.into_response();
+            assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+            // The create may have gone through; retrying could insert twice.
+            assert_eq!(mock.creates.load(std::sync::atomic::Ordering::SeqCst), 1);
+        }
+
+        #[tokio::test]
+        async fn request_id_reaches_backend_extensions() {
+            use axum::{body::Body, middleware, routing::post, Router};
+            use tower::ServiceExt;
+
+            let (state, mock) = state().await;
+            let app = Router::new()
+                .route("/data/timeseries/query", post(query_timeseries))
+                .layer(middleware::from_fn(request_id::assign))
+                .with_state(state);
+            let body = serde_json::json!({"measurement": "fast", "start": "-1h", "stop": "now()"});
+            let resp = app
+                .oneshot(
+                    axum::http::Request::post("/data/timeseries/query")
+                        .header("content-type", "application/json")
+                        .header(::request_id::HEADER, "trace-me-42")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[::request_id::HEADER], "trace-me-42");
+            assert_eq!(*mock.request_ids.lock().unwrap(), vec![Some("trace-me-42".to_string())]);
+        }
+    }
+
+    mod summary {
+        use proto::influxdb_service::influx_db_service_client::InfluxDbServiceClient;
+        use proto::postgres_service::postgres_service_client::PostgresServiceClient;
+        use proto::supervisor_service::supervisor_service_client::SupervisorServiceClient;
+        use sqlx::postgres::PgPoolOptions;
+        use sqlx::{Executor, PgPool};
+        use tonic::transport::Channel;
+
+        use super::*;
+
+        const SCHEMA_SQL: &str =
+            include_str!("../../postgres-service/db/migrations/001_plant_health_schema.sql");
+
+        fn state(db_pool: Option<PgPool>) -> Arc<AppState> {
+            let channel = || Channel::from_static("http://127.0.0.1:1").connect_lazy();
+            Arc::new(AppState {
+                pg_client: PostgresServiceClient::new(channel()),
+                influx_client: InfluxDbServiceClient::new(channel()),
+                supervisor_client: SupervisorServiceClient::new(channel()),
+                db_pool,
+                dashboard_cache: None,
+                redactor: Default::default(),
+                status_feed: None,
+                metrics: metrics_exporter_prometheus::PrometheusBuilder::new()
+                    .build_recorder()
+                    .handle(),
+                grpc_retry: Default::default(),
+                rpc_timeout: std::time::Duration::from_secs(1),
+            })
+        }
+
+        /// A pool on a fresh schema of `TEST_DATABASE_URL`, so the counts
+        /// only see rows seeded by the test.
+        async fn test_pool() -> Option<PgPool> {
+            let url = std::env::var("TEST_DATABASE_URL").ok()?;
+            let schema = format!("summary_{}", uuid::Uuid::new_v4().simple());
+            let search_path = format!("SET search_path TO {schema}, public");
+            let pool = PgPoolOptions::new()
+                .max_connections(1)
+                .after_connect(move |conn, _| {
+                    let search_path = search_path.clone();
+                    Box::pin(async move {
+                        conn.execute(search_path.as_str()).await?;
+                        Ok(())
+                    })
+                })
+                .connect(&url)
+                .await
+                .expect("connect to TEST_DATABASE_URL");
+            pool.execute(format!("CREATE SCHEMA {schema}").as_str()).await.unwrap();
+            sqlx::raw_sql(SCHEMA_SQL).execute(&pool).await.expect("apply schema");
+            Some(pool)
+        }
+
+        async fn summary(state: Arc<AppState>) -> (StatusCode, serde_json::Value) {
+            let resp = dashboard_summary(State(state), Query(Default::default()))
+                .await
+                .into_response();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+
+        #[tokio::test]
+        async fn without_database_is_503() {
+            assert_eq!(summary(state(None)).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        async fn current(state: Arc<AppState>, plant_id: &str) -> (StatusCode, serde_json::Value) {
+            let resp = plant_current(State(state), Path(plant_id.to_string())).await.into_response();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+
+        #[tokio::test]
+        async fn current_returns_latest_readings_and_severity() {
+            let Some(pool) = test_pool().await else { return };
+            sqlx::raw_sql(
+                r#"
+                INSERT INTO plant_type (id, name) VALUES ('00000000-0000-0000-0000-000000000001', 'fern');
+                INSERT INTO plant (id, plant_type_id, display_name) VALUES
+                    ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-000000000001', 'desk fern'),
+                    ('00000000-0000-0000-0000-0000000000a2', '00000000-0000-0000-0000-000000000001', 'new fern');
+                INSERT INTO plant_current_state
+                    (plant_id, updated_at, severity, soil_moisture, ambient_temp_c, metric_severity)
+                VALUES ('00000000-0000-0000-0000-0000000000a1', '2024-05-01T10:00:00Z', 'WARN', 24.5, 21.0,
+                        '{"soil_moisture": "WARN", "ambient_temp_c": "NORMAL"}');
+                "#,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            let state = state(Some(pool));
+
+            let (status, body) = current(state.clone(), "00000000-0000-0000-0000-0000000000a1").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                body,
+                serde_json::json!({
+                    "plant_id": "00000000-0000-0000-0000-0000000000a1",
+                    "display_name": "desk fern",
+                    "severity": "WARN",
+                    "updated_at": "2024-05-01T10:00:00+00:00",
+                    "readings": {"soil_moisture": 24.5, "ambient_temp_c": 21.0},
+                    "metric_severity": {"soil_moisture": "WARN", "ambient_temp_c": "NORMAL"},
+                })
+            );
+
+            // Registered but never reported.
+            let (status, body) = current(state, "00000000-0000-0000-0000-0000000000a2").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!((body["severity"].is_null(), body["readings"].clone()), (true, serde_json::json!({})));
+        }
+
+        #[tokio::test]
+        async fn current_of_unknown_or_inactive_plant_is_404() {
+            let Some(pool) = test_pool().await else { return };
+            sqlx::raw_sql(
+                r#"
+                INSERT INTO plant_type (id, name) VALUES ('00000000-0000-0000-0000-000000000001', 'fern');
+                INSERT INTO plant (id, plant_type_id, display_name, is_active) VALUES
+                    ('00000000-0000-0000-0000-0000000000a5', '00000000-0000-0000-0000-000000000001', 'retired', FALSE);
+                INSERT INTO plant_current_state (plant_id, severity) VALUES
+                    ('00000000-0000-0000-0000-0000000000a5', 'CRITICAL');
+                "#,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            let state = state(Some(pool));
+
+            for plant_id in ["00000000-0000-0000-0000-0000000000a5", "00000000-0000-0000-0000-0000000000ff"] {
+                let (status, body) = current(state.clone(), plant_id).await;
+                assert_eq!(status, StatusCode::NOT_FOUND, "{plant_id}");
+                assert!(body["error"].is_string());
+            }
+            assert_eq!(current(state, "not-a-uuid").await.0, StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn counts_active_plants_by_severity_and_devices_by_liveness() {
+            let Some(pool) = test_pool().await else { return };
+            sqlx::raw_sql(
+                r#"
+                INSERT INTO plant_type (id, name) VALUES ('00000000-0000-0000-0000-000000000001', 'fern');
+                INSERT INTO plant (id, plant_type_id, display_name, is_active) VALUES
+                    ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-000000000001', 'a', TRUE),
+                    ('00000000-0000-0000-0000-0000000000a2', '00000000-0000-0000-0000-000000000001', 'b', TRUE),
+                    ('00000000-0000-0000-0000-0000000000a3', '00000000-0000-0000-0000-000000000001', 'c', TRUE),
+                    ('00000000-0000-0000-0000-0000000000a4', '00000000-0000-0000-0000-000000000001', 'd', TRUE),
+                    ('00000000-0000-0000-0000-0000000000a5', '00000000-0000-0000-0000-000000000001', 'retired', FALSE);
+                INSERT INTO plant_current_state (plant_id, severity) VALUES
+                    ('00000000-0000-0000-0000-0000000000a1', 'CRITICAL'),
+                    ('00000000-0000-0000-0000-0000000000a2', 'WARN'),
+                    ('00000000-0000-0000-0000-0000000000a3', 'WARN'),
+                    ('00000000-0000-0000-0000-0000000000a4', 'NORMAL'),
+                    ('00000000-0000-0000-0000-0000000000a5', 'CRITICAL');
+                INSERT INTO device (device_uid, last_seen_at, is_active) VALUES
+                    ('online',       NOW(),                       TRUE),
+                    ('offline',      NOW() - INTERVAL '1 hour',   TRUE),
+                    ('never-seen',   NULL,                        TRUE),
+                    ('decommissioned', NOW(),                     FALSE);
+                "#,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+            let (status, body) = summary(state(Some(pool))).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                body,
+                serde_json::json!({
+                    "plants": {
+                        "by_severity": {"NORMAL": 1, "WARN": 2, "CRITICAL": 1, "STALE": 0},
+                        "total": 4,
+                    },
+                    "devices": {"online": 1, "offline": 2, "total": 3},
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn history_since_is_rfc3339() {
+        assert_eq!(parse_since(None).unwrap(), None);
+        assert_eq!(
+            parse_since(Some("2024-05-01T12:00:00+02:00")).unwrap().unwrap().to_rfc3339(),
+            "2024-05-01T10:00:00+00:00"
+        );
+        assert!(parse_since(Some("yesterday")).is_err());
+    }
+
+    #[test]
+    fn update_outcomes_map_to_status() {
+        let (status, Json(body)) = update_response(UpdateResponse {
+            success: true,
+            version: 3,
+            ..Default::default()
+        });
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], 3);
+
+        let (status, Json(body)) = update_response(UpdateResponse {
+            success: false,
+            error: "version conflict: record is at version 4".into(),
+            conflict: true,
+            version: 4,
+        });
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["current_version"], 4);
+
+        let (status, _) = update_response(UpdateResponse {
+            success: false,
+            error: "record not found".into(),
+            ..Default::default()
+        });
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn empty_successful_query_is_200_with_empty_points() {
+        let (status, Json(body)) = query_response(QueryResponse {
+            points: vec![],
+            success: true,
+            error: String::new(),
+            error_code: ErrorCode::Unspecified as i32,
+            next_cursor_ns: 0,
+        });
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["points"], serde_json::json!([]));
+        assert_eq!(body["success"], true);
+        assert_eq!(body["next_cursor"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn full_page_surfaces_next_cursor() {
+        let (status, Json(body)) = query_response(QueryResponse {
+            success: true,
+            next_cursor_ns: 1_700_000_000_000_000_000,
+            ..Default::default()
+        });
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["next_cursor"], 1_700_000_000_000_000_000_i64);
+    }
+
+    #[test]
+    fn backend_errors_map_to_http_status() {
+        let cases = [
+            (ErrorCode::InvalidArgument, StatusCode::BAD_REQUEST),
+            (ErrorCode::Backend, StatusCode::BAD_GATEWAY),
+            (ErrorCode::Unavailable, StatusCode::SERVICE_UNAVAILABLE),
+            (ErrorCode::Unspecified, StatusCode::BAD_GATEWAY),
+        ];
+        for (code, expected) in cases {
+            let (status, Json(body)) = query_response(QueryResponse {
+                points: vec![],
+                success: false,
+                error: "boom".into(),
+                error_code: code as i32,
+                next_cursor_ns: 0,
+            });
+            assert_eq!(status, expected, "{code:?}");
+            assert_eq!(body, serde_json::json!({"error": "boom"}));
+        }
+    }
+
+    #[test]
+    fn grpc_status_codes_map_to_http_status() {
+        assert_eq!(
+            grpc_status_to_http(&tonic::Status::invalid_argument("bad")),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            grpc_status_to_http(&tonic::Status::unavailable("down")),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            grpc_status_to_http(&tonic::Status::internal("oops")),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn admin_rpc_results_map_to_http() {
+        let (status, Json(body)) = created(
+            Ok(tonic::Response::new("abc".to_string())),
+            |id| id,
+        );
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, serde_json::json!({"id": "abc"}));
+
+        let (status, Json(body)) = created::<String>(
+            Err(tonic::Status::invalid_argument("plant_type x does not exist")),
+            |id| id,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "plant_type x does not exist");
+
+        let (status, _) = created::<String>(Err(tonic::Status::already_exists("dup")), |id| id);
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+}
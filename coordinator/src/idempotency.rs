@@ -0,0 +1,429 @@
+//! `Idempotency-Key` support for non-idempotent writes (`POST /data`).
+//!
+//! The first request carrying a key runs normally and its response is kept
+//! for `COORDINATOR_IDEMPOTENCY_TTL_SECS` (default 86400, `0` disables).  A
+//! repeat with the same key on the same endpoint gets that response back,
+//! marked `Idempotent-Replayed: true`, without running again; a repeat while
+//! the first is still running answers 409.  5xx responses are not kept, so
+//! retrying after a backend failure runs the request again.
+//!
+//! Keys live in the `idempotency` table (migration `017_idempotency.sql`),
+//! shared by every coordinator instance: a request claims its key with an
+//! `INSERT ... ON CONFLICT DO NOTHING` before anything downstream runs, and
+//! [`spawn_pruner`] deletes expired rows.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Request header carrying the client's key.
+pub const HEADER: &str = "idempotency-key";
+/// Response header marking a replayed response.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+/// Longest accepted key.
+pub const MAX_KEY_LEN: usize = 255;
+/// How often [`spawn_pruner`] deletes expired keys.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// What is kept of a response.
+#[derive(Debug, Clone)]
+struct Stored {
+    status: StatusCode,
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+/// `(status, content_type, body)` of a row; `status` is NULL while in flight.
+type StoredRow = (Option<i16>, Option<String>, Option<Vec<u8>>);
+
+/// How to handle a request with a key.
+#[derive(Debug)]
+enum Begin {
+    /// First (or expired) use: the key is now claimed, run it.
+    Run,
+    /// Seen before: answer with the kept response.
+    Replay(Stored),
+    /// The first request with this key has not finished yet.
+    InProgress,
+}
+
+/// Kept responses keyed by `(key, route)` in the `idempotency` table.
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    pool: PgPool,
+    ttl: chrono::Duration,
+}
+
+impl IdempotencyStore {
+    pub fn new(pool: PgPool, ttl: Duration) -> Self {
+        Self {
+            pool,
+            // Beyond a century is as good as forever.
+            ttl: chrono::Duration::from_std(ttl)
+                .unwrap_or(chrono::Duration::MAX)
+                .min(chrono::Duration::days(36_500)),
+        }
+    }
+
+    /// Build from `COORDINATOR_IDEMPOTENCY_TTL_SECS`; `None` when it is `0`.
+    pub fn from_env(pool: PgPool) -> Option<Self> {
+        let secs = std::env::var("COORDINATOR_IDEMPOTENCY_TTL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(86_400);
+        (secs > 0).then(|| Self::new(pool, Duration::from_secs(secs)))
+    }
+
+    async fn begin(&self, route: &str, key: &str, now: DateTime<Utc>) -> Result<Begin, sqlx::Error> {
+        // An expired row, kept or abandoned, no longer holds the key.
+        sqlx::query("DELETE FROM idempotency WHERE key = $1 AND route = $2 AND expires_at <= $3")
+            .bind(key)
+            .bind(route)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let claimed = sqlx::query(
+            "INSERT INTO idempotency (key, route, expires_at) VALUES ($1, $2, $3) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(key)
+        .bind(route)
+        .bind(now + self.ttl)
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            == 1;
+        if claimed {
+            return Ok(Begin::Run);
+        }
+
+        let row: Option<StoredRow> = sqlx::query_as(
+            "SELECT status, content_type, body FROM idempotency WHERE key = $1 AND route = $2",
+        )
+        .bind(key)
+        .bind(route)
+        .fetch_optional(&self.pool)
+        .await?;
+        match row {
+            Some((Some(status), content_type, body)) => Ok(Begin::Replay(Stored {
+                status: StatusCode::from_u16(status as u16)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                content_type: content_type.and_then(|v| HeaderValue::from_str(&v).ok()),
+                body: body.map(Bytes::from).unwrap_or_default(),
+            })),
+            // Still running, or it just failed and let go of the key; either
+            // way a retry sorts it out.
+            _ => Ok(Begin::InProgress),
+        }
+    }
+
+    /// Keep `response` for the key, or forget the claim when `None`.
+    async fn finish(
+        &self,
+        route: &str,
+        key: &str,
+        response: Option<Stored>,
+        now: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        match response {
+            Some(response) => {
+                sqlx::query(
+                    "UPDATE idempotency SET status = $3, content_type = $4, body = $5, \
+                     expires_at = $6 WHERE key = $1 AND route = $2",
+                )
+                .bind(key)
+                .bind(route)
+                .bind(response.status.as_u16() as i16)
+                .bind(response.content_type.as_ref().and_then(|v| v.to_str().ok()))
+                .bind(response.body.as_ref())
+                .bind(now + self.ttl)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query(
+                    "DELETE FROM idempotency WHERE key = $1 AND route = $2 AND status IS NULL",
+                )
+                .bind(key)
+                .bind(route)
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete keys that expired by `now`; returns how many.
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        Ok(sqlx::query("DELETE FROM idempotency WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?
+            .rows_affected())
+    }
+}
+
+/// Delete expired keys every few minutes.
+pub fn spawn_pruner(store: Arc<IdempotencyStore>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match store.prune(Utc::now()).await {
+                Ok(pruned) => debug!(pruned, "pruned expired idempotency keys"),
+                Err(e) => warn!(error = %e, "failed to prune idempotency keys"),
+            }
+        }
+    })
+}
+
+/// Forgets a claimed key if the request is dropped (e.g. the client
+/// disconnected) before it finished, so a retry is not stuck on 409.
+struct Reservation {
+    store: Arc<IdempotencyStore>,
+    route: String,
+    key: String,
+    finished: bool,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let store = self.store.clone();
+        let route = std::mem::take(&mut self.route);
+        let key = std::mem::take(&mut self.key);
+        tokio::spawn(async move {
+            if let Err(e) = store.finish(&route, &key, None, Utc::now()).await {
+                warn!(error = %e, key, "failed to release idempotency key");
+            }
+        });
+    }
+}
+
+/// Middleware replaying responses for repeated `Idempotency-Key`s; requests
+/// without the header pass straight through.
+pub async fn guard(
+    State(store): State<Arc<IdempotencyStore>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = req.headers().get(HEADER) else {
+        return next.run(req).await;
+    };
+    let key = match key.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": format!("{HEADER} must be 1-{MAX_KEY_LEN} visible characters")
+                })),
+            )
+                .into_response();
+        }
+    };
+    let route = format!("{} {}", req.method(), req.uri().path());
+
+    match store.begin(&route, &key, Utc::now()).await {
+        Ok(Begin::Run) => {}
+        Ok(Begin::Replay(stored)) => return replay(stored),
+        Err(e) => {
+            warn!(error = %e, "idempotency store unavailable");
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({"error": format!("idempotency store unavailable: {e}")})),
+            )
+                .into_response();
+        }
+        Ok(Begin::InProgress) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "error": "a request with this Idempotency-Key is still in progress"
+                })),
+            )
+                .into_response();
+        }
+    }
+
+    let mut reservation =
+        Reservation { store: store.clone(), route: route.clone(), key: key.clone(), finished: false };
+    let response = next.run(req).await;
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": format!("failed to read response: {e}")})),
+            )
+                .into_response();
+        }
+    };
+    let kept = (!parts.status.is_server_error()).then(|| Stored {
+        status: parts.status,
+        content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+        body: body.clone(),
+    });
+    match store.finish(&route, &key, kept, Utc::now()).await {
+        Ok(()) => reservation.finished = true,
+        // Dropping the reservation tries to let go of the key instead.
+        Err(e) => warn!(error = %e, key, "failed to store idempotent response"),
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: Stored) -> Response {
+    let mut response = (stored.status, stored.body).into_response();
+    let headers = response.headers_mut();
+    match stored.content_type {
+        Some(content_type) => headers.insert(header::CONTENT_TYPE, content_type),
+        None => headers.remove(header::CONTENT_TYPE),
+    };
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{middleware, routing::post, Router};
+    use sqlx::postgres::PgPoolOptions;
+    use sqlx::Executor;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const SCHEMA_SQL: &str =
+        include_str!("../../postgres-service/db/migrations/017_idempotency.sql");
+
+    /// A store on a fresh schema of `TEST_DATABASE_URL`.
+    async fn test_store(ttl: Duration) -> Option<Arc<IdempotencyStore>> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let schema = format!("idempotency_{}", uuid::Uuid::new_v4().simple());
+        let search_path = format!("SET search_path TO {schema}, public");
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .after_connect(move |conn, _| {
+                let search_path = search_path.clone();
+                Box::pin(async move {
+                    conn.execute(search_path.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await
+            .expect("connect to TEST_DATABASE_URL");
+        pool.execute(format!("CREATE SCHEMA {schema}").as_str()).await.unwrap();
+        sqlx::raw_sql(SCHEMA_SQL).execute(&pool).await.expect("apply schema");
+        Some(Arc::new(IdempotencyStore::new(pool, ttl)))
+    }
+
+    /// `POST /data` and `POST /other` answering with how often they ran.
+    fn app(store: Arc<IdempotencyStore>, runs: Arc<AtomicUsize>) -> Router {
+        let handler = move || {
+            let runs = runs.clone();
+            async move {
+                let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                Json(serde_json::json!({ "run": n }))
+            }
+        };
+        Router::new()
+            .route("/data", post(handler.clone()))
+            .route("/other", post(handler))
+            .layer(middleware::from_fn_with_state(store, guard))
+    }
+
+    async fn call(app: &Router, path: &str, key: Option<&str>) -> (StatusCode, bool, serde_json::Value) {
+        let mut req = axum::http::Request::post(path);
+        if let Some(key) = key {
+            req = req.header(HEADER, key);
+        }
+        let resp = app.clone().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = resp.status();
+        let replayed = resp.headers().contains_key(REPLAYED_HEADER);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, replayed, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn first_call_runs_and_duplicate_is_replayed() {
+        let Some(store) = test_store(Duration::from_secs(60)).await else { return };
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(store, runs.clone());
+
+        let first = call(&app, "/data", Some("k-1")).await;
+        assert_eq!(first, (StatusCode::OK, false, serde_json::json!({"run": 1})));
+        let repeat = call(&app, "/data", Some("k-1")).await;
+        assert_eq!(repeat, (StatusCode::OK, true, serde_json::json!({"run": 1})));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // Another key, another endpoint, or no key at all runs again.
+        assert_eq!(call(&app, "/data", Some("k-2")).await.2["run"], 2);
+        assert_eq!(call(&app, "/other", Some("k-1")).await.2["run"], 3);
+        assert_eq!(call(&app, "/data", None).await.2["run"], 4);
+    }
+
+    #[tokio::test]
+    async fn blank_key_is_rejected() {
+        // Rejected before the store is touched, so no database is needed.
+        let pool = PgPoolOptions::new().connect_lazy("postgres://127.0.0.1:1/none").unwrap();
+        let store = Arc::new(IdempotencyStore::new(pool, Duration::from_secs(60)));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(store, runs.clone());
+        assert_eq!(call(&app, "/data", Some(" ")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn expired_key_runs_again() {
+        let Some(store) = test_store(Duration::from_secs(60)).await else { return };
+        let now = Utc::now();
+        let stored = Stored { status: StatusCode::OK, content_type: None, body: Bytes::new() };
+        let begin = |at| store.begin("POST /data", "k", at);
+
+        assert!(matches!(begin(now).await.unwrap(), Begin::Run));
+        assert!(matches!(begin(now).await.unwrap(), Begin::InProgress));
+        store.finish("POST /data", "k", Some(stored), now).await.unwrap();
+        assert!(matches!(
+            begin(now + chrono::Duration::seconds(59)).await.unwrap(),
+            Begin::Replay(_)
+        ));
+        assert!(matches!(begin(now + chrono::Duration::seconds(60)).await.unwrap(), Begin::Run));
+    }
+
+    #[tokio::test]
+    async fn failed_request_is_forgotten() {
+        let Some(store) = test_store(Duration::from_secs(60)).await else { return };
+        let now = Utc::now();
+        assert!(matches!(store.begin("POST /data", "k", now).await.unwrap(), Begin::Run));
+        store.finish("POST /data", "k", None, now).await.unwrap();
+        assert!(matches!(store.begin("POST /data", "k", now).await.unwrap(), Begin::Run));
+    }
+
+    #[tokio::test]
+    async fn prune_deletes_only_expired_keys() {
+        let Some(store) = test_store(Duration::from_secs(60)).await else { return };
+        let now = Utc::now();
+        store.begin("POST /data", "old", now - chrono::Duration::seconds(61)).await.unwrap();
+        store.begin("POST /data", "new", now).await.unwrap();
+
+        assert_eq!(store.prune(now).await.unwrap(), 1);
+        assert!(matches!(store.begin("POST /data", "new", now).await.unwrap(), Begin::InProgress));
+    }
+}
@@ -0,0 +1,135 @@
+//! Request body limits and JSON bodies that fail with the API's error shape.
+//!
+//! Bodies larger than `COORDINATOR_MAX_BODY_BYTES` (default 2 MiB) are
+//! refused with 413 by [`tower_http::limit::RequestBodyLimitLayer`]; handlers
+//! take JSON through [`ApiJson`], so a body that is too large, malformed or
+//! of the wrong shape is answered `{"error": "..."}` like every other
+//! failure instead of axum's plain-text rejection.
+
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Largest accepted request body when `COORDINATOR_MAX_BODY_BYTES` is unset.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// `COORDINATOR_MAX_BODY_BYTES`, or [`DEFAULT_MAX_BODY_BYTES`] when unset or
+/// not a positive number.
+pub fn max_body_bytes_from_env() -> usize {
+    std::env::var("COORDINATOR_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+/// `Json<T>` whose rejections are `{"error": ...}` with axum's status: 400
+/// for malformed JSON, 413 for a body over the limit, 415 without a JSON
+/// content type and 422 for JSON of the wrong shape.
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => Err(error(rejection.status(), rejection.body_text())),
+        }
+    }
+}
+
+/// Middleware giving the limit layer's bare 413 (sent when `Content-Length`
+/// is already over the limit) the API's error shape.
+pub async fn json_too_large(State(max_bytes): State<usize>, req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json {
+        return response;
+    }
+    error(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("request body is larger than {max_bytes} bytes"),
+    )
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, extract::DefaultBodyLimit, middleware, routing::post, Router};
+    use tower::ServiceExt;
+    use tower_http::limit::RequestBodyLimitLayer;
+
+    use super::*;
+
+    /// `POST /echo` behind the same layers as the coordinator, limited to
+    /// `max_bytes`.
+    fn app(max_bytes: usize) -> Router {
+        Router::new()
+            .route("/echo", post(|ApiJson(body): ApiJson<serde_json::Value>| async { Json(body) }))
+            .layer(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(max_bytes))
+            .layer(middleware::from_fn_with_state(max_bytes, json_too_large))
+    }
+
+    async fn post_body(app: Router, body: Body, content_length: Option<usize>) -> (StatusCode, serde_json::Value) {
+        let mut req = axum::http::Request::post("/echo").header("content-type", "application/json");
+        if let Some(len) = content_length {
+            req = req.header("content-length", len);
+        }
+        let resp = app.oneshot(req.body(body).unwrap()).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn big_json(len: usize) -> String {
+        format!("{{\"pad\": \"{}\"}}", "x".repeat(len))
+    }
+
+    #[tokio::test]
+    async fn body_within_limit_is_accepted() {
+        let body = big_json(10);
+        let (status, echoed) = post_body(app(64), Body::from(body.clone()), Some(body.len())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(echoed["pad"], "x".repeat(10));
+    }
+
+    #[tokio::test]
+    async fn oversize_body_is_413_with_error_shape() {
+        let body = big_json(100);
+        let (status, resp) = post_body(app(64), Body::from(body.clone()), Some(body.len())).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(resp["error"].as_str().unwrap().contains("64 bytes"), "{resp}");
+
+        // Without a Content-Length the limit trips while reading the body.
+        let stream = futures::stream::iter([Ok::<_, std::io::Error>(big_json(100))]);
+        let (status, resp) = post_body(app(64), Body::from_stream(stream), None).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(resp["error"].is_string(), "{resp}");
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_400_with_error_shape() {
+        let body = "{\"pad\": ";
+        let (status, resp) = post_body(app(64), Body::from(body), Some(body.len())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(resp["error"].is_string(), "{resp}");
+    }
+}
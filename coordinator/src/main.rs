@@ -1,7 +1,8 @@
 //! Coordinator service — HTTP API gateway.
 //!
 //! Receives JSON requests from clients and fans them out to the appropriate
-//! backend gRPC services (`postgres-service` and/or `influxdb-service`).
+//! backend gRPC services (`postgres-service`, `influxdb-service`, and the
+//! `database-supervisor` admin RPCs).
 //! Internal communication uses serialised protobuf messages over gRPC.
 //!
 //! # Configuration
@@ -13,25 +14,54 @@
 //! | `COORDINATOR_ADDR`               | `0.0.0.0:8080`         |
 //! | `POSTGRES_SERVICE_ADDR`          | `http://[::1]:50051`   |
 //! | `INFLUXDB_SERVICE_ADDR`          | `http://[::1]:50052`   |
+//! | `SUPERVISOR_SERVICE_ADDR`        | `http://[::1]:50053`   |
+//! | `COORDINATOR_REDACT_KEYS`        | unset (no redaction)   |
+//! | `COORDINATOR_API_TOKEN`          | unset (no auth)        |
+//! | `COORDINATOR_RATE_LIMIT_RPS`     | unset (no limit)       |
+//! | `COORDINATOR_RATE_LIMIT_BURST`   | the RPS, rounded up    |
+//! | `AMQP_URL`                       | unset (no `/ws/status`) |
+//! | `BACKEND_RPC_TIMEOUT_MS`         | `5000`                 |
+//! | `COORDINATOR_GRPC_RETRIES`       | `3`                    |
+//! | `COORDINATOR_GRPC_RETRY_BASE_MS` | `100`                  |
+//! | `COORDINATOR_DASHBOARD_CACHE_TTL_SECS` | `60`             |
+//! | `COORDINATOR_MAX_BODY_BYTES`     | `2097152` (2 MiB)      |
+//! | `DB_CONNECT_ATTEMPTS`            | `10`                   |
+//! | `DB_CONNECT_BACKOFF_MS`          | `500`                  |
 
+mod auth;
+mod channel;
+mod compression;
+mod csv;
+mod dashboard_cache;
+mod deadline;
+mod grpc_retry;
 mod handlers;
+mod idempotency;
+mod json_body;
+mod metrics;
 mod models;
-mod secrets;
+mod rate_limit;
+mod request_id;
+mod status_feed;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use anyhow::Result;
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{delete, get, post},
     Router,
 };
 use proto::{
     influxdb_service::influx_db_service_client::InfluxDbServiceClient,
     postgres_service::postgres_service_client::PostgresServiceClient,
+    supervisor_service::supervisor_service_client::SupervisorServiceClient,
 };
 use tonic::transport::Channel;
-use tower_http::trace::TraceLayer;
-use tracing::info;
+use tower_http::{limit::RequestBodyLimitLayer, trace::TraceLayer};
+use tracing::{info, warn};
 
 // ------------------------------------------------------------------ //
 //  Shared application state                                           //
@@ -43,8 +73,23 @@ pub struct AppState {
     pub pg_client: PostgresServiceClient<Channel>,
     /// gRPC client stub for the InfluxDB service.
     pub influx_client: InfluxDbServiceClient<Channel>,
+    /// gRPC client stub for the database supervisor (admin registration).
+    pub supervisor_client: SupervisorServiceClient<Channel>,
     /// Direct Postgres connection pool for dashboard queries (optional).
     pub db_pool: Option<sqlx::PgPool>,
+    /// Per-plant dashboard responses, dropped on supervisor notifications
+    /// (only with `db_pool`).
+    pub dashboard_cache: Option<Arc<dashboard_cache::PlantCache>>,
+    /// Masks sensitive keys in request payloads before they are logged.
+    pub redactor: redact::Redactor,
+    /// RabbitMQ status-change feed behind `/ws/status` (optional).
+    pub status_feed: Option<Arc<status_feed::StatusFeed>>,
+    /// Renders the Prometheus scrape output for `/metrics`.
+    pub metrics: metrics_exporter_prometheus::PrometheusHandle,
+    /// Retries for postgres/influxdb calls hitting a restarting backend.
+    pub grpc_retry: grpc_retry::RetryPolicy,
+    /// Deadline for each postgres/influxdb call.
+    pub rpc_timeout: std::time::Duration,
 }
 
 // ------------------------------------------------------------------ //
@@ -64,73 +109,159 @@ async fn main() -> Result<()> {
         .init();
 
     // Resolve downstream service addresses (Bitwarden → env fallback).
-    let pg_addr = secrets::get_secret(
-        &std::env::var("BWS_POSTGRES_SERVICE_ADDR_ID")
-            .unwrap_or_else(|_| "postgres-service-addr".to_string()),
-        "POSTGRES_SERVICE_ADDR",
-    )
+    let pg_id = std::env::var("BWS_POSTGRES_SERVICE_ADDR_ID")
+        .unwrap_or_else(|_| "postgres-service-addr".to_string());
+    let influx_id = std::env::var("BWS_INFLUXDB_SERVICE_ADDR_ID")
+        .unwrap_or_else(|_| "influxdb-service-addr".to_string());
+    let supervisor_id = std::env::var("BWS_SUPERVISOR_SERVICE_ADDR_ID")
+        .unwrap_or_else(|_| "supervisor-service-addr".to_string());
+    let [pg_addr, influx_addr, supervisor_addr]: [Result<String>; 3] = secrets::get_secrets(&[
+        (pg_id.as_str(), "POSTGRES_SERVICE_ADDR"),
+        (influx_id.as_str(), "INFLUXDB_SERVICE_ADDR"),
+        (supervisor_id.as_str(), "SUPERVISOR_SERVICE_ADDR"),
+    ])
     .await
-    .unwrap_or_else(|_| "http://[::1]:50051".to_string());
+    .try_into()
+    .expect("one result per requested secret");
+    let pg_addr = pg_addr.unwrap_or_else(|_| "http://[::1]:50051".to_string());
+    let influx_addr = influx_addr.unwrap_or_else(|_| "http://[::1]:50052".to_string());
+    let supervisor_addr = supervisor_addr.unwrap_or_else(|_| "http://[::1]:50053".to_string());
 
-    let influx_addr = secrets::get_secret(
-        &std::env::var("BWS_INFLUXDB_SERVICE_ADDR_ID")
-            .unwrap_or_else(|_| "influxdb-service-addr".to_string()),
-        "INFLUXDB_SERVICE_ADDR",
-    )
-    .await
-    .unwrap_or_else(|_| "http://[::1]:50052".to_string());
+    info!(pg_addr, influx_addr, supervisor_addr, "connecting to backend services");
 
-    info!(pg_addr, influx_addr, "connecting to backend services");
-
-    let pg_channel = Channel::from_shared(pg_addr)?.connect_lazy();
-    let influx_channel = Channel::from_shared(influx_addr)?.connect_lazy();
+    let channels = channel::ChannelConfig::from_env();
+    let pg_channel = channels.connect_lazy(pg_addr)?;
+    let influx_channel = channels.connect_lazy(influx_addr)?;
+    let supervisor_channel = channels.connect_lazy(supervisor_addr)?;
 
     // Optionally connect directly to Postgres for dashboard queries.
     let db_pool = match std::env::var("DATABASE_URL").ok() {
         Some(url) => {
-            let pool = sqlx::postgres::PgPoolOptions::new()
-                .max_connections(5)
-                .connect(&url)
-                .await
-                .ok();
-            if pool.is_some() {
-                info!("Dashboard Postgres pool connected");
+            let pool = connect_retry::retry("Postgres", &connect_retry::Backoff::from_env(), || {
+                sqlx::postgres::PgPoolOptions::new().max_connections(5).connect(&url)
+            })
+            .await;
+            match pool {
+                Ok(pool) => {
+                    info!("Dashboard Postgres pool connected");
+                    Some(pool)
+                }
+                Err(e) => {
+                    warn!(error = %e, "dashboard Postgres pool unavailable; dashboard routes will answer 503");
+                    None
+                }
             }
-            pool
         }
         None => None,
     };
+    let dashboard_cache = db_pool.as_ref().and_then(|pool| {
+        let cache = Arc::new(dashboard_cache::PlantCache::from_env()?);
+        dashboard_cache::spawn_listener(pool.clone(), cache.clone());
+        Some(cache)
+    });
 
     let state = Arc::new(AppState {
         pg_client: PostgresServiceClient::new(pg_channel),
         influx_client: InfluxDbServiceClient::new(influx_channel),
+        supervisor_client: SupervisorServiceClient::new(supervisor_channel),
         db_pool,
+        dashboard_cache,
+        redactor: redact::Redactor::from_env(),
+        status_feed: status_feed::StatusFeed::from_env().map(Arc::new),
+        metrics: metrics::install()?,
+        grpc_retry: grpc_retry::RetryPolicy::from_env(),
+        rpc_timeout: deadline::timeout_from_env(),
     });
 
+    let auth = auth::ApiAuth::from_env();
+    if !auth.is_enabled() {
+        warn!("COORDINATOR_API_TOKEN is not set; the HTTP API is unauthenticated");
+    }
+
+    // Repeated `Idempotency-Key`s on POST /data replay the first response.
+    let idempotency = match state.db_pool.clone() {
+        Some(pool) => idempotency::IdempotencyStore::from_env(pool).map(Arc::new),
+        None => {
+            warn!("DATABASE_URL is not set; Idempotency-Key headers are ignored");
+            None
+        }
+    };
+    let post_data = match idempotency {
+        Some(store) => {
+            idempotency::spawn_pruner(store.clone());
+            post(handlers::post_data).layer(middleware::from_fn_with_state(store, idempotency::guard))
+        }
+        None => post(handlers::post_data),
+    };
+
     let app = Router::new()
         // Health check
         .route("/health", get(handlers::health))
+        // Prometheus scrape endpoint
+        .route("/metrics", get(handlers::metrics))
+        // JSON Schema of the HTTP models
+        .route("/schema", get(handlers::schema))
         // Combined data endpoint (structured + time-series in one request)
-        .route("/data", post(handlers::post_data))
+        .route("/data", post_data)
         // Structured (PostgreSQL) CRUD
         .route(
             "/data/structured/:table",
             get(handlers::list_structured),
         )
+        .route(
+            "/data/structured/:table/count",
+            get(handlers::count_structured),
+        )
         .route(
             "/data/structured/:table/:id",
             get(handlers::get_structured)
                 .put(handlers::update_structured)
+                .patch(handlers::patch_structured)
                 .delete(handlers::delete_structured),
         )
         // Time-series (InfluxDB) endpoints
         .route("/data/timeseries/query", post(handlers::query_timeseries))
+        .route("/data/timeseries/export", get(handlers::export_timeseries))
         .route("/data/timeseries", delete(handlers::delete_timeseries))
         // Dashboard endpoints
         .route("/dashboard/attention", get(handlers::dashboard_attention))
         .route("/dashboard/ticker", get(handlers::dashboard_ticker))
         .route("/dashboard/edges", get(handlers::dashboard_edges))
+        .route("/dashboard/summary", get(handlers::dashboard_summary))
+        .route("/dashboard/history/:plant_id", get(handlers::dashboard_history))
+        .route("/plants/:plant_id/current", get(handlers::plant_current))
+        // Admin registration (via database-supervisor)
+        .route("/admin/plant-types", post(handlers::create_plant_type))
+        .route("/admin/plants", post(handlers::create_plant))
+        .route("/admin/devices", post(handlers::create_device))
+        .route("/admin/ledger", get(handlers::query_ledger))
+        .route("/admin/plants/:plant_id/thresholds", get(handlers::effective_thresholds))
+        // Only wraps the routes above.
+        .layer(compression::layer())
+        // Live status changes (RabbitMQ → WebSocket)
+        .route("/ws/status", get(handlers::ws_status))
+        .layer(middleware::from_fn_with_state(auth, auth::require_token));
+
+    // Rate limiting runs before auth so token guessing is throttled too.
+    let app = match rate_limit::RateLimiter::from_env() {
+        Some(limiter) => {
+            info!("rate limiting mutating requests per client IP");
+            app.layer(middleware::from_fn_with_state(
+                Arc::new(limiter),
+                rate_limit::limit,
+            ))
+        }
+        None => app,
+    };
+    // One body limit for every route, answered in the API's error shape.
+    let max_body_bytes = json_body::max_body_bytes_from_env();
+    let app = app
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .layer(middleware::from_fn_with_state(max_body_bytes, json_body::json_too_large))
+        .layer(middleware::from_fn(metrics::track))
         .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn(request_id::assign))
         .with_state(state);
 
     let bind_addr = std::env::var("COORDINATOR_ADDR")
@@ -139,7 +270,32 @@ async fn main() -> Result<()> {
     let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
     info!(addr = bind_addr, "coordinator listening");
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
 
+    info!("coordinator stopped");
     Ok(())
 }
+
+/// Resolves on the first SIGTERM (sent by Kubernetes before SIGKILL) or
+/// Ctrl-C.  The SIGTERM handler is installed when this is called.
+fn shutdown_signal() -> impl std::future::Future<Output = ()> {
+    #[cfg(unix)]
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    async move {
+        #[cfg(unix)]
+        let term = term.recv();
+        #[cfg(not(unix))]
+        let term = std::future::pending::<Option<()>>();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term => {}
+        }
+        info!("shutdown signal received");
+    }
+}
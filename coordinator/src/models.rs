@@ -1,5 +1,6 @@
 //! HTTP request/response models for the coordinator's public REST API.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -8,7 +9,7 @@ use std::collections::HashMap;
 // ------------------------------------------------------------------ //
 
 /// A single structured record destined for PostgreSQL.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct StructuredRecord {
     /// Target table / collection name.
     pub table: String,
@@ -17,7 +18,7 @@ pub struct StructuredRecord {
 }
 
 /// A single time-series data point destined for InfluxDB.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct TimeSeriesPoint {
     pub measurement: String,
     #[serde(default)]
@@ -31,7 +32,7 @@ pub struct TimeSeriesPoint {
 /// Top-level request body accepted by `POST /data`.
 ///
 /// At least one of `structured` or `timeseries` must be present.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct DataRequest {
     /// One or more structured records to persist in PostgreSQL.
     pub structured: Option<Vec<StructuredRecord>>,
@@ -39,32 +40,244 @@ pub struct DataRequest {
     pub timeseries: Option<Vec<TimeSeriesPoint>>,
 }
 
-/// Request body for `PUT /data/structured/{table}/{id}`.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Query parameters for `GET /data/structured/{table}`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct ListStructuredQuery {
+    /// Page size, clamped to 1..=1000 (default 100).
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Records to skip, clamped to >= 0 (default 0).
+    #[serde(default)]
+    pub offset: Option<i64>,
+    /// JSON object the payload must contain (e.g. `{"status":"active"}`).
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Sort column: `created_at` (default) or `updated_at`.
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// Sort direction: `asc` or `desc` (default).
+    #[serde(default)]
+    pub dir: Option<String>,
+}
+
+/// Query parameters for `GET /data/structured/{table}/count`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct CountStructuredQuery {
+    /// Same as [`ListStructuredQuery::filter`].
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+/// Request body for `PUT` (replace) and `PATCH` (merge)
+/// `/data/structured/{table}/{id}`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct UpdateStructuredRequest {
     pub payload: serde_json::Value,
+    /// Version the client last read; a stale one is rejected with 409.
+    #[serde(default)]
+    pub version: Option<i64>,
 }
 
 /// Request body for `POST /data/timeseries/query`.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct TimeSeriesQueryRequest {
     pub measurement: String,
+    /// Further measurements queried together with `measurement`.
+    #[serde(default)]
+    pub measurements: Vec<String>,
     pub start: String,
     pub stop: String,
     #[serde(default)]
     pub tag_filters: HashMap<String, String>,
     #[serde(default)]
     pub limit: u32,
+    /// Optional aggregation window (Flux duration, e.g. `5m`).
+    #[serde(default)]
+    pub aggregate_window: String,
+    /// Optional aggregate function (`mean`, `max`, `min`, `sum`, `last`).
+    #[serde(default)]
+    pub aggregate_fn: String,
+    /// Cursor from a previous page's `next_cursor` (Unix ns).
+    #[serde(default)]
+    pub after_time_ns: Option<i64>,
+}
+
+/// Query parameters for `GET /data/timeseries/export`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct TimeSeriesExportQuery {
+    pub measurement: String,
+    pub start: String,
+    pub stop: String,
+    /// JSON object of tag key to expected value (e.g. `{"plant_id":"p1"}`).
+    #[serde(default)]
+    pub tag_filters: Option<String>,
+    /// Maximum number of records (default 0, unlimited).
+    #[serde(default)]
+    pub limit: u32,
 }
 
 /// Request body for `DELETE /data/timeseries`.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct DeleteTimeSeriesRequest {
+    /// Empty (or omitted) deletes every measurement in the range.
+    #[serde(default)]
     pub measurement: String,
     pub start: String,
     pub stop: String,
     #[serde(default)]
     pub tag_filters: HashMap<String, String>,
+    #[serde(default)]
+    pub confirm_full_range: bool,
+}
+
+/// Threshold bands for one metric, part of [`RegisterPlantTypeRequest`].
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ThresholdSpec {
+    pub metric: String,
+    pub warn_min: Option<f64>,
+    pub warn_max: Option<f64>,
+    pub crit_min: Option<f64>,
+    pub crit_max: Option<f64>,
+    #[serde(default)]
+    pub unit: String,
+    /// Optional smoothing before evaluation: `sma` or `ema`.
+    #[serde(default)]
+    pub smoothing: String,
+    /// Window for `sma` (>= 2).
+    #[serde(default)]
+    pub smoothing_window: u32,
+    /// Factor for `ema`, in (0, 1].
+    #[serde(default)]
+    pub smoothing_alpha: Option<f64>,
+    /// Margin a reading must recover by before the metric downgrades.
+    #[serde(default)]
+    pub hysteresis: Option<f64>,
+    /// Largest change per minute before the metric is WARN.
+    #[serde(default)]
+    pub max_rate_per_min: Option<f64>,
+    /// A reading without this metric escalates the plant.
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Request body for `POST /admin/plant-types`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RegisterPlantTypeRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub thresholds: Vec<ThresholdSpec>,
+}
+
+/// Query parameters for `GET /admin/ledger`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct LedgerQuery {
+    #[serde(default)]
+    pub device_uid: String,
+    #[serde(default)]
+    pub plant_id: String,
+    /// Reading time window in Unix ns, `[start_ns, stop_ns)`.
+    #[serde(default)]
+    pub start_ns: i64,
+    #[serde(default)]
+    pub stop_ns: i64,
+    #[serde(default)]
+    pub limit: u32,
+}
+
+/// Query parameters for `GET /dashboard/history/{plant_id}`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct SeverityHistoryQuery {
+    /// Only transitions at or after this RFC 3339 time.
+    #[serde(default)]
+    pub since: Option<String>,
+}
+
+/// Request body for `POST /admin/plants`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RegisterPlantRequest {
+    pub plant_type_id: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub device_id: String,
+}
+
+/// Request body for `POST /admin/devices`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RegisterDeviceRequest {
+    pub device_uid: String,
+    #[serde(default)]
+    pub firmware_version: String,
+    /// Expected reporting interval in seconds; enables cadence alerts.
+    #[serde(default)]
+    pub expected_interval_s: Option<u32>,
+    /// Allowed drift from `expected_interval_s`, in percent (default 20).
+    #[serde(default)]
+    pub interval_tolerance_pct: Option<u32>,
+}
+
+// ------------------------------------------------------------------ //
+//  Validation                                                         //
+// ------------------------------------------------------------------ //
+
+/// Names rejected by [`validate`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InvalidNames {
+    /// A measurement that must be set was empty.
+    pub empty_measurement: bool,
+    /// Tag or field keys not matching `[A-Za-z0-9_]+`, sorted, without
+    /// duplicates.
+    pub invalid_keys: Vec<String>,
+}
+
+impl std::fmt::Display for InvalidNames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut problems = Vec::new();
+        if self.empty_measurement {
+            problems.push("measurement must not be empty".to_string());
+        }
+        if !self.invalid_keys.is_empty() {
+            problems.push(format!(
+                "tag/field keys must match [A-Za-z0-9_]+: {}",
+                self.invalid_keys.join(", ")
+            ));
+        }
+        f.write_str(&problems.join("; "))
+    }
+}
+
+/// Check time-series names before they reach InfluxDB, where an empty
+/// measurement or a key with spaces or punctuation yields broken line
+/// protocol or Flux: every one of `measurements` must be non-blank and
+/// every one of `keys` must match `[A-Za-z0-9_]+`.
+pub fn validate<'a>(
+    measurements: impl IntoIterator<Item = &'a str>,
+    keys: impl IntoIterator<Item = &'a str>,
+) -> Result<(), InvalidNames> {
+    let empty_measurement = measurements.into_iter().any(|m| m.trim().is_empty());
+    let invalid_keys: std::collections::BTreeSet<&str> = keys
+        .into_iter()
+        .filter(|k| k.is_empty() || !k.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'))
+        .collect();
+    if !empty_measurement && invalid_keys.is_empty() {
+        return Ok(());
+    }
+    Err(InvalidNames {
+        empty_measurement,
+        invalid_keys: invalid_keys.into_iter().map(str::to_string).collect(),
+    })
+}
+
+impl TimeSeriesPoint {
+    /// The tag and field keys of this point.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.tags.keys().chain(self.fields.keys()).map(String::as_str)
+    }
 }
 
 // ------------------------------------------------------------------ //
@@ -72,7 +285,7 @@ pub struct DeleteTimeSeriesRequest {
 // ------------------------------------------------------------------ //
 
 /// Outcome of writing a single structured record.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct StructuredWriteResult {
     pub table: String,
     pub id: Option<String>,
@@ -81,7 +294,7 @@ pub struct StructuredWriteResult {
 }
 
 /// Combined response for `POST /data`.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct DataResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub structured: Option<Vec<StructuredWriteResult>>,
@@ -90,8 +303,109 @@ pub struct DataResponse {
 }
 
 /// Outcome of writing time-series data.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct TimeSeriesWriteResult {
     pub success: bool,
     pub error: Option<String>,
 }
+
+// ------------------------------------------------------------------ //
+//  JSON Schema                                                        //
+// ------------------------------------------------------------------ //
+
+/// JSON Schema of every public request body, query and response above, by
+/// type name, as served by `GET /schema`.
+pub fn json_schemas() -> serde_json::Value {
+    fn schema<T: JsonSchema>() -> (String, serde_json::Value) {
+        let root = schemars::schema_for!(T);
+        (T::schema_name(), serde_json::to_value(root).unwrap_or_default())
+    }
+
+    let schemas: serde_json::Map<String, serde_json::Value> = [
+        schema::<DataRequest>(),
+        schema::<DataResponse>(),
+        schema::<ListStructuredQuery>(),
+        schema::<CountStructuredQuery>(),
+        schema::<UpdateStructuredRequest>(),
+        schema::<TimeSeriesQueryRequest>(),
+        schema::<TimeSeriesExportQuery>(),
+        schema::<DeleteTimeSeriesRequest>(),
+        schema::<RegisterPlantTypeRequest>(),
+        schema::<RegisterPlantRequest>(),
+        schema::<RegisterDeviceRequest>(),
+        schema::<LedgerQuery>(),
+        schema::<SeverityHistoryQuery>(),
+    ]
+    .into_iter()
+    .collect();
+    schemas.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(measurement: &str, tag_key: &str) -> TimeSeriesPoint {
+        TimeSeriesPoint {
+            measurement: measurement.to_string(),
+            tags: [(tag_key.to_string(), "v".to_string())].into(),
+            fields: [("moisture_pct".to_string(), 41.5)].into(),
+            timestamp_ns: 0,
+        }
+    }
+
+    fn validate_points(points: &[TimeSeriesPoint]) -> Result<(), InvalidNames> {
+        validate(
+            points.iter().map(|p| p.measurement.as_str()),
+            points.iter().flat_map(TimeSeriesPoint::keys),
+        )
+    }
+
+    #[test]
+    fn valid_points_pass() {
+        assert_eq!(validate_points(&[point("plant_telemetry", "device_uid")]), Ok(()));
+    }
+
+    #[test]
+    fn empty_measurement_is_rejected() {
+        let err = validate_points(&[point("plant_telemetry", "zone"), point("  ", "zone")]).unwrap_err();
+        assert_eq!(err, InvalidNames { empty_measurement: true, invalid_keys: vec![] });
+        assert_eq!(err.to_string(), "measurement must not be empty");
+    }
+
+    #[test]
+    fn illegal_tag_keys_are_listed_once() {
+        let err = validate_points(&[
+            point("m", "device uid"),
+            point("m", "zone"),
+            point("m", "device uid"),
+            point("m", "room-1"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.invalid_keys, vec!["device uid", "room-1"]);
+        assert!(!err.empty_measurement);
+        assert_eq!(
+            err.to_string(),
+            "tag/field keys must match [A-Za-z0-9_]+: device uid, room-1"
+        );
+    }
+
+    #[test]
+    fn data_request_schema_documents_at_least_one_section() {
+        let schemas = json_schemas();
+        let data = &schemas["DataRequest"];
+        assert!(data["properties"]["structured"].is_object(), "{data}");
+        assert!(data["properties"]["timeseries"].is_object(), "{data}");
+        let required = data["required"].as_array().cloned().unwrap_or_default();
+        assert!(!required.contains(&"structured".into()), "{required:?}");
+        assert!(!required.contains(&"timeseries".into()), "{required:?}");
+        assert!(data["description"]
+            .as_str()
+            .unwrap()
+            .contains("At least one of `structured` or `timeseries` must be present"));
+
+        // Nested models come along as definitions.
+        assert!(data["definitions"]["TimeSeriesPoint"].is_object(), "{data}");
+        assert!(schemas["TimeSeriesQueryRequest"]["properties"]["measurement"].is_object());
+    }
+}
@@ -0,0 +1,239 @@
+//! Per-client rate limiting of mutating requests.
+//!
+//! Each client IP gets a token bucket holding up to `burst` tokens, refilled
+//! at `COORDINATOR_RATE_LIMIT_RPS` per second.  Every `POST`/`PUT`/`PATCH`/
+//! `DELETE` (other than read-only query routes) takes one token; an empty
+//! bucket answers 429 with `Retry-After`.  Reads and `/health` are never
+//! limited.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// POST routes that only read and are therefore not limited.
+const EXEMPT_PATHS: &[&str] = &["/health", "/data/timeseries/query"];
+
+/// Buckets tracked before idle, full ones are dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token buckets keyed by client IP.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Allow `rate_per_sec` sustained requests per client, with bursts of up
+    /// to `burst` (at least 1).
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        Self {
+            rate_per_sec,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Build from `COORDINATOR_RATE_LIMIT_RPS` and
+    /// `COORDINATOR_RATE_LIMIT_BURST` (default: the rate, rounded up).
+    /// `None` when the rate is unset or not positive.
+    pub fn from_env() -> Option<Self> {
+        let rate: f64 = std::env::var("COORDINATOR_RATE_LIMIT_RPS")
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+            .filter(|r: &f64| *r > 0.0)?;
+        let burst = std::env::var("COORDINATOR_RATE_LIMIT_BURST")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(rate.ceil() as u32);
+        Some(Self::new(rate, burst))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, Bucket>> {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take a token for `client` at `now`, or return how long until one is
+    /// available.
+    fn check(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.lock();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, b| self.refilled(b, now) < self.burst);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.rate_per_sec,
+            ))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate_per_sec).min(self.burst)
+    }
+}
+
+fn is_limited(req: &Request) -> bool {
+    matches!(
+        *req.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    ) && !EXEMPT_PATHS.contains(&req.uri().path())
+}
+
+/// Middleware applying [`RateLimiter`] to mutating requests.
+pub async fn limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !is_limited(&req) {
+        return next.run(req).await;
+    }
+    match limiter.check(addr.ip(), Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(serde_json::json!({ "error": "rate limit exceeded" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        extract::connect_info::MockConnectInfo,
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn request_beyond_burst_is_rejected() {
+        let limiter = RateLimiter::new(1.0, 3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check(CLIENT, now).is_ok());
+        }
+        let wait = limiter.check(CLIENT, now).unwrap_err();
+        assert!(
+            wait > Duration::ZERO && wait <= Duration::from_secs(1),
+            "{wait:?}"
+        );
+
+        // Other clients have their own bucket.
+        assert!(limiter.check("10.0.0.2".parse().unwrap(), now).is_ok());
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limiter = RateLimiter::new(2.0, 2);
+        let start = Instant::now();
+        assert!(limiter.check(CLIENT, start).is_ok());
+        assert!(limiter.check(CLIENT, start).is_ok());
+        assert!(limiter.check(CLIENT, start).is_err());
+
+        // Half a second at 2/s buys exactly one more request.
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.check(CLIENT, later).is_ok());
+        assert!(limiter.check(CLIENT, later).is_err());
+
+        // A long pause refills only up to the burst.
+        let much_later = later + Duration::from_secs(60);
+        assert!(limiter.check(CLIENT, much_later).is_ok());
+        assert!(limiter.check(CLIENT, much_later).is_ok());
+        assert!(limiter.check(CLIENT, much_later).is_err());
+    }
+
+    #[test]
+    fn poisoned_lock_does_not_stop_limiting() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _held = limiter.lock();
+            panic!("holder panicked");
+        }));
+        assert!(limiter.buckets.is_poisoned());
+
+        let now = Instant::now();
+        assert!(limiter.check(CLIENT, now).is_ok());
+        assert!(limiter.check(CLIENT, now).is_err());
+    }
+
+    fn app(limiter: RateLimiter) -> Router {
+        Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/data", post(|| async { "written" }))
+            .layer(middleware::from_fn_with_state(Arc::new(limiter), limit))
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 5000))))
+    }
+
+    async fn send(app: &Router, method: Method, path: &str) -> Response {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn over_limit_answers_429_with_retry_after() {
+        let app = app(RateLimiter::new(0.5, 2));
+        assert_eq!(
+            send(&app, Method::POST, "/data").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&app, Method::POST, "/data").await.status(),
+            StatusCode::OK
+        );
+
+        let resp = send(&app, Method::POST, "/data").await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "2");
+
+        // Reads are exempt.
+        assert_eq!(
+            send(&app, Method::GET, "/health").await.status(),
+            StatusCode::OK
+        );
+    }
+}
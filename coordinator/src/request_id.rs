@@ -0,0 +1,98 @@
+//! Per-request correlation ids.
+//!
+//! [`assign`] gives every HTTP request an id — the client's `x-request-id`
+//! if usable, a fresh UUID otherwise — logs the request inside a span
+//! carrying it, echoes it in the response, and makes it available to
+//! [`outgoing`] so backend gRPC calls forward it as `x-request-id` metadata.
+
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use ::request_id::{RequestId, HEADER};
+use tracing::Instrument;
+
+tokio::task_local! {
+    /// Id of the HTTP request whose handler is running.
+    static CURRENT: RequestId;
+}
+
+/// Middleware assigning the request id; outermost, so every log line and
+/// every response (including 401/429 rejections) carries it.
+pub async fn assign(mut req: Request, next: Next) -> Response {
+    let id = RequestId::from_headers(req.headers());
+    req.extensions_mut().insert(id.clone());
+
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut response = CURRENT.scope(id.clone(), next.run(req)).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+        response.headers_mut().insert(HEADER, value);
+    }
+    response
+}
+
+/// `message` as a gRPC request carrying the current HTTP request's id
+/// (none outside a request, e.g. in background tasks).
+pub fn outgoing<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    if let Ok(id) = CURRENT.try_with(RequestId::clone) {
+        id.attach(&mut request);
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Answers with the `x-request-id` an outgoing gRPC call would carry.
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    outgoing(())
+                        .metadata()
+                        .get(HEADER)
+                        .map(|v| v.to_str().unwrap().to_string())
+                        .unwrap_or_default()
+                }),
+            )
+            .layer(middleware::from_fn(assign))
+    }
+
+    async fn call(header: Option<&str>) -> (String, String) {
+        let mut req = axum::http::Request::builder().uri("/");
+        if let Some(value) = header {
+            req = req.header(HEADER, value);
+        }
+        let resp = app().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let echoed = resp.headers()[HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (echoed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn provided_id_is_echoed_and_forwarded() {
+        let (echoed, forwarded) = call(Some("client-42")).await;
+        assert_eq!(echoed, "client-42");
+        assert_eq!(forwarded, "client-42");
+    }
+
+    #[tokio::test]
+    async fn missing_id_is_generated_once() {
+        let (echoed, forwarded) = call(None).await;
+        assert!(uuid::Uuid::parse_str(&echoed).is_ok(), "{echoed}");
+        assert_eq!(forwarded, echoed);
+    }
+
+    #[test]
+    fn no_id_outside_a_request() {
+        assert!(outgoing(()).metadata().get(HEADER).is_none());
+    }
+}
@@ -0,0 +1,318 @@
+//! Live plant status changes for `GET /ws/status`.
+//!
+//! The supervisor publishes `PlantStatusChanged.v1` to the `plant.status`
+//! fanout exchange.  While at least one WebSocket client is connected, the
+//! coordinator binds its own server-named, exclusive, auto-delete queue to
+//! that exchange, so every replica sees every change and nothing piles up
+//! while no one is watching.  A single consumer on that queue fans each
+//! message out to every client as a JSON text frame; it reconnects to
+//! RabbitMQ with exponential backoff and is cancelled (dropping the queue)
+//! when the last client disconnects.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::extract::ws::{Message, WebSocket};
+use futures::StreamExt;
+use lapin::{
+    options::{
+        BasicAckOptions, BasicConsumeOptions, ExchangeDeclareOptions, QueueBindOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+    Connection, ConnectionProperties, ExchangeKind,
+};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Fanout exchange the supervisor publishes status changes to.
+pub const STATUS_EXCHANGE: &str = "plant.status";
+
+/// Messages buffered per client before a slow one starts skipping.
+const CLIENT_BUFFER: usize = 256;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Shared AMQP connection state behind the WebSocket endpoint.
+pub struct StatusFeed {
+    amqp_url: String,
+    tx: broadcast::Sender<String>,
+    /// Consumer task; running only while clients are subscribed.
+    consumer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl StatusFeed {
+    pub fn new(amqp_url: String) -> Self {
+        let (tx, _) = broadcast::channel(CLIENT_BUFFER);
+        Self {
+            amqp_url,
+            tx,
+            consumer: Mutex::new(None),
+        }
+    }
+
+    /// Build from `AMQP_URL`.  `None` disables the endpoint.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("AMQP_URL").ok().map(Self::new)
+    }
+
+    /// Receive status changes, starting the queue consumer if needed.
+    pub fn subscribe(self: &Arc<Self>) -> Subscription {
+        let mut consumer = self.consumer.lock().unwrap_or_else(|e| e.into_inner());
+        let rx = self.tx.subscribe();
+        if consumer.is_none() {
+            *consumer = Some(tokio::spawn(consume_forever(
+                self.amqp_url.clone(),
+                self.tx.clone(),
+            )));
+        }
+        Subscription {
+            rx,
+            feed: self.clone(),
+        }
+    }
+}
+
+/// One client's view of the feed; dropping the last one stops the consumer.
+pub struct Subscription {
+    rx: broadcast::Receiver<String>,
+    feed: Arc<StatusFeed>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut consumer = self.feed.consumer.lock().unwrap_or_else(|e| e.into_inner());
+        // Our own receiver is still alive here.
+        if self.feed.tx.receiver_count() <= 1 {
+            if let Some(task) = consumer.take() {
+                debug!("last status client gone; stopping consumer");
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Forward status changes to `socket` until either side goes away.
+pub async fn forward(mut socket: WebSocket, feed: Arc<StatusFeed>) {
+    let mut sub = feed.subscribe();
+    loop {
+        tokio::select! {
+            msg = sub.rx.recv() => match msg {
+                Ok(text) => {
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "status client too slow; skipping messages");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Clients have nothing to say; pings are answered by axum.
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Consume [`STATUS_EXCHANGE`], reconnecting with exponential backoff.
+async fn consume_forever(amqp_url: String, tx: broadcast::Sender<String>) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        match consume(&amqp_url, &tx, &mut backoff).await {
+            Ok(()) => warn!("status consumer ended; reconnecting"),
+            Err(e) => warn!(error = %e, retry_in_s = backoff.as_secs(), "status consumer failed"),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn consume(
+    amqp_url: &str,
+    tx: &broadcast::Sender<String>,
+    backoff: &mut Duration,
+) -> Result<()> {
+    let conn = Connection::connect(amqp_url, ConnectionProperties::default())
+        .await
+        .context("connecting to RabbitMQ")?;
+    let chan = conn.create_channel().await?;
+    chan.exchange_declare(
+        STATUS_EXCHANGE,
+        ExchangeKind::Fanout,
+        ExchangeDeclareOptions {
+            durable: true,
+            ..Default::default()
+        },
+        FieldTable::default(),
+    )
+    .await?;
+    // Ours alone, and gone with the connection.
+    let queue = chan
+        .queue_declare(
+            "",
+            QueueDeclareOptions {
+                exclusive: true,
+                auto_delete: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    chan.queue_bind(
+        queue.name().as_str(),
+        STATUS_EXCHANGE,
+        "",
+        QueueBindOptions::default(),
+        FieldTable::default(),
+    )
+    .await?;
+    let mut consumer = chan
+        .basic_consume(
+            queue.name().as_str(),
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+    info!(exchange = STATUS_EXCHANGE, queue = queue.name().as_str(), "status consumer ready");
+    *backoff = INITIAL_BACKOFF;
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery?;
+        match serde_json::from_slice::<serde_json::Value>(&delivery.data) {
+            // No receivers only means every client left; the task is aborted.
+            Ok(event) => {
+                let _ = tx.send(event.to_string());
+            }
+            Err(e) => warn!(error = %e, "dropping non-JSON status message"),
+        }
+        delivery.ack(BasicAckOptions::default()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::{extract::WebSocketUpgrade, routing::get, Router};
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite;
+
+    use super::*;
+
+    /// Serve only `/ws/status` for `feed`; returns the WebSocket URL.
+    async fn serve(feed: Arc<StatusFeed>) -> String {
+        let app = Router::new().route(
+            "/ws/status",
+            get(move |ws: WebSocketUpgrade| async move {
+                ws.on_upgrade(move |socket| forward(socket, feed))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("ws://{addr}/ws/status")
+    }
+
+    /// Wait for a text frame containing `marker`, skipping unrelated ones.
+    async fn next_matching<S>(ws: &mut S, marker: &str) -> serde_json::Value
+    where
+        S: futures::Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+    {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let tungstenite::Message::Text(text) = ws.next().await.unwrap().unwrap() {
+                    if text.contains(marker) {
+                        return serde_json::from_str(&text).unwrap();
+                    }
+                }
+            }
+        })
+        .await
+        .expect("status message not delivered")
+    }
+
+    #[tokio::test]
+    async fn consumer_runs_only_while_clients_are_connected() {
+        // Unreachable broker: the consumer just keeps retrying.
+        let feed = Arc::new(StatusFeed::new("amqp://127.0.0.1:1".into()));
+        let first = feed.subscribe();
+        let second = feed.subscribe();
+        let running = || feed.consumer.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+        assert!(running());
+
+        drop(first);
+        assert!(running());
+        drop(second);
+        assert!(!running());
+    }
+
+    #[tokio::test]
+    async fn feed_messages_reach_websocket_clients() {
+        let feed = Arc::new(StatusFeed::new("amqp://127.0.0.1:1".into()));
+        let url = serve(feed.clone()).await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        // Wait for the server side to subscribe before sending.
+        while feed.tx.receiver_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        feed.tx
+            .send(r#"{"schema":"PlantStatusChanged.v1","plant_id":"feed-test"}"#.into())
+            .unwrap();
+        let event = next_matching(&mut ws, "feed-test").await;
+        assert_eq!(event["schema"], "PlantStatusChanged.v1");
+    }
+
+    /// Needs a broker; set `TEST_AMQP_URL` (e.g. `amqp://127.0.0.1:5672`).
+    #[tokio::test]
+    async fn published_amqp_message_reaches_websocket_client() {
+        let Ok(amqp_url) = std::env::var("TEST_AMQP_URL") else {
+            return;
+        };
+        let feed = Arc::new(StatusFeed::new(amqp_url.clone()));
+        let url = serve(feed).await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        // The feed's queue exists only once its consumer is up; publish
+        // until a copy gets through.
+        let conn = Connection::connect(&amqp_url, ConnectionProperties::default())
+            .await
+            .unwrap();
+        let chan = conn.create_channel().await.unwrap();
+        let marker = uuid::Uuid::new_v4().to_string();
+        let body = serde_json::json!({
+            "schema": "PlantStatusChanged.v1",
+            "plant_id": marker,
+            "new_severity": "CRITICAL",
+        });
+        let publisher = tokio::spawn(async move {
+            loop {
+                chan.basic_publish(
+                    STATUS_EXCHANGE,
+                    "",
+                    lapin::options::BasicPublishOptions::default(),
+                    &serde_json::to_vec(&body).unwrap(),
+                    lapin::BasicProperties::default(),
+                )
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        });
+
+        let event = next_matching(&mut ws, &marker).await;
+        publisher.abort();
+        assert_eq!(event["plant_id"], marker.as_str());
+        assert_eq!(event["new_severity"], "CRITICAL");
+    }
+}
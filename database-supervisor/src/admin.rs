@@ -0,0 +1,461 @@
+//! Registration of plant types, plants and devices.
+//!
+//! Lets a deployment be bootstrapped over gRPC instead of hand-written SQL
+//! against `plant_type` / `plant` / `device`, and shows the thresholds a
+//! plant is evaluated against once it is.
+
+use proto::supervisor_service::{
+    CreateDeviceRequest, CreatePlantRequest, CreatePlantTypeRequest, GetEffectiveThresholdsRequest,
+    GetEffectiveThresholdsResponse, MetricThresholdSpec,
+};
+use sqlx::PgPool;
+use thiserror::Error;
+use tonic::Status;
+use uuid::Uuid;
+
+use crate::cadence;
+use crate::smoothing::Smoothing;
+use crate::store::PlantStore;
+use crate::threshold::MetricThreshold;
+
+/// Postgres SQLSTATE for `unique_violation`.
+const UNIQUE_VIOLATION: &str = "23505";
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("database error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error(transparent)]
+    Store(#[from] anyhow::Error),
+}
+
+impl From<AdminError> for Status {
+    fn from(e: AdminError) -> Self {
+        match e {
+            AdminError::InvalidArgument(msg) => Status::invalid_argument(msg),
+            AdminError::AlreadyExists(msg)   => Status::already_exists(msg),
+            AdminError::NotFound(msg)        => Status::not_found(msg),
+            AdminError::Db(e)                => Status::internal(e.to_string()),
+            AdminError::Store(e)             => Status::internal(e.to_string()),
+        }
+    }
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.code().as_deref() == Some(UNIQUE_VIOLATION))
+}
+
+fn required(field: &str, value: &str) -> Result<(), AdminError> {
+    if value.trim().is_empty() {
+        return Err(AdminError::InvalidArgument(format!("{field} is required")));
+    }
+    Ok(())
+}
+
+fn parse_uuid(field: &str, value: &str) -> Result<Uuid, AdminError> {
+    Uuid::parse_str(value)
+        .map_err(|_| AdminError::InvalidArgument(format!("{field} is not a valid UUID: {value}")))
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    let v = value.trim();
+    (!v.is_empty()).then_some(v)
+}
+
+// ------------------------------------------------------------------ //
+//  Validation                                                         //
+// ------------------------------------------------------------------ //
+
+/// Validate a plant-type request before touching the database.
+pub fn validate_plant_type(req: &CreatePlantTypeRequest) -> Result<(), AdminError> {
+    required("name", &req.name)?;
+    let mut seen = std::collections::HashSet::new();
+    for t in &req.thresholds {
+        required("threshold metric", &t.metric)?;
+        if !seen.insert(t.metric.as_str()) {
+            return Err(AdminError::InvalidArgument(format!(
+                "duplicate threshold for metric '{}'",
+                t.metric
+            )));
+        }
+        validate_band(t)?;
+        validate_smoothing(t)?;
+        if t.hysteresis.is_some_and(|h| !(h >= 0.0 && h.is_finite())) {
+            return Err(AdminError::InvalidArgument(format!(
+                "{}: hysteresis must be a non-negative number",
+                t.metric
+            )));
+        }
+        if t.max_rate_per_min.is_some_and(|r| !(r > 0.0 && r.is_finite())) {
+            return Err(AdminError::InvalidArgument(format!(
+                "{}: max_rate_per_min must be positive",
+                t.metric
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_smoothing(t: &MetricThresholdSpec) -> Result<(), AdminError> {
+    let invalid = |msg: &str| Err(AdminError::InvalidArgument(format!("{}: {msg}", t.metric)));
+    match t.smoothing.as_str() {
+        "" => Ok(()),
+        "sma" if t.smoothing_window < 2 => invalid("sma smoothing_window must be at least 2"),
+        "sma" => Ok(()),
+        "ema" => match t.smoothing_alpha {
+            Some(a) if a > 0.0 && a <= 1.0 => Ok(()),
+            _ => invalid("ema smoothing_alpha must be in (0, 1]"),
+        },
+        other => invalid(&format!("unknown smoothing '{other}' (expected sma or ema)")),
+    }
+}
+
+fn validate_band(t: &MetricThresholdSpec) -> Result<(), AdminError> {
+    let bands = [
+        ("warn", t.warn_min, t.warn_max),
+        ("crit", t.crit_min, t.crit_max),
+    ];
+    for (name, min, max) in bands {
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(AdminError::InvalidArgument(format!(
+                    "{}: {name}_min ({min}) exceeds {name}_max ({max})",
+                    t.metric
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate a device request before touching the database.
+pub fn validate_device(req: &CreateDeviceRequest) -> Result<(), AdminError> {
+    required("device_uid", &req.device_uid)?;
+    if req.expected_interval_s == Some(0) {
+        return Err(AdminError::InvalidArgument("expected_interval_s must be positive".into()));
+    }
+    if req.interval_tolerance_pct.is_some_and(|p| p > 100) {
+        return Err(AdminError::InvalidArgument(
+            "interval_tolerance_pct must be at most 100".into(),
+        ));
+    }
+    Ok(())
+}
+
+// ------------------------------------------------------------------ //
+//  Inserts                                                            //
+// ------------------------------------------------------------------ //
+
+/// Insert a plant type and its thresholds in one transaction.
+pub async fn create_plant_type(pool: &PgPool, req: &CreatePlantTypeRequest) -> Result<Uuid, AdminError> {
+    validate_plant_type(req)?;
+
+    let mut tx = pool.begin().await?;
+
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO plant_type (name, description) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(req.name.trim())
+    .bind(non_empty(&req.description))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        if is_unique_violation(&e) {
+            AdminError::AlreadyExists(format!("plant_type '{}' already exists", req.name.trim()))
+        } else {
+            e.into()
+        }
+    })?;
+
+    for t in &req.thresholds {
+        sqlx::query(r#"
+            INSERT INTO plant_type_metric_threshold
+                (plant_type_id, metric, warn_min, warn_max, crit_min, crit_max, unit,
+                 smoothing, smoothing_window, smoothing_alpha, hysteresis, max_rate_per_min, required)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#)
+        .bind(id)
+        .bind(t.metric.trim())
+        .bind(t.warn_min)
+        .bind(t.warn_max)
+        .bind(t.crit_min)
+        .bind(t.crit_max)
+        .bind(non_empty(&t.unit))
+        .bind(non_empty(&t.smoothing))
+        .bind((t.smoothing == "sma").then_some(t.smoothing_window as i32))
+        .bind(t.smoothing_alpha.filter(|_| t.smoothing == "ema"))
+        .bind(t.hysteresis)
+        .bind(t.max_rate_per_min)
+        .bind(t.required)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Insert a plant, checking that its plant type (and device, if given) exist.
+pub async fn create_plant(pool: &PgPool, req: &CreatePlantRequest) -> Result<Uuid, AdminError> {
+    required("display_name", &req.display_name)?;
+    let plant_type_id = parse_uuid("plant_type_id", &req.plant_type_id)?;
+    let device_id = match non_empty(&req.device_id) {
+        Some(d) => Some(parse_uuid("device_id", d)?),
+        None => None,
+    };
+
+    let type_exists: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM plant_type WHERE id = $1)")
+            .bind(plant_type_id)
+            .fetch_one(pool)
+            .await?;
+    if !type_exists {
+        return Err(AdminError::InvalidArgument(format!(
+            "plant_type {plant_type_id} does not exist"
+        )));
+    }
+
+    if let Some(device_id) = device_id {
+        let device_exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM device WHERE id = $1)")
+                .bind(device_id)
+                .fetch_one(pool)
+                .await?;
+        if !device_exists {
+            return Err(AdminError::InvalidArgument(format!(
+                "device {device_id} does not exist"
+            )));
+        }
+    }
+
+    let id: Uuid = sqlx::query_scalar(r#"
+        INSERT INTO plant (plant_type_id, display_name, location, notes, device_id)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+    "#)
+    .bind(plant_type_id)
+    .bind(req.display_name.trim())
+    .bind(non_empty(&req.location))
+    .bind(non_empty(&req.notes))
+    .bind(device_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+/// Register an edge device by its unique `device_uid`.
+pub async fn create_device(pool: &PgPool, req: &CreateDeviceRequest) -> Result<Uuid, AdminError> {
+    validate_device(req)?;
+
+    sqlx::query_scalar(
+        r#"INSERT INTO device (device_uid, firmware_version, expected_interval_s, interval_tolerance_pct)
+           VALUES ($1, $2, $3, $4) RETURNING id"#,
+    )
+    .bind(req.device_uid.trim())
+    .bind(non_empty(&req.firmware_version))
+    .bind(req.expected_interval_s.map(|s| s as i32))
+    .bind(req.interval_tolerance_pct.unwrap_or(cadence::DEFAULT_TOLERANCE_PCT) as i32)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        if is_unique_violation(&e) {
+            AdminError::AlreadyExists(format!("device '{}' already exists", req.device_uid.trim()))
+        } else {
+            e.into()
+        }
+    })
+}
+
+// ------------------------------------------------------------------ //
+//  Effective thresholds                                               //
+// ------------------------------------------------------------------ //
+
+/// The thresholds ingest applies to an active plant, looked up and merged
+/// through the same [`PlantStore`] calls as `process_envelope`, so edits to
+/// `plant_type_metric_threshold` / `plant_metric_threshold` can be checked.
+pub async fn effective_thresholds(
+    store: &dyn PlantStore,
+    req: &GetEffectiveThresholdsRequest,
+) -> Result<GetEffectiveThresholdsResponse, AdminError> {
+    let plant_id = parse_uuid("plant_id", &req.plant_id)?;
+
+    // Read-only: the transaction only gives both reads one snapshot.
+    let mut tx = store.begin().await?;
+    let found = match tx.plant_type(plant_id).await? {
+        Some(plant_type_id) => Some((plant_type_id, tx.thresholds(plant_id, plant_type_id).await?)),
+        None => None,
+    };
+    tx.rollback().await?;
+
+    let (plant_type_id, thresholds) =
+        found.ok_or_else(|| AdminError::NotFound(format!("no active plant with id {plant_id}")))?;
+    let mut thresholds: Vec<MetricThresholdSpec> = thresholds.iter().map(threshold_spec).collect();
+    thresholds.sort_by(|a, b| a.metric.cmp(&b.metric));
+    Ok(GetEffectiveThresholdsResponse {
+        plant_type_id: plant_type_id.to_string(),
+        thresholds,
+    })
+}
+
+/// The wire form of a loaded threshold; the inverse of what
+/// [`create_plant_type`] stores, minus `unit`.
+fn threshold_spec(t: &MetricThreshold) -> MetricThresholdSpec {
+    let (smoothing, smoothing_window, smoothing_alpha) = match t.smoothing {
+        None => ("", 0, None),
+        Some(Smoothing::Sma { window }) => ("sma", window as u32, None),
+        Some(Smoothing::Ema { alpha }) => ("ema", 0, Some(alpha)),
+    };
+    MetricThresholdSpec {
+        metric: t.metric.clone(),
+        warn_min: t.warn_min,
+        warn_max: t.warn_max,
+        crit_min: t.crit_min,
+        crit_max: t.crit_max,
+        unit: String::new(),
+        smoothing: smoothing.into(),
+        smoothing_window,
+        smoothing_alpha,
+        hysteresis: (t.hysteresis > 0.0).then_some(t.hysteresis),
+        max_rate_per_min: t.max_rate_per_min,
+        required: t.required,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(metric: &str, warn: (Option<f64>, Option<f64>), crit: (Option<f64>, Option<f64>)) -> MetricThresholdSpec {
+        MetricThresholdSpec {
+            metric: metric.into(),
+            warn_min: warn.0,
+            warn_max: warn.1,
+            crit_min: crit.0,
+            crit_max: crit.1,
+            unit: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn device_interval_must_be_sane() {
+        let req = |expected, tol| CreateDeviceRequest {
+            device_uid: "esp32".into(),
+            expected_interval_s: expected,
+            interval_tolerance_pct: tol,
+            ..Default::default()
+        };
+        assert!(validate_device(&req(Some(60), Some(20))).is_ok());
+        assert!(validate_device(&req(None, None)).is_ok());
+        assert!(matches!(validate_device(&req(Some(0), None)), Err(AdminError::InvalidArgument(_))));
+        assert!(matches!(validate_device(&req(Some(60), Some(101))), Err(AdminError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn plant_type_requires_name() {
+        let req = CreatePlantTypeRequest { name: "  ".into(), ..Default::default() };
+        assert!(matches!(validate_plant_type(&req), Err(AdminError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn plant_type_rejects_duplicate_metric() {
+        let req = CreatePlantTypeRequest {
+            name: "fern".into(),
+            description: String::new(),
+            thresholds: vec![
+                spec("soil_moisture", (Some(20.0), None), (None, None)),
+                spec("soil_moisture", (Some(30.0), None), (None, None)),
+            ],
+        };
+        assert!(matches!(validate_plant_type(&req), Err(AdminError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn plant_type_rejects_inverted_band() {
+        let req = CreatePlantTypeRequest {
+            name: "fern".into(),
+            description: String::new(),
+            thresholds: vec![spec("ambient_temp_c", (Some(30.0), Some(10.0)), (None, None))],
+        };
+        assert!(matches!(validate_plant_type(&req), Err(AdminError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn plant_type_accepts_valid_thresholds() {
+        let req = CreatePlantTypeRequest {
+            name: "fern".into(),
+            description: "shade lover".into(),
+            thresholds: vec![
+                spec("soil_moisture", (Some(30.0), Some(70.0)), (Some(15.0), Some(90.0))),
+                spec("ambient_temp_c", (None, Some(28.0)), (None, Some(35.0))),
+            ],
+        };
+        assert!(validate_plant_type(&req).is_ok());
+    }
+
+    #[test]
+    fn plant_type_validates_smoothing() {
+        let with = |smoothing: &str, window: u32, alpha: Option<f64>| CreatePlantTypeRequest {
+            name: "fern".into(),
+            description: String::new(),
+            thresholds: vec![MetricThresholdSpec {
+                smoothing: smoothing.into(),
+                smoothing_window: window,
+                smoothing_alpha: alpha,
+                ..spec("soil_moisture", (Some(30.0), None), (None, None))
+            }],
+        };
+        assert!(validate_plant_type(&with("sma", 5, None)).is_ok());
+        assert!(validate_plant_type(&with("ema", 0, Some(0.3))).is_ok());
+        assert!(validate_plant_type(&with("sma", 1, None)).is_err());
+        assert!(validate_plant_type(&with("ema", 0, None)).is_err());
+        assert!(validate_plant_type(&with("ema", 0, Some(1.5))).is_err());
+        assert!(validate_plant_type(&with("median", 3, None)).is_err());
+    }
+
+    #[test]
+    fn plant_type_rejects_negative_hysteresis() {
+        let with = |hysteresis: Option<f64>| CreatePlantTypeRequest {
+            name: "fern".into(),
+            description: String::new(),
+            thresholds: vec![MetricThresholdSpec {
+                hysteresis,
+                ..spec("soil_moisture", (Some(30.0), None), (None, None))
+            }],
+        };
+        assert!(validate_plant_type(&with(None)).is_ok());
+        assert!(validate_plant_type(&with(Some(2.5))).is_ok());
+        assert!(validate_plant_type(&with(Some(-1.0))).is_err());
+        assert!(validate_plant_type(&with(Some(f64::NAN))).is_err());
+    }
+
+    #[test]
+    fn plant_type_rejects_non_positive_max_rate() {
+        let with = |max_rate_per_min: Option<f64>| CreatePlantTypeRequest {
+            name: "fern".into(),
+            description: String::new(),
+            thresholds: vec![MetricThresholdSpec {
+                max_rate_per_min,
+                ..spec("soil_moisture", (Some(30.0), None), (None, None))
+            }],
+        };
+        assert!(validate_plant_type(&with(Some(5.0))).is_ok());
+        assert!(validate_plant_type(&with(Some(0.0))).is_err());
+        assert!(validate_plant_type(&with(Some(-2.0))).is_err());
+    }
+
+    #[test]
+    fn error_to_status_codes() {
+        let s: Status = AdminError::InvalidArgument("x".into()).into();
+        assert_eq!(s.code(), tonic::Code::InvalidArgument);
+        let s: Status = AdminError::AlreadyExists("x".into()).into();
+        assert_eq!(s.code(), tonic::Code::AlreadyExists);
+    }
+}
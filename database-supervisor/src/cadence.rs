@@ -0,0 +1,51 @@
+//! Reporting-interval checks: is a device reporting too often or too rarely?
+
+/// Tolerance applied when a device doesn't set `interval_tolerance_pct`.
+pub const DEFAULT_TOLERANCE_PCT: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    TooFast,
+    TooSlow,
+}
+
+impl Drift {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Drift::TooFast => "too_fast",
+            Drift::TooSlow => "too_slow",
+        }
+    }
+}
+
+/// Compare an observed inter-arrival interval with `expected_s ± tolerance_pct`.
+pub fn check(interval_s: f64, expected_s: u32, tolerance_pct: u32) -> Option<Drift> {
+    let expected = f64::from(expected_s);
+    let slack = expected * f64::from(tolerance_pct) / 100.0;
+    if interval_s < expected - slack {
+        Some(Drift::TooFast)
+    } else if interval_s > expected + slack {
+        Some(Drift::TooSlow)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn within_band_is_fine() {
+        assert_eq!(check(60.0, 60, 20), None);
+        assert_eq!(check(48.0, 60, 20), None);
+        assert_eq!(check(72.0, 60, 20), None);
+    }
+
+    #[test]
+    fn outside_band_drifts() {
+        assert_eq!(check(47.9, 60, 20), Some(Drift::TooFast));
+        assert_eq!(check(72.1, 60, 20), Some(Drift::TooSlow));
+        assert_eq!(check(61.0, 60, 0), Some(Drift::TooSlow));
+    }
+}
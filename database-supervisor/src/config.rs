@@ -0,0 +1,154 @@
+//! Runtime options for the ingest pipeline, read from the environment.
+
+use std::time::Duration;
+
+use crate::threshold::Severity;
+
+/// Envelopes of one `IngestTelemetry` batch processed at once by default.
+pub const DEFAULT_INGEST_CONCURRENCY: usize = 8;
+
+/// Dedup window used when `SUPERVISOR_DEDUP_WINDOW_HOURS` is unset.
+pub const DEFAULT_DEDUP_WINDOW_HOURS: u64 = 24;
+
+/// Metrics evaluated and forwarded by default: the typed envelope readings.
+pub const DEFAULT_METRICS: [&str; 4] =
+    ["soil_moisture", "ambient_light_lux", "ambient_humidity_rh", "ambient_temp_c"];
+
+/// Tunables for [`crate::ingest::SupervisorServiceImpl`].
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// Value of the static `deployment` tag added to every telemetry point
+    /// (`SUPERVISOR_DEPLOYMENT`).  Used by the sink to pick a tenant bucket.
+    pub deployment: Option<String>,
+    /// Mark the ticker event of a WARN/CRITICAL -> NORMAL transition as a
+    /// recovery (`SUPERVISOR_EMIT_RECOVERY_EVENTS=true`).
+    pub emit_recovery_events: bool,
+    /// Insert a ticker event for every reading, not just severity changes
+    /// (`SUPERVISOR_TICKER_ALL=true`).
+    pub ticker_all: bool,
+    /// Plants not updated for this long are marked STALE
+    /// (`SUPERVISOR_STALE_TTL_S`); `None` disables the sweep.
+    pub stale_ttl: Option<Duration>,
+    /// Envelopes of one batch processed concurrently
+    /// (`SUPERVISOR_INGEST_CONCURRENCY`, default 8).
+    pub ingest_concurrency: usize,
+    /// Evaluate envelopes and report the results without writing anything:
+    /// no Postgres writes, sink points or AMQP events
+    /// (`SUPERVISOR_DRY_RUN=true`).
+    pub dry_run: bool,
+    /// Metrics read from an envelope, evaluated against thresholds and sent
+    /// to the sink (`SUPERVISOR_METRICS`, comma-separated); others are
+    /// ignored.
+    pub metrics: Vec<String>,
+    /// Only ledger rows with a reading time within this window count as
+    /// duplicates, and older ones are pruned (`SUPERVISOR_DEDUP_WINDOW_HOURS`,
+    /// default 24, `0` keeps them forever).  `None` dedups against the whole
+    /// ledger.
+    pub dedup_window: Option<Duration>,
+    /// Severity of a `required` metric missing from an envelope
+    /// (`SUPERVISOR_MISSING_METRIC_SEVERITY`: `WARN` or `CRITICAL`, the
+    /// default).
+    pub missing_severity: Severity,
+    /// Don't publish a plant's status change to AMQP again if it published
+    /// the same transition within this window (`SUPERVISOR_STATUS_DEBOUNCE_S`);
+    /// `None` publishes every one.  See [`crate::debounce`].
+    pub status_debounce: Option<Duration>,
+    /// Write a plant's point to the sink only if its reading time is at
+    /// least this far from the last one written
+    /// (`SUPERVISOR_SINK_MIN_INTERVAL_MS`); `None` writes every one.  See
+    /// [`crate::sampling`].
+    pub sink_min_interval: Option<Duration>,
+    /// A metric that has read WARN (or worse) this many times in a row is
+    /// treated as CRITICAL (`SUPERVISOR_WARN_ESCALATION_COUNT`); `None`
+    /// never escalates.  See [`crate::threshold::escalate_warn_streak`].
+    pub warn_escalation: Option<u32>,
+    /// Register a device the first time it sends telemetry
+    /// (`SUPERVISOR_AUTO_PROVISION=true`); otherwise unknown devices are
+    /// only logged.
+    pub auto_provision: bool,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            deployment: None,
+            emit_recovery_events: false,
+            ticker_all: false,
+            stale_ttl: None,
+            ingest_concurrency: DEFAULT_INGEST_CONCURRENCY,
+            dry_run: false,
+            metrics: DEFAULT_METRICS.iter().map(|m| m.to_string()).collect(),
+            dedup_window: None,
+            missing_severity: Severity::Critical,
+            status_debounce: None,
+            sink_min_interval: None,
+            warn_escalation: None,
+            auto_provision: false,
+        }
+    }
+}
+
+impl SupervisorConfig {
+    pub fn from_env() -> Self {
+        Self {
+            deployment: std::env::var("SUPERVISOR_DEPLOYMENT")
+                .ok()
+                .filter(|s| !s.trim().is_empty()),
+            emit_recovery_events: std::env::var("SUPERVISOR_EMIT_RECOVERY_EVENTS")
+                .is_ok_and(|v| matches!(v.trim(), "1" | "true")),
+            ticker_all: std::env::var("SUPERVISOR_TICKER_ALL")
+                .is_ok_and(|v| matches!(v.trim(), "1" | "true")),
+            stale_ttl: std::env::var("SUPERVISOR_STALE_TTL_S")
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|&s| s > 0)
+                .map(Duration::from_secs),
+            ingest_concurrency: std::env::var("SUPERVISOR_INGEST_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(DEFAULT_INGEST_CONCURRENCY),
+            dry_run: std::env::var("SUPERVISOR_DRY_RUN")
+                .is_ok_and(|v| matches!(v.trim(), "1" | "true")),
+            metrics: std::env::var("SUPERVISOR_METRICS")
+                .ok()
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|m| !m.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|metrics| !metrics.is_empty())
+                .unwrap_or_else(|| DEFAULT_METRICS.iter().map(|m| m.to_string()).collect()),
+            dedup_window: Some(
+                std::env::var("SUPERVISOR_DEDUP_WINDOW_HOURS")
+                    .ok()
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .unwrap_or(DEFAULT_DEDUP_WINDOW_HOURS),
+            )
+            .filter(|&hours| hours > 0)
+            .map(|hours| Duration::from_secs(hours * 3600)),
+            missing_severity: match std::env::var("SUPERVISOR_MISSING_METRIC_SEVERITY") {
+                Ok(v) if v.trim().eq_ignore_ascii_case("WARN") => Severity::Warn,
+                _ => Severity::Critical,
+            },
+            status_debounce: std::env::var("SUPERVISOR_STATUS_DEBOUNCE_S")
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|&s| s > 0)
+                .map(Duration::from_secs),
+            sink_min_interval: std::env::var("SUPERVISOR_SINK_MIN_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            warn_escalation: std::env::var("SUPERVISOR_WARN_ESCALATION_COUNT")
+                .ok()
+                .and_then(|v| v.trim().parse::<u32>().ok())
+                .filter(|&n| n > 0),
+            auto_provision: std::env::var("SUPERVISOR_AUTO_PROVISION")
+                .is_ok_and(|v| matches!(v.trim(), "1" | "true")),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! `telemetry_dead_letter`: envelopes that failed ingest, kept so they can
+//! be replayed once the cause (usually a missing plant) is fixed.
+
+use chrono::{DateTime, Utc};
+use proto::supervisor_service::{ReplayDeadLetterRequest, TelemetryEnvelope};
+use sqlx::{PgExecutor, PgPool, Row};
+
+use crate::admin::AdminError;
+
+pub const DEFAULT_LIMIT: u32 = 100;
+pub const MAX_LIMIT: u32 = 1000;
+
+/// Reason recorded when `plant_id` doesn't parse.
+pub const INVALID_PLANT_ID: &str = "plant_id is not a valid UUID";
+/// Reason recorded when no active plant has the envelope's `plant_id`.
+pub const UNKNOWN_PLANT: &str = "unknown or inactive plant";
+
+/// Validated form of a [`ReplayDeadLetterRequest`].
+#[derive(Debug, PartialEq)]
+pub struct ReplayWindow {
+    pub start: Option<DateTime<Utc>>,
+    pub stop: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+impl ReplayWindow {
+    pub fn from_request(req: &ReplayDeadLetterRequest) -> Result<Self, AdminError> {
+        let start_ns = (req.start_ns != 0).then_some(req.start_ns);
+        let stop_ns = (req.stop_ns != 0).then_some(req.stop_ns);
+        if let (Some(start), Some(stop)) = (start_ns, stop_ns) {
+            if start >= stop {
+                return Err(AdminError::InvalidArgument(format!(
+                    "start_ns ({start}) must be before stop_ns ({stop})"
+                )));
+            }
+        }
+        let limit = match req.limit {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        };
+        Ok(Self {
+            start: start_ns.map(DateTime::from_timestamp_nanos),
+            stop: stop_ns.map(DateTime::from_timestamp_nanos),
+            limit: limit as i64,
+        })
+    }
+}
+
+/// Record `env` as failed with `error`.  A repeat of the same `ingest_id`
+/// keeps its original `received_at` and only refreshes the reason.
+pub async fn record(executor: impl PgExecutor<'_>, env: &TelemetryEnvelope, error: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"
+        INSERT INTO telemetry_dead_letter
+            (ingest_id, device_uid, plant_id, timestamp_ns, seq,
+             soil_moisture, ambient_light_lux, ambient_humidity_rh, ambient_temp_c,
+             battery_v, rssi_dbm, raw_payload_b64, error, readings, firmware_version)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (ingest_id) DO UPDATE SET error = EXCLUDED.error
+    "#)
+    .bind(&env.ingest_id)
+    .bind(&env.device_uid)
+    .bind(&env.plant_id)
+    .bind(env.timestamp_ns)
+    .bind(env.seq as i64)
+    .bind(env.soil_moisture)
+    .bind(env.ambient_light_lux)
+    .bind(env.ambient_humidity_rh)
+    .bind(env.ambient_temp_c)
+    .bind(env.battery_v)
+    .bind(env.rssi_dbm)
+    .bind(&env.raw_payload_b64)
+    .bind(error)
+    .bind(sqlx::types::Json(&env.readings))
+    .bind(&env.firmware_version)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Dead-lettered envelopes in `window`, oldest first.
+pub async fn pending(pool: &PgPool, window: &ReplayWindow) -> Result<Vec<TelemetryEnvelope>, AdminError> {
+    let rows = sqlx::query(r#"
+        SELECT ingest_id, device_uid, plant_id, timestamp_ns, seq,
+               soil_moisture, ambient_light_lux, ambient_humidity_rh, ambient_temp_c,
+               battery_v, rssi_dbm, raw_payload_b64, readings, firmware_version
+        FROM telemetry_dead_letter
+        WHERE ($1::timestamptz IS NULL OR received_at >= $1)
+          AND ($2::timestamptz IS NULL OR received_at < $2)
+        ORDER BY received_at, timestamp_ns, ingest_id
+        LIMIT $3
+    "#)
+    .bind(window.start)
+    .bind(window.stop)
+    .bind(window.limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|r| {
+            Ok(TelemetryEnvelope {
+                ingest_id:           r.try_get("ingest_id")?,
+                device_uid:          r.try_get("device_uid")?,
+                plant_id:            r.try_get("plant_id")?,
+                timestamp_ns:        r.try_get("timestamp_ns")?,
+                seq:                 r.try_get::<i64, _>("seq")? as u32,
+                soil_moisture:       r.try_get("soil_moisture")?,
+                ambient_light_lux:   r.try_get("ambient_light_lux")?,
+                ambient_humidity_rh: r.try_get("ambient_humidity_rh")?,
+                ambient_temp_c:      r.try_get("ambient_temp_c")?,
+                battery_v:           r.try_get("battery_v")?,
+                rssi_dbm:            r.try_get("rssi_dbm")?,
+                raw_payload_b64:     r.try_get("raw_payload_b64")?,
+                readings:            r.try_get::<sqlx::types::Json<_>, _>("readings")?.0,
+                firmware_version:    r.try_get("firmware_version")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(AdminError::from)
+}
+
+/// Drop the ERROR ledger row a failed attempt left behind, so a replay of
+/// `ingest_id` isn't deduplicated against it.
+pub async fn release_ledger(pool: &PgPool, ingest_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM telemetry_ingest_ledger WHERE ingest_id = $1 AND result = 'ERROR'")
+        .bind(ingest_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove a dead letter that has now been ingested.
+pub async fn clear(pool: &PgPool, ingest_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM telemetry_dead_letter WHERE ingest_id = $1")
+        .bind(ingest_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_request_is_open_window() {
+        let w = ReplayWindow::from_request(&ReplayDeadLetterRequest::default()).unwrap();
+        assert_eq!(w, ReplayWindow { start: None, stop: None, limit: DEFAULT_LIMIT as i64 });
+    }
+
+    #[test]
+    fn window_bounds_and_limit() {
+        let req = ReplayDeadLetterRequest { start_ns: 1_000_000_000, stop_ns: 0, limit: 50_000 };
+        let w = ReplayWindow::from_request(&req).unwrap();
+        assert_eq!(w.start.unwrap().timestamp(), 1);
+        assert_eq!(w.stop, None);
+        assert_eq!(w.limit, MAX_LIMIT as i64);
+
+        let req = ReplayDeadLetterRequest { start_ns: 10, stop_ns: 5, ..Default::default() };
+        assert!(matches!(ReplayWindow::from_request(&req), Err(AdminError::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,79 @@
+//! Debouncing of `PlantStatusChanged.v1` publishes.
+//!
+//! A plant hovering on a threshold flips NORMAL <-> WARN on every reading.
+//! With `SUPERVISOR_STATUS_DEBOUNCE_S` set, a transition is only published
+//! if the same plant hasn't published the same transition (same previous
+//! and new severity) within that window; any other transition, e.g.
+//! WARN -> CRITICAL, still goes out at once.  Only the AMQP publish is
+//! debounced: state, ticker and the `IngestTelemetry` response are not.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::threshold::Severity;
+
+type Transition = (Uuid, Severity, Severity);
+
+/// When each plant last published each transition.
+#[derive(Debug, Default)]
+pub struct StatusDebounce {
+    published: Mutex<HashMap<Transition, Instant>>,
+}
+
+impl StatusDebounce {
+    /// Whether `plant_id`'s `prev -> new` should be published at `now`:
+    /// not if it already was within `window`.  Records it when allowed.
+    pub fn allow(&self, plant_id: Uuid, prev: Severity, new: Severity, window: Duration, now: Instant) -> bool {
+        let mut published = self.published.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget transitions outside the window, so the map stays small.
+        published.retain(|_, at| now.duration_since(*at) < window);
+        if published.contains_key(&(plant_id, prev, new)) {
+            return false;
+        }
+        published.insert((plant_id, prev, new), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn repeat_within_window_is_suppressed() {
+        let debounce = StatusDebounce::default();
+        let plant = Uuid::new_v4();
+        let now = Instant::now();
+
+        assert!(debounce.allow(plant, Severity::Normal, Severity::Warn, WINDOW, now));
+        assert!(debounce.allow(plant, Severity::Warn, Severity::Normal, WINDOW, now + Duration::from_secs(5)));
+        assert!(!debounce.allow(plant, Severity::Normal, Severity::Warn, WINDOW, now + Duration::from_secs(10)));
+        assert!(!debounce.allow(plant, Severity::Warn, Severity::Normal, WINDOW, now + Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn repeat_after_window_is_published() {
+        let debounce = StatusDebounce::default();
+        let plant = Uuid::new_v4();
+        let now = Instant::now();
+
+        assert!(debounce.allow(plant, Severity::Normal, Severity::Warn, WINDOW, now));
+        assert!(debounce.allow(plant, Severity::Normal, Severity::Warn, WINDOW, now + WINDOW));
+    }
+
+    #[test]
+    fn new_transitions_and_other_plants_are_not_suppressed() {
+        let debounce = StatusDebounce::default();
+        let plant = Uuid::new_v4();
+        let now = Instant::now();
+
+        assert!(debounce.allow(plant, Severity::Normal, Severity::Warn, WINDOW, now));
+        assert!(debounce.allow(plant, Severity::Warn, Severity::Critical, WINDOW, now));
+        assert!(debounce.allow(Uuid::new_v4(), Severity::Normal, Severity::Warn, WINDOW, now));
+    }
+}
@@ -0,0 +1,29 @@
+//! gRPC health reporting (`grpc.health.v1.Health`).
+
+use proto::supervisor_service::supervisor_service_server::SupervisorServiceServer;
+use sqlx::PgPool;
+use tonic::server::NamedService;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tracing::error;
+
+use crate::ingest::SupervisorServiceImpl;
+
+/// Create the health service, reporting SERVING if `pool` answers a
+/// `SELECT 1` and NOT_SERVING otherwise.
+pub async fn service(pool: &PgPool) -> (HealthReporter, HealthServer<impl Health>) {
+    let (mut reporter, service) = tonic_health::server::health_reporter();
+    let status = match sqlx::query("SELECT 1").execute(pool).await {
+        Ok(_) => ServingStatus::Serving,
+        Err(e) => {
+            error!(error = %e, "database not reachable; reporting NOT_SERVING");
+            ServingStatus::NotServing
+        }
+    };
+    reporter.set_service_status("", status).await;
+    reporter
+        .set_service_status(SupervisorServiceServer::<SupervisorServiceImpl>::NAME, status)
+        .await;
+    (reporter, service)
+}
@@ -1,30 +1,73 @@
 //! IngestTelemetry gRPC handler.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use anyhow::Result;
+use futures::{stream, StreamExt};
 use proto::supervisor_service::{
     supervisor_service_server::SupervisorService,
-    IngestResult, IngestTelemetryRequest, IngestTelemetryResponse, ItemResult, Severity,
-    StatusChange, TelemetryEnvelope,
+    CreateDeviceRequest, CreateDeviceResponse, CreatePlantRequest, CreatePlantResponse,
+    CreatePlantTypeRequest, CreatePlantTypeResponse, GetEffectiveThresholdsRequest,
+    GetEffectiveThresholdsResponse, IngestResult, IngestTelemetryRequest,
+    IngestTelemetryResponse, ItemResult, QueryLedgerRequest, QueryLedgerResponse,
+    ReplayDeadLetterRequest, ReplayDeadLetterResponse, SetMaintenanceModeRequest, SetMaintenanceModeResponse, Severity, StatusChange,
+    TelemetryEnvelope,
 };
-use sqlx::{PgPool, Row};
+use redact::Redactor;
+use sqlx::PgPool;
 use tonic::{Request, Response, Status};
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::telemetry_sink::{TelemetryPoint, TelemetrySink};
-use crate::threshold::{self, MetricThreshold, Severity as ThreshSeverity};
+use crate::admin;
+use crate::cadence;
+use crate::config::SupervisorConfig;
+use crate::dead_letter;
+use crate::debounce::StatusDebounce;
+use crate::ledger;
+use crate::metrics;
+use crate::sampling::SinkSampler;
+use crate::smoothing;
+use crate::telemetry_sink::{TelemetryPoint, TelemetrySink, DEPLOYMENT_TAG};
+use crate::store::{DeviceCadence, PlantState, PlantStore, PlantTx, SqlxPlantStore, TickerEvent};
+use crate::threshold::{self, LastReading, Severity as ThreshSeverity};
 
 // ------------------------------------------------------------------ //
 //  gRPC service implementation                                        //
 // ------------------------------------------------------------------ //
 
+/// Message returned with `UNAVAILABLE` while maintenance mode is on.
+pub const MAINTENANCE_MESSAGE: &str =
+    "database-supervisor is in maintenance mode; ingest paused, retry later";
+
+/// Postgres channel notified with the plant id whenever an ingest updates
+/// `plant_current_state`, so dashboard caches can drop that plant.
+pub const STATE_CHANGED_CHANNEL: &str = "plant_state_changed";
+
 pub struct SupervisorServiceImpl {
     pub pool: PgPool,
+    /// What ingest reads and writes; Postgres via `pool` unless replaced.
+    pub store: Arc<dyn PlantStore>,
     pub sink: Arc<dyn TelemetrySink>,
     pub amqp_chan: Option<lapin::Channel>,
+    /// Masks configured keys in ledger/ticker payloads before logging.
+    pub redactor: Redactor,
+    pub config: SupervisorConfig,
+    /// When set, `IngestTelemetry` is rejected with `UNAVAILABLE`.
+    pub maintenance: Arc<AtomicBool>,
+    /// Per-plant state ingest keeps in memory.
+    pub memory: Arc<IngestMemory>,
+}
+
+/// Per-plant state ingest keeps in process rather than in Postgres.
+#[derive(Debug, Default)]
+pub struct IngestMemory {
+    /// Recently published status changes, for `config.status_debounce`.
+    pub status_debounce: StatusDebounce,
+    /// Last sink write per plant, for `config.sink_min_interval`.
+    pub sink_sampler: SinkSampler,
 }
 
 impl SupervisorServiceImpl {
@@ -33,7 +76,35 @@ impl SupervisorServiceImpl {
         sink: Arc<dyn TelemetrySink>,
         amqp_chan: Option<lapin::Channel>,
     ) -> Self {
-        Self { pool, sink, amqp_chan }
+        Self {
+            store: Arc::new(SqlxPlantStore::new(pool.clone())),
+            pool,
+            sink,
+            amqp_chan,
+            redactor: Redactor::default(),
+            config: SupervisorConfig::default(),
+            maintenance: Arc::new(AtomicBool::new(false)),
+            memory: Arc::default(),
+        }
+    }
+
+    /// Replace the default ingest options.
+    pub fn with_config(mut self, config: SupervisorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Replace the Postgres-backed ingest store, e.g. with
+    /// [`InMemoryPlantStore`](crate::store::InMemoryPlantStore) in tests.
+    pub fn with_store(mut self, store: Arc<dyn PlantStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Replace the default (no-op) payload redactor.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
     }
 }
 
@@ -43,191 +114,281 @@ impl SupervisorServiceImpl {
 
 async fn process_envelope(
     envelope: &TelemetryEnvelope,
-    pool: &PgPool,
+    store: &dyn PlantStore,
     sink: &dyn TelemetrySink,
     amqp_chan: Option<&lapin::Channel>,
+    memory: &IngestMemory,
+    redactor: &Redactor,
+    config: &SupervisorConfig,
 ) -> Result<(IngestResult, Option<StatusChange>)> {
     let plant_id = match Uuid::parse_str(&envelope.plant_id) {
         Ok(id) => id,
-        Err(_) => return Ok((IngestResult::Error, None)),
+        Err(_) => {
+            if config.dry_run {
+                dry_run_skip(envelope, "dead-letter the envelope (invalid plant_id)");
+            } else {
+                store.dead_letter(envelope, dead_letter::INVALID_PLANT_ID).await?;
+            }
+            return Ok((IngestResult::Error, None));
+        }
     };
 
-    // Deduplication check
-    let existing: Option<String> = sqlx::query_scalar(
-        "SELECT result FROM telemetry_ingest_ledger WHERE ingest_id = $1",
-    )
-    .bind(&envelope.ingest_id)
-    .fetch_optional(pool)
-    .await?;
-
-    if existing.is_some() {
-        let _ = sqlx::query(
-            "UPDATE device SET last_seen_at = NOW() WHERE device_uid = $1",
-        )
-        .bind(&envelope.device_uid)
-        .execute(pool)
-        .await;
+    // Deduplication check, against ledger rows inside the dedup window
+    let not_before_ns = config.dedup_window.map(|w| ledger::dedup_cutoff_ns(chrono::Utc::now(), w));
+    if store.is_ingested(&envelope.ingest_id, not_before_ns).await? {
+        if config.dry_run {
+            dry_run_skip(envelope, "touch device last_seen_at (duplicate)");
+        } else {
+            let _ = store.touch_device(&envelope.device_uid).await;
+        }
         return Ok((IngestResult::Duplicate, None));
     }
 
+    // Raw payload capture (forensic; failures don't block ingest)
+    if config.dry_run {
+        dry_run_skip(envelope, "capture the raw payload");
+    } else if let Err(e) = store.capture_raw(envelope).await {
+        warn!(error = %e, ingest_id = %envelope.ingest_id, "raw payload capture failed");
+    }
+
+    // Everything below commits together with the ledger row, or not at all.
+    // In dry-run mode only the reads run and the transaction is rolled back.
+    let mut tx = store.begin().await?;
+
     // Plant lookup
-    let plant_row = sqlx::query(
-        "SELECT id, plant_type_id FROM plant WHERE id = $1 AND is_active = TRUE",
-    )
-    .bind(plant_id)
-    .fetch_optional(pool)
-    .await?;
-
-    let (plant_id_db, plant_type_id): (Uuid, Uuid) = match plant_row {
-        Some(row) => (row.try_get("id")?, row.try_get("plant_type_id")?),
+    let plant_type_id = match tx.plant_type(plant_id).await? {
+        Some(plant_type_id) => plant_type_id,
         None => {
-            record_ledger(pool, envelope, "ERROR").await?;
+            if config.dry_run {
+                dry_run_skip(envelope, "record an ERROR ledger row and dead-letter the envelope (unknown plant)");
+                tx.rollback().await?;
+            } else {
+                record_ledger(&mut *tx, envelope, "ERROR", redactor).await?;
+                tx.dead_letter(envelope, dead_letter::UNKNOWN_PLANT).await?;
+                tx.commit().await?;
+            }
             return Ok((IngestResult::Error, None));
         }
     };
 
-    // Thresholds
-    let threshold_rows = sqlx::query(
-        r#"SELECT metric, warn_min, warn_max, crit_min, crit_max
-           FROM plant_type_metric_threshold
-           WHERE plant_type_id = $1"#,
-    )
-    .bind(plant_type_id)
-    .fetch_all(pool)
-    .await?;
-
-    let thresholds: Vec<MetricThreshold> = threshold_rows
-        .iter()
-        .map(|r| MetricThreshold {
-            metric:   r.try_get("metric").unwrap_or_default(),
-            warn_min: r.try_get("warn_min").unwrap_or(None),
-            warn_max: r.try_get("warn_max").unwrap_or(None),
-            crit_min: r.try_get("crit_min").unwrap_or(None),
-            crit_max: r.try_get("crit_max").unwrap_or(None),
-        })
-        .collect();
+    // Thresholds: the plant's own rows win over its type's, per metric
+    let thresholds = tx.thresholds(plant_id, plant_type_id).await?;
 
     // Per-metric severity
-    let readings: &[(&str, Option<f64>)] = &[
-        ("soil_moisture",       envelope.soil_moisture),
-        ("ambient_light_lux",   envelope.ambient_light_lux),
-        ("ambient_humidity_rh", envelope.ambient_humidity_rh),
-        ("ambient_temp_c",      envelope.ambient_temp_c),
-    ];
+    let readings = envelope_readings(envelope, &config.metrics);
+
+    // Previous severities and recent raw readings (for hysteresis, smoothing
+    // and rate of change)
+    let prev_state = tx.current_state(plant_id).await?;
+    let prev_severity = prev_state.as_ref().map_or(ThreshSeverity::Normal, |s| s.severity);
+    let (prev_metric_severities, mut metric_history, mut last_readings, mut warn_streaks) = match prev_state.as_ref() {
+        Some(s) => (
+            s.metric_severity.clone(),
+            s.metric_history.clone(),
+            s.last_readings.clone(),
+            s.warn_streaks.clone(),
+        ),
+        None => Default::default(),
+    };
+
 
     let mut metric_severities: HashMap<String, ThreshSeverity> = HashMap::new();
-    for (metric_name, opt_val) in readings {
-        if let Some(val) = opt_val {
-            let thresh = thresholds.iter().find(|t| t.metric == *metric_name);
-            let sev = match thresh {
-                Some(t) => threshold::evaluate_metric(*val, t),
-                None    => ThreshSeverity::Normal,
-            };
-            metric_severities.insert(metric_name.to_string(), sev);
+    for (metric_name, val) in &readings {
+        let thresh = thresholds.iter().find(|t| t.metric == *metric_name);
+        let prev = last_readings.get(*metric_name).copied();
+        let rate_sev = match thresh.and_then(|t| t.max_rate_per_min) {
+            Some(max_rate) => threshold::evaluate_rate(
+                prev.map(|p| p.value),
+                prev.map(|p| p.ts_ns),
+                *val,
+                envelope.timestamp_ns,
+                max_rate,
+            ),
+            None => ThreshSeverity::Normal,
+        };
+        // Keep the newest reading; late envelopes don't rewind it.
+        if !matches!(prev, Some(p) if p.ts_ns >= envelope.timestamp_ns) {
+            last_readings.insert(
+                metric_name.to_string(),
+                LastReading { value: *val, ts_ns: envelope.timestamp_ns },
+            );
         }
+
+        let sev = match thresh {
+            Some(t) => {
+                let value = match &t.smoothing {
+                    Some(s) => {
+                        let history = metric_history.entry(metric_name.to_string()).or_default();
+                        let smoothed = s.apply(history, *val);
+                        smoothing::push_history(history, *val, s.history_len());
+                        smoothed
+                    }
+                    None => *val,
+                };
+                match prev_metric_severities.get(*metric_name) {
+                    Some(prev) => threshold::evaluate_metric_hysteresis(
+                        value,
+                        t,
+                        ThreshSeverity::parse(prev),
+                        t.hysteresis,
+                    ),
+                    None => threshold::evaluate_metric(value, t),
+                }
+            }
+            None    => ThreshSeverity::Normal,
+        };
+        metric_severities.insert(metric_name.to_string(), sev.max(rate_sev));
+    }
+
+    // Required metrics the envelope didn't carry (e.g. a dead sensor)
+    let present: HashSet<&str> = readings.iter().map(|(name, _)| *name).collect();
+    for (metric_name, sev) in threshold::evaluate_missing(&present, &thresholds, config.missing_severity) {
+        metric_severities.insert(metric_name.to_string(), sev);
+    }
+
+    // Long runs of WARN escalate; metrics not evaluated keep their streak
+    match config.warn_escalation {
+        Some(escalate_after) => {
+            for (metric_name, sev) in metric_severities.iter_mut() {
+                let prev_streak = warn_streaks.get(metric_name).copied().unwrap_or(0);
+                let (escalated, streak) = threshold::escalate_warn_streak(*sev, prev_streak, escalate_after);
+                *sev = escalated;
+                warn_streaks.insert(metric_name.clone(), streak);
+            }
+        }
+        None => warn_streaks.clear(),
     }
 
     let overall_severity = threshold::aggregate_severity(metric_severities.values().copied());
 
-    // Previous severity
-    let prev_row = sqlx::query(
-        "SELECT severity FROM plant_current_state WHERE plant_id = $1",
-    )
-    .bind(plant_id_db)
-    .fetch_optional(pool)
-    .await?;
-
-    let prev_severity = prev_row
-        .as_ref()
-        .and_then(|r| r.try_get::<String, _>("severity").ok())
-        .map(|s| ThreshSeverity::from_str(&s))
-        .unwrap_or(ThreshSeverity::Normal);
-
-    // Write to TelemetrySink
+    // TelemetrySink point, written once the transaction has committed
     let mut tags = HashMap::new();
     tags.insert("plant_id".to_string(),      envelope.plant_id.clone());
     tags.insert("device_uid".to_string(),    envelope.device_uid.clone());
     tags.insert("plant_type_id".to_string(), plant_type_id.to_string());
+    if let Some(deployment) = &config.deployment {
+        tags.insert(DEPLOYMENT_TAG.to_string(), deployment.clone());
+    }
+
+    let fields: HashMap<String, f64> =
+        readings.iter().map(|(name, v)| (name.to_string(), *v)).collect();
+
+    let point = (!fields.is_empty()).then(|| TelemetryPoint {
+        measurement: "plant_telemetry".to_string(),
+        tags,
+        fields,
+        timestamp_ns: envelope.timestamp_ns,
+        bucket: None,
+    });
+
+    // Update plant_current_state
+    let state = PlantState {
+        severity: overall_severity,
+        metric_severity: metric_severities
+            .iter()
+            .map(|(k, v)| (k.clone(), v.as_str().to_string()))
+            .collect(),
+        metric_history,
+        last_readings,
+        warn_streaks,
+    };
+
+    if config.dry_run {
+        dry_run_skip(envelope, &format!("set plant_current_state severity to {overall_severity}"));
+    } else {
+        tx.upsert_state(plant_id, envelope, &state).await?;
+    }
+
+    // Reporting cadence, measured against the previous last_seen_at
+    check_cadence(&mut *tx, envelope, plant_id, config.dry_run).await?;
+
+    // Update device (health fields only when reported)
+    if config.dry_run {
+        dry_run_skip(envelope, "update the device's last_seen_at and health fields");
+    } else if !tx.update_device(envelope).await? {
+        if config.auto_provision {
+            tx.provision_device(envelope).await?;
+            info!(device_uid = %envelope.device_uid, "device auto-provisioned on first telemetry");
+        } else {
+            warn!(
+                device_uid = %envelope.device_uid,
+                "telemetry from unregistered device; register it or set SUPERVISOR_AUTO_PROVISION=true"
+            );
+        }
+    }
 
-    let mut fields: HashMap<String, f64> = HashMap::new();
-    if let Some(v) = envelope.soil_moisture       { fields.insert("soil_moisture".into(), v); }
-    if let Some(v) = envelope.ambient_light_lux   { fields.insert("ambient_light_lux".into(), v); }
-    if let Some(v) = envelope.ambient_humidity_rh { fields.insert("ambient_humidity_rh".into(), v); }
-    if let Some(v) = envelope.ambient_temp_c      { fields.insert("ambient_temp_c".into(), v); }
-
-    if !fields.is_empty() {
-        let point = TelemetryPoint {
-            measurement: "plant_telemetry".to_string(),
-            tags,
-            fields,
-            timestamp_ns: envelope.timestamp_ns,
+    // Ticker event, on state changes only unless SUPERVISOR_TICKER_ALL
+    let prev_state = prev_state.is_some().then_some(prev_severity);
+    if should_insert_ticker(prev_state, overall_severity, config.ticker_all) {
+        let recovered_from = recovery(prev_severity, overall_severity)
+            .filter(|_| config.emit_recovery_events);
+        let message = format!(
+            "Plant {} {}",
+            envelope.plant_id,
+            describe_transition(prev_state, overall_severity)
+        );
+        let ticker_payload = match recovered_from {
+            Some(prior) => serde_json::json!({
+                "ingest_id":     &envelope.ingest_id,
+                "recovered":     true,
+                "prev_severity": prior.as_str(),
+            }),
+            None => serde_json::json!({"ingest_id": &envelope.ingest_id}),
         };
-        if let Err(e) = sink.write_points(vec![point]).await {
-            warn!(error = %e, "TelemetrySink write failed (non-fatal)");
+        debug!(payload = %redactor.redact(&ticker_payload), "ticker payload");
+        if config.dry_run {
+            dry_run_skip(envelope, &format!("insert ticker event \"{message}\""));
+        } else {
+            tx.insert_ticker(&TickerEvent {
+                plant_id,
+                device_uid: envelope.device_uid.clone(),
+                severity: overall_severity,
+                message,
+                payload: ticker_payload,
+                occurred_at_ns: envelope.timestamp_ns,
+            })
+            .await?;
         }
     }
 
-    // Update plant_current_state
-    let metric_sev_json = serde_json::to_value(
-        metric_severities
-            .iter()
-            .map(|(k, v)| (k.clone(), v.as_str()))
-            .collect::<HashMap<_, _>>(),
-    )
-    .unwrap_or_default();
-
-    sqlx::query(r#"
-        INSERT INTO plant_current_state
-            (plant_id, updated_at, last_ingest_id, severity,
-             soil_moisture, ambient_light_lux, ambient_humidity_rh, ambient_temp_c,
-             metric_severity)
-        VALUES ($1, NOW(), $2, $3, $4, $5, $6, $7, $8)
-        ON CONFLICT (plant_id) DO UPDATE SET
-            updated_at          = EXCLUDED.updated_at,
-            last_ingest_id      = EXCLUDED.last_ingest_id,
-            severity            = EXCLUDED.severity,
-            soil_moisture       = COALESCE(EXCLUDED.soil_moisture, plant_current_state.soil_moisture),
-            ambient_light_lux   = COALESCE(EXCLUDED.ambient_light_lux, plant_current_state.ambient_light_lux),
-            ambient_humidity_rh = COALESCE(EXCLUDED.ambient_humidity_rh, plant_current_state.ambient_humidity_rh),
-            ambient_temp_c      = COALESCE(EXCLUDED.ambient_temp_c, plant_current_state.ambient_temp_c),
-            metric_severity     = EXCLUDED.metric_severity
-    "#)
-    .bind(plant_id_db)
-    .bind(&envelope.ingest_id)
-    .bind(overall_severity.as_str())
-    .bind(envelope.soil_moisture)
-    .bind(envelope.ambient_light_lux)
-    .bind(envelope.ambient_humidity_rh)
-    .bind(envelope.ambient_temp_c)
-    .bind(metric_sev_json)
-    .execute(pool)
-    .await?;
-
-    // Update device
-    sqlx::query(
-        "UPDATE device SET last_seen_at = NOW(), last_ingest_id = $2 WHERE device_uid = $1",
-    )
-    .bind(&envelope.device_uid)
-    .bind(&envelope.ingest_id)
-    .execute(pool)
-    .await?;
-
-    // Ticker event
-    let message = format!(
-        "Plant {} reading: severity={}",
-        envelope.plant_id, overall_severity
-    );
-    sqlx::query(r#"
-        INSERT INTO ticker_event (plant_id, device_uid, severity, message, payload)
-        VALUES ($1, $2, $3, $4, $5)
-    "#)
-    .bind(plant_id_db)
-    .bind(&envelope.device_uid)
-    .bind(overall_severity.as_str())
-    .bind(&message)
-    .bind(serde_json::json!({"ingest_id": &envelope.ingest_id}))
-    .execute(pool)
-    .await?;
+    // Severity history, for trend charts
+    if let Some((prev, new)) = history_transition(prev_state, overall_severity) {
+        if config.dry_run {
+            dry_run_skip(envelope, &format!("record severity history {} -> {new}", prev.unwrap_or("none")));
+        } else {
+            tx.insert_history(plant_id, prev, new, &state.metric_severity).await?;
+        }
+    }
+
+    if config.dry_run {
+        dry_run_skip(envelope, "record an OK ledger row");
+        tx.rollback().await?;
+    } else {
+        record_ledger(&mut *tx, envelope, "OK", redactor).await?;
+        tx.commit().await?;
+    }
+
+    // Side effects outside Postgres, only for committed readings
+    let sampled_out = point.is_some()
+        && !config.dry_run
+        && config.sink_min_interval.is_some_and(|interval| {
+            !memory.sink_sampler.allow(plant_id, envelope.timestamp_ns, interval)
+        });
+    match point {
+        Some(point) if config.dry_run => {
+            dry_run_skip(envelope, &format!("write {} field(s) to the telemetry sink", point.fields.len()));
+        }
+        Some(_) if sampled_out => {
+            debug!(plant_id = %envelope.plant_id, "point within the sink's minimum interval; not writing it");
+        }
+        Some(point) => {
+            if let Err(e) = sink.write_points(vec![point]).await {
+                warn!(error = %e, "TelemetrySink write failed (non-fatal)");
+                metrics::record_sink_failure();
+            }
+        }
+        None => {}
+    }
 
     // Status change event
     let status_change = if overall_severity != prev_severity {
@@ -238,7 +399,18 @@ async fn process_envelope(
             occurred_at_ns: envelope.timestamp_ns,
         };
 
-        if let Some(chan) = amqp_chan {
+        if config.dry_run && amqp_chan.is_some() {
+            dry_run_skip(envelope, &format!("publish {prev_severity} -> {overall_severity} to plant.status"));
+        }
+        let debounced = amqp_chan.is_some()
+            && !config.dry_run
+            && config.status_debounce.is_some_and(|window| {
+                !memory.status_debounce.allow(plant_id, prev_severity, overall_severity, window, std::time::Instant::now())
+            });
+        if debounced {
+            debug!(plant_id = %envelope.plant_id, "{prev_severity} -> {overall_severity} published recently; not publishing again");
+        }
+        if let Some(chan) = amqp_chan.filter(|_| !config.dry_run && !debounced) {
             let payload = serde_json::json!({
                 "type":          "PlantStatusChanged.v1",
                 "plant_id":      &envelope.plant_id,
@@ -249,7 +421,7 @@ async fn process_envelope(
             let body = serde_json::to_vec(&payload).unwrap_or_default();
             let _ = chan
                 .basic_publish(
-                    "",
+                    "plant.status",
                     "plant.status_change",
                     lapin::options::BasicPublishOptions::default(),
                     &body,
@@ -264,34 +436,202 @@ async fn process_envelope(
         None
     };
 
-    record_ledger(pool, envelope, "OK").await?;
-
     Ok((IngestResult::Ok, status_change))
 }
 
+/// The readings of `envelope` for the configured `metrics`, in that order:
+/// the typed field for the four built-in metrics, otherwise the `readings`
+/// map.  Metrics not listed, and unmeasured ones, are left out.
+fn envelope_readings<'a>(envelope: &TelemetryEnvelope, metrics: &'a [String]) -> Vec<(&'a str, f64)> {
+    metrics
+        .iter()
+        .filter_map(|metric| {
+            let typed = match metric.as_str() {
+                "soil_moisture"       => envelope.soil_moisture,
+                "ambient_light_lux"   => envelope.ambient_light_lux,
+                "ambient_humidity_rh" => envelope.ambient_humidity_rh,
+                "ambient_temp_c"      => envelope.ambient_temp_c,
+                _                     => None,
+            };
+            let value = typed.or_else(|| envelope.readings.get(metric).copied())?;
+            Some((metric.as_str(), value))
+        })
+        .collect()
+}
+
+/// Whether a reading taking a plant from `prev` (`None` before its first
+/// reading) to `new` gets a ticker event: only on a change, or always with
+/// `ticker_all`.
+fn should_insert_ticker(prev: Option<ThreshSeverity>, new: ThreshSeverity, ticker_all: bool) -> bool {
+    ticker_all || prev != Some(new)
+}
+
+/// The `(prev_severity, new_severity)` history row for a reading taking a
+/// plant from `prev` (`None` before its first reading) to `new`, if it is a
+/// transition.
+fn history_transition(
+    prev: Option<ThreshSeverity>,
+    new: ThreshSeverity,
+) -> Option<(Option<&'static str>, &'static str)> {
+    (prev != Some(new)).then(|| (prev.map(|p| p.as_str()), new.as_str()))
+}
+
+/// Ticker message text for a reading, e.g. `WARN → CRITICAL`.
+fn describe_transition(prev: Option<ThreshSeverity>, new: ThreshSeverity) -> String {
+    match prev {
+        None => format!("first reading: {new}"),
+        Some(prev) if prev == new => format!("reading: {new}"),
+        Some(prev) => format!("{prev} → {new}"),
+    }
+}
+
+/// The prior severity if `prev -> new` is a WARN/CRITICAL -> NORMAL recovery.
+fn recovery(prev: ThreshSeverity, new: ThreshSeverity) -> Option<ThreshSeverity> {
+    (new == ThreshSeverity::Normal && prev != ThreshSeverity::Normal).then_some(prev)
+}
+
+/// Raise a WARN ticker event if the time since the device's previous reading
+/// is outside its expected interval band.  With `dry_run` the drift is only
+/// logged.
+async fn check_cadence(
+    tx: &mut dyn PlantTx,
+    envelope: &TelemetryEnvelope,
+    plant_id: Uuid,
+    dry_run: bool,
+) -> Result<()> {
+    let Some(DeviceCadence { interval_s, expected_interval_s: expected_s, tolerance_pct }) =
+        tx.device_cadence(&envelope.device_uid).await?
+    else {
+        return Ok(());
+    };
+
+    let Some(drift) = cadence::check(interval_s, expected_s, tolerance_pct) else {
+        return Ok(());
+    };
+    let message = format!(
+        "Device {} reporting {}: interval {:.1}s, expected {}s ±{}%",
+        envelope.device_uid,
+        match drift {
+            cadence::Drift::TooFast => "too frequently",
+            cadence::Drift::TooSlow => "too infrequently",
+        },
+        interval_s,
+        expected_s,
+        tolerance_pct,
+    );
+    warn!(device_uid = %envelope.device_uid, interval_s, expected_s, "{}", drift.as_str());
+    if dry_run {
+        dry_run_skip(envelope, &format!("insert ticker event \"{message}\""));
+        return Ok(());
+    }
+    tx.insert_ticker(&TickerEvent {
+        plant_id,
+        device_uid: envelope.device_uid.clone(),
+        severity: ThreshSeverity::Warn,
+        message,
+        payload: serde_json::json!({
+            "ingest_id":           &envelope.ingest_id,
+            "cadence":             drift.as_str(),
+            "interval_s":          interval_s,
+            "expected_interval_s": expected_s,
+        }),
+        occurred_at_ns: envelope.timestamp_ns,
+    })
+    .await
+}
+
+/// Log a write `process_envelope` skipped in dry-run mode.
+fn dry_run_skip(envelope: &TelemetryEnvelope, what: &str) {
+    debug!(ingest_id = %envelope.ingest_id, plant_id = %envelope.plant_id, "dry run: would {what}");
+}
+
 fn severity_to_proto(s: ThreshSeverity) -> Severity {
     match s {
         ThreshSeverity::Normal   => Severity::Normal,
+        ThreshSeverity::Stale    => Severity::Stale,
         ThreshSeverity::Warn     => Severity::Warn,
         ThreshSeverity::Critical => Severity::Critical,
     }
 }
 
-async fn record_ledger(pool: &PgPool, env: &TelemetryEnvelope, result: &str) -> Result<()> {
-    sqlx::query(r#"
-        INSERT INTO telemetry_ingest_ledger
-            (ingest_id, device_uid, plant_id, timestamp_ns, result)
-        VALUES ($1, $2, $3, $4, $5)
-        ON CONFLICT (ingest_id) DO NOTHING
-    "#)
-    .bind(&env.ingest_id)
-    .bind(&env.device_uid)
-    .bind(Uuid::parse_str(&env.plant_id).ok())
-    .bind(env.timestamp_ns)
-    .bind(result)
-    .execute(pool)
-    .await?;
-    Ok(())
+async fn record_ledger(
+    tx: &mut dyn PlantTx,
+    env: &TelemetryEnvelope,
+    result: &str,
+    redactor: &Redactor,
+) -> Result<()> {
+    let entry = serde_json::json!({
+        "ingest_id":    &env.ingest_id,
+        "device_uid":   &env.device_uid,
+        "plant_id":     &env.plant_id,
+        "timestamp_ns": env.timestamp_ns,
+        "result":       result,
+    });
+    debug!(payload = %redactor.redact(&entry), "ledger entry");
+    tx.record_ledger(env, result).await
+}
+
+/// Partition a batch into groups of envelope indices that must be processed
+/// in order: envelopes for the same plant, whose current state they update.
+/// Groups are in first-seen order.  Repeats of an `ingest_id` need no
+/// ordering; they are settled up front by [`repeated_in_batch`].
+fn ingest_groups(envelopes: &[TelemetryEnvelope]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut by_plant: HashMap<&str, usize> = HashMap::new();
+    for (i, env) in envelopes.iter().enumerate() {
+        let group = *by_plant.entry(&env.plant_id).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+    groups
+}
+
+/// Indices of envelopes whose `ingest_id` already appeared earlier in the
+/// batch.  These are answered `Duplicate` without any store work, whatever
+/// becomes of the first copy: if it fails, the repeat is still `Duplicate`
+/// rather than a second attempt at the same reading.
+fn repeated_in_batch(envelopes: &[TelemetryEnvelope]) -> HashSet<usize> {
+    let mut seen = HashSet::new();
+    envelopes
+        .iter()
+        .enumerate()
+        .filter(|(_, env)| !seen.insert(env.ingest_id.as_str()))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// The response entry (and any status change) for one processed envelope.
+fn item_result(
+    envelope: &TelemetryEnvelope,
+    outcome: Result<(IngestResult, Option<StatusChange>)>,
+) -> (ItemResult, Option<StatusChange>) {
+    metrics::record_ingest(match &outcome {
+        Ok((code, _)) => *code,
+        Err(_) => IngestResult::Error,
+    });
+    match outcome {
+        Ok((code, change)) => (
+            ItemResult {
+                ingest_id: envelope.ingest_id.clone(),
+                result:    code as i32,
+                error:     String::new(),
+            },
+            change,
+        ),
+        Err(e) => {
+            error!(error = %e, ingest_id = %envelope.ingest_id, "ingest failed");
+            (
+                ItemResult {
+                    ingest_id: envelope.ingest_id.clone(),
+                    result:    IngestResult::Error as i32,
+                    error:     e.to_string(),
+                },
+                None,
+            )
+        }
+    }
 }
 
 // ------------------------------------------------------------------ //
@@ -304,38 +644,51 @@ impl SupervisorService for SupervisorServiceImpl {
         &self,
         request: Request<IngestTelemetryRequest>,
     ) -> Result<Response<IngestTelemetryResponse>, Status> {
+        if self.maintenance.load(Ordering::SeqCst) {
+            return Err(Status::unavailable(MAINTENANCE_MESSAGE));
+        }
+
         let req = request.into_inner();
-        let mut results        = Vec::with_capacity(req.envelopes.len());
-        let mut status_changes = Vec::new();
+        let envelopes = &req.envelopes;
+        let repeats = &repeated_in_batch(envelopes);
 
-        for envelope in &req.envelopes {
-            match process_envelope(
-                envelope,
-                &self.pool,
-                &*self.sink,
-                self.amqp_chan.as_ref(),
-            )
-            .await
-            {
-                Ok((code, opt_change)) => {
-                    results.push(ItemResult {
-                        ingest_id: envelope.ingest_id.clone(),
-                        result:    code as i32,
-                        error:     String::new(),
-                    });
-                    if let Some(c) = opt_change {
-                        status_changes.push(c);
+        // Groups run concurrently; envelopes within a group run in order.
+        let outcomes: BTreeMap<usize, (ItemResult, Option<StatusChange>)> =
+            stream::iter(ingest_groups(envelopes))
+                .map(|indices| async move {
+                    let mut outcomes = Vec::with_capacity(indices.len());
+                    for i in indices {
+                        let envelope = &envelopes[i];
+                        let outcome = if repeats.contains(&i) {
+                            Ok((IngestResult::Duplicate, None))
+                        } else {
+                            process_envelope(
+                                envelope,
+                                &*self.store,
+                                &*self.sink,
+                                self.amqp_chan.as_ref(),
+                                &self.memory,
+                                &self.redactor,
+                                &self.config,
+                            )
+                            .await
+                        };
+                        outcomes.push((i, item_result(envelope, outcome)));
                     }
-                }
-                Err(e) => {
-                    error!(error = %e, ingest_id = %envelope.ingest_id, "ingest failed");
-                    results.push(ItemResult {
-                        ingest_id: envelope.ingest_id.clone(),
-                        result:    IngestResult::Error as i32,
-                        error:     e.to_string(),
-                    });
-                }
-            }
+                    outcomes
+                })
+                .buffer_unordered(self.config.ingest_concurrency.max(1))
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+
+        let mut results        = Vec::with_capacity(envelopes.len());
+        let mut status_changes = Vec::new();
+        for (result, change) in outcomes.into_values() {
+            results.push(result);
+            status_changes.extend(change);
         }
 
         info!(
@@ -345,4 +698,599 @@ impl SupervisorService for SupervisorServiceImpl {
         );
         Ok(Response::new(IngestTelemetryResponse { results, status_changes }))
     }
+
+    async fn create_plant_type(
+        &self,
+        request: Request<CreatePlantTypeRequest>,
+    ) -> Result<Response<CreatePlantTypeResponse>, Status> {
+        let req = request.into_inner();
+        let id = admin::create_plant_type(&self.pool, &req).await?;
+        info!(%id, name = %req.name, "plant_type created");
+        Ok(Response::new(CreatePlantTypeResponse { id: id.to_string() }))
+    }
+
+    async fn create_plant(
+        &self,
+        request: Request<CreatePlantRequest>,
+    ) -> Result<Response<CreatePlantResponse>, Status> {
+        let req = request.into_inner();
+        let id = admin::create_plant(&self.pool, &req).await?;
+        info!(%id, plant_type_id = %req.plant_type_id, "plant created");
+        Ok(Response::new(CreatePlantResponse { id: id.to_string() }))
+    }
+
+    async fn create_device(
+        &self,
+        request: Request<CreateDeviceRequest>,
+    ) -> Result<Response<CreateDeviceResponse>, Status> {
+        let req = request.into_inner();
+        let id = admin::create_device(&self.pool, &req).await?;
+        info!(%id, device_uid = %req.device_uid, "device created");
+        Ok(Response::new(CreateDeviceResponse { id: id.to_string() }))
+    }
+
+    async fn set_maintenance_mode(
+        &self,
+        request: Request<SetMaintenanceModeRequest>,
+    ) -> Result<Response<SetMaintenanceModeResponse>, Status> {
+        let on = request.into_inner().on;
+        let was_on = self.maintenance.swap(on, Ordering::SeqCst);
+        if on != was_on {
+            warn!(on, "maintenance mode changed");
+        }
+        Ok(Response::new(SetMaintenanceModeResponse { was_on }))
+    }
+
+    async fn query_ledger(
+        &self,
+        request: Request<QueryLedgerRequest>,
+    ) -> Result<Response<QueryLedgerResponse>, Status> {
+        let entries = ledger::query_ledger(&self.pool, &request.into_inner()).await?;
+        Ok(Response::new(QueryLedgerResponse { entries }))
+    }
+
+    async fn get_effective_thresholds(
+        &self,
+        request: Request<GetEffectiveThresholdsRequest>,
+    ) -> Result<Response<GetEffectiveThresholdsResponse>, Status> {
+        let resp = admin::effective_thresholds(&*self.store, &request.into_inner()).await?;
+        Ok(Response::new(resp))
+    }
+
+    async fn replay_dead_letter(
+        &self,
+        request: Request<ReplayDeadLetterRequest>,
+    ) -> Result<Response<ReplayDeadLetterResponse>, Status> {
+        if self.maintenance.load(Ordering::SeqCst) {
+            return Err(Status::unavailable(MAINTENANCE_MESSAGE));
+        }
+
+        if self.config.dry_run {
+            // Replaying releases and clears dead-letter rows, which dry-run
+            // mode must not do.
+            return Err(Status::failed_precondition("ReplayDeadLetter is disabled in dry-run mode"));
+        }
+
+        let window = dead_letter::ReplayWindow::from_request(&request.into_inner())?;
+        let envelopes = dead_letter::pending(&self.pool, &window).await?;
+
+        // Oldest first and one at a time, so per-plant state sees readings
+        // in the order they originally arrived.
+        let mut results = Vec::with_capacity(envelopes.len());
+        let mut cleared = 0;
+        for envelope in &envelopes {
+            let outcome = match dead_letter::release_ledger(&self.pool, &envelope.ingest_id).await {
+                Ok(()) => {
+                    process_envelope(
+                        envelope,
+                        &*self.store,
+                        &*self.sink,
+                        self.amqp_chan.as_ref(),
+                        &self.memory,
+                        &self.redactor,
+                        &self.config,
+                    )
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            let (result, _) = item_result(envelope, outcome);
+            if result.result != IngestResult::Error as i32 {
+                dead_letter::clear(&self.pool, &envelope.ingest_id)
+                    .await
+                    .map_err(|e| Status::internal(e.to_string()))?;
+                cleared += 1;
+            }
+            results.push(result);
+        }
+
+        info!(replayed = results.len(), cleared, "ReplayDeadLetter complete");
+        Ok(Response::new(ReplayDeadLetterResponse { results, cleared }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+    use crate::store::InMemoryPlantStore;
+    use crate::telemetry_sink::FakeTelemetrySink;
+    use crate::threshold::MetricThreshold;
+
+    /// A service whose pool never connects; fine for paths that don't query.
+    fn offline_service() -> SupervisorServiceImpl {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://unused@localhost/unused")
+            .unwrap();
+        SupervisorServiceImpl::new(pool, Arc::new(FakeTelemetrySink::new()), None)
+    }
+
+    fn envelope() -> TelemetryEnvelope {
+        TelemetryEnvelope {
+            ingest_id: "abc".into(),
+            plant_id: Uuid::new_v4().to_string(),
+            soil_moisture: Some(40.0),
+            ..Default::default()
+        }
+    }
+
+    /// An offline service whose ingest runs against `store`.
+    fn fake_service(store: &InMemoryPlantStore) -> SupervisorServiceImpl {
+        offline_service().with_store(Arc::new(store.clone()))
+    }
+
+    async fn ingest(svc: &SupervisorServiceImpl, envelope: TelemetryEnvelope) -> IngestTelemetryResponse {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![envelope] }))
+            .await
+            .unwrap()
+            .into_inner()
+    }
+
+    #[tokio::test]
+    async fn duplicate_ingest_is_not_applied_twice() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store);
+        let env = TelemetryEnvelope { plant_id: store.add_plant(vec![]).to_string(), ..envelope() };
+
+        let first = ingest(&svc, env.clone()).await;
+        assert_eq!(first.results[0].result, IngestResult::Ok as i32);
+        let repeat = ingest(&svc, env).await;
+        assert_eq!(repeat.results[0].result, IngestResult::Duplicate as i32);
+        assert!(repeat.status_changes.is_empty());
+
+        let memory = store.snapshot();
+        assert_eq!(memory.ledger.len(), 1);
+        assert_eq!(memory.ticker.len(), 1, "only the first reading is on the ticker");
+        assert_eq!(memory.history.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn repeat_within_one_batch_is_a_duplicate() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store);
+        let env = TelemetryEnvelope { plant_id: store.add_plant(vec![]).to_string(), ..envelope() };
+
+        let resp = svc
+            .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env.clone(), env] }))
+            .await
+            .unwrap()
+            .into_inner();
+        let results: Vec<i32> = resp.results.iter().map(|r| r.result).collect();
+        assert_eq!(results, [IngestResult::Ok as i32, IngestResult::Duplicate as i32]);
+
+        let memory = store.snapshot();
+        assert_eq!(memory.ledger.len(), 1);
+        assert_eq!(memory.history.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn repeat_within_one_batch_is_a_duplicate_even_if_the_first_fails() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store);
+        // No such plant: the first copy is dead-lettered.
+        let env = envelope();
+
+        let resp = svc
+            .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env.clone(), env] }))
+            .await
+            .unwrap()
+            .into_inner();
+        let results: Vec<i32> = resp.results.iter().map(|r| r.result).collect();
+        assert_eq!(results, [IngestResult::Error as i32, IngestResult::Duplicate as i32]);
+        assert_eq!(store.snapshot().dead_letters.len(), 1);
+    }
+
+    /// Collects what a `fmt` subscriber writes.
+    #[derive(Clone, Default)]
+    struct CaptureWriter(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CaptureWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn configured_keys_are_masked_in_ledger_and_ticker_logs() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store).with_redactor(Redactor::new(["ingest_id", "device_uid"]));
+        let env = TelemetryEnvelope {
+            ingest_id: "ingest-7f3a".into(),
+            device_uid: "esp32-kitchen".into(),
+            plant_id: store.add_plant(vec![]).to_string(),
+            ..envelope()
+        };
+
+        let writer = CaptureWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .finish();
+        {
+            let _guard = tracing::subscriber::set_default(subscriber);
+            assert_eq!(ingest(&svc, env.clone()).await.results[0].result, IngestResult::Ok as i32);
+        }
+
+        let logged = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let line = |message: &str| {
+            logged
+                .lines()
+                .find(|line| line.contains(message))
+                .unwrap_or_else(|| panic!("no {message:?} event in {logged}"))
+        };
+        let ledger = line("ledger entry");
+        assert!(!ledger.contains("ingest-7f3a") && !ledger.contains("esp32-kitchen"), "{ledger}");
+        assert!(ledger.contains(&env.plant_id), "{ledger}");
+        let ticker = line("ticker payload");
+        assert!(!ticker.contains("ingest-7f3a"), "{ticker}");
+        assert!(ticker.contains(redact::MASK), "{ticker}");
+
+        // Only the log is masked.
+        let memory = store.snapshot();
+        assert!(memory.ledger.contains_key("ingest-7f3a"));
+        assert_eq!(memory.ticker[0].payload["ingest_id"], "ingest-7f3a");
+    }
+
+    #[tokio::test]
+    async fn sink_writes_are_downsampled_per_plant() {
+        let store = InMemoryPlantStore::new();
+        let sink = FakeTelemetrySink::new();
+        let mut svc = fake_service(&store).with_config(SupervisorConfig {
+            sink_min_interval: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        });
+        svc.sink = Arc::new(sink.clone());
+        let plant_id = store.add_plant(vec![]).to_string();
+        let reading = |ingest_id: &str, secs: i64| TelemetryEnvelope {
+            ingest_id: ingest_id.into(),
+            plant_id: plant_id.clone(),
+            timestamp_ns: secs * 1_000_000_000,
+            ..envelope()
+        };
+
+        // Within the interval: both ingest, only the first is written.
+        for (id, secs) in [("a", 100), ("b", 105)] {
+            let resp = ingest(&svc, reading(id, secs)).await;
+            assert_eq!(resp.results[0].result, IngestResult::Ok as i32);
+        }
+        assert_eq!(sink.drain().len(), 1);
+        assert_eq!(store.snapshot().ledger["b"], ("OK".to_string(), 105 * 1_000_000_000));
+
+        // Spaced beyond it: both written.
+        for (id, secs) in [("c", 110), ("d", 125)] {
+            ingest(&svc, reading(id, secs)).await;
+        }
+        let written: Vec<_> = sink.drain().iter().map(|p| p.timestamp_ns).collect();
+        assert_eq!(written, [110 * 1_000_000_000, 125 * 1_000_000_000]);
+    }
+
+    #[tokio::test]
+    async fn unknown_plant_is_dead_lettered_with_an_error_ledger_row() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store);
+        let env = envelope();
+
+        let resp = ingest(&svc, env.clone()).await;
+        assert_eq!(resp.results[0].result, IngestResult::Error as i32);
+        assert!(resp.status_changes.is_empty());
+
+        let memory = store.snapshot();
+        assert_eq!(memory.ledger[&env.ingest_id].0, "ERROR");
+        assert_eq!(
+            memory.dead_letters,
+            vec![(env.ingest_id, dead_letter::UNKNOWN_PLANT.to_string())]
+        );
+        assert!(memory.states.is_empty());
+    }
+
+    #[tokio::test]
+    async fn threshold_breach_and_recovery_emit_status_changes() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store);
+        let plant_id = store.add_plant(vec![MetricThreshold {
+            metric: "soil_moisture".into(),
+            warn_min: None,
+            warn_max: None,
+            crit_min: Some(20.0),
+            crit_max: None,
+            smoothing: None,
+            hysteresis: 0.0,
+            max_rate_per_min: None,
+            required: false,
+        }]);
+        let reading = |ingest_id: &str, soil_moisture: f64| TelemetryEnvelope {
+            ingest_id: ingest_id.into(),
+            plant_id: plant_id.to_string(),
+            soil_moisture: Some(soil_moisture),
+            ..envelope()
+        };
+
+        let dry = ingest(&svc, reading("a", 10.0)).await;
+        let change = &dry.status_changes[0];
+        assert_eq!(
+            (change.prev_severity, change.new_severity),
+            (Severity::Normal as i32, Severity::Critical as i32)
+        );
+        assert_eq!(store.snapshot().states[&plant_id].severity, ThreshSeverity::Critical);
+
+        let steady = ingest(&svc, reading("b", 12.0)).await;
+        assert!(steady.status_changes.is_empty());
+
+        let recovered = ingest(&svc, reading("c", 40.0)).await;
+        assert_eq!(recovered.status_changes[0].new_severity, Severity::Normal as i32);
+        let memory = store.snapshot();
+        assert_eq!(memory.states[&plant_id].metric_severity["soil_moisture"], "NORMAL");
+        assert_eq!(memory.ledger.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn consecutive_warn_readings_escalate_until_recovery() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store).with_config(SupervisorConfig {
+            warn_escalation: Some(3),
+            ..Default::default()
+        });
+        let plant_id = store.add_plant(vec![MetricThreshold {
+            metric: "soil_moisture".into(),
+            warn_min: Some(30.0),
+            warn_max: None,
+            crit_min: Some(10.0),
+            crit_max: None,
+            smoothing: None,
+            hysteresis: 0.0,
+            max_rate_per_min: None,
+            required: false,
+        }]);
+        let reading = |ingest_id: &str, soil_moisture: f64| TelemetryEnvelope {
+            ingest_id: ingest_id.into(),
+            plant_id: plant_id.to_string(),
+            soil_moisture: Some(soil_moisture),
+            ..envelope()
+        };
+        let severity = || store.snapshot().states[&plant_id].severity;
+
+        ingest(&svc, reading("a", 25.0)).await;
+        ingest(&svc, reading("b", 25.0)).await;
+        assert_eq!(severity(), ThreshSeverity::Warn);
+        let third = ingest(&svc, reading("c", 25.0)).await;
+        assert_eq!(severity(), ThreshSeverity::Critical);
+        assert_eq!(third.status_changes[0].new_severity, Severity::Critical as i32);
+        assert_eq!(store.snapshot().states[&plant_id].warn_streaks["soil_moisture"], 3);
+
+        // Recovery resets the streak, so the next WARN is just a WARN.
+        ingest(&svc, reading("d", 50.0)).await;
+        assert_eq!(severity(), ThreshSeverity::Normal);
+        assert_eq!(store.snapshot().states[&plant_id].warn_streaks["soil_moisture"], 0);
+        ingest(&svc, reading("e", 25.0)).await;
+        assert_eq!(severity(), ThreshSeverity::Warn);
+    }
+
+    #[tokio::test]
+    async fn missing_required_metric_escalates_per_policy() {
+        let store = InMemoryPlantStore::new();
+        let plant_id = store.add_plant(vec![MetricThreshold {
+            metric: "soil_moisture".into(),
+            warn_min: None,
+            warn_max: None,
+            crit_min: None,
+            crit_max: None,
+            smoothing: None,
+            hysteresis: 0.0,
+            max_rate_per_min: None,
+            required: true,
+        }]);
+        let without_moisture = |ingest_id: &str| TelemetryEnvelope {
+            ingest_id: ingest_id.into(),
+            plant_id: plant_id.to_string(),
+            soil_moisture: None,
+            ambient_temp_c: Some(21.0),
+            ..envelope()
+        };
+
+        ingest(&fake_service(&store), without_moisture("a")).await;
+        let state = &store.snapshot().states[&plant_id];
+        assert_eq!(state.severity, ThreshSeverity::Critical);
+        assert_eq!(state.metric_severity["soil_moisture"], "CRITICAL");
+
+        let warn_only = fake_service(&store).with_config(SupervisorConfig {
+            missing_severity: ThreshSeverity::Warn,
+            ..Default::default()
+        });
+        ingest(&warn_only, without_moisture("b")).await;
+        assert_eq!(store.snapshot().states[&plant_id].severity, ThreshSeverity::Warn);
+    }
+
+    #[test]
+    fn readings_follow_the_configured_metrics() {
+        let metrics: Vec<String> =
+            ["soil_moisture", "co2_ppm", "ambient_temp_c"].iter().map(|m| m.to_string()).collect();
+        let env = TelemetryEnvelope {
+            soil_moisture: Some(40.0),
+            ambient_light_lux: Some(900.0),
+            readings: [
+                ("co2_ppm".to_string(), 650.0),
+                ("soil_moisture".to_string(), 1.0),
+                ("unlisted".to_string(), 3.0),
+            ]
+            .into(),
+            ..envelope()
+        };
+        // Typed field wins; unlisted and unmeasured metrics are left out.
+        assert_eq!(
+            envelope_readings(&env, &metrics),
+            vec![("soil_moisture", 40.0), ("co2_ppm", 650.0)]
+        );
+    }
+
+    #[test]
+    fn history_rows_only_for_transitions() {
+        use ThreshSeverity::*;
+        assert_eq!(history_transition(None, Normal), Some((None, "NORMAL")));
+        assert_eq!(history_transition(Some(Normal), Warn), Some((Some("NORMAL"), "WARN")));
+        assert_eq!(history_transition(Some(Critical), Stale), Some((Some("CRITICAL"), "STALE")));
+        assert_eq!(history_transition(Some(Warn), Warn), None);
+    }
+
+    #[test]
+    fn recovery_only_on_transition_to_normal() {
+        use ThreshSeverity::*;
+        assert_eq!(recovery(Critical, Normal), Some(Critical));
+        assert_eq!(recovery(Warn, Normal), Some(Warn));
+        assert_eq!(recovery(Normal, Normal), None);
+        assert_eq!(recovery(Critical, Warn), None);
+        assert_eq!(recovery(Normal, Critical), None);
+    }
+
+    #[test]
+    fn groups_serialize_same_plant() {
+        let env = |ingest_id: &str, plant_id: &str| TelemetryEnvelope {
+            ingest_id: ingest_id.into(),
+            plant_id: plant_id.into(),
+            ..Default::default()
+        };
+        let batch = [
+            env("a", "p1"),
+            env("b", "p2"),
+            env("c", "p1"),
+            env("b", "p3"),
+            env("d", "p4"),
+        ];
+        assert_eq!(ingest_groups(&batch), vec![vec![0, 2], vec![1], vec![3], vec![4]]);
+        assert!(ingest_groups(&[]).is_empty());
+    }
+
+    #[test]
+    fn ticker_only_on_change_unless_ticker_all() {
+        use ThreshSeverity::*;
+        assert!(should_insert_ticker(None, Normal, false));
+        assert!(should_insert_ticker(Some(Warn), Critical, false));
+        assert!(should_insert_ticker(Some(Critical), Normal, false));
+        assert!(!should_insert_ticker(Some(Normal), Normal, false));
+        assert!(!should_insert_ticker(Some(Warn), Warn, false));
+        assert!(should_insert_ticker(Some(Warn), Warn, true));
+    }
+
+    #[test]
+    fn ticker_message_describes_transition() {
+        use ThreshSeverity::*;
+        assert_eq!(describe_transition(Some(Warn), Critical), "WARN → CRITICAL");
+        assert_eq!(describe_transition(None, Normal), "first reading: NORMAL");
+        assert_eq!(describe_transition(Some(Warn), Warn), "reading: WARN");
+    }
+
+    #[tokio::test]
+    async fn ingest_rejected_while_in_maintenance() {
+        let svc = offline_service();
+        let resp = svc
+            .set_maintenance_mode(Request::new(SetMaintenanceModeRequest { on: true }))
+            .await
+            .unwrap();
+        assert!(!resp.into_inner().was_on);
+
+        let err = svc
+            .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![envelope()] }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        assert_eq!(err.message(), MAINTENANCE_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn ingest_accepted_again_after_maintenance_ends() {
+        let svc = offline_service();
+        svc.set_maintenance_mode(Request::new(SetMaintenanceModeRequest { on: true }))
+            .await
+            .unwrap();
+        let resp = svc
+            .set_maintenance_mode(Request::new(SetMaintenanceModeRequest { on: false }))
+            .await
+            .unwrap();
+        assert!(resp.into_inner().was_on);
+
+        let resp = svc
+            .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![] }))
+            .await
+            .unwrap();
+        assert!(resp.into_inner().results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_device_is_provisioned_only_when_enabled() {
+        let store = InMemoryPlantStore::new();
+        let plant_id = store.add_plant(vec![]).to_string();
+        let reading = |ingest_id: &str| TelemetryEnvelope {
+            ingest_id: ingest_id.into(),
+            device_uid: "esp32-new".into(),
+            plant_id: plant_id.clone(),
+            ..envelope()
+        };
+
+        ingest(&fake_service(&store), reading("a")).await;
+        assert!(store.snapshot().devices.is_empty());
+
+        let svc = fake_service(&store)
+            .with_config(SupervisorConfig { auto_provision: true, ..Default::default() });
+        ingest(&svc, reading("b")).await;
+        assert_eq!(store.snapshot().devices["esp32-new"].as_deref(), Some("b"));
+
+        // Known from then on: updated, not provisioned again.
+        ingest(&svc, reading("c")).await;
+        assert_eq!(store.snapshot().devices["esp32-new"].as_deref(), Some("c"));
+    }
+
+    #[tokio::test]
+    async fn effective_thresholds_apply_plant_overrides() {
+        let store = InMemoryPlantStore::new();
+        let svc = fake_service(&store);
+        let threshold = |metric: &str, crit_min: f64| MetricThreshold {
+            metric: metric.into(),
+            warn_min: None,
+            warn_max: None,
+            crit_min: Some(crit_min),
+            crit_max: None,
+            smoothing: None,
+            hysteresis: 0.0,
+            max_rate_per_min: None,
+            required: false,
+        };
+        let plant_id = store.add_plant(vec![threshold("soil_moisture", 20.0), threshold("temperature_c", 5.0)]);
+        store.memory.lock().unwrap().plant_thresholds.insert(plant_id, vec![threshold("temperature_c", 10.0)]);
+        let get = |plant_id: String| svc.get_effective_thresholds(Request::new(GetEffectiveThresholdsRequest { plant_id }));
+
+        let resp = get(plant_id.to_string()).await.unwrap().into_inner();
+        let crit_min: Vec<_> = resp.thresholds.iter().map(|t| (t.metric.as_str(), t.crit_min)).collect();
+        assert_eq!(crit_min, [("soil_moisture", Some(20.0)), ("temperature_c", Some(10.0))]);
+        assert_eq!(resp.plant_type_id, store.snapshot().plants[&plant_id].to_string());
+
+        assert_eq!(get(Uuid::new_v4().to_string()).await.unwrap_err().code(), tonic::Code::NotFound);
+        assert_eq!(get("nope".into()).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
 }
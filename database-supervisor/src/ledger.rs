@@ -0,0 +1,180 @@
+//! Read path over `telemetry_ingest_ledger`, for auditing which readings
+//! were received and how they were handled, and pruning of rows past the
+//! dedup window.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use proto::supervisor_service::{LedgerEntry, QueryLedgerRequest};
+use sqlx::{PgPool, Row};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::admin::AdminError;
+
+pub const DEFAULT_LIMIT: u32 = 100;
+pub const MAX_LIMIT: u32 = 1000;
+/// How often [`spawn_prune`] deletes rows past the dedup window.
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Validated form of a [`QueryLedgerRequest`].
+#[derive(Debug, PartialEq)]
+pub struct LedgerFilter {
+    pub device_uid: Option<String>,
+    pub plant_id: Option<Uuid>,
+    pub start_ns: Option<i64>,
+    pub stop_ns: Option<i64>,
+    pub limit: i64,
+}
+
+impl LedgerFilter {
+    pub fn from_request(req: &QueryLedgerRequest) -> Result<Self, AdminError> {
+        let plant_id = match req.plant_id.trim() {
+            "" => None,
+            s => Some(Uuid::parse_str(s).map_err(|_| {
+                AdminError::InvalidArgument(format!("plant_id is not a valid UUID: {s}"))
+            })?),
+        };
+        let start_ns = (req.start_ns != 0).then_some(req.start_ns);
+        let stop_ns = (req.stop_ns != 0).then_some(req.stop_ns);
+        if let (Some(start), Some(stop)) = (start_ns, stop_ns) {
+            if start >= stop {
+                return Err(AdminError::InvalidArgument(format!(
+                    "start_ns ({start}) must be before stop_ns ({stop})"
+                )));
+            }
+        }
+        let limit = match req.limit {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        };
+        Ok(Self {
+            device_uid: Some(req.device_uid.trim().to_string()).filter(|s| !s.is_empty()),
+            plant_id,
+            start_ns,
+            stop_ns,
+            limit: limit as i64,
+        })
+    }
+}
+
+/// Ledger entries matching `req`, newest reading first.
+pub async fn query_ledger(pool: &PgPool, req: &QueryLedgerRequest) -> Result<Vec<LedgerEntry>, AdminError> {
+    let f = LedgerFilter::from_request(req)?;
+
+    let rows = sqlx::query(r#"
+        SELECT ingest_id, device_uid, plant_id::text AS plant_id, timestamp_ns, result, received_at
+        FROM telemetry_ingest_ledger
+        WHERE ($1::text   IS NULL OR device_uid = $1)
+          AND ($2::uuid   IS NULL OR plant_id = $2)
+          AND ($3::bigint IS NULL OR timestamp_ns >= $3)
+          AND ($4::bigint IS NULL OR timestamp_ns < $4)
+        ORDER BY timestamp_ns DESC, ingest_id
+        LIMIT $5
+    "#)
+    .bind(&f.device_uid)
+    .bind(f.plant_id)
+    .bind(f.start_ns)
+    .bind(f.stop_ns)
+    .bind(f.limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|r| {
+            let received_at: DateTime<Utc> = r.try_get("received_at")?;
+            Ok(LedgerEntry {
+                ingest_id:      r.try_get("ingest_id")?,
+                device_uid:     r.try_get("device_uid")?,
+                plant_id:       r.try_get::<Option<String>, _>("plant_id")?.unwrap_or_default(),
+                timestamp_ns:   r.try_get("timestamp_ns")?,
+                result:         r.try_get("result")?,
+                received_at_ns: received_at.timestamp_nanos_opt().unwrap_or(0),
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(AdminError::from)
+}
+
+/// Oldest reading time (`timestamp_ns`) still inside a dedup `window`
+/// ending at `now`; `i64::MIN` when the window reaches back further than
+/// an `i64` can say.
+pub fn dedup_cutoff_ns(now: DateTime<Utc>, window: Duration) -> i64 {
+    let now_ns = now.timestamp_nanos_opt().unwrap_or(i64::MAX);
+    let window_ns = i128::try_from(window.as_nanos()).unwrap_or(i128::MAX);
+    // The cutoff is at most `now_ns`, so only the low end can overflow.
+    i64::try_from(i128::from(now_ns).saturating_sub(window_ns)).unwrap_or(i64::MIN)
+}
+
+/// Delete ledger rows with a reading time before `cutoff_ns`, returning how
+/// many were removed.
+pub async fn prune(pool: &PgPool, cutoff_ns: i64) -> Result<u64, sqlx::Error> {
+    let deleted = sqlx::query("DELETE FROM telemetry_ingest_ledger WHERE timestamp_ns < $1")
+        .bind(cutoff_ns)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(deleted)
+}
+
+/// Run [`prune`] for `window` every [`PRUNE_INTERVAL`] until the task is
+/// aborted.
+pub fn spawn_prune(pool: PgPool, window: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match prune(&pool, dedup_cutoff_ns(Utc::now(), window)).await {
+                Ok(0) => {}
+                Ok(deleted) => info!(deleted, "pruned ledger rows past the dedup window"),
+                Err(e) => warn!(error = %e, "ledger prune failed"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutoff_is_window_before_now() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(
+            dedup_cutoff_ns(now, Duration::from_secs(24 * 3600)),
+            (1_700_000_000 - 24 * 3600) * 1_000_000_000
+        );
+        assert_eq!(dedup_cutoff_ns(now, Duration::MAX), i64::MIN);
+    }
+
+    #[test]
+    fn empty_request_uses_defaults() {
+        let f = LedgerFilter::from_request(&QueryLedgerRequest::default()).unwrap();
+        assert_eq!(
+            f,
+            LedgerFilter {
+                device_uid: None,
+                plant_id: None,
+                start_ns: None,
+                stop_ns: None,
+                limit: DEFAULT_LIMIT as i64,
+            }
+        );
+    }
+
+    #[test]
+    fn limit_is_capped() {
+        let req = QueryLedgerRequest { limit: 50_000, ..Default::default() };
+        assert_eq!(LedgerFilter::from_request(&req).unwrap().limit, MAX_LIMIT as i64);
+    }
+
+    #[test]
+    fn rejects_bad_plant_id_and_inverted_window() {
+        let req = QueryLedgerRequest { plant_id: "nope".into(), ..Default::default() };
+        assert!(matches!(LedgerFilter::from_request(&req), Err(AdminError::InvalidArgument(_))));
+
+        let req = QueryLedgerRequest { start_ns: 10, stop_ns: 5, ..Default::default() };
+        assert!(matches!(LedgerFilter::from_request(&req), Err(AdminError::InvalidArgument(_))));
+    }
+}
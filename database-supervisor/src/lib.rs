@@ -1,5 +1,19 @@
 //! Database Supervisor library — plant health telemetry ingestion.
 
+pub mod admin;
+pub mod cadence;
+pub mod config;
+pub mod dead_letter;
+pub mod debounce;
+pub mod health;
 pub mod ingest;
+pub mod ledger;
+pub mod metrics;
+pub mod raw_capture;
+pub mod sampling;
+pub mod shutdown;
+pub mod smoothing;
+pub mod stale;
+pub mod store;
 pub mod telemetry_sink;
 pub mod threshold;
@@ -4,23 +4,54 @@
 //! | Var                         | Default              |
 //! |-----------------------------|----------------------|
 //! | `DATABASE_URL`              | required             |
+//! | `BWS_SUPERVISOR_DATABASE_URL_ID` | `supervisor-database-url` |
 //! | `SUPERVISOR_ADDR`           | `[::1]:50053`        |
+//! | `SUPERVISOR_METRICS_ADDR`   | `[::1]:9464`         |
 //! | `INFLUXDB_URL`              | optional             |
 //! | `INFLUXDB_ORG`              | optional             |
 //! | `INFLUXDB_TOKEN`            | optional             |
 //! | `INFLUXDB_BUCKET`           | optional             |
+//! | `INFLUXDB_BUCKET_MAP`       | optional             |
+//! | `INFLUXDB_ALLOWED_BUCKETS`  | optional             |
+//! | `INFLUXDB_BUFFER_POINTS`    | `10000`              |
+//! | `SUPERVISOR_DEPLOYMENT`     | optional             |
+//! | `SUPERVISOR_EMIT_RECOVERY_EVENTS` | `false`        |
+//! | `SUPERVISOR_STALE_TTL_S`    | unset (no sweep)     |
+//! | `SUPERVISOR_TICKER_ALL`     | `false`              |
+//! | `SUPERVISOR_INGEST_CONCURRENCY` | `8`              |
+//! | `SUPERVISOR_DRY_RUN`        | `false`              |
+//! | `SUPERVISOR_METRICS`        | the four typed readings |
+//! | `SUPERVISOR_DEDUP_WINDOW_HOURS` | `24` (`0`: forever) |
+//! | `SUPERVISOR_MISSING_METRIC_SEVERITY` | `CRITICAL`  |
+//! | `SUPERVISOR_STATUS_DEBOUNCE_S` | unset (no debounce) |
+//! | `SUPERVISOR_AUTO_PROVISION` | `false`              |
+//! | `SUPERVISOR_SINK_MIN_INTERVAL_MS` | `0` (write every point) |
+//! | `SUPERVISOR_WARN_ESCALATION_COUNT` | unset (no escalation) |
 //! | `AMQP_URL`                  | optional             |
+//! | `COORDINATOR_REDACT_KEYS`   | unset                |
+//! | `GRPC_REFLECTION`           | `true`               |
+//! | `DB_CONNECT_ATTEMPTS`       | `10`                 |
+//! | `DB_CONNECT_BACKOFF_MS`     | `500`                |
 
 use std::sync::Arc;
 
 use anyhow::Result;
 use proto::supervisor_service::supervisor_service_server::SupervisorServiceServer;
+use redact::Redactor;
 use sqlx::postgres::PgPoolOptions;
 use tonic::transport::Server;
 use tracing::info;
 
+use database_supervisor::config::SupervisorConfig;
+use database_supervisor::health;
 use database_supervisor::ingest::SupervisorServiceImpl;
-use database_supervisor::telemetry_sink::{FakeTelemetrySink, InfluxTelemetrySink, TelemetrySink};
+use database_supervisor::ledger;
+use database_supervisor::metrics;
+use database_supervisor::shutdown;
+use database_supervisor::stale;
+use database_supervisor::telemetry_sink::{
+    BucketRouter, FakeTelemetrySink, InfluxTelemetrySink, TelemetrySink, DEFAULT_BUFFER_CAPACITY,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -34,12 +65,19 @@ async fn main() -> Result<()> {
         .json()
         .init();
 
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    // Resolve DATABASE_URL via Bitwarden (or env fallback).
+    let database_url = secrets::get_secret(
+        &std::env::var("BWS_SUPERVISOR_DATABASE_URL_ID")
+            .unwrap_or_else(|_| "supervisor-database-url".to_string()),
+        "DATABASE_URL",
+    )
+    .await?;
 
-    let pool = PgPoolOptions::new()
-        .max_connections(10)
-        .connect(&database_url)
-        .await?;
+    // Postgres may still be starting (e.g. under docker-compose).
+    let pool = connect_retry::retry("Postgres", &connect_retry::Backoff::from_env(), || {
+        PgPoolOptions::new().max_connections(10).connect(&database_url)
+    })
+    .await?;
 
     // Optionally connect to InfluxDB
     let sink: Arc<dyn TelemetrySink> = match (
@@ -49,8 +87,22 @@ async fn main() -> Result<()> {
         std::env::var("INFLUXDB_BUCKET").ok(),
     ) {
         (Some(url), Some(org), Some(token), Some(bucket)) => {
-            info!("Using InfluxTelemetrySink");
-            Arc::new(InfluxTelemetrySink::new(&url, &org, &token, &bucket))
+            let routes = BucketRouter::parse_routes(
+                &std::env::var("INFLUXDB_BUCKET_MAP").unwrap_or_default(),
+            )?;
+            let buffer_capacity = std::env::var("INFLUXDB_BUFFER_POINTS")
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or(DEFAULT_BUFFER_CAPACITY);
+            let allowed_buckets = BucketRouter::parse_bucket_list(
+                &std::env::var("INFLUXDB_ALLOWED_BUCKETS").unwrap_or_default(),
+            );
+            info!(routes = routes.len(), ?allowed_buckets, buffer_capacity, "Using InfluxTelemetrySink");
+            Arc::new(
+                InfluxTelemetrySink::with_routes(&url, &org, &token, &bucket, routes)
+                    .with_allowed_buckets(allowed_buckets)
+                    .with_buffer_capacity(buffer_capacity),
+            )
         }
         _ => {
             info!("No InfluxDB config; using FakeTelemetrySink");
@@ -67,6 +119,19 @@ async fn main() -> Result<()> {
             )
             .await?;
             let chan = conn.create_channel().await?;
+            // Status changes go to a fanout exchange so every coordinator
+            // can bind its own queue; `plant.status_change` keeps a durable
+            // copy for other consumers.
+            chan.exchange_declare(
+                "plant.status",
+                lapin::ExchangeKind::Fanout,
+                lapin::options::ExchangeDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                lapin::types::FieldTable::default(),
+            )
+            .await?;
             chan.queue_declare(
                 "plant.status_change",
                 lapin::options::QueueDeclareOptions {
@@ -76,6 +141,14 @@ async fn main() -> Result<()> {
                 lapin::types::FieldTable::default(),
             )
             .await?;
+            chan.queue_bind(
+                "plant.status_change",
+                "plant.status",
+                "",
+                lapin::options::QueueBindOptions::default(),
+                lapin::types::FieldTable::default(),
+            )
+            .await?;
             chan.queue_declare(
                 "plant.ticker_update",
                 lapin::options::QueueDeclareOptions {
@@ -98,14 +171,42 @@ async fn main() -> Result<()> {
         .unwrap_or_else(|_| "[::1]:50053".to_string())
         .parse()?;
 
-    let svc = SupervisorServiceImpl::new(pool, sink, amqp_chan);
+    let metrics_addr = std::env::var("SUPERVISOR_METRICS_ADDR")
+        .unwrap_or_else(|_| metrics::DEFAULT_ADDR.to_string())
+        .parse()?;
+    metrics::install(metrics_addr)?;
+    info!(%metrics_addr, "serving Prometheus metrics");
+
+    let config = SupervisorConfig::from_env();
+    if config.dry_run {
+        info!("dry run: ingest evaluates envelopes without writing anything");
+    }
+    if let Some(ttl) = config.stale_ttl.filter(|_| !config.dry_run) {
+        info!(ttl_s = ttl.as_secs(), "stale sweep enabled");
+        stale::spawn(pool.clone(), ttl);
+    }
+    if let Some(window) = config.dedup_window.filter(|_| !config.dry_run) {
+        info!(window_h = window.as_secs() / 3600, "ledger pruning enabled");
+        ledger::spawn_prune(pool.clone(), window);
+    }
+
+    let (_reporter, health_service) = health::service(&pool).await;
+
+    let svc = SupervisorServiceImpl::new(pool, sink, amqp_chan)
+        .with_redactor(Redactor::from_env())
+        .with_config(config);
 
     info!(%addr, "database-supervisor listening");
 
     Server::builder()
+        .layer(request_id::RequestIdLayer)
+        .add_service(health_service)
         .add_service(SupervisorServiceServer::new(svc))
-        .serve(addr)
+        .add_optional_service(proto::reflection::from_env()?)
+        .serve_with_shutdown(addr, shutdown::signal())
         .await?;
 
+    info!("database-supervisor stopped");
+
     Ok(())
 }
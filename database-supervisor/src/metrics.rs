@@ -0,0 +1,73 @@
+//! Prometheus metrics, served over HTTP on `SUPERVISOR_METRICS_ADDR`.
+//!
+//! | Metric                                  | Labels   |
+//! |-----------------------------------------|----------|
+//! | `supervisor_ingest_total`               | `result` (`ok`, `duplicate`, `error`) |
+//! | `supervisor_sink_write_failures_total`  | —        |
+//! | `supervisor_sink_buffered_points`       | —        |
+//! | `supervisor_sink_points_dropped_total`  | —        |
+
+use std::net::SocketAddr;
+
+use ::metrics::{counter, describe_counter, describe_gauge, gauge};
+use anyhow::Result;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use proto::supervisor_service::IngestResult;
+
+pub const INGEST_TOTAL: &str = "supervisor_ingest_total";
+pub const SINK_WRITE_FAILURES_TOTAL: &str = "supervisor_sink_write_failures_total";
+pub const SINK_BUFFERED_POINTS: &str = "supervisor_sink_buffered_points";
+pub const SINK_POINTS_DROPPED_TOTAL: &str = "supervisor_sink_points_dropped_total";
+
+/// Default for `SUPERVISOR_METRICS_ADDR`.
+pub const DEFAULT_ADDR: &str = "[::1]:9464";
+
+/// Install the global recorder and serve `/metrics` on `addr`.  Must be
+/// called from within the Tokio runtime.
+pub fn install(addr: SocketAddr) -> Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .install()?;
+    describe();
+    Ok(())
+}
+
+/// Register help text for the supervisor's metrics with the installed
+/// recorder.
+pub fn describe() {
+    describe_counter!(INGEST_TOTAL, "Telemetry envelopes processed, by outcome");
+    describe_counter!(
+        SINK_WRITE_FAILURES_TOTAL,
+        "Failed (non-fatal) writes to the telemetry sink"
+    );
+    describe_gauge!(SINK_BUFFERED_POINTS, "Telemetry points held for retry after failed sink writes");
+    describe_counter!(
+        SINK_POINTS_DROPPED_TOTAL,
+        "Buffered telemetry points dropped because the retry buffer was full"
+    );
+}
+
+/// Count one processed envelope.
+pub fn record_ingest(result: IngestResult) {
+    let label = match result {
+        IngestResult::Ok => "ok",
+        IngestResult::Duplicate => "duplicate",
+        IngestResult::Error | IngestResult::Unspecified => "error",
+    };
+    counter!(INGEST_TOTAL, "result" => label).increment(1);
+}
+
+/// Count one failed telemetry sink write.
+pub fn record_sink_failure() {
+    counter!(SINK_WRITE_FAILURES_TOTAL).increment(1);
+}
+
+/// Report how many points the sink holds for retry.
+pub fn set_sink_buffered_points(count: usize) {
+    gauge!(SINK_BUFFERED_POINTS).set(count as f64);
+}
+
+/// Count points dropped from a full sink retry buffer.
+pub fn record_sink_points_dropped(count: usize) {
+    counter!(SINK_POINTS_DROPPED_TOTAL).increment(count as u64);
+}
@@ -0,0 +1,36 @@
+//! Short-retention storage of raw UDP payloads forwarded by the router.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use proto::supervisor_service::TelemetryEnvelope;
+use sqlx::PgPool;
+
+/// How long captured payloads are kept.
+pub const RETENTION_HOURS: i32 = 24;
+
+/// Store `env.raw_payload_b64`, if present, and prune expired captures.
+pub async fn store(pool: &PgPool, env: &TelemetryEnvelope) -> Result<()> {
+    if env.raw_payload_b64.is_empty() {
+        return Ok(());
+    }
+    let payload = STANDARD
+        .decode(&env.raw_payload_b64)
+        .context("raw_payload_b64 is not valid base64")?;
+
+    sqlx::query(r#"
+        INSERT INTO raw_payload_capture (ingest_id, device_uid, payload)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (ingest_id) DO NOTHING
+    "#)
+    .bind(&env.ingest_id)
+    .bind(&env.device_uid)
+    .bind(payload)
+    .execute(pool)
+    .await?;
+
+    sqlx::query("DELETE FROM raw_payload_capture WHERE captured_at < NOW() - make_interval(hours => $1)")
+        .bind(RETENTION_HOURS)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
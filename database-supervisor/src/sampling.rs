@@ -0,0 +1,80 @@
+//! Downsampling of telemetry sink writes.
+//!
+//! Devices reporting every second produce more points than dashboards need.
+//! With `SUPERVISOR_SINK_MIN_INTERVAL_MS` set, a plant's reading is only
+//! written to the sink if it is at least that far (by reading timestamp)
+//! from the last one written for the plant.  Measuring reading time rather
+//! than wall time keeps a replayed backlog downsampled the same way as live
+//! traffic.  Only the sink write is sampled: `plant_current_state`,
+//! thresholds, the ledger and status changes see every reading.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+/// Timestamp of the last point each plant wrote to the sink.
+#[derive(Debug, Default)]
+pub struct SinkSampler {
+    last_written_ns: Mutex<HashMap<Uuid, i64>>,
+}
+
+impl SinkSampler {
+    /// Whether `plant_id`'s reading at `timestamp_ns` should be written: not
+    /// if it is within `min_interval` of the last one written.  Records it
+    /// when allowed.
+    pub fn allow(&self, plant_id: Uuid, timestamp_ns: i64, min_interval: Duration) -> bool {
+        let mut last_written = self.last_written_ns.lock().unwrap_or_else(|e| e.into_inner());
+        let min_interval_ns = u64::try_from(min_interval.as_nanos()).unwrap_or(u64::MAX);
+        // Either direction, so a late or replayed reading doesn't pin the plant.
+        if last_written
+            .get(&plant_id)
+            .is_some_and(|&last| last.abs_diff(timestamp_ns) < min_interval_ns)
+        {
+            return false;
+        }
+        last_written.insert(plant_id, timestamp_ns);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+    const SEC: i64 = 1_000_000_000;
+
+    #[test]
+    fn readings_within_interval_are_dropped() {
+        let sampler = SinkSampler::default();
+        let plant = Uuid::new_v4();
+
+        assert!(sampler.allow(plant, 100 * SEC, INTERVAL));
+        assert!(!sampler.allow(plant, 105 * SEC, INTERVAL));
+        assert!(!sampler.allow(plant, 109 * SEC, INTERVAL));
+        assert!(sampler.allow(plant, 110 * SEC, INTERVAL));
+        assert!(!sampler.allow(plant, 101 * SEC, INTERVAL));
+    }
+
+    #[test]
+    fn plants_are_sampled_independently() {
+        let sampler = SinkSampler::default();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(sampler.allow(a, 100 * SEC, INTERVAL));
+        assert!(sampler.allow(b, 101 * SEC, INTERVAL));
+        assert!(!sampler.allow(a, 102 * SEC, INTERVAL));
+    }
+
+    #[test]
+    fn older_reading_far_enough_back_is_written() {
+        let sampler = SinkSampler::default();
+        let plant = Uuid::new_v4();
+
+        assert!(sampler.allow(plant, 100 * SEC, INTERVAL));
+        assert!(sampler.allow(plant, 50 * SEC, INTERVAL));
+        assert!(!sampler.allow(plant, 55 * SEC, INTERVAL));
+    }
+}
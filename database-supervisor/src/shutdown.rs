@@ -0,0 +1,25 @@
+//! Graceful shutdown on SIGTERM (sent by Kubernetes before SIGKILL) or Ctrl-C.
+
+use std::future::Future;
+
+use tracing::info;
+
+/// A future resolving on the first SIGTERM or Ctrl-C, for
+/// `serve_with_shutdown`.  The SIGTERM handler is installed when this is
+/// called, not when the future is first polled.
+pub fn signal() -> impl Future<Output = ()> {
+    #[cfg(unix)]
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    async move {
+        #[cfg(unix)]
+        let term = term.recv();
+        #[cfg(not(unix))]
+        let term = std::future::pending::<Option<()>>();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term => {}
+        }
+        info!("shutdown signal received; draining in-flight requests");
+    }
+}
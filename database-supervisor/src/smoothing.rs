@@ -0,0 +1,150 @@
+//! Per-metric smoothing applied to readings before threshold evaluation.
+//!
+//! The raw reading is always what gets stored; only the value handed to
+//! [`crate::threshold::evaluate_metric`] is smoothed, so a single noisy
+//! sample can't flip a plant's severity on its own.
+
+/// Readings kept for EMA.  Older samples contribute less than `(1-alpha)^32`.
+pub const EMA_HISTORY: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Mean of the last `window` readings (including the current one).
+    Sma { window: usize },
+    /// Exponential moving average; higher `alpha` follows new readings faster.
+    Ema { alpha: f64 },
+}
+
+impl Smoothing {
+    /// Build from the `plant_type_metric_threshold` columns.  Returns `None`
+    /// when smoothing is off or the parameters are unusable.
+    pub fn from_columns(kind: Option<&str>, window: Option<i32>, alpha: Option<f64>) -> Option<Self> {
+        match kind? {
+            "sma" => {
+                let window = usize::try_from(window?).ok()?;
+                (window >= 2).then_some(Smoothing::Sma { window })
+            }
+            "ema" => {
+                let alpha = alpha?;
+                (alpha > 0.0 && alpha <= 1.0).then_some(Smoothing::Ema { alpha })
+            }
+            _ => None,
+        }
+    }
+
+    /// How many previous raw readings to keep for this method.
+    pub fn history_len(&self) -> usize {
+        match self {
+            Smoothing::Sma { window } => window - 1,
+            Smoothing::Ema { .. } => EMA_HISTORY,
+        }
+    }
+
+    /// Smoothed value of `value` given the previous raw readings (oldest first).
+    pub fn apply(&self, history: &[f64], value: f64) -> f64 {
+        match *self {
+            Smoothing::Sma { window } => {
+                let start = history.len().saturating_sub(window - 1);
+                let recent = &history[start..];
+                (recent.iter().sum::<f64>() + value) / (recent.len() + 1) as f64
+            }
+            Smoothing::Ema { alpha } => {
+                let mut iter = history.iter().copied();
+                let Some(mut ema) = iter.next() else { return value };
+                for v in iter {
+                    ema = alpha * v + (1.0 - alpha) * ema;
+                }
+                alpha * value + (1.0 - alpha) * ema
+            }
+        }
+    }
+}
+
+/// Append `value` to `history`, keeping at most `max` entries.
+pub fn push_history(history: &mut Vec<f64>, value: f64, max: usize) {
+    history.push(value);
+    let excess = history.len().saturating_sub(max);
+    history.drain(..excess);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threshold::{evaluate_metric, MetricThreshold, Severity};
+
+    fn moisture() -> MetricThreshold {
+        MetricThreshold {
+            metric: "soil_moisture".into(),
+            warn_min: Some(30.0),
+            warn_max: None,
+            crit_min: Some(15.0),
+            crit_max: None,
+            smoothing: None,
+            hysteresis: 0.0,
+            max_rate_per_min: None,
+            required: false,
+        }
+    }
+
+    /// Feed `readings` through `s`, returning the severity after each one.
+    fn run(s: Smoothing, readings: &[f64]) -> Vec<Severity> {
+        let t = moisture();
+        let mut history = Vec::new();
+        readings
+            .iter()
+            .map(|&v| {
+                let smoothed = s.apply(&history, v);
+                push_history(&mut history, v, s.history_len());
+                evaluate_metric(smoothed, &t)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sma_ignores_single_spike_within_window() {
+        let sev = run(Smoothing::Sma { window: 5 }, &[50.0, 50.0, 50.0, 50.0, 5.0, 50.0]);
+        assert!(sev.iter().all(|s| *s == Severity::Normal), "{sev:?}");
+    }
+
+    #[test]
+    fn sma_follows_sustained_change() {
+        let sev = run(Smoothing::Sma { window: 3 }, &[50.0, 50.0, 10.0, 10.0, 10.0]);
+        assert_eq!(sev.last(), Some(&Severity::Critical));
+        assert_eq!(sev[2], Severity::Normal);
+    }
+
+    #[test]
+    fn ema_ignores_single_spike() {
+        let sev = run(Smoothing::Ema { alpha: 0.3 }, &[50.0, 50.0, 50.0, 5.0, 50.0]);
+        assert!(sev.iter().all(|s| *s == Severity::Normal), "{sev:?}");
+    }
+
+    #[test]
+    fn ema_follows_sustained_change() {
+        let sev = run(Smoothing::Ema { alpha: 0.5 }, &[50.0, 50.0, 10.0, 10.0, 10.0, 10.0]);
+        assert_eq!(sev[2], Severity::Normal);
+        assert_eq!(sev.last(), Some(&Severity::Critical));
+    }
+
+    #[test]
+    fn no_history_returns_raw_value() {
+        assert_eq!(Smoothing::Sma { window: 4 }.apply(&[], 7.0), 7.0);
+        assert_eq!(Smoothing::Ema { alpha: 0.2 }.apply(&[], 7.0), 7.0);
+    }
+
+    #[test]
+    fn from_columns_rejects_bad_parameters() {
+        assert_eq!(Smoothing::from_columns(None, Some(3), None), None);
+        assert_eq!(Smoothing::from_columns(Some("sma"), Some(1), None), None);
+        assert_eq!(Smoothing::from_columns(Some("ema"), None, Some(1.5)), None);
+        assert_eq!(Smoothing::from_columns(Some("sma"), Some(3), None), Some(Smoothing::Sma { window: 3 }));
+        assert_eq!(Smoothing::from_columns(Some("ema"), None, Some(0.25)), Some(Smoothing::Ema { alpha: 0.25 }));
+    }
+
+    #[test]
+    fn history_is_trimmed_oldest_first() {
+        let mut h = vec![1.0, 2.0];
+        push_history(&mut h, 3.0, 2);
+        assert_eq!(h, vec![2.0, 3.0]);
+    }
+}
@@ -0,0 +1,70 @@
+//! Marking plants STALE when their device stops reporting.
+//!
+//! `plant_current_state` only changes on ingest, so a plant whose device
+//! went offline would otherwise keep its last severity forever.  A periodic
+//! sweep flips such plants to STALE and records a ticker event and a
+//! severity history row; the next reading overwrites the severity as usual.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::threshold::Severity;
+
+/// How often [`spawn`]'s sweep runs.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Mark every plant not updated within `ttl` as STALE, returning how many
+/// were newly marked.  `updated_at` is left alone so the last report time
+/// stays visible.
+pub async fn mark_stale(pool: &PgPool, ttl: Duration) -> Result<u64> {
+    let marked = sqlx::query(r#"
+        WITH due AS (
+            SELECT plant_id, severity
+            FROM plant_current_state
+            WHERE updated_at < NOW() - make_interval(secs => $1)
+              AND severity <> $2
+            FOR UPDATE
+        ),
+        stale AS (
+            UPDATE plant_current_state s
+            SET severity = $2
+            FROM due
+            WHERE s.plant_id = due.plant_id
+            RETURNING s.plant_id, s.updated_at, due.severity AS prev_severity
+        ),
+        history AS (
+            INSERT INTO plant_severity_history (plant_id, prev_severity, new_severity)
+            SELECT plant_id, prev_severity, $2
+            FROM stale
+        )
+        INSERT INTO ticker_event (plant_id, severity, message, payload)
+        SELECT plant_id, $2, 'No telemetry received; marked stale',
+               jsonb_build_object('stale', true, 'last_update', updated_at)
+        FROM stale
+    "#)
+    .bind(ttl.as_secs_f64())
+    .bind(Severity::Stale.as_str())
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(marked)
+}
+
+/// Run [`mark_stale`] every [`SWEEP_INTERVAL`] until the task is aborted.
+pub fn spawn(pool: PgPool, ttl: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match mark_stale(&pool, ttl).await {
+                Ok(0) => {}
+                Ok(marked) => info!(marked, "plants marked stale"),
+                Err(e) => warn!(error = %e, "stale sweep failed"),
+            }
+        }
+    })
+}
@@ -0,0 +1,619 @@
+//! Storage used by telemetry ingest.
+//!
+//! [`PlantStore`] covers the reads and writes `process_envelope` makes, so
+//! ingest decisions (dedup, thresholds, status changes) can be exercised
+//! against [`InMemoryPlantStore`] without a database.  [`SqlxPlantStore`]
+//! is the Postgres implementation used in production.
+//!
+//! Everything an ingest changes about a plant goes through one
+//! [`PlantTx`], committed together with the ledger row or not at all.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use proto::supervisor_service::TelemetryEnvelope;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+use uuid::Uuid;
+
+use crate::dead_letter;
+use crate::ingest::STATE_CHANGED_CHANNEL;
+use crate::raw_capture;
+use crate::smoothing::Smoothing;
+use crate::threshold::{self, LastReading, MetricThreshold, Severity};
+
+// ------------------------------------------------------------------ //
+//  Domain types                                                       //
+// ------------------------------------------------------------------ //
+
+/// A plant's evaluated state, as kept in `plant_current_state`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlantState {
+    pub severity: Severity,
+    /// Severity name per metric.
+    pub metric_severity: HashMap<String, String>,
+    /// Recent raw readings per smoothed metric, oldest first.
+    pub metric_history: HashMap<String, Vec<f64>>,
+    /// Last raw reading per metric, for rate-of-change checks.
+    pub last_readings: HashMap<String, LastReading>,
+    /// Consecutive non-NORMAL readings per metric, for WARN escalation.
+    pub warn_streaks: HashMap<String, u32>,
+}
+
+/// A row for `ticker_event`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickerEvent {
+    pub plant_id: Uuid,
+    pub device_uid: String,
+    pub severity: Severity,
+    pub message: String,
+    pub payload: serde_json::Value,
+    /// Device timestamp of the reading that raised the event (Unix ns).
+    pub occurred_at_ns: i64,
+}
+
+/// A device's expected reporting interval and the time since it last
+/// reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceCadence {
+    pub interval_s: f64,
+    pub expected_interval_s: u32,
+    pub tolerance_pct: u32,
+}
+
+// ------------------------------------------------------------------ //
+//  Traits                                                             //
+// ------------------------------------------------------------------ //
+
+/// Reads and writes made by ingest outside the per-envelope transaction.
+#[async_trait]
+pub trait PlantStore: Send + Sync {
+    /// Whether `ingest_id` already has a ledger row, counting only rows with
+    /// a reading time of at least `not_before_ns` when given.
+    async fn is_ingested(&self, ingest_id: &str, not_before_ns: Option<i64>) -> Result<bool>;
+
+    /// Bump the device's `last_seen_at` (for duplicates); best effort.
+    async fn touch_device(&self, device_uid: &str) -> Result<()>;
+
+    /// Dead-letter an envelope that never reached a transaction.
+    async fn dead_letter(&self, env: &TelemetryEnvelope, error: &str) -> Result<()>;
+
+    /// Keep the envelope's raw payload, if it carries one.
+    async fn capture_raw(&self, env: &TelemetryEnvelope) -> Result<()>;
+
+    /// Start the transaction one envelope's changes are made in.
+    async fn begin(&self) -> Result<Box<dyn PlantTx>>;
+}
+
+/// The per-envelope transaction.  Dropping it without [`PlantTx::commit`]
+/// discards its writes.
+#[async_trait]
+pub trait PlantTx: Send {
+    /// The plant type of an active plant, `None` for unknown plants.
+    async fn plant_type(&mut self, plant_id: Uuid) -> Result<Option<Uuid>>;
+
+    /// The plant's thresholds: its own rows, and its type's for the other
+    /// metrics.
+    async fn thresholds(&mut self, plant_id: Uuid, plant_type_id: Uuid) -> Result<Vec<MetricThreshold>>;
+
+    async fn current_state(&mut self, plant_id: Uuid) -> Result<Option<PlantState>>;
+
+    /// Replace the plant's state with `state` and the envelope's readings.
+    async fn upsert_state(&mut self, plant_id: Uuid, env: &TelemetryEnvelope, state: &PlantState) -> Result<()>;
+
+    /// `None` when the device is unknown or has no expected interval.
+    async fn device_cadence(&mut self, device_uid: &str) -> Result<Option<DeviceCadence>>;
+
+    /// Record the envelope as the device's latest, with any health fields
+    /// and firmware version it carries.  `false` when the device is not
+    /// registered.
+    async fn update_device(&mut self, env: &TelemetryEnvelope) -> Result<bool>;
+
+    /// Register an unknown device as active and seen now, with what
+    /// [`PlantTx::update_device`] would have recorded.
+    async fn provision_device(&mut self, env: &TelemetryEnvelope) -> Result<()>;
+
+    async fn insert_ticker(&mut self, event: &TickerEvent) -> Result<()>;
+
+    async fn insert_history(
+        &mut self,
+        plant_id: Uuid,
+        prev: Option<&str>,
+        new: &str,
+        metric_severity: &HashMap<String, String>,
+    ) -> Result<()>;
+
+    /// Ledger row with `result` (`OK` / `ERROR`); repeats are ignored.
+    async fn record_ledger(&mut self, env: &TelemetryEnvelope, result: &str) -> Result<()>;
+
+    async fn dead_letter(&mut self, env: &TelemetryEnvelope, error: &str) -> Result<()>;
+
+    async fn commit(self: Box<Self>) -> Result<()>;
+
+    async fn rollback(self: Box<Self>) -> Result<()>;
+}
+
+// ------------------------------------------------------------------ //
+//  SqlxPlantStore (production)                                        //
+// ------------------------------------------------------------------ //
+
+/// [`PlantStore`] over the plant-health schema in Postgres.
+#[derive(Debug, Clone)]
+pub struct SqlxPlantStore {
+    pool: PgPool,
+}
+
+impl SqlxPlantStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PlantStore for SqlxPlantStore {
+    async fn is_ingested(&self, ingest_id: &str, not_before_ns: Option<i64>) -> Result<bool> {
+        let existing: Option<String> = sqlx::query_scalar(
+            "SELECT result FROM telemetry_ingest_ledger \
+             WHERE ingest_id = $1 AND ($2::bigint IS NULL OR timestamp_ns >= $2)",
+        )
+        .bind(ingest_id)
+        .bind(not_before_ns)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(existing.is_some())
+    }
+
+    async fn touch_device(&self, device_uid: &str) -> Result<()> {
+        sqlx::query("UPDATE device SET last_seen_at = NOW() WHERE device_uid = $1")
+            .bind(device_uid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn dead_letter(&self, env: &TelemetryEnvelope, error: &str) -> Result<()> {
+        dead_letter::record(&self.pool, env, error).await?;
+        Ok(())
+    }
+
+    async fn capture_raw(&self, env: &TelemetryEnvelope) -> Result<()> {
+        raw_capture::store(&self.pool, env).await
+    }
+
+    async fn begin(&self) -> Result<Box<dyn PlantTx>> {
+        Ok(Box::new(SqlxPlantTx { tx: self.pool.begin().await? }))
+    }
+}
+
+struct SqlxPlantTx {
+    tx: Transaction<'static, Postgres>,
+}
+
+const THRESHOLD_COLUMNS: &str = "metric, warn_min, warn_max, crit_min, crit_max, \
+     smoothing, smoothing_window, smoothing_alpha, hysteresis, max_rate_per_min, required";
+
+/// A `plant_type_metric_threshold` or `plant_metric_threshold` row.
+fn threshold_from_row(r: &sqlx::postgres::PgRow) -> MetricThreshold {
+    MetricThreshold {
+        metric:   r.try_get("metric").unwrap_or_default(),
+        warn_min: r.try_get("warn_min").unwrap_or(None),
+        warn_max: r.try_get("warn_max").unwrap_or(None),
+        crit_min: r.try_get("crit_min").unwrap_or(None),
+        crit_max: r.try_get("crit_max").unwrap_or(None),
+        smoothing: Smoothing::from_columns(
+            r.try_get::<Option<String>, _>("smoothing").unwrap_or(None).as_deref(),
+            r.try_get("smoothing_window").unwrap_or(None),
+            r.try_get("smoothing_alpha").unwrap_or(None),
+        ),
+        hysteresis: r.try_get::<Option<f64>, _>("hysteresis").unwrap_or(None).unwrap_or(0.0),
+        max_rate_per_min: r.try_get("max_rate_per_min").unwrap_or(None),
+        required: r.try_get("required").unwrap_or(false),
+    }
+}
+
+/// A JSONB column decoded into `T`, or `T::default()` when NULL or malformed.
+fn json_column<T: serde::de::DeserializeOwned + Default>(row: &sqlx::postgres::PgRow, column: &str) -> T {
+    row.try_get::<Option<serde_json::Value>, _>(column)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl PlantTx for SqlxPlantTx {
+    async fn plant_type(&mut self, plant_id: Uuid) -> Result<Option<Uuid>> {
+        let plant_type_id = sqlx::query_scalar(
+            "SELECT plant_type_id FROM plant WHERE id = $1 AND is_active = TRUE",
+        )
+        .bind(plant_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(plant_type_id)
+    }
+
+    async fn thresholds(&mut self, plant_id: Uuid, plant_type_id: Uuid) -> Result<Vec<MetricThreshold>> {
+        let type_rows = sqlx::query(&format!(
+            "SELECT {THRESHOLD_COLUMNS} FROM plant_type_metric_threshold WHERE plant_type_id = $1"
+        ))
+        .bind(plant_type_id)
+        .fetch_all(&mut *self.tx)
+        .await?;
+        let plant_rows = sqlx::query(&format!(
+            "SELECT {THRESHOLD_COLUMNS} FROM plant_metric_threshold WHERE plant_id = $1"
+        ))
+        .bind(plant_id)
+        .fetch_all(&mut *self.tx)
+        .await?;
+        Ok(threshold::merge_thresholds(
+            type_rows.iter().map(threshold_from_row).collect(),
+            plant_rows.iter().map(threshold_from_row).collect(),
+        ))
+    }
+
+    async fn current_state(&mut self, plant_id: Uuid) -> Result<Option<PlantState>> {
+        let row = sqlx::query(
+            "SELECT severity, metric_severity, metric_history, metric_last_reading, metric_warn_streak \
+             FROM plant_current_state WHERE plant_id = $1",
+        )
+        .bind(plant_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        Ok(row.map(|r| PlantState {
+            severity: r
+                .try_get::<String, _>("severity")
+                .map(|s| Severity::parse(&s))
+                .unwrap_or(Severity::Normal),
+            metric_severity: json_column(&r, "metric_severity"),
+            metric_history: json_column(&r, "metric_history"),
+            last_readings: json_column(&r, "metric_last_reading"),
+            warn_streaks: json_column(&r, "metric_warn_streak"),
+        }))
+    }
+
+    async fn upsert_state(&mut self, plant_id: Uuid, env: &TelemetryEnvelope, state: &PlantState) -> Result<()> {
+        sqlx::query(r#"
+            INSERT INTO plant_current_state
+                (plant_id, updated_at, last_ingest_id, severity,
+                 soil_moisture, ambient_light_lux, ambient_humidity_rh, ambient_temp_c,
+                 metric_severity, metric_history, metric_last_reading, metric_warn_streak)
+            VALUES ($1, NOW(), $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (plant_id) DO UPDATE SET
+                updated_at          = EXCLUDED.updated_at,
+                last_ingest_id      = EXCLUDED.last_ingest_id,
+                severity            = EXCLUDED.severity,
+                soil_moisture       = COALESCE(EXCLUDED.soil_moisture, plant_current_state.soil_moisture),
+                ambient_light_lux   = COALESCE(EXCLUDED.ambient_light_lux, plant_current_state.ambient_light_lux),
+                ambient_humidity_rh = COALESCE(EXCLUDED.ambient_humidity_rh, plant_current_state.ambient_humidity_rh),
+                ambient_temp_c      = COALESCE(EXCLUDED.ambient_temp_c, plant_current_state.ambient_temp_c),
+                metric_severity     = EXCLUDED.metric_severity,
+                metric_history      = EXCLUDED.metric_history,
+                metric_last_reading = EXCLUDED.metric_last_reading,
+                metric_warn_streak  = EXCLUDED.metric_warn_streak
+        "#)
+        .bind(plant_id)
+        .bind(&env.ingest_id)
+        .bind(state.severity.as_str())
+        .bind(env.soil_moisture)
+        .bind(env.ambient_light_lux)
+        .bind(env.ambient_humidity_rh)
+        .bind(env.ambient_temp_c)
+        .bind(serde_json::to_value(&state.metric_severity).unwrap_or_default())
+        .bind(serde_json::to_value(&state.metric_history).unwrap_or_default())
+        .bind(serde_json::to_value(&state.last_readings).unwrap_or_default())
+        .bind(serde_json::to_value(&state.warn_streaks).unwrap_or_default())
+        .execute(&mut *self.tx)
+        .await?;
+
+        // Delivered on commit only, so listeners never see rolled-back state.
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(STATE_CHANGED_CHANNEL)
+            .bind(plant_id.to_string())
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    async fn device_cadence(&mut self, device_uid: &str) -> Result<Option<DeviceCadence>> {
+        let row = sqlx::query(r#"
+            SELECT EXTRACT(EPOCH FROM NOW() - last_seen_at)::float8 AS interval_s,
+                   expected_interval_s, interval_tolerance_pct
+            FROM device
+            WHERE device_uid = $1
+        "#)
+        .bind(device_uid)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+
+        let Some(row) = row else { return Ok(None) };
+        let (Some(interval_s), Some(expected_s)) = (
+            row.try_get::<Option<f64>, _>("interval_s")?,
+            row.try_get::<Option<i32>, _>("expected_interval_s")?,
+        ) else {
+            return Ok(None);
+        };
+        let tolerance_pct: i32 = row.try_get("interval_tolerance_pct")?;
+        Ok(Some(DeviceCadence {
+            interval_s,
+            expected_interval_s: expected_s as u32,
+            tolerance_pct: tolerance_pct as u32,
+        }))
+    }
+
+    async fn update_device(&mut self, env: &TelemetryEnvelope) -> Result<bool> {
+        let updated = sqlx::query(r#"
+            UPDATE device SET
+                last_seen_at   = NOW(),
+                last_ingest_id = $2,
+                battery_v        = COALESCE($3, battery_v),
+                rssi_dbm         = COALESCE($4, rssi_dbm),
+                firmware_version = COALESCE($5, firmware_version)
+            WHERE device_uid = $1
+        "#)
+        .bind(&env.device_uid)
+        .bind(&env.ingest_id)
+        .bind(env.battery_v)
+        .bind(env.rssi_dbm)
+        .bind(env.firmware_version.as_deref().filter(|v| !v.trim().is_empty()))
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn provision_device(&mut self, env: &TelemetryEnvelope) -> Result<()> {
+        // Another envelope of the same batch may have provisioned it first.
+        sqlx::query(r#"
+            INSERT INTO device
+                (device_uid, is_active, last_seen_at, last_ingest_id, battery_v, rssi_dbm, firmware_version)
+            VALUES ($1, TRUE, NOW(), $2, $3, $4, $5)
+            ON CONFLICT (device_uid) DO NOTHING
+        "#)
+        .bind(&env.device_uid)
+        .bind(&env.ingest_id)
+        .bind(env.battery_v)
+        .bind(env.rssi_dbm)
+        .bind(env.firmware_version.as_deref().filter(|v| !v.trim().is_empty()))
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_ticker(&mut self, event: &TickerEvent) -> Result<()> {
+        sqlx::query(r#"
+            INSERT INTO ticker_event (plant_id, device_uid, severity, message, payload, occurred_at_ns)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        "#)
+        .bind(event.plant_id)
+        .bind(&event.device_uid)
+        .bind(event.severity.as_str())
+        .bind(&event.message)
+        .bind(&event.payload)
+        .bind(event.occurred_at_ns)
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_history(
+        &mut self,
+        plant_id: Uuid,
+        prev: Option<&str>,
+        new: &str,
+        metric_severity: &HashMap<String, String>,
+    ) -> Result<()> {
+        sqlx::query(r#"
+            INSERT INTO plant_severity_history
+                (plant_id, prev_severity, new_severity, metric_severity)
+            VALUES ($1, $2, $3, $4)
+        "#)
+        .bind(plant_id)
+        .bind(prev)
+        .bind(new)
+        .bind(serde_json::to_value(metric_severity).unwrap_or_default())
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_ledger(&mut self, env: &TelemetryEnvelope, result: &str) -> Result<()> {
+        sqlx::query(r#"
+            INSERT INTO telemetry_ingest_ledger
+                (ingest_id, device_uid, plant_id, timestamp_ns, result)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (ingest_id) DO NOTHING
+        "#)
+        .bind(&env.ingest_id)
+        .bind(&env.device_uid)
+        .bind(Uuid::parse_str(&env.plant_id).ok())
+        .bind(env.timestamp_ns)
+        .bind(result)
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn dead_letter(&mut self, env: &TelemetryEnvelope, error: &str) -> Result<()> {
+        dead_letter::record(&mut *self.tx, env, error).await?;
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> Result<()> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<()> {
+        self.tx.rollback().await?;
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------ //
+//  InMemoryPlantStore (for tests)                                     //
+// ------------------------------------------------------------------ //
+
+/// Everything [`InMemoryPlantStore`] holds.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    /// Active plants and their plant type.
+    pub plants: HashMap<Uuid, Uuid>,
+    pub type_thresholds: HashMap<Uuid, Vec<MetricThreshold>>,
+    pub plant_thresholds: HashMap<Uuid, Vec<MetricThreshold>>,
+    pub states: HashMap<Uuid, PlantState>,
+    /// Known devices and the last ingest id each reported.
+    pub devices: HashMap<String, Option<String>>,
+    /// Ledger result and reading time by ingest id.
+    pub ledger: HashMap<String, (String, i64)>,
+    /// `(ingest_id, error)` per dead-lettered envelope.
+    pub dead_letters: Vec<(String, String)>,
+    pub ticker: Vec<TickerEvent>,
+    /// `(plant_id, prev, new)` per severity transition.
+    pub history: Vec<(Uuid, Option<String>, String)>,
+}
+
+/// In-memory [`PlantStore`] for tests.  A transaction works on a copy that
+/// replaces the shared state on commit.  Devices have no reporting
+/// interval, so cadence checks never fire.
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryPlantStore {
+    pub memory: Arc<Mutex<Memory>>,
+}
+
+impl InMemoryPlantStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an active plant of a new type with `thresholds`; returns its id.
+    pub fn add_plant(&self, thresholds: Vec<MetricThreshold>) -> Uuid {
+        let (plant_id, plant_type_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut memory = self.lock();
+        memory.plants.insert(plant_id, plant_type_id);
+        memory.type_thresholds.insert(plant_type_id, thresholds);
+        plant_id
+    }
+
+    /// Non-destructive snapshot of the current state.
+    pub fn snapshot(&self) -> Memory {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Memory> {
+        self.memory.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl PlantStore for InMemoryPlantStore {
+    async fn is_ingested(&self, ingest_id: &str, not_before_ns: Option<i64>) -> Result<bool> {
+        Ok(self
+            .lock()
+            .ledger
+            .get(ingest_id)
+            .is_some_and(|&(_, ts_ns)| not_before_ns.is_none_or(|not_before| ts_ns >= not_before)))
+    }
+
+    async fn touch_device(&self, _device_uid: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn dead_letter(&self, env: &TelemetryEnvelope, error: &str) -> Result<()> {
+        self.lock().dead_letters.push((env.ingest_id.clone(), error.to_string()));
+        Ok(())
+    }
+
+    async fn capture_raw(&self, _env: &TelemetryEnvelope) -> Result<()> {
+        Ok(())
+    }
+
+    async fn begin(&self) -> Result<Box<dyn PlantTx>> {
+        Ok(Box::new(InMemoryPlantTx { store: self.clone(), memory: self.snapshot() }))
+    }
+}
+
+struct InMemoryPlantTx {
+    store: InMemoryPlantStore,
+    memory: Memory,
+}
+
+#[async_trait]
+impl PlantTx for InMemoryPlantTx {
+    async fn plant_type(&mut self, plant_id: Uuid) -> Result<Option<Uuid>> {
+        Ok(self.memory.plants.get(&plant_id).copied())
+    }
+
+    async fn thresholds(&mut self, plant_id: Uuid, plant_type_id: Uuid) -> Result<Vec<MetricThreshold>> {
+        Ok(threshold::merge_thresholds(
+            self.memory.type_thresholds.get(&plant_type_id).cloned().unwrap_or_default(),
+            self.memory.plant_thresholds.get(&plant_id).cloned().unwrap_or_default(),
+        ))
+    }
+
+    async fn current_state(&mut self, plant_id: Uuid) -> Result<Option<PlantState>> {
+        Ok(self.memory.states.get(&plant_id).cloned())
+    }
+
+    async fn upsert_state(&mut self, plant_id: Uuid, _env: &TelemetryEnvelope, state: &PlantState) -> Result<()> {
+        self.memory.states.insert(plant_id, state.clone());
+        Ok(())
+    }
+
+    async fn device_cadence(&mut self, _device_uid: &str) -> Result<Option<DeviceCadence>> {
+        Ok(None)
+    }
+
+    async fn update_device(&mut self, env: &TelemetryEnvelope) -> Result<bool> {
+        let Some(last) = self.memory.devices.get_mut(&env.device_uid) else {
+            return Ok(false);
+        };
+        *last = Some(env.ingest_id.clone());
+        Ok(true)
+    }
+
+    async fn provision_device(&mut self, env: &TelemetryEnvelope) -> Result<()> {
+        self.memory
+            .devices
+            .entry(env.device_uid.clone())
+            .or_insert_with(|| Some(env.ingest_id.clone()));
+        Ok(())
+    }
+
+    async fn insert_ticker(&mut self, event: &TickerEvent) -> Result<()> {
+        self.memory.ticker.push(event.clone());
+        Ok(())
+    }
+
+    async fn insert_history(
+        &mut self,
+        plant_id: Uuid,
+        prev: Option<&str>,
+        new: &str,
+        _metric_severity: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.memory.history.push((plant_id, prev.map(str::to_string), new.to_string()));
+        Ok(())
+    }
+
+    async fn record_ledger(&mut self, env: &TelemetryEnvelope, result: &str) -> Result<()> {
+        self.memory
+            .ledger
+            .entry(env.ingest_id.clone())
+            .or_insert_with(|| (result.to_string(), env.timestamp_ns));
+        Ok(())
+    }
+
+    async fn dead_letter(&mut self, env: &TelemetryEnvelope, error: &str) -> Result<()> {
+        self.memory.dead_letters.push((env.ingest_id.clone(), error.to_string()));
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> Result<()> {
+        *self.store.lock() = self.memory;
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
 //! TelemetrySink trait and implementations.
 
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use async_trait::async_trait;
+use tracing::{info, warn};
+
+use crate::metrics;
 
 // ------------------------------------------------------------------ //
 //  Domain types                                                       //
@@ -19,6 +23,9 @@ pub struct TelemetryPoint {
     pub fields: std::collections::HashMap<String, f64>,
     /// Unix nanoseconds timestamp.
     pub timestamp_ns: i64,
+    /// Bucket to write to instead of the one [`BucketRouter`] would pick;
+    /// `None` or empty routes as usual.  Must be a configured bucket.
+    pub bucket: Option<String>,
 }
 
 // ------------------------------------------------------------------ //
@@ -73,63 +80,443 @@ fn escape_lp(s: &str) -> String {
     s.replace(' ', "\\ ").replace(',', "\\,").replace('=', "\\=")
 }
 
+/// Tag consulted by [`BucketRouter`] to pick a tenant bucket.
+pub const DEPLOYMENT_TAG: &str = "deployment";
+
+/// Chooses the target bucket for a point from its `deployment` tag.
+///
+/// Points without the tag, or with a deployment that has no entry in the map,
+/// go to the default bucket.  A point naming its own `bucket` goes there
+/// instead, provided that is a configured bucket: the default, a mapped one,
+/// or one listed in `INFLUXDB_ALLOWED_BUCKETS`.
+#[derive(Debug, Clone)]
+pub struct BucketRouter {
+    default_bucket: String,
+    routes: HashMap<String, String>,
+    allowed: HashSet<String>,
+}
+
+impl BucketRouter {
+    pub fn new(default_bucket: &str, routes: HashMap<String, String>) -> Self {
+        let allowed = routes.values().cloned().chain([default_bucket.to_string()]).collect();
+        Self {
+            default_bucket: default_bucket.to_string(),
+            routes,
+            allowed,
+        }
+    }
+
+    /// Also accept these buckets as a point's `bucket`.
+    pub fn with_allowed_buckets(mut self, buckets: impl IntoIterator<Item = String>) -> Self {
+        self.allowed.extend(buckets);
+        self
+    }
+
+    /// Parse a comma-separated bucket list (as found in
+    /// `INFLUXDB_ALLOWED_BUCKETS`), skipping empty entries.
+    pub fn parse_bucket_list(spec: &str) -> Vec<String> {
+        spec.split(',').map(str::trim).filter(|b| !b.is_empty()).map(str::to_string).collect()
+    }
+
+    /// The bucket `point` asks for, if any.
+    fn requested(point: &TelemetryPoint) -> Option<&str> {
+        point.bucket.as_deref().filter(|b| !b.is_empty())
+    }
+
+    /// Whether `point` may be written: it names no bucket, or a configured one.
+    pub fn allows(&self, point: &TelemetryPoint) -> bool {
+        Self::requested(point).is_none_or(|b| self.allowed.contains(b))
+    }
+
+    /// Parse a `deployment=bucket,deployment=bucket` map (as found in
+    /// `INFLUXDB_BUCKET_MAP`).  Malformed entries are an error.
+    pub fn parse_routes(spec: &str) -> Result<HashMap<String, String>> {
+        let mut routes = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((dep, bucket)) if !dep.trim().is_empty() && !bucket.trim().is_empty() => {
+                    routes.insert(dep.trim().to_string(), bucket.trim().to_string());
+                }
+                _ => anyhow::bail!("invalid bucket map entry '{entry}' (expected deployment=bucket)"),
+            }
+        }
+        Ok(routes)
+    }
+
+    /// Bucket a point should be written to.  A `bucket` the point names but
+    /// isn't configured is ignored here; see [`BucketRouter::allows`].
+    pub fn bucket_for<'a>(&'a self, point: &'a TelemetryPoint) -> &'a str {
+        if let Some(bucket) = Self::requested(point).filter(|b| self.allowed.contains(*b)) {
+            return bucket;
+        }
+        point
+            .tags
+            .get(DEPLOYMENT_TAG)
+            .and_then(|d| self.routes.get(d))
+            .unwrap_or(&self.default_bucket)
+    }
+
+    /// Group points by target bucket, preserving their relative order.
+    pub fn group<'a>(&self, points: &'a [TelemetryPoint]) -> Vec<(String, Vec<&'a TelemetryPoint>)> {
+        let mut groups: Vec<(String, Vec<&TelemetryPoint>)> = Vec::new();
+        for p in points {
+            let bucket = self.bucket_for(p);
+            match groups.iter_mut().find(|(b, _)| b.as_str() == bucket) {
+                Some((_, group)) => group.push(p),
+                None => groups.push((bucket.to_string(), vec![p])),
+            }
+        }
+        groups
+    }
+}
+
+/// Default for `INFLUXDB_BUFFER_POINTS`.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 10_000;
+
 /// Production sink that writes to InfluxDB 2.x via the `influxdb2` client.
+///
+/// Points whose write fails (InfluxDB down or erroring) are kept in a
+/// bounded in-memory buffer and sent again ahead of the next write, so a
+/// short outage loses nothing.  Points are still lost when:
+///
+/// - the buffer is full: the oldest are dropped to make room (counted in
+///   `supervisor_sink_points_dropped_total`);
+/// - the supervisor stops while points are buffered;
+/// - InfluxDB rejects a write as invalid (4xx other than 429): retrying it
+///   can't succeed, so that write's points are dropped with the error;
+/// - a point names a `bucket` that isn't configured (see [`BucketRouter`]).
+///
+/// Buffered points are only retried when another write comes in.
 pub struct InfluxTelemetrySink {
     client: influxdb2::Client,
     org: String,
-    bucket: String,
+    router: BucketRouter,
+    /// Failed points awaiting a retry, oldest first.
+    buffer: Mutex<VecDeque<TelemetryPoint>>,
+    buffer_capacity: usize,
 }
 
 impl InfluxTelemetrySink {
     pub fn new(url: &str, org: &str, token: &str, bucket: &str) -> Self {
+        Self::with_routes(url, org, token, bucket, HashMap::new())
+    }
+
+    /// Like [`InfluxTelemetrySink::new`], but route points to per-deployment
+    /// buckets (see [`BucketRouter`]).
+    pub fn with_routes(
+        url: &str,
+        org: &str,
+        token: &str,
+        bucket: &str,
+        routes: HashMap<String, String>,
+    ) -> Self {
         let client = influxdb2::Client::new(url, org, token);
         Self {
             client,
             org: org.to_string(),
-            bucket: bucket.to_string(),
+            router: BucketRouter::new(bucket, routes),
+            buffer: Mutex::new(VecDeque::new()),
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
         }
     }
+
+    /// Keep at most `capacity` failed points for retry; 0 keeps none.
+    pub fn with_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// Also accept these buckets as a point's `bucket`.
+    pub fn with_allowed_buckets(mut self, buckets: impl IntoIterator<Item = String>) -> Self {
+        self.router = self.router.with_allowed_buckets(buckets);
+        self
+    }
+
+    /// Points currently held for retry.
+    pub fn buffered_point_count(&self) -> usize {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    fn take_buffered(&self) -> Vec<TelemetryPoint> {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect()
+    }
+
+    /// Put `failed` back at the front of the buffer (they are older than
+    /// anything buffered meanwhile), dropping the oldest points over
+    /// capacity.
+    fn rebuffer(&self, failed: Vec<TelemetryPoint>) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        for point in failed.into_iter().rev() {
+            buffer.push_front(point);
+        }
+        let dropped = buffer.len().saturating_sub(self.buffer_capacity);
+        buffer.drain(..dropped);
+        if dropped > 0 {
+            warn!(dropped, capacity = self.buffer_capacity, "sink retry buffer full; dropped oldest points");
+            metrics::record_sink_points_dropped(dropped);
+        }
+        metrics::set_sink_buffered_points(buffer.len());
+    }
+}
+
+/// Whether InfluxDB refused a write as invalid, so it must not be retried.
+fn is_rejected(e: &influxdb2::RequestError) -> bool {
+    matches!(e, influxdb2::RequestError::Http { status, .. }
+        if status.is_client_error() && status.as_u16() != 429)
+}
+
+fn to_line_protocol(p: &TelemetryPoint) -> String {
+    let tags: String = p
+        .tags
+        .iter()
+        .map(|(k, v)| format!(",{}={}", escape_lp(k), escape_lp(v)))
+        .collect();
+    let fields: String = p
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, (k, v))| {
+            let sep = if i == 0 { "" } else { "," };
+            format!("{}{k}={v}", sep)
+        })
+        .collect();
+    if p.timestamp_ns != 0 {
+        format!(
+            "{}{} {} {}",
+            escape_lp(&p.measurement),
+            tags,
+            fields,
+            p.timestamp_ns
+        )
+    } else {
+        format!("{}{} {}", escape_lp(&p.measurement), tags, fields)
+    }
 }
 
 #[async_trait]
 impl TelemetrySink for InfluxTelemetrySink {
     async fn write_points(&self, points: Vec<TelemetryPoint>) -> Result<()> {
-        let mut lines = Vec::with_capacity(points.len());
-        for p in &points {
-            let tags: String = p
-                .tags
-                .iter()
-                .map(|(k, v)| format!(",{}={}", escape_lp(k), escape_lp(v)))
-                .collect();
-            let fields: String = p
-                .fields
+        let mut batch = self.take_buffered();
+        let retried = batch.len();
+        let (allowed, refused): (Vec<_>, Vec<_>) = points.into_iter().partition(|p| self.router.allows(p));
+        batch.extend(allowed);
+
+        let mut failed = Vec::new();
+        let mut first_error = None;
+        if !refused.is_empty() {
+            warn!(points = refused.len(), "dropping points naming a bucket that is not configured");
+            metrics::record_sink_points_dropped(refused.len());
+            first_error = Some(anyhow::anyhow!(
+                "{} point(s) name a bucket not in INFLUXDB_ALLOWED_BUCKETS",
+                refused.len()
+            ));
+        }
+        for (bucket, group) in self.router.group(&batch) {
+            let data = group
                 .iter()
-                .enumerate()
-                .map(|(i, (k, v))| {
-                    let sep = if i == 0 { "" } else { "," };
-                    format!("{}{k}={v}", sep)
-                })
-                .collect();
-            let line = if p.timestamp_ns != 0 {
-                format!(
-                    "{}{} {} {}",
-                    escape_lp(&p.measurement),
-                    tags,
-                    fields,
-                    p.timestamp_ns
-                )
-            } else {
-                format!("{}{} {}", escape_lp(&p.measurement), tags, fields)
-            };
-            lines.push(line);
+                .map(|p| to_line_protocol(p))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if let Err(e) = self.client.write_line_protocol(&self.org, &bucket, data).await {
+                if !is_rejected(&e) {
+                    failed.extend(group.into_iter().cloned());
+                }
+                first_error.get_or_insert_with(|| anyhow::anyhow!("InfluxDB write to bucket '{bucket}' failed: {e}"));
+            }
         }
 
-        let data = lines.join("\n");
-        self.client
-            .write_line_protocol(&self.org, &self.bucket, data)
-            .await
-            .map_err(|e| anyhow::anyhow!("InfluxDB write failed: {e}"))?;
+        self.rebuffer(failed);
+        match first_error {
+            Some(e) => Err(e),
+            None => {
+                if retried > 0 {
+                    info!(points = retried, "wrote buffered telemetry points after earlier failures");
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
-        Ok(())
+// ------------------------------------------------------------------ //
+//  Tests                                                              //
+// ------------------------------------------------------------------ //
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    use super::*;
+
+    fn point(deployment: Option<&str>) -> TelemetryPoint {
+        let mut tags = HashMap::new();
+        tags.insert("plant_id".to_string(), "p1".to_string());
+        if let Some(d) = deployment {
+            tags.insert(DEPLOYMENT_TAG.to_string(), d.to_string());
+        }
+        TelemetryPoint {
+            measurement: "plant_telemetry".into(),
+            tags,
+            fields: HashMap::from([("soil_moisture".to_string(), 40.0)]),
+            timestamp_ns: 1,
+            bucket: None,
+        }
+    }
+
+    fn point_for_bucket(bucket: &str) -> TelemetryPoint {
+        TelemetryPoint { bucket: Some(bucket.to_string()), ..point(Some("tenant-a")) }
+    }
+
+    fn router() -> BucketRouter {
+        let routes = BucketRouter::parse_routes("tenant-a=bucket-a, tenant-b=bucket-b").unwrap();
+        BucketRouter::new("default", routes)
+    }
+
+    #[test]
+    fn points_route_by_deployment_tag() {
+        let r = router();
+        assert_eq!(r.bucket_for(&point(Some("tenant-a"))), "bucket-a");
+        assert_eq!(r.bucket_for(&point(Some("tenant-b"))), "bucket-b");
+    }
+
+    #[test]
+    fn unknown_or_missing_deployment_uses_default() {
+        let r = router();
+        assert_eq!(r.bucket_for(&point(Some("tenant-z"))), "default");
+        assert_eq!(r.bucket_for(&point(None)), "default");
+    }
+
+    #[test]
+    fn explicit_bucket_overrides_routing_when_configured() {
+        let r = router().with_allowed_buckets(BucketRouter::parse_bucket_list("plants-tropical, "));
+        for bucket in ["plants-tropical", "bucket-b", "default"] {
+            let p = point_for_bucket(bucket);
+            assert!(r.allows(&p));
+            assert_eq!(r.bucket_for(&p), bucket);
+        }
+        // Empty means no override.
+        assert!(r.allows(&point_for_bucket("")));
+        assert_eq!(r.bucket_for(&point_for_bucket("")), "bucket-a");
+    }
+
+    #[test]
+    fn unconfigured_bucket_is_refused() {
+        let r = router();
+        let p = point_for_bucket("plants-tropical");
+        assert!(!r.allows(&p));
+        assert_eq!(r.bucket_for(&p), "bucket-a");
+    }
+
+    #[test]
+    fn group_splits_batch_per_bucket() {
+        let points = vec![
+            point(Some("tenant-a")),
+            point(None),
+            point(Some("tenant-b")),
+            point(Some("tenant-a")),
+        ];
+        let groups = router().group(&points);
+        let summary: Vec<(&str, usize)> =
+            groups.iter().map(|(b, g)| (b.as_str(), g.len())).collect();
+        assert_eq!(summary, vec![("bucket-a", 2), ("default", 1), ("bucket-b", 1)]);
+    }
+
+    #[test]
+    fn parse_routes_rejects_malformed_entries() {
+        assert!(BucketRouter::parse_routes("tenant-a").is_err());
+        assert!(BucketRouter::parse_routes("=bucket").is_err());
+        assert!(BucketRouter::parse_routes("").unwrap().is_empty());
+    }
+
+    /// The status [`fake_influx`] answers and the bodies it accepted.
+    type FakeInfluxState = (Arc<AtomicU16>, Arc<Mutex<Vec<String>>>);
+
+    /// A stand-in for InfluxDB's write endpoint: answers `status` and keeps
+    /// the line protocol bodies it accepted.  Returns its URL.
+    async fn fake_influx(status: Arc<AtomicU16>, accepted: Arc<Mutex<Vec<String>>>) -> String {
+        use axum::{extract::State, http::StatusCode, routing::post, Router};
+
+        let app = Router::new()
+            .route(
+                "/api/v2/write",
+                post(|State((status, accepted)): State<FakeInfluxState>, body: String| async move {
+                    let status = StatusCode::from_u16(status.load(Ordering::SeqCst)).unwrap();
+                    if status.is_success() {
+                        accepted.lock().unwrap().push(body);
+                    }
+                    status
+                }),
+            )
+            .with_state((status, accepted));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    fn timed_point(timestamp_ns: i64) -> TelemetryPoint {
+        TelemetryPoint { timestamp_ns, ..point(None) }
+    }
+
+    fn timestamps(body: &str) -> Vec<&str> {
+        body.lines().map(|line| line.rsplit(' ').next().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn failed_write_is_buffered_and_sent_with_the_next_one() {
+        let status = Arc::new(AtomicU16::new(503));
+        let accepted = Arc::new(Mutex::new(Vec::new()));
+        let url = fake_influx(status.clone(), accepted.clone()).await;
+        let sink = InfluxTelemetrySink::new(&url, "org", "token", "default");
+
+        assert!(sink.write_points(vec![timed_point(1), timed_point(2)]).await.is_err());
+        assert_eq!(sink.buffered_point_count(), 2);
+        assert!(accepted.lock().unwrap().is_empty());
+
+        status.store(204, Ordering::SeqCst);
+        sink.write_points(vec![timed_point(3)]).await.unwrap();
+        assert_eq!(sink.buffered_point_count(), 0);
+        let accepted = accepted.lock().unwrap();
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(timestamps(&accepted[0]), ["1", "2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn full_buffer_drops_the_oldest_points() {
+        let status = Arc::new(AtomicU16::new(500));
+        let accepted = Arc::new(Mutex::new(Vec::new()));
+        let url = fake_influx(status.clone(), accepted.clone()).await;
+        let sink = InfluxTelemetrySink::new(&url, "org", "token", "default").with_buffer_capacity(2);
+
+        assert!(sink.write_points(vec![timed_point(1), timed_point(2)]).await.is_err());
+        assert!(sink.write_points(vec![timed_point(3)]).await.is_err());
+        assert_eq!(sink.buffered_point_count(), 2);
+
+        status.store(204, Ordering::SeqCst);
+        sink.write_points(vec![]).await.unwrap();
+        assert_eq!(timestamps(&accepted.lock().unwrap()[0]), ["2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn point_for_unconfigured_bucket_is_dropped_not_buffered() {
+        let status = Arc::new(AtomicU16::new(204));
+        let accepted = Arc::new(Mutex::new(Vec::new()));
+        let url = fake_influx(status, accepted.clone()).await;
+        let sink = InfluxTelemetrySink::new(&url, "org", "token", "default");
+        let refused = TelemetryPoint { bucket: Some("elsewhere".into()), ..timed_point(2) };
+
+        assert!(sink.write_points(vec![timed_point(1), refused]).await.is_err());
+        assert_eq!(sink.buffered_point_count(), 0);
+        assert_eq!(timestamps(&accepted.lock().unwrap()[0]), ["1"]);
+    }
+
+    #[tokio::test]
+    async fn rejected_write_is_not_retried() {
+        let status = Arc::new(AtomicU16::new(400));
+        let url = fake_influx(status, Arc::default()).await;
+        let sink = InfluxTelemetrySink::new(&url, "org", "token", "default");
+
+        assert!(sink.write_points(vec![timed_point(1)]).await.is_err());
+        assert_eq!(sink.buffered_point_count(), 0);
     }
 }
@@ -1,15 +1,24 @@
 //! Plant-type metric threshold evaluation.
 
+use std::collections::HashSet;
+
 use serde::{Deserialize, Serialize};
 
+use crate::smoothing::Smoothing;
+
 // ------------------------------------------------------------------ //
 //  Types                                                              //
 // ------------------------------------------------------------------ //
 
 /// Severity level for an individual metric or an entire plant.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+///
+/// Ordered by urgency.  `Stale` is never produced by evaluation; it marks a
+/// plant whose device stopped reporting (see [`crate::stale`]) and ranks
+/// above `Normal` so such plants surface for attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Severity {
     Normal,
+    Stale,
     Warn,
     Critical,
 }
@@ -18,13 +27,15 @@ impl Severity {
     pub fn as_str(self) -> &'static str {
         match self {
             Severity::Normal   => "NORMAL",
+            Severity::Stale    => "STALE",
             Severity::Warn     => "WARN",
             Severity::Critical => "CRITICAL",
         }
     }
 
-    pub fn from_str(s: &str) -> Self {
+    pub fn parse(s: &str) -> Self {
         match s {
+            "STALE"    => Severity::Stale,
             "WARN"     => Severity::Warn,
             "CRITICAL" => Severity::Critical,
             _          => Severity::Normal,
@@ -46,6 +57,21 @@ pub struct MetricThreshold {
     pub warn_max: Option<f64>,
     pub crit_min: Option<f64>,
     pub crit_max: Option<f64>,
+    /// Applied to readings before they are evaluated; `None` evaluates raw.
+    pub smoothing: Option<Smoothing>,
+    /// Hysteresis margin for [`evaluate_metric_hysteresis`]; 0 disables it.
+    pub hysteresis: f64,
+    /// Largest allowed change per minute, see [`evaluate_rate`].
+    pub max_rate_per_min: Option<f64>,
+    /// A reading must carry this metric, see [`evaluate_missing`].
+    pub required: bool,
+}
+
+/// The last raw reading of a metric, kept to evaluate its rate of change.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LastReading {
+    pub value: f64,
+    pub ts_ns: i64,
 }
 
 // ------------------------------------------------------------------ //
@@ -77,6 +103,106 @@ pub fn evaluate_metric(value: f64, threshold: &MetricThreshold) -> Severity {
     Severity::Normal
 }
 
+/// Evaluate a reading given the metric's previous severity.
+///
+/// Upgrades apply immediately.  A downgrade only happens once the value is
+/// inside the better band by at least `margin`, so a reading hovering on a
+/// boundary keeps its previous severity.  With no previous severity (cold
+/// start) use [`evaluate_metric`].
+pub fn evaluate_metric_hysteresis(
+    value: f64,
+    threshold: &MetricThreshold,
+    prev: Severity,
+    margin: f64,
+) -> Severity {
+    let raw = evaluate_metric(value, threshold);
+    if raw >= prev || margin <= 0.0 {
+        return raw;
+    }
+    let narrowed = MetricThreshold {
+        warn_min: threshold.warn_min.map(|v| v + margin),
+        warn_max: threshold.warn_max.map(|v| v - margin),
+        crit_min: threshold.crit_min.map(|v| v + margin),
+        crit_max: threshold.crit_max.map(|v| v - margin),
+        ..threshold.clone()
+    };
+    evaluate_metric(value, &narrowed).min(prev)
+}
+
+/// WARN if the reading changed faster than `max_rate` per minute since the
+/// previous one.
+///
+/// The first reading (no previous) is NORMAL, as is a reading whose
+/// timestamp is not after the previous one (duplicate or out of order).
+pub fn evaluate_rate(
+    prev_value: Option<f64>,
+    prev_ts_ns: Option<i64>,
+    value: f64,
+    ts_ns: i64,
+    max_rate: f64,
+) -> Severity {
+    let (Some(prev_value), Some(prev_ts_ns)) = (prev_value, prev_ts_ns) else {
+        return Severity::Normal;
+    };
+    let elapsed_ns = ts_ns.saturating_sub(prev_ts_ns);
+    if elapsed_ns <= 0 {
+        return Severity::Normal;
+    }
+    let per_minute = (value - prev_value).abs() / (elapsed_ns as f64 / 60e9);
+    if per_minute > max_rate {
+        Severity::Warn
+    } else {
+        Severity::Normal
+    }
+}
+
+/// Severity of each required metric missing from a reading: `policy`
+/// (WARN or CRITICAL) for every threshold marked `required` whose metric is
+/// not in `present`.  Optional metrics are simply absent.
+pub fn evaluate_missing<'a>(
+    present: &HashSet<&str>,
+    thresholds: &'a [MetricThreshold],
+    policy: Severity,
+) -> Vec<(&'a str, Severity)> {
+    thresholds
+        .iter()
+        .filter(|t| t.required && !present.contains(t.metric.as_str()))
+        .map(|t| (t.metric.as_str(), policy))
+        .collect()
+}
+
+/// Escalate a long run of WARN readings.  `streak` counts a metric's
+/// consecutive readings that were not NORMAL, `prev_streak` before this one;
+/// a NORMAL reading resets it.  Once it reaches `escalate_after`, a WARN
+/// reading counts as CRITICAL.  Returns the severity to apply and the new
+/// streak.
+pub fn escalate_warn_streak(severity: Severity, prev_streak: u32, escalate_after: u32) -> (Severity, u32) {
+    if severity == Severity::Normal {
+        return (Severity::Normal, 0);
+    }
+    let streak = prev_streak.saturating_add(1);
+    if severity == Severity::Warn && streak >= escalate_after {
+        (Severity::Critical, streak)
+    } else {
+        (severity, streak)
+    }
+}
+
+/// Thresholds for one plant: its own `overrides` replace the plant type's
+/// `defaults` metric by metric; metrics without an override keep the
+/// default.
+pub fn merge_thresholds(
+    defaults: Vec<MetricThreshold>,
+    overrides: Vec<MetricThreshold>,
+) -> Vec<MetricThreshold> {
+    let mut merged: Vec<MetricThreshold> = defaults
+        .into_iter()
+        .filter(|d| !overrides.iter().any(|o| o.metric == d.metric))
+        .collect();
+    merged.extend(overrides);
+    merged
+}
+
 /// Compute the overall plant severity from per-metric severities.
 pub fn aggregate_severity(severities: impl IntoIterator<Item = Severity>) -> Severity {
     let mut overall = Severity::Normal;
@@ -102,7 +228,17 @@ mod tests {
         crit_min: Option<f64>,
         crit_max: Option<f64>,
     ) -> MetricThreshold {
-        MetricThreshold { metric: "test".into(), warn_min, warn_max, crit_min, crit_max }
+        MetricThreshold {
+            metric: "test".into(),
+            warn_min,
+            warn_max,
+            crit_min,
+            crit_max,
+            smoothing: None,
+            hysteresis: 0.0,
+            max_rate_per_min: None,
+            required: false,
+        }
     }
 
     #[test]
@@ -148,6 +284,76 @@ mod tests {
         assert_eq!(evaluate_metric(100.0, &t), Severity::Normal);
     }
 
+    #[test]
+    fn hysteresis_holds_warn_just_inside_band() {
+        let t = thresh(Some(20.0), Some(80.0), Some(10.0), Some(90.0));
+        // 21 is inside the warn band but within the 2.0 margin of warn_min.
+        assert_eq!(evaluate_metric_hysteresis(21.0, &t, Severity::Warn, 2.0), Severity::Warn);
+        assert_eq!(evaluate_metric_hysteresis(21.0, &t, Severity::Normal, 2.0), Severity::Normal);
+        assert_eq!(evaluate_metric_hysteresis(79.0, &t, Severity::Warn, 2.0), Severity::Warn);
+    }
+
+    #[test]
+    fn hysteresis_downgrades_once_past_margin() {
+        let t = thresh(Some(20.0), Some(80.0), Some(10.0), Some(90.0));
+        assert_eq!(evaluate_metric_hysteresis(22.5, &t, Severity::Warn, 2.0), Severity::Normal);
+        // CRITICAL steps down to WARN, not straight to NORMAL.
+        assert_eq!(evaluate_metric_hysteresis(11.0, &t, Severity::Critical, 2.0), Severity::Critical);
+        assert_eq!(evaluate_metric_hysteresis(15.0, &t, Severity::Critical, 2.0), Severity::Warn);
+        assert_eq!(evaluate_metric_hysteresis(50.0, &t, Severity::Critical, 2.0), Severity::Normal);
+    }
+
+    #[test]
+    fn hysteresis_never_delays_upgrades() {
+        let t = thresh(Some(20.0), Some(80.0), Some(10.0), Some(90.0));
+        assert_eq!(evaluate_metric_hysteresis(19.0, &t, Severity::Normal, 5.0), Severity::Warn);
+        assert_eq!(evaluate_metric_hysteresis(5.0, &t, Severity::Warn, 5.0), Severity::Critical);
+    }
+
+    #[test]
+    fn zero_margin_matches_evaluate_metric() {
+        let t = thresh(Some(20.0), Some(80.0), Some(10.0), Some(90.0));
+        for value in [5.0, 15.0, 20.0, 21.0, 50.0, 85.0, 95.0] {
+            for prev in [Severity::Normal, Severity::Warn, Severity::Critical] {
+                assert_eq!(evaluate_metric_hysteresis(value, &t, prev, 0.0), evaluate_metric(value, &t));
+            }
+        }
+    }
+
+    const MINUTE_NS: i64 = 60_000_000_000;
+
+    #[test]
+    fn sharp_drop_is_warn() {
+        // 40 -> 25 in one minute against a 5/min limit.
+        assert_eq!(evaluate_rate(Some(40.0), Some(0), 25.0, MINUTE_NS, 5.0), Severity::Warn);
+    }
+
+    #[test]
+    fn gentle_change_is_normal() {
+        // 40 -> 38 over two minutes is 1/min.
+        assert_eq!(evaluate_rate(Some(40.0), Some(0), 38.0, 2 * MINUTE_NS, 5.0), Severity::Normal);
+        assert_eq!(evaluate_rate(Some(40.0), Some(0), 45.0, MINUTE_NS, 5.0), Severity::Normal);
+    }
+
+    #[test]
+    fn duplicate_or_earlier_timestamp_is_normal() {
+        assert_eq!(evaluate_rate(Some(40.0), Some(MINUTE_NS), 10.0, MINUTE_NS, 5.0), Severity::Normal);
+        assert_eq!(evaluate_rate(Some(40.0), Some(MINUTE_NS), 10.0, 0, 5.0), Severity::Normal);
+    }
+
+    #[test]
+    fn first_reading_is_normal() {
+        assert_eq!(evaluate_rate(None, None, 10.0, MINUTE_NS, 5.0), Severity::Normal);
+    }
+
+    #[test]
+    fn rate_severity_folds_into_aggregate() {
+        let t = thresh(Some(20.0), Some(80.0), Some(10.0), Some(90.0));
+        let level = evaluate_metric(50.0, &t);
+        let rate = evaluate_rate(Some(75.0), Some(0), 50.0, MINUTE_NS, 5.0);
+        assert_eq!(aggregate_severity([level, rate]), Severity::Warn);
+    }
+
     #[test]
     fn aggregate_any_critical_wins() {
         let result = aggregate_severity([Severity::Normal, Severity::Critical, Severity::Warn]);
@@ -166,12 +372,66 @@ mod tests {
         assert_eq!(result, Severity::Normal);
     }
 
+    #[test]
+    fn stale_ranks_between_normal_and_warn() {
+        assert_eq!(aggregate_severity([Severity::Normal, Severity::Stale]), Severity::Stale);
+        assert_eq!(aggregate_severity([Severity::Stale, Severity::Warn]), Severity::Warn);
+        assert_eq!(aggregate_severity([Severity::Critical, Severity::Stale]), Severity::Critical);
+    }
+
+    #[test]
+    fn as_str_round_trips() {
+        for s in [Severity::Normal, Severity::Stale, Severity::Warn, Severity::Critical] {
+            assert_eq!(Severity::parse(s.as_str()), s);
+        }
+        assert_eq!(Severity::parse("bogus"), Severity::Normal);
+    }
+
     #[test]
     fn aggregate_empty_is_normal() {
         let result = aggregate_severity(std::iter::empty());
         assert_eq!(result, Severity::Normal);
     }
 
+    fn named(metric: &str, crit_min: f64) -> MetricThreshold {
+        MetricThreshold { metric: metric.into(), ..thresh(None, None, Some(crit_min), None) }
+    }
+
+    fn crit_mins(merged: &[MetricThreshold]) -> Vec<(String, Option<f64>)> {
+        let mut v: Vec<_> = merged.iter().map(|t| (t.metric.clone(), t.crit_min)).collect();
+        v.sort_by(|a, b| a.0.cmp(&b.0));
+        v
+    }
+
+    #[test]
+    fn plant_override_replaces_type_default_for_that_metric() {
+        let merged = merge_thresholds(
+            vec![named("ambient_light_lux", 1000.0), named("soil_moisture", 20.0)],
+            vec![named("ambient_light_lux", 200.0)],
+        );
+        assert_eq!(
+            crit_mins(&merged),
+            vec![
+                ("ambient_light_lux".into(), Some(200.0)),
+                ("soil_moisture".into(), Some(20.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn disjoint_overrides_and_defaults_are_all_kept() {
+        let merged = merge_thresholds(
+            vec![named("soil_moisture", 20.0)],
+            vec![named("co2_ppm", 300.0)],
+        );
+        assert_eq!(
+            crit_mins(&merged),
+            vec![("co2_ppm".into(), Some(300.0)), ("soil_moisture".into(), Some(20.0))]
+        );
+        assert_eq!(merge_thresholds(vec![named("soil_moisture", 20.0)], vec![]).len(), 1);
+        assert_eq!(merge_thresholds(vec![], vec![named("soil_moisture", 20.0)]).len(), 1);
+    }
+
     #[test]
     fn no_transition_emit_same_severity() {
         let prev = Severity::Warn;
@@ -197,4 +457,63 @@ mod tests {
         let next = Severity::Normal;
         assert_ne!(prev, next, "critical->normal should emit");
     }
+
+    fn required(metric: &str) -> MetricThreshold {
+        MetricThreshold { metric: metric.into(), required: true, ..thresh(None, None, None, None) }
+    }
+
+    #[test]
+    fn required_metric_present_is_not_escalated() {
+        let present = HashSet::from(["soil_moisture"]);
+        let thresholds = [required("soil_moisture")];
+        assert!(evaluate_missing(&present, &thresholds, Severity::Critical).is_empty());
+    }
+
+    #[test]
+    fn required_metric_missing_gets_the_policy_severity() {
+        let present = HashSet::from(["ambient_temp_c"]);
+        let thresholds = [required("soil_moisture")];
+        assert_eq!(
+            evaluate_missing(&present, &thresholds, Severity::Critical),
+            vec![("soil_moisture", Severity::Critical)]
+        );
+        assert_eq!(
+            evaluate_missing(&present, &thresholds, Severity::Warn),
+            vec![("soil_moisture", Severity::Warn)]
+        );
+    }
+
+    #[test]
+    fn optional_metric_missing_is_ignored() {
+        let present = HashSet::new();
+        let thresholds = [MetricThreshold { metric: "soil_moisture".into(), ..thresh(None, None, Some(10.0), None) }];
+        assert!(evaluate_missing(&present, &thresholds, Severity::Critical).is_empty());
+    }
+
+    #[test]
+    fn warn_escalates_after_consecutive_count() {
+        use Severity::*;
+        let mut streak = 0;
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            let (sev, next) = escalate_warn_streak(Warn, streak, 3);
+            seen.push(sev);
+            streak = next;
+        }
+        assert_eq!(seen, [Warn, Warn, Critical, Critical, Critical]);
+        assert_eq!(streak, 5);
+    }
+
+    #[test]
+    fn normal_reading_resets_the_streak() {
+        assert_eq!(escalate_warn_streak(Severity::Normal, 7, 3), (Severity::Normal, 0));
+        // After the reset a WARN starts over.
+        assert_eq!(escalate_warn_streak(Severity::Warn, 0, 3), (Severity::Warn, 1));
+    }
+
+    #[test]
+    fn critical_readings_extend_the_streak() {
+        assert_eq!(escalate_warn_streak(Severity::Critical, 0, 3), (Severity::Critical, 1));
+        assert_eq!(escalate_warn_streak(Severity::Warn, 2, 3), (Severity::Critical, 3));
+    }
 }
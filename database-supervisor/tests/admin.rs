@@ -0,0 +1,96 @@
+//! Plant-type / plant / device registration against a real database.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, CreateDeviceRequest, CreatePlantRequest,
+    CreatePlantTypeRequest, MetricThresholdSpec,
+};
+use tonic::{Code, Request};
+
+#[tokio::test]
+async fn create_plant_type_then_plant() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+
+    let plant_type_id = svc
+        .create_plant_type(Request::new(CreatePlantTypeRequest {
+            name: common::unique("fern"),
+            description: "shade lover".into(),
+            thresholds: vec![MetricThresholdSpec {
+                metric: "soil_moisture".into(),
+                warn_min: Some(30.0),
+                crit_min: Some(15.0),
+                ..Default::default()
+            }],
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+
+    let plant_id = svc
+        .create_plant(Request::new(CreatePlantRequest {
+            plant_type_id: plant_type_id.clone(),
+            display_name: "Fern by the window".into(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+
+    let stored_type: String =
+        sqlx::query_scalar("SELECT plant_type_id::text FROM plant WHERE id = $1::uuid")
+            .bind(&plant_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored_type, plant_type_id);
+
+    let thresholds: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM plant_type_metric_threshold WHERE plant_type_id = $1::uuid",
+    )
+    .bind(&plant_type_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(thresholds, 1);
+}
+
+#[tokio::test]
+async fn plant_with_unknown_plant_type_is_rejected() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool);
+
+    let err = svc
+        .create_plant(Request::new(CreatePlantRequest {
+            plant_type_id: uuid::Uuid::new_v4().to_string(),
+            display_name: "Orphan".into(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn duplicate_plant_type_and_device_are_rejected() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool);
+
+    let name = common::unique("cactus");
+    let req = CreatePlantTypeRequest { name, ..Default::default() };
+    svc.create_plant_type(Request::new(req.clone())).await.unwrap();
+    let err = svc.create_plant_type(Request::new(req)).await.unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+
+    let req = CreateDeviceRequest {
+        device_uid: common::unique("esp32"),
+        firmware_version: "1.0.0".into(),
+        ..Default::default()
+    };
+    svc.create_device(Request::new(req.clone())).await.unwrap();
+    let err = svc.create_device(Request::new(req)).await.unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+}
@@ -0,0 +1,90 @@
+//! `SUPERVISOR_AUTO_PROVISION`: telemetry from an unregistered device adds
+//! its `device` row only when enabled.
+
+mod common;
+
+use database_supervisor::config::SupervisorConfig;
+use database_supervisor::store::InMemoryPlantStore;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestResult, IngestTelemetryRequest,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(device_uid: &str, plant_id: &str) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        device_uid: device_uid.to_string(),
+        battery_v: Some(3.9),
+        ..common::envelope(plant_id)
+    }
+}
+
+/// `(is_active, last_seen_at set, last_ingest_id, battery_v)` of the device,
+/// if it has a row.
+async fn device(pool: &sqlx::PgPool, device_uid: &str) -> Option<(bool, bool, Option<String>, Option<f64>)> {
+    sqlx::query_as(
+        "SELECT is_active, last_seen_at IS NOT NULL, last_ingest_id, battery_v \
+         FROM device WHERE device_uid = $1",
+    )
+    .bind(device_uid)
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn unknown_device_is_provisioned_on_first_sight() {
+    let store = InMemoryPlantStore::new();
+    let (svc, _) = common::memory_service(&store);
+    let svc = svc.with_config(SupervisorConfig { auto_provision: true, ..Default::default() });
+    let plant_id = store.add_plant(vec![]).to_string();
+    let device_uid = common::unique("esp32");
+
+    let env = envelope(&device_uid, &plant_id);
+    let ingest_id = env.ingest_id.clone();
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env] }))
+        .await
+        .unwrap();
+
+    assert_eq!(store.snapshot().devices.get(&device_uid), Some(&Some(ingest_id)));
+}
+
+#[tokio::test]
+async fn unknown_device_is_left_alone_when_disabled() {
+    let store = InMemoryPlantStore::new();
+    let (svc, _) = common::memory_service(&store);
+    let plant_id = store.add_plant(vec![]).to_string();
+    let device_uid = common::unique("esp32");
+
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&device_uid, &plant_id)],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // The reading itself still ingests.
+    assert_eq!(resp.results[0].result(), IngestResult::Ok);
+    assert!(store.snapshot().devices.is_empty());
+}
+
+#[tokio::test]
+async fn provisioned_row_is_active_with_the_reading() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let svc = svc.with_config(SupervisorConfig { auto_provision: true, ..Default::default() });
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let device_uid = common::unique("esp32");
+
+    let env = envelope(&device_uid, &plant_id);
+    let ingest_id = env.ingest_id.clone();
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env] }))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        device(&pool, &device_uid).await,
+        Some((true, true, Some(ingest_id), Some(3.9)))
+    );
+}
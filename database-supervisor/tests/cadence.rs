@@ -0,0 +1,83 @@
+//! Reporting-interval alerts: too-fast and too-slow devices raise a WARN
+//! ticker event, a device on schedule does not.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, CreateDeviceRequest, IngestTelemetryRequest,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+const EXPECTED_INTERVAL_S: u32 = 60;
+
+fn envelope(device_uid: &str, plant_id: &str, seq: u32) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: device_uid.to_string(),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000 + seq as i64,
+        seq,
+        soil_moisture: Some(45.0),
+        ..Default::default()
+    }
+}
+
+/// Register a device, ingest once, pretend that reading was `ago_s` seconds
+/// ago, ingest again and return the cadence alerts raised.
+async fn alerts_after(ago_s: f64) -> Option<Vec<String>> {
+    let pool = common::test_pool().await?;
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let device_uid = common::unique("esp32");
+    svc.create_device(Request::new(CreateDeviceRequest {
+        device_uid: device_uid.clone(),
+        expected_interval_s: Some(EXPECTED_INTERVAL_S),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    for seq in 1..=2 {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&device_uid, &plant_id, seq)],
+        }))
+        .await
+        .unwrap();
+        sqlx::query("UPDATE device SET last_seen_at = NOW() - make_interval(secs => $2) WHERE device_uid = $1")
+            .bind(&device_uid)
+            .bind(ago_s)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    Some(
+        sqlx::query_scalar(
+            r#"SELECT payload->>'cadence' FROM ticker_event
+               WHERE device_uid = $1 AND severity = 'WARN' AND payload ? 'cadence'"#,
+        )
+        .bind(&device_uid)
+        .fetch_all(&pool)
+        .await
+        .unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn too_fast_device_raises_warn() {
+    let Some(alerts) = alerts_after(5.0).await else { return };
+    assert_eq!(alerts, ["too_fast"]);
+}
+
+#[tokio::test]
+async fn too_slow_device_raises_warn() {
+    let Some(alerts) = alerts_after(300.0).await else { return };
+    assert_eq!(alerts, ["too_slow"]);
+}
+
+#[tokio::test]
+async fn on_schedule_device_is_quiet() {
+    let Some(alerts) = alerts_after(60.0).await else { return };
+    assert!(alerts.is_empty(), "{alerts:?}");
+}
@@ -0,0 +1,118 @@
+//! Shared helpers for integration tests.
+//!
+//! Tests that need a real PostgreSQL connect to `TEST_DATABASE_URL` and apply
+//! the plant-health schema.  When the variable is unset they return early, so
+//! `cargo test` stays green on machines without a database.  Tests of ingest
+//! behaviour alone run against an [`InMemoryPlantStore`] instead.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use database_supervisor::ingest::SupervisorServiceImpl;
+use database_supervisor::store::InMemoryPlantStore;
+use database_supervisor::telemetry_sink::FakeTelemetrySink;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, CreatePlantRequest, CreatePlantTypeRequest,
+    MetricThresholdSpec, TelemetryEnvelope,
+};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use tonic::Request;
+
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../../postgres-service/db/migrations/001_plant_health_schema.sql"),
+    include_str!("../../../postgres-service/db/migrations/002_metric_smoothing.sql"),
+    include_str!("../../../postgres-service/db/migrations/003_device_health.sql"),
+    include_str!("../../../postgres-service/db/migrations/004_raw_payload_capture.sql"),
+    include_str!("../../../postgres-service/db/migrations/005_device_cadence.sql"),
+    include_str!("../../../postgres-service/db/migrations/006_threshold_hysteresis.sql"),
+    include_str!("../../../postgres-service/db/migrations/007_rate_of_change.sql"),
+    include_str!("../../../postgres-service/db/migrations/008_severity_history.sql"),
+    include_str!("../../../postgres-service/db/migrations/009_telemetry_dead_letter.sql"),
+    include_str!("../../../postgres-service/db/migrations/010_dead_letter_readings.sql"),
+    include_str!("../../../postgres-service/db/migrations/011_plant_metric_threshold.sql"),
+    include_str!("../../../postgres-service/db/migrations/012_ledger_timestamp_index.sql"),
+    include_str!("../../../postgres-service/db/migrations/013_dead_letter_firmware.sql"),
+    include_str!("../../../postgres-service/db/migrations/014_required_metrics.sql"),
+    include_str!("../../../postgres-service/db/migrations/015_ticker_occurred_at_ns.sql"),
+    include_str!("../../../postgres-service/db/migrations/016_warn_escalation.sql"),
+    include_str!("../../../postgres-service/db/migrations/017_idempotency.sql"),
+];
+
+/// Connect to the test database and ensure the schema exists.
+pub async fn test_pool() -> Option<PgPool> {
+    let url = std::env::var("TEST_DATABASE_URL").ok()?;
+    let pool = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&url)
+        .await
+        .expect("connect to TEST_DATABASE_URL");
+    for sql in MIGRATIONS {
+        sqlx::raw_sql(sql)
+            .execute(&pool)
+            .await
+            .expect("apply plant-health schema");
+    }
+    Some(pool)
+}
+
+/// A service wired to `pool` with an in-memory sink and no AMQP.
+pub fn service(pool: PgPool) -> (SupervisorServiceImpl, FakeTelemetrySink) {
+    let sink = FakeTelemetrySink::new();
+    let svc = SupervisorServiceImpl::new(pool, Arc::new(sink.clone()), None);
+    (svc, sink)
+}
+
+/// A service whose ingest runs against `store`; its pool never connects, so
+/// only RPCs that go through the store work.
+pub fn memory_service(store: &InMemoryPlantStore) -> (SupervisorServiceImpl, FakeTelemetrySink) {
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://unused@localhost/unused")
+        .expect("lazy pool");
+    let (svc, sink) = service(pool);
+    (svc.with_store(Arc::new(store.clone())), sink)
+}
+
+/// A reading for `plant_id` from a fresh device, with a unique `ingest_id`.
+/// Override fields with struct update syntax.
+pub fn envelope(plant_id: &str) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: unique("ingest"),
+        device_uid: unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(45.0),
+        ..Default::default()
+    }
+}
+
+/// A name that won't collide with rows left behind by earlier runs.
+pub fn unique(prefix: &str) -> String {
+    format!("{prefix}-{}", uuid::Uuid::new_v4())
+}
+
+/// Register a fresh plant type with `thresholds` and one plant of that type.
+/// Returns the plant id.
+pub async fn register_plant(svc: &SupervisorServiceImpl, thresholds: Vec<MetricThresholdSpec>) -> String {
+    let plant_type_id = svc
+        .create_plant_type(Request::new(CreatePlantTypeRequest {
+            name: unique("type"),
+            description: String::new(),
+            thresholds,
+        }))
+        .await
+        .expect("create plant type")
+        .into_inner()
+        .id;
+    svc.create_plant(Request::new(CreatePlantRequest {
+        plant_type_id,
+        display_name: unique("plant"),
+        ..Default::default()
+    }))
+    .await
+    .expect("create plant")
+    .into_inner()
+    .id
+}
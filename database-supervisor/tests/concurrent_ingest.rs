@@ -0,0 +1,67 @@
+//! Concurrent batch ingest: results come back in input order, and repeated
+//! ingest ids within one batch still dedup.
+
+mod common;
+
+use database_supervisor::config::SupervisorConfig;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestResult, IngestTelemetryRequest,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(plant_id: &str, seq: u32) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000 + seq as i64,
+        seq,
+        soil_moisture: Some(40.0),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn independent_envelopes_all_succeed_in_input_order() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let svc = svc.with_config(SupervisorConfig { ingest_concurrency: 4, ..Default::default() });
+
+    let mut envelopes = Vec::new();
+    for seq in 0..12 {
+        let plant_id = common::register_plant(&svc, vec![]).await;
+        envelopes.push(envelope(&plant_id, seq));
+    }
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: envelopes.clone() }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let ids: Vec<&str> = resp.results.iter().map(|r| r.ingest_id.as_str()).collect();
+    let expected: Vec<&str> = envelopes.iter().map(|e| e.ingest_id.as_str()).collect();
+    assert_eq!(ids, expected);
+    assert!(resp.results.iter().all(|r| r.result() == IngestResult::Ok), "{:?}", resp.results);
+}
+
+#[tokio::test]
+async fn repeated_ingest_id_in_one_batch_is_duplicate() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let first = common::register_plant(&svc, vec![]).await;
+    let second = common::register_plant(&svc, vec![]).await;
+
+    let original = envelope(&first, 1);
+    let repeat = TelemetryEnvelope { plant_id: second, ..original.clone() };
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![original, envelope(&first, 2), repeat],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let results: Vec<IngestResult> = resp.results.iter().map(|r| r.result()).collect();
+    assert_eq!(results, vec![IngestResult::Ok, IngestResult::Ok, IngestResult::Duplicate]);
+}
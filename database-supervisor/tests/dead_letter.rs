@@ -0,0 +1,126 @@
+//! Dead letters: envelopes that fail ingest are kept, and ReplayDeadLetter
+//! ingests and removes them once the cause is fixed.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestResult, IngestTelemetryRequest,
+    ReplayDeadLetterRequest, TelemetryEnvelope,
+};
+use sqlx::Row;
+use tonic::Request;
+
+fn envelope(plant_id: &str) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 7,
+        soil_moisture: Some(42.5),
+        rssi_dbm: Some(-61),
+        ..Default::default()
+    }
+}
+
+fn now_ns() -> i64 {
+    chrono::Utc::now().timestamp_nanos_opt().unwrap()
+}
+
+async fn set_active(pool: &sqlx::PgPool, plant_id: &str, active: bool) {
+    sqlx::query("UPDATE plant SET is_active = $2 WHERE id = $1::uuid")
+        .bind(plant_id)
+        .bind(active)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn dead_letter_count(pool: &sqlx::PgPool, ingest_id: &str) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM telemetry_dead_letter WHERE ingest_id = $1")
+        .bind(ingest_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn unknown_plant_is_dead_lettered() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let env = envelope(&uuid::Uuid::new_v4().to_string());
+
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env.clone()] }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(resp.results[0].result, IngestResult::Error as i32);
+
+    let row = sqlx::query(
+        "SELECT device_uid, plant_id, seq, soil_moisture, rssi_dbm, error
+         FROM telemetry_dead_letter WHERE ingest_id = $1",
+    )
+    .bind(&env.ingest_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(row.get::<String, _>("device_uid"), env.device_uid);
+    assert_eq!(row.get::<String, _>("plant_id"), env.plant_id);
+    assert_eq!(row.get::<i64, _>("seq"), 7);
+    assert_eq!(row.get::<Option<f64>, _>("soil_moisture"), Some(42.5));
+    assert_eq!(row.get::<Option<i32>, _>("rssi_dbm"), Some(-61));
+    assert_eq!(row.get::<String, _>("error"), "unknown or inactive plant");
+}
+
+#[tokio::test]
+async fn replay_clears_row_once_plant_exists() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, sink) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    set_active(&pool, &plant_id, false).await;
+
+    let start_ns = now_ns();
+    let env = envelope(&plant_id);
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env.clone()] }))
+        .await
+        .unwrap();
+    assert_eq!(dead_letter_count(&pool, &env.ingest_id).await, 1);
+
+    // Still missing: the row stays.
+    let window = ReplayDeadLetterRequest { start_ns, ..Default::default() };
+    let resp = svc
+        .replay_dead_letter(Request::new(window))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(resp.cleared, 0);
+    assert_eq!(dead_letter_count(&pool, &env.ingest_id).await, 1);
+
+    set_active(&pool, &plant_id, true).await;
+    let resp = svc
+        .replay_dead_letter(Request::new(window))
+        .await
+        .unwrap()
+        .into_inner();
+    let ours = resp
+        .results
+        .iter()
+        .find(|r| r.ingest_id == env.ingest_id)
+        .expect("replayed");
+    assert_eq!(ours.result, IngestResult::Ok as i32, "{}", ours.error);
+    assert!(resp.cleared >= 1);
+    assert_eq!(dead_letter_count(&pool, &env.ingest_id).await, 0);
+
+    let result: String =
+        sqlx::query_scalar("SELECT result FROM telemetry_ingest_ledger WHERE ingest_id = $1")
+            .bind(&env.ingest_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(result, "OK");
+    assert!(sink
+        .snapshot()
+        .iter()
+        .any(|p| p.tags.get("plant_id") == Some(&plant_id)));
+}
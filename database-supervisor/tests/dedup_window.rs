@@ -0,0 +1,64 @@
+//! With a dedup window, only ledger rows with a recent reading time make an
+//! envelope a duplicate.
+
+mod common;
+
+use std::time::Duration;
+
+use database_supervisor::config::SupervisorConfig;
+use database_supervisor::ingest::SupervisorServiceImpl;
+use database_supervisor::store::InMemoryPlantStore;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestResult, IngestTelemetryRequest,
+    MetricThresholdSpec, TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn with_window(svc: SupervisorServiceImpl) -> SupervisorServiceImpl {
+    svc.with_config(SupervisorConfig {
+        dedup_window: Some(Duration::from_secs(24 * 3600)),
+        ..Default::default()
+    })
+}
+
+/// Ingest a reading from 23h and one from 25h ago, then both again: only
+/// the one inside the 24h window is a duplicate.
+async fn assert_window_applies(svc: &SupervisorServiceImpl, plant_id: &str) {
+    let hours_ago = |h: i64| (chrono::Utc::now() - chrono::Duration::hours(h)).timestamp_nanos_opt().unwrap();
+    let inside = TelemetryEnvelope { timestamp_ns: hours_ago(23), ..common::envelope(plant_id) };
+    let outside = TelemetryEnvelope { timestamp_ns: hours_ago(25), ..common::envelope(plant_id) };
+
+    let ingest = |env: TelemetryEnvelope| async move {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env] }))
+            .await
+            .unwrap()
+            .into_inner()
+            .results[0]
+            .result
+    };
+    assert_eq!(ingest(inside.clone()).await, IngestResult::Ok as i32);
+    assert_eq!(ingest(outside.clone()).await, IngestResult::Ok as i32);
+    assert_eq!(ingest(inside).await, IngestResult::Duplicate as i32);
+    assert_eq!(ingest(outside).await, IngestResult::Ok as i32);
+}
+
+#[tokio::test]
+async fn ingest_id_outside_the_window_is_new_again() {
+    let store = InMemoryPlantStore::new();
+    let (svc, _) = common::memory_service(&store);
+    let plant_id = store.add_plant(vec![]).to_string();
+    assert_window_applies(&with_window(svc), &plant_id).await;
+}
+
+#[tokio::test]
+async fn ledger_query_honours_the_window() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool);
+    let svc = with_window(svc);
+    let plant_id = common::register_plant(
+        &svc,
+        vec![MetricThresholdSpec { metric: "soil_moisture".into(), ..Default::default() }],
+    )
+    .await;
+    assert_window_applies(&svc, &plant_id).await;
+}
@@ -0,0 +1,102 @@
+//! Battery / RSSI and firmware version reported in envelopes land on the
+//! `device` row.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, CreateDeviceRequest, IngestTelemetryRequest,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(device_uid: &str, plant_id: &str, seq: u32) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: device_uid.to_string(),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000 + seq as i64,
+        seq,
+        soil_moisture: Some(45.0),
+        ..Default::default()
+    }
+}
+
+async fn device_health(pool: &sqlx::PgPool, device_uid: &str) -> (Option<f64>, Option<i32>) {
+    sqlx::query_as("SELECT battery_v, rssi_dbm FROM device WHERE device_uid = $1")
+        .bind(device_uid)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn health_fields_update_device_and_are_optional() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let device_uid = common::unique("esp32");
+    svc.create_device(Request::new(CreateDeviceRequest {
+        device_uid: device_uid.clone(),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    let with_health = TelemetryEnvelope {
+        battery_v: Some(3.7),
+        rssi_dbm: Some(-61),
+        ..envelope(&device_uid, &plant_id, 1)
+    };
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![with_health] }))
+        .await
+        .unwrap();
+    assert_eq!(device_health(&pool, &device_uid).await, (Some(3.7), Some(-61)));
+
+    // A reading without health fields keeps the last reported values.
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![envelope(&device_uid, &plant_id, 2)],
+    }))
+    .await
+    .unwrap();
+    assert_eq!(device_health(&pool, &device_uid).await, (Some(3.7), Some(-61)));
+}
+
+async fn firmware_version(pool: &sqlx::PgPool, device_uid: &str) -> Option<String> {
+    sqlx::query_scalar("SELECT firmware_version FROM device WHERE device_uid = $1")
+        .bind(device_uid)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn firmware_version_updates_device_only_when_reported() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let device_uid = common::unique("esp32");
+    svc.create_device(Request::new(CreateDeviceRequest {
+        device_uid: device_uid.clone(),
+        firmware_version: "1.0.0".into(),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    // A reading without a firmware version keeps the registered one.
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![envelope(&device_uid, &plant_id, 1)],
+    }))
+    .await
+    .unwrap();
+    assert_eq!(firmware_version(&pool, &device_uid).await.as_deref(), Some("1.0.0"));
+
+    let upgraded = TelemetryEnvelope {
+        firmware_version: Some("1.1.0".into()),
+        ..envelope(&device_uid, &plant_id, 2)
+    };
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![upgraded] }))
+        .await
+        .unwrap();
+    assert_eq!(firmware_version(&pool, &device_uid).await.as_deref(), Some("1.1.0"));
+}
@@ -0,0 +1,87 @@
+//! Dry-run mode reaches the same decisions as a normal ingest without
+//! writing anything.
+
+mod common;
+
+use database_supervisor::config::SupervisorConfig;
+use database_supervisor::ingest::SupervisorServiceImpl;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest,
+    IngestTelemetryResponse, MetricThresholdSpec, TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(plant_id: &str, soil_moisture: f64) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(soil_moisture),
+        ..Default::default()
+    }
+}
+
+fn thresholds() -> Vec<MetricThresholdSpec> {
+    vec![MetricThresholdSpec {
+        metric: "soil_moisture".into(),
+        crit_min: Some(20.0),
+        ..Default::default()
+    }]
+}
+
+async fn ingest(svc: &SupervisorServiceImpl, envelope: TelemetryEnvelope) -> IngestTelemetryResponse {
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![envelope] }))
+        .await
+        .unwrap()
+        .into_inner()
+}
+
+/// Results and status changes with the plant id left out, for comparing
+/// ingests of different plants.
+fn decisions(resp: &IngestTelemetryResponse) -> (Vec<i32>, Vec<(i32, i32)>) {
+    (
+        resp.results.iter().map(|r| r.result).collect(),
+        resp.status_changes.iter().map(|c| (c.prev_severity, c.new_severity)).collect(),
+    )
+}
+
+#[tokio::test]
+async fn dry_run_matches_normal_ingest_and_writes_nothing() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (live, live_sink) = common::service(pool.clone());
+    let (dry, dry_sink) = common::service(pool.clone());
+    let dry = dry.with_config(SupervisorConfig { dry_run: true, ..Default::default() });
+    let live_plant = common::register_plant(&live, thresholds()).await;
+    let dry_plant = common::register_plant(&live, thresholds()).await;
+
+    // Both plants start CRITICAL, so the next reading is a recovery.
+    ingest(&live, envelope(&live_plant, 10.0)).await;
+    ingest(&live, envelope(&dry_plant, 10.0)).await;
+    live_sink.drain();
+
+    let live_resp = ingest(&live, envelope(&live_plant, 45.0)).await;
+    let dry_envelope = envelope(&dry_plant, 45.0);
+    let dry_resp = ingest(&dry, dry_envelope.clone()).await;
+
+    assert_eq!(decisions(&dry_resp), decisions(&live_resp));
+    assert_eq!(dry_resp.status_changes.len(), 1);
+    assert_eq!(live_sink.snapshot().len(), 1);
+    assert!(dry_sink.snapshot().is_empty());
+
+    let severity: String =
+        sqlx::query_scalar("SELECT severity FROM plant_current_state WHERE plant_id = $1::uuid")
+            .bind(&dry_plant)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(severity, "CRITICAL");
+    let ledger_rows: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM telemetry_ingest_ledger WHERE ingest_id = $1")
+            .bind(&dry_envelope.ingest_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(ledger_rows, 0);
+}
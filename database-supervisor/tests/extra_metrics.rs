@@ -0,0 +1,69 @@
+//! A metric outside the four typed readings, listed in `SUPERVISOR_METRICS`,
+//! reaches both the sink and threshold evaluation.
+
+mod common;
+
+use database_supervisor::config::SupervisorConfig;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, MetricThresholdSpec,
+    Severity, TelemetryEnvelope,
+};
+use tonic::Request;
+
+#[tokio::test]
+async fn configured_metric_is_evaluated_and_forwarded() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, sink) = common::service(pool.clone());
+    let svc = svc.with_config(SupervisorConfig {
+        metrics: vec!["soil_moisture".into(), "co2_ppm".into()],
+        ..Default::default()
+    });
+    let plant_id = common::register_plant(
+        &svc,
+        vec![MetricThresholdSpec {
+            metric: "co2_ppm".into(),
+            warn_max: Some(1000.0),
+            ..Default::default()
+        }],
+    )
+    .await;
+
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![TelemetryEnvelope {
+                ingest_id: common::unique("ingest"),
+                device_uid: common::unique("esp32"),
+                plant_id: plant_id.clone(),
+                timestamp_ns: 1_700_000_000_000_000_000,
+                seq: 1,
+                soil_moisture: Some(40.0),
+                readings: [
+                    ("co2_ppm".to_string(), 1500.0),
+                    ("not_configured".to_string(), 1.0),
+                ]
+                .into(),
+                ..Default::default()
+            }],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(resp.status_changes.len(), 1);
+    assert_eq!(resp.status_changes[0].new_severity, Severity::Warn as i32);
+
+    let points = sink.snapshot();
+    assert_eq!(points.len(), 1);
+    assert_eq!(points[0].fields.get("co2_ppm"), Some(&1500.0));
+    assert_eq!(points[0].fields.get("soil_moisture"), Some(&40.0));
+    assert!(!points[0].fields.contains_key("not_configured"));
+
+    let metric_severity: serde_json::Value = sqlx::query_scalar(
+        "SELECT metric_severity FROM plant_current_state WHERE plant_id = $1::uuid",
+    )
+    .bind(&plant_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(metric_severity["co2_ppm"], "WARN");
+}
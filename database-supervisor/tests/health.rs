@@ -0,0 +1,39 @@
+//! The gRPC health service reports SERVING on a started server.
+
+mod common;
+
+use database_supervisor::health;
+use proto::supervisor_service::supervisor_service_server::SupervisorServiceServer;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Channel, Server};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+#[tokio::test]
+async fn started_server_reports_serving() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (_reporter, health_service) = health::service(&pool).await;
+    let (svc, _) = common::service(pool);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(health_service)
+            .add_service(SupervisorServiceServer::new(svc))
+            .serve_with_incoming(incoming),
+    );
+
+    let channel = Channel::from_shared(format!("http://{addr}")).unwrap().connect().await.unwrap();
+    let mut client = HealthClient::new(channel);
+    for service in ["", "supervisor_service.SupervisorService"] {
+        let resp = client
+            .check(HealthCheckRequest { service: service.to_string() })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(resp.status(), ServingStatus::Serving, "{service:?}");
+    }
+}
@@ -0,0 +1,61 @@
+//! Hysteresis: a reading hovering just inside the warn band keeps WARN until
+//! it recovers by the configured margin.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, MetricThresholdSpec,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(plant_id: &str, soil_moisture: f64) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(soil_moisture),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn boundary_readings_do_not_flap() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(
+        &svc,
+        vec![MetricThresholdSpec {
+            metric: "soil_moisture".into(),
+            warn_min: Some(30.0),
+            hysteresis: Some(3.0),
+            ..Default::default()
+        }],
+    )
+    .await;
+
+    for (value, expected) in [
+        (31.0, "NORMAL"),
+        (29.0, "WARN"),
+        (31.0, "WARN"),
+        (29.5, "WARN"),
+        (32.0, "WARN"),
+        (33.5, "NORMAL"),
+    ] {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id, value)],
+        }))
+        .await
+        .unwrap();
+
+        let severity: String =
+            sqlx::query_scalar("SELECT severity FROM plant_current_state WHERE plant_id = $1::uuid")
+                .bind(&plant_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(severity, expected, "after {value}");
+    }
+}
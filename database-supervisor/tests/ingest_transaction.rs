@@ -0,0 +1,91 @@
+//! Ingest writes commit together with the ledger row: a failure part-way
+//! through leaves no partial state behind.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, CreateDeviceRequest, IngestResult,
+    IngestTelemetryRequest, TelemetryEnvelope,
+};
+use tonic::Request;
+
+/// Device uids with this prefix make the ticker insert fail.
+const FAILING_PREFIX: &str = "fail-ticker";
+
+async fn install_failing_ticker_trigger(pool: &sqlx::PgPool) {
+    sqlx::raw_sql(&format!(
+        r#"
+        CREATE OR REPLACE FUNCTION test_fail_ticker_insert() RETURNS trigger AS $$
+        BEGIN
+            IF NEW.device_uid LIKE '{FAILING_PREFIX}-%' THEN
+                RAISE EXCEPTION 'simulated ticker_event failure';
+            END IF;
+            RETURN NEW;
+        END $$ LANGUAGE plpgsql;
+
+        DROP TRIGGER IF EXISTS test_fail_ticker_insert ON ticker_event;
+        CREATE TRIGGER test_fail_ticker_insert BEFORE INSERT ON ticker_event
+            FOR EACH ROW EXECUTE FUNCTION test_fail_ticker_insert();
+        "#
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn ticker_failure_rolls_back_state_device_and_ledger() {
+    let Some(pool) = common::test_pool().await else { return };
+    install_failing_ticker_trigger(&pool).await;
+    let (svc, sink) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let device_uid = common::unique(FAILING_PREFIX);
+    svc.create_device(Request::new(CreateDeviceRequest {
+        device_uid: device_uid.clone(),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    let envelope = TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: device_uid.clone(),
+        plant_id: plant_id.clone(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(45.0),
+        battery_v: Some(3.7),
+        ..Default::default()
+    };
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![envelope.clone()] }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(resp.results[0].result(), IngestResult::Error);
+    assert!(resp.results[0].error.contains("simulated ticker_event failure"), "{:?}", resp.results);
+
+    let ledger: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM telemetry_ingest_ledger WHERE ingest_id = $1")
+        .bind(&envelope.ingest_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(ledger, 0);
+
+    let state: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM plant_current_state WHERE plant_id = $1::uuid")
+        .bind(&plant_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(state, 0);
+
+    let (last_ingest_id, battery_v): (Option<String>, Option<f64>) =
+        sqlx::query_as("SELECT last_ingest_id, battery_v FROM device WHERE device_uid = $1")
+            .bind(&device_uid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((last_ingest_id, battery_v), (None, None));
+
+    assert!(sink.snapshot().is_empty(), "no telemetry for a rolled-back reading");
+}
@@ -0,0 +1,78 @@
+//! QueryLedger filters by device and reading-time window.
+
+mod common;
+
+use proto::supervisor_service::{supervisor_service_server::SupervisorService, QueryLedgerRequest};
+use tonic::{Code, Request};
+
+async fn insert(pool: &sqlx::PgPool, ingest_id: &str, device_uid: &str, timestamp_ns: i64, result: &str) {
+    sqlx::query(
+        "INSERT INTO telemetry_ingest_ledger (ingest_id, device_uid, timestamp_ns, result) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(ingest_id)
+    .bind(device_uid)
+    .bind(timestamp_ns)
+    .bind(result)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn query_by_device_and_time_window() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+
+    let device = common::unique("esp32");
+    let other = common::unique("esp32");
+    let ids: Vec<String> = (0..4).map(|_| common::unique("ingest")).collect();
+    insert(&pool, &ids[0], &device, 1_000, "OK").await;
+    insert(&pool, &ids[1], &device, 2_000, "ERROR").await;
+    insert(&pool, &ids[2], &device, 3_000, "OK").await;
+    insert(&pool, &ids[3], &other, 2_000, "OK").await;
+
+    let entries = svc
+        .query_ledger(Request::new(QueryLedgerRequest {
+            device_uid: device.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .entries;
+    let got: Vec<&str> = entries.iter().map(|e| e.ingest_id.as_str()).collect();
+    assert_eq!(got, vec![ids[2].as_str(), ids[1].as_str(), ids[0].as_str()]);
+
+    let entries = svc
+        .query_ledger(Request::new(QueryLedgerRequest {
+            device_uid: device.clone(),
+            start_ns: 1_500,
+            stop_ns: 3_000,
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .entries;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].ingest_id, ids[1]);
+    assert_eq!(entries[0].result, "ERROR");
+    assert_eq!(entries[0].timestamp_ns, 2_000);
+    assert!(entries[0].received_at_ns > 0);
+}
+
+#[tokio::test]
+async fn inverted_window_is_invalid() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool);
+
+    let err = svc
+        .query_ledger(Request::new(QueryLedgerRequest {
+            start_ns: 10,
+            stop_ns: 5,
+            ..Default::default()
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
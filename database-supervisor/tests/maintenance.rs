@@ -0,0 +1,51 @@
+//! Maintenance mode: ingest is paused while on and resumes once switched off.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestResult, IngestTelemetryRequest,
+    SetMaintenanceModeRequest, TelemetryEnvelope,
+};
+use tonic::{Code, Request};
+
+fn envelope(plant_id: &str) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(45.0),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn ingest_is_rejected_during_maintenance_and_processed_after() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, sink) = common::service(pool);
+    let plant_id = common::register_plant(&svc, vec![]).await;
+
+    svc.set_maintenance_mode(Request::new(SetMaintenanceModeRequest { on: true }))
+        .await
+        .unwrap();
+    let env = envelope(&plant_id);
+    let err = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env.clone()] }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+    assert!(sink.snapshot().is_empty());
+
+    svc.set_maintenance_mode(Request::new(SetMaintenanceModeRequest { on: false }))
+        .await
+        .unwrap();
+    let resp = svc
+        .ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![env] }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(resp.results.len(), 1);
+    assert_eq!(resp.results[0].result(), IngestResult::Ok);
+    assert_eq!(sink.snapshot().len(), 1);
+}
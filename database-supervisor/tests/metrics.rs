@@ -0,0 +1,79 @@
+//! Ingest outcomes and sink failures are counted in the Prometheus output.
+
+mod common;
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use database_supervisor::ingest::SupervisorServiceImpl;
+use database_supervisor::telemetry_sink::{TelemetryPoint, TelemetrySink};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, TelemetryEnvelope,
+};
+use tonic::Request;
+
+struct FailingSink;
+
+#[async_trait]
+impl TelemetrySink for FailingSink {
+    async fn write_points(&self, _points: Vec<TelemetryPoint>) -> Result<()> {
+        Err(anyhow!("influx unavailable"))
+    }
+}
+
+fn envelope(plant_id: &str) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        soil_moisture: Some(40.0),
+        ..Default::default()
+    }
+}
+
+/// The value of the sample line for `metric` whose labels contain `labels`.
+fn sample(rendered: &str, metric: &str, labels: &str) -> Option<f64> {
+    rendered
+        .lines()
+        .filter(|l| l.starts_with(metric) && l.contains(labels))
+        .find_map(|l| l.rsplit(' ').next()?.parse().ok())
+}
+
+#[tokio::test]
+async fn ingest_counters_render_with_result_labels() {
+    let Some(pool) = common::test_pool().await else { return };
+    // This test binary owns the global recorder.
+    let handle = PrometheusBuilder::new().install_recorder().unwrap();
+    database_supervisor::metrics::describe();
+
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let ok = envelope(&plant_id);
+    let bad = TelemetryEnvelope { plant_id: "not-a-uuid".into(), ..envelope(&plant_id) };
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![ok.clone(), bad],
+    }))
+    .await
+    .unwrap();
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest { envelopes: vec![ok] }))
+        .await
+        .unwrap();
+
+    let failing = SupervisorServiceImpl::new(pool, Arc::new(FailingSink), None);
+    failing
+        .ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id)],
+        }))
+        .await
+        .unwrap();
+
+    let rendered = handle.render();
+    assert_eq!(sample(&rendered, "supervisor_ingest_total", r#"result="ok""#), Some(2.0), "{rendered}");
+    assert_eq!(sample(&rendered, "supervisor_ingest_total", r#"result="duplicate""#), Some(1.0));
+    assert_eq!(sample(&rendered, "supervisor_ingest_total", r#"result="error""#), Some(1.0));
+    assert_eq!(sample(&rendered, "supervisor_sink_write_failures_total", ""), Some(1.0));
+    assert!(rendered.contains("# HELP supervisor_ingest_total"));
+}
@@ -0,0 +1,143 @@
+//! Per-plant thresholds override the plant type's for the same metric and
+//! leave the type's other metrics in force.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, GetEffectiveThresholdsRequest,
+    IngestTelemetryRequest, MetricThresholdSpec, TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(plant_id: &str, soil_moisture: f64, light: f64) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(soil_moisture),
+        ambient_light_lux: Some(light),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn plant_override_wins_over_type_threshold() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let thresholds = vec![
+        MetricThresholdSpec {
+            metric: "soil_moisture".into(),
+            crit_min: Some(20.0),
+            ..Default::default()
+        },
+        MetricThresholdSpec {
+            metric: "ambient_light_lux".into(),
+            crit_min: Some(1000.0),
+            ..Default::default()
+        },
+    ];
+    let sunny = common::register_plant(&svc, thresholds.clone()).await;
+    let shaded = common::register_plant(&svc, thresholds).await;
+    sqlx::query(
+        "INSERT INTO plant_metric_threshold (plant_id, metric, crit_min) \
+         VALUES ($1::uuid, 'ambient_light_lux', 200.0)",
+    )
+    .bind(&shaded)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let severity = |plant_id: String| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_scalar::<_, String>(
+                "SELECT severity FROM plant_current_state WHERE plant_id = $1::uuid",
+            )
+            .bind(plant_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        }
+    };
+
+    // 500 lux is too dark by the type's band but fine for the shaded plant.
+    for plant_id in [&sunny, &shaded] {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(plant_id, 45.0, 500.0)],
+        }))
+        .await
+        .unwrap();
+    }
+    assert_eq!(severity(sunny.clone()).await, "CRITICAL");
+    assert_eq!(severity(shaded.clone()).await, "NORMAL");
+
+    // The type's soil_moisture threshold still applies to the shaded plant.
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![envelope(&shaded, 10.0, 500.0)],
+    }))
+    .await
+    .unwrap();
+    assert_eq!(severity(shaded).await, "CRITICAL");
+}
+
+#[tokio::test]
+async fn effective_thresholds_merge_type_defaults_and_overrides() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(
+        &svc,
+        vec![
+            MetricThresholdSpec {
+                metric: "soil_moisture".into(),
+                crit_min: Some(20.0),
+                required: true,
+                ..Default::default()
+            },
+            MetricThresholdSpec {
+                metric: "ambient_light_lux".into(),
+                crit_min: Some(1000.0),
+                smoothing: "sma".into(),
+                smoothing_window: 3,
+                ..Default::default()
+            },
+        ],
+    )
+    .await;
+    sqlx::query(
+        "INSERT INTO plant_metric_threshold (plant_id, metric, crit_min) \
+         VALUES ($1::uuid, 'ambient_light_lux', 200.0)",
+    )
+    .bind(&plant_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let resp = svc
+        .get_effective_thresholds(Request::new(GetEffectiveThresholdsRequest {
+            plant_id: plant_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let metrics: Vec<_> = resp.thresholds.iter().map(|t| t.metric.as_str()).collect();
+    assert_eq!(metrics, ["ambient_light_lux", "soil_moisture"]);
+    // The override replaces the type's row whole, smoothing included.
+    let light = &resp.thresholds[0];
+    assert_eq!(light.crit_min, Some(200.0));
+    assert_eq!(light.smoothing, "");
+    // The type's row applies where the plant has none.
+    let soil = &resp.thresholds[1];
+    assert_eq!(soil.crit_min, Some(20.0));
+    assert!(soil.required);
+
+    let err = svc
+        .get_effective_thresholds(Request::new(GetEffectiveThresholdsRequest {
+            plant_id: uuid::Uuid::new_v4().to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::NotFound);
+}
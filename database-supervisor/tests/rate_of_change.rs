@@ -0,0 +1,61 @@
+//! Rate-of-change thresholds: a sharp in-band drop is WARN, a gentle one is not.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, MetricThresholdSpec,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+const MINUTE_NS: i64 = 60_000_000_000;
+
+fn envelope(plant_id: &str, soil_moisture: f64, timestamp_ns: i64) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns,
+        seq: 1,
+        soil_moisture: Some(soil_moisture),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn sharp_drop_warns_while_value_is_in_band() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(
+        &svc,
+        vec![MetricThresholdSpec {
+            metric: "soil_moisture".into(),
+            warn_min: Some(20.0),
+            max_rate_per_min: Some(5.0),
+            ..Default::default()
+        }],
+    )
+    .await;
+
+    let t0 = 1_700_000_000_000_000_000;
+    for (value, ts, expected) in [
+        (60.0, t0, "NORMAL"),
+        (58.0, t0 + MINUTE_NS, "NORMAL"),
+        (35.0, t0 + 2 * MINUTE_NS, "WARN"),
+        (34.0, t0 + 3 * MINUTE_NS, "NORMAL"),
+    ] {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id, value, ts)],
+        }))
+        .await
+        .unwrap();
+
+        let severity: String =
+            sqlx::query_scalar("SELECT severity FROM plant_current_state WHERE plant_id = $1::uuid")
+                .bind(&plant_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(severity, expected, "after {value}");
+    }
+}
@@ -0,0 +1,49 @@
+//! Raw payloads forwarded by the router land in `raw_payload_capture`.
+
+mod common;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(plant_id: &str, raw: Option<&[u8]>) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(45.0),
+        raw_payload_b64: raw.map(|b| STANDARD.encode(b)).unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+async fn captured(pool: &sqlx::PgPool, ingest_id: &str) -> Option<Vec<u8>> {
+    sqlx::query_scalar("SELECT payload FROM raw_payload_capture WHERE ingest_id = $1")
+        .bind(ingest_id)
+        .fetch_optional(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn raw_payload_is_stored_only_when_forwarded() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+
+    let raw = br#"{"version":1,"device_uid":"esp32","seq":1,"junk":"\u0000"}"#;
+    let with_raw = envelope(&plant_id, Some(raw));
+    let without_raw = envelope(&plant_id, None);
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![with_raw.clone(), without_raw.clone()],
+    }))
+    .await
+    .unwrap();
+
+    assert_eq!(captured(&pool, &with_raw.ingest_id).await.as_deref(), Some(&raw[..]));
+    assert_eq!(captured(&pool, &without_raw.ingest_id).await, None);
+}
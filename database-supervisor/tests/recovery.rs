@@ -0,0 +1,85 @@
+//! Ticker events: WARN/CRITICAL -> NORMAL is marked as a recovery, and
+//! NORMAL -> NORMAL only gets an event with `ticker_all`.
+
+mod common;
+
+use database_supervisor::config::SupervisorConfig;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, MetricThresholdSpec,
+    TelemetryEnvelope,
+};
+use tonic::Request;
+
+fn envelope(plant_id: &str, soil_moisture: f64) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(soil_moisture),
+        ..Default::default()
+    }
+}
+
+async fn ticker_payloads(pool: &sqlx::PgPool, plant_id: &str) -> Vec<serde_json::Value> {
+    sqlx::query_scalar("SELECT payload FROM ticker_event WHERE plant_id = $1::uuid ORDER BY id")
+        .bind(plant_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn recovery_transition_is_marked_and_steady_normal_is_not() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let svc = svc.with_config(SupervisorConfig {
+        emit_recovery_events: true,
+        ..Default::default()
+    });
+    let plant_id = common::register_plant(
+        &svc,
+        vec![MetricThresholdSpec {
+            metric: "soil_moisture".into(),
+            crit_min: Some(20.0),
+            ..Default::default()
+        }],
+    )
+    .await;
+
+    for value in [10.0, 45.0, 50.0] {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id, value)],
+        }))
+        .await
+        .unwrap();
+    }
+
+    // First reading (CRITICAL) and the recovery; steady NORMAL adds nothing.
+    let payloads = ticker_payloads(&pool, &plant_id).await;
+    assert_eq!(payloads.len(), 2, "{payloads:?}");
+    assert!(payloads[0].get("recovered").is_none(), "{}", payloads[0]);
+    assert_eq!(payloads[1]["recovered"], true);
+    assert_eq!(payloads[1]["prev_severity"], "CRITICAL");
+}
+
+#[tokio::test]
+async fn ticker_all_records_every_reading() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let svc = svc.with_config(SupervisorConfig {
+        ticker_all: true,
+        ..Default::default()
+    });
+    let plant_id = common::register_plant(&svc, vec![]).await;
+
+    for value in [40.0, 41.0, 42.0] {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id, value)],
+        }))
+        .await
+        .unwrap();
+    }
+    assert_eq!(ticker_payloads(&pool, &plant_id).await.len(), 3);
+}
@@ -0,0 +1,113 @@
+//! Severity history: one row per overall severity transition, with the
+//! per-metric snapshot.
+
+mod common;
+
+use std::time::Duration;
+
+use database_supervisor::stale;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, MetricThresholdSpec,
+    TelemetryEnvelope,
+};
+use sqlx::Row;
+use tonic::Request;
+
+fn envelope(plant_id: &str, soil_moisture: f64) -> TelemetryEnvelope {
+    TelemetryEnvelope {
+        ingest_id: common::unique("ingest"),
+        device_uid: common::unique("esp32"),
+        plant_id: plant_id.to_string(),
+        timestamp_ns: 1_700_000_000_000_000_000,
+        seq: 1,
+        soil_moisture: Some(soil_moisture),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn transitions_are_appended_in_order() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(
+        &svc,
+        vec![MetricThresholdSpec {
+            metric: "soil_moisture".into(),
+            warn_min: Some(30.0),
+            crit_min: Some(20.0),
+            ..Default::default()
+        }],
+    )
+    .await;
+
+    // NORMAL, NORMAL, WARN, CRITICAL, CRITICAL, NORMAL
+    for value in [45.0, 46.0, 25.0, 10.0, 11.0, 50.0] {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id, value)],
+        }))
+        .await
+        .unwrap();
+    }
+
+    let rows = sqlx::query(
+        "SELECT prev_severity, new_severity, metric_severity FROM plant_severity_history
+         WHERE plant_id = $1::uuid ORDER BY occurred_at, id",
+    )
+    .bind(&plant_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let transitions: Vec<(Option<String>, String)> = rows
+        .iter()
+        .map(|r| (r.get("prev_severity"), r.get("new_severity")))
+        .collect();
+    assert_eq!(
+        transitions,
+        vec![
+            (None, "NORMAL".to_string()),
+            (Some("NORMAL".into()), "WARN".into()),
+            (Some("WARN".into()), "CRITICAL".into()),
+            (Some("CRITICAL".into()), "NORMAL".into()),
+        ]
+    );
+    let snapshot: serde_json::Value = rows[2].get("metric_severity");
+    assert_eq!(snapshot["soil_moisture"], "CRITICAL");
+}
+
+#[tokio::test]
+async fn stale_sweep_records_the_transition_in_and_out() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+    let ingest = |value| {
+        svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+            envelopes: vec![envelope(&plant_id, value)],
+        }))
+    };
+
+    ingest(45.0).await.unwrap();
+    sqlx::query("UPDATE plant_current_state SET updated_at = NOW() - INTERVAL '2 hours' WHERE plant_id = $1::uuid")
+        .bind(&plant_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    stale::mark_stale(&pool, Duration::from_secs(3600)).await.unwrap();
+    ingest(46.0).await.unwrap();
+
+    let transitions: Vec<(Option<String>, String)> = sqlx::query_as(
+        "SELECT prev_severity, new_severity FROM plant_severity_history
+         WHERE plant_id = $1::uuid ORDER BY occurred_at, id",
+    )
+    .bind(&plant_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        transitions,
+        vec![
+            (None, "NORMAL".to_string()),
+            (Some("NORMAL".into()), "STALE".into()),
+            (Some("STALE".into()), "NORMAL".into()),
+        ]
+    );
+}
@@ -0,0 +1,36 @@
+//! SIGTERM stops a running server cleanly: the serve future resolves `Ok(())`.
+
+#![cfg(unix)]
+
+use std::time::Duration;
+
+use database_supervisor::shutdown;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+
+#[tokio::test]
+async fn sigterm_resolves_serve_with_ok() {
+    let (_reporter, health_service) = tonic_health::server::health_reporter();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+
+    let signal = shutdown::signal();
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(health_service)
+            .serve_with_incoming_shutdown(incoming, signal),
+    );
+
+    let status = tokio::process::Command::new("kill")
+        .args(["-TERM", &std::process::id().to_string()])
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let result = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not shut down")
+        .unwrap();
+    assert!(result.is_ok(), "{result:?}");
+}
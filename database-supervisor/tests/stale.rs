@@ -0,0 +1,79 @@
+//! Stale sweep: plants not updated within the TTL are marked STALE once.
+
+mod common;
+
+use std::time::Duration;
+
+use database_supervisor::stale;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, TelemetryEnvelope,
+};
+use tonic::Request;
+
+async fn ingest(svc: &impl SupervisorService, plant_id: &str) {
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![TelemetryEnvelope {
+            ingest_id: common::unique("ingest"),
+            device_uid: common::unique("esp32"),
+            plant_id: plant_id.to_string(),
+            timestamp_ns: 1_700_000_000_000_000_000,
+            seq: 1,
+            soil_moisture: Some(45.0),
+            ..Default::default()
+        }],
+    }))
+    .await
+    .unwrap();
+}
+
+async fn severity(pool: &sqlx::PgPool, plant_id: &str) -> String {
+    sqlx::query_scalar("SELECT severity FROM plant_current_state WHERE plant_id = $1::uuid")
+        .bind(plant_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn silent_plant_is_marked_stale_and_fresh_one_is_not() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let silent = common::register_plant(&svc, vec![]).await;
+    let fresh = common::register_plant(&svc, vec![]).await;
+    ingest(&svc, &silent).await;
+    ingest(&svc, &fresh).await;
+    sqlx::query("UPDATE plant_current_state SET updated_at = NOW() - INTERVAL '2 hours' WHERE plant_id = $1::uuid")
+        .bind(&silent)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let ttl = Duration::from_secs(3600);
+    assert!(stale::mark_stale(&pool, ttl).await.unwrap() >= 1);
+    assert_eq!(severity(&pool, &silent).await, "STALE");
+    assert_eq!(severity(&pool, &fresh).await, "NORMAL");
+
+    let events: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM ticker_event WHERE plant_id = $1::uuid AND severity = 'STALE'",
+    )
+    .bind(&silent)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(events, 1);
+
+    // Already stale: not marked again.
+    stale::mark_stale(&pool, ttl).await.unwrap();
+    let events: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM ticker_event WHERE plant_id = $1::uuid AND severity = 'STALE'",
+    )
+    .bind(&silent)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(events, 1);
+
+    // A new reading clears it.
+    ingest(&svc, &silent).await;
+    assert_eq!(severity(&pool, &silent).await, "NORMAL");
+}
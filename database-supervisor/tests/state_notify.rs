@@ -0,0 +1,48 @@
+//! An ingest notifies `plant_state_changed` with the plant id on commit.
+
+mod common;
+
+use std::time::Duration;
+
+use database_supervisor::ingest::STATE_CHANGED_CHANNEL;
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, TelemetryEnvelope,
+};
+use sqlx::postgres::PgListener;
+use tonic::Request;
+
+#[tokio::test]
+async fn ingest_notifies_plant_state_changed() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+
+    let mut listener = PgListener::connect_with(&pool).await.unwrap();
+    listener.listen(STATE_CHANGED_CHANNEL).await.unwrap();
+
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![TelemetryEnvelope {
+            ingest_id: common::unique("ingest"),
+            device_uid: common::unique("esp32"),
+            plant_id: plant_id.clone(),
+            timestamp_ns: 1_700_000_000_000_000_000,
+            seq: 1,
+            soil_moisture: Some(40.0),
+            ..Default::default()
+        }],
+    }))
+    .await
+    .unwrap();
+
+    // Other tests may be ingesting into the same database concurrently.
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let notification = listener.recv().await.unwrap();
+            if notification.payload() == plant_id {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("no plant_state_changed notification for the plant");
+}
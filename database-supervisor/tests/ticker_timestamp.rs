@@ -0,0 +1,38 @@
+//! Ticker events keep the device timestamp of the reading that raised them.
+
+mod common;
+
+use proto::supervisor_service::{
+    supervisor_service_server::SupervisorService, IngestTelemetryRequest, TelemetryEnvelope,
+};
+use tonic::Request;
+
+#[tokio::test]
+async fn ticker_event_stores_reading_timestamp_ns() {
+    let Some(pool) = common::test_pool().await else { return };
+    let (svc, _) = common::service(pool.clone());
+    let plant_id = common::register_plant(&svc, vec![]).await;
+
+    // The plant's first reading always gets a ticker event.
+    svc.ingest_telemetry(Request::new(IngestTelemetryRequest {
+        envelopes: vec![TelemetryEnvelope {
+            ingest_id: common::unique("ingest"),
+            device_uid: common::unique("esp32"),
+            plant_id: plant_id.clone(),
+            timestamp_ns: 1_700_000_000_123_456_789,
+            seq: 1,
+            soil_moisture: Some(45.0),
+            ..Default::default()
+        }],
+    }))
+    .await
+    .unwrap();
+
+    let stored: Vec<Option<i64>> =
+        sqlx::query_scalar("SELECT occurred_at_ns FROM ticker_event WHERE plant_id = $1::uuid")
+            .bind(&plant_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored, vec![Some(1_700_000_000_123_456_789)]);
+}
@@ -0,0 +1,136 @@
+//! Source allowlist for UDP packets.
+//!
+//! With `ROUTER_ALLOWED_CIDRS` set (comma-separated, e.g.
+//! `10.20.0.0/16, 192.168.1.7, fd00::/8`), packets from any other peer are
+//! dropped before they are decoded, and counted with a [`DropCounter`] so
+//! the log isn't flooded.  A bare address is a single host.  Unset or blank
+//! accepts every peer.  Only the UDP listener is filtered.
+//!
+//! [`DropCounter`]: crate::drops::DropCounter
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("invalid CIDR {0:?}: expected an IP address, optionally followed by /prefix")]
+pub struct InvalidCidr(pub String);
+
+/// An IPv4 or IPv6 network, e.g. `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Whether `ip` is in this network.  IPv4-mapped IPv6 addresses (as a
+    /// dual-stack socket reports IPv4 peers) match as IPv4.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCidr(s.to_string());
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let network: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().ok().filter(|&p| p <= max_prefix).ok_or_else(invalid)?,
+            None => max_prefix,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+/// The networks packets may come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allowlist(Vec<Cidr>);
+
+impl Allowlist {
+    /// Parse a comma-separated list; `None` when it lists nothing, i.e.
+    /// every peer is allowed.
+    pub fn parse(spec: &str) -> Result<Option<Self>, InvalidCidr> {
+        let cidrs = spec
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Cidr>, _>>()?;
+        Ok((!cidrs.is_empty()).then_some(Self(cidrs)))
+    }
+
+    /// Whether `ip` is in any listed network.
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn allowlist(spec: &str) -> Allowlist {
+        Allowlist::parse(spec).unwrap().unwrap()
+    }
+
+    #[test]
+    fn ipv4_peer_inside_a_network_is_allowed() {
+        let allowed = allowlist("10.20.0.0/16, 192.168.1.7");
+        assert!(allowed.allows(ip("10.20.3.4")));
+        assert!(allowed.allows(ip("192.168.1.7")));
+        // As a dual-stack socket reports an IPv4 peer.
+        assert!(allowed.allows(ip("::ffff:10.20.3.4")));
+    }
+
+    #[test]
+    fn ipv4_peer_outside_every_network_is_denied() {
+        let allowed = allowlist("10.20.0.0/16, 192.168.1.7");
+        assert!(!allowed.allows(ip("10.21.0.1")));
+        assert!(!allowed.allows(ip("192.168.1.8")));
+        assert!(!allowed.allows(ip("fd00::1")));
+    }
+
+    #[test]
+    fn ipv6_networks_match_by_prefix() {
+        let allowed = allowlist("fd00:1234::/32");
+        assert!(allowed.allows(ip("fd00:1234:5678::9")));
+        assert!(!allowed.allows(ip("fd00:1235::1")));
+        assert!(!allowed.allows(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn zero_prefix_matches_the_whole_family() {
+        assert!(allowlist("0.0.0.0/0").allows(ip("203.0.113.9")));
+        assert!(allowlist("::/0").allows(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn blank_spec_allows_everyone_and_bad_entries_are_rejected() {
+        assert_eq!(Allowlist::parse(" , "), Ok(None));
+        assert_eq!(Allowlist::parse("10.0.0.0/33"), Err(InvalidCidr("10.0.0.0/33".into())));
+        assert!(Allowlist::parse("10.0.0.0/8, example.com").is_err());
+        assert!(Allowlist::parse("fd00::/129").is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Gathering decoded envelopes into batches.
+//!
+//! A batch is sent when it reaches `ROUTER_BATCH_SIZE`, when the flush
+//! interval (`ROUTER_BATCH_INTERVAL_MS`) runs out, or when the UDP side
+//! closes the channel on shutdown, whichever comes first.
+
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// What one [`collect`] round received.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Collected<T> {
+    pub items: Vec<T>,
+    /// The sender side is gone; `items` is the final partial batch.
+    pub closed: bool,
+}
+
+/// Receive from `rx` until `deadline`, until `limit` items have arrived
+/// (`None`: no size trigger, e.g. while backing off), or until the channel
+/// closes.  A `limit` of zero returns at once.
+pub async fn collect<T>(
+    rx: &mut mpsc::Receiver<T>,
+    deadline: Instant,
+    limit: Option<usize>,
+) -> Collected<T> {
+    let mut items = Vec::new();
+    loop {
+        if limit.is_some_and(|limit| items.len() >= limit) {
+            return Collected { items, closed: false };
+        }
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(item)) => items.push(item),
+            Ok(None) => return Collected { items, closed: true },
+            Err(_) => return Collected { items, closed: false },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn full_batch_is_returned_before_the_deadline() {
+        let (tx, mut rx) = mpsc::channel(16);
+        for i in 0..5 {
+            tx.send(i).await.unwrap();
+        }
+        let started = Instant::now();
+        let got = collect(&mut rx, started + Duration::from_secs(60), Some(3)).await;
+        assert_eq!(got, Collected { items: vec![0, 1, 2], closed: false });
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        // The rest waits for the next round.
+        let got = collect(&mut rx, Instant::now(), Some(0)).await;
+        assert!(got.items.is_empty());
+        assert_eq!(rx.recv().await, Some(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn partial_batch_is_returned_at_the_deadline() {
+        let (tx, mut rx) = mpsc::channel(16);
+        tx.send(1).await.unwrap();
+        let started = Instant::now();
+        let got = collect(&mut rx, started + Duration::from_millis(100), Some(64)).await;
+        assert_eq!(got, Collected { items: vec![1], closed: false });
+        assert_eq!(started.elapsed(), Duration::from_millis(100));
+        drop(tx);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn close_returns_the_final_partial_batch() {
+        let (tx, mut rx) = mpsc::channel(16);
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        drop(tx);
+        let got = collect(&mut rx, Instant::now() + Duration::from_secs(60), Some(64)).await;
+        assert_eq!(got, Collected { items: vec![1, 2], closed: true });
+    }
+}
@@ -0,0 +1,216 @@
+//! Holding area for envelopes while the supervisor is unavailable.
+//!
+//! When the supervisor rejects a batch with `UNAVAILABLE` (maintenance mode,
+//! restart, ...) the router keeps the envelopes in a bounded FIFO and retries
+//! with exponential backoff instead of logging an error per batch.  Once the
+//! buffer is full the oldest envelopes are dropped first.
+//!
+//! Other transient failures (timeouts, broken connections, internal errors)
+//! keep the batch too, but only for [`MAX_TRANSIENT_ATTEMPTS`] tries so a
+//! batch the supervisor can never accept does not block the rest; see
+//! [`RetryPolicy`].
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tonic::Code;
+
+/// Tries of one batch failing with a transient error other than
+/// `UNAVAILABLE` before it is given up on.
+pub const MAX_TRANSIENT_ATTEMPTS: u32 = 5;
+
+/// Bounded FIFO of envelopes waiting to be forwarded.
+#[derive(Debug)]
+pub struct PendingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: Clone> PendingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self { items: VecDeque::new(), capacity }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Append `items`, evicting the oldest entries past capacity.
+    /// Returns how many were evicted.
+    pub fn extend(&mut self, items: impl IntoIterator<Item = T>) -> usize {
+        self.items.extend(items);
+        let overflow = self.items.len().saturating_sub(self.capacity);
+        self.items.drain(..overflow);
+        overflow
+    }
+
+    /// Copy of the first `n` entries, left in place until [`Self::consume`].
+    pub fn peek(&self, n: usize) -> Vec<T> {
+        self.items.iter().take(n).cloned().collect()
+    }
+
+    /// Remove the first `n` entries (after they were sent or given up on).
+    pub fn consume(&mut self, n: usize) {
+        let n = n.min(self.items.len());
+        self.items.drain(..n);
+    }
+
+    /// The first `sent` entries failed to send with `code`: keep them for
+    /// another try if `retry` says so, else drop them.  Returns whether
+    /// they were kept.
+    pub fn fail_head(&mut self, sent: usize, code: Code, retry: &mut RetryPolicy) -> bool {
+        if retry.keep(code) {
+            return true;
+        }
+        self.consume(sent);
+        false
+    }
+}
+
+/// Exponential backoff between retries, doubling up to `max`.
+#[derive(Debug)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self { min, max, current: min }
+    }
+
+    /// Delay to wait before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.min;
+    }
+}
+
+/// Whether a batch that failed to send is kept for another try.
+#[derive(Debug, Default)]
+pub struct RetryPolicy {
+    /// Consecutive transient failures of the batch at the head.
+    failures: u32,
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a failure with `code`; `true` when the batch should stay in
+    /// the buffer and be retried after a backoff.
+    pub fn keep(&mut self, code: Code) -> bool {
+        match code {
+            Code::Unavailable => true,
+            Code::DeadlineExceeded
+            | Code::Unknown
+            | Code::Internal
+            | Code::Aborted
+            | Code::ResourceExhausted
+            | Code::Cancelled => {
+                self.failures += 1;
+                if self.failures < MAX_TRANSIENT_ATTEMPTS {
+                    return true;
+                }
+                self.failures = 0;
+                false
+            }
+            _ => {
+                self.failures = 0;
+                false
+            }
+        }
+    }
+
+    /// The batch at the head went through (or was given up on).
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend_evicts_oldest_past_capacity() {
+        let mut buf = PendingBuffer::new(3);
+        assert_eq!(buf.extend([1, 2]), 0);
+        assert_eq!(buf.extend([3, 4, 5]), 2);
+        assert_eq!(buf.peek(10), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn peek_then_consume() {
+        let mut buf = PendingBuffer::new(10);
+        buf.extend([1, 2, 3]);
+        assert_eq!(buf.peek(2), vec![1, 2]);
+        assert_eq!(buf.len(), 3);
+        buf.consume(2);
+        assert_eq!(buf.peek(2), vec![3]);
+        buf.consume(5);
+        assert!(buf.is_empty());
+    }
+
+    /// Try to send the head batch of two, failing with `code`, the way
+    /// `batch_sender` does.
+    #[test]
+    fn failed_send_keeps_the_batch() {
+        let mut buf = PendingBuffer::new(10);
+        let mut retry = RetryPolicy::new();
+        buf.extend([1, 2, 3]);
+
+        for _ in 0..20 {
+            assert!(buf.fail_head(2, Code::Unavailable, &mut retry));
+        }
+        assert_eq!(buf.peek(10), vec![1, 2, 3]);
+
+        for _ in 1..MAX_TRANSIENT_ATTEMPTS {
+            assert!(buf.fail_head(2, Code::DeadlineExceeded, &mut retry));
+            assert_eq!(buf.peek(10), vec![1, 2, 3]);
+        }
+        assert!(!buf.fail_head(2, Code::DeadlineExceeded, &mut retry));
+        assert_eq!(buf.peek(10), vec![3]);
+    }
+
+    #[test]
+    fn rejected_batch_is_dropped_at_once() {
+        let mut buf = PendingBuffer::new(10);
+        let mut retry = RetryPolicy::new();
+        buf.extend([1, 2, 3]);
+        assert!(!buf.fail_head(2, Code::InvalidArgument, &mut retry));
+        assert_eq!(buf.peek(10), vec![3]);
+    }
+
+    #[test]
+    fn retained_batch_is_evicted_first_when_full() {
+        let mut buf = PendingBuffer::new(3);
+        let mut retry = RetryPolicy::new();
+        buf.extend([1, 2]);
+        assert!(buf.fail_head(2, Code::Unavailable, &mut retry));
+        assert_eq!(buf.extend([3, 4]), 1);
+        assert_eq!(buf.peek(10), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn backoff_doubles_to_max_and_resets() {
+        let mut b = Backoff::new(Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(b.next_delay(), Duration::from_millis(100));
+        assert_eq!(b.next_delay(), Duration::from_millis(200));
+        assert_eq!(b.next_delay(), Duration::from_millis(350));
+        assert_eq!(b.next_delay(), Duration::from_millis(350));
+        b.reset();
+        assert_eq!(b.next_delay(), Duration::from_millis(100));
+    }
+}
@@ -1,14 +1,45 @@
 //! UDP payload codec.
 //!
-//! Decodes JSON-encoded telemetry messages from ESP32-S3 devices.
+//! Decodes telemetry messages from ESP32-S3 devices.  [`decode`] takes
+//! either [`Codec`]: a JSON object, or for the smallest nodes a compact
+//! binary message without field names, told apart by its magic prefix.
+//!
+//! # Binary (postcard) layout
+//!
+//! | Offset | Size | Field                                           |
+//! |--------|------|-------------------------------------------------|
+//! | 0      | 2    | magic `0xA5 0x17` ([`POSTCARD_MAGIC`])          |
+//! | 2      | 1    | layout version, `1` ([`POSTCARD_VERSION`])      |
+//! | 3      | rest | [postcard] body, the fields below in this order |
+//!
+//! 1. `device_uid`: varint length, then UTF-8 bytes
+//! 2. `plant_id`: 16 raw UUID bytes, most significant first
+//! 3. `seq`: varint `u32`
+//! 4. `timestamp_ns`: zigzag varint `i64`
+//! 5. `soil_moisture`, `ambient_light_lux`, `ambient_humidity_rh`,
+//!    `ambient_temp_c`, `battery_v`: each `0x00` when absent, or `0x01` and
+//!    an 8-byte little-endian `f64`
+//! 6. `rssi_dbm`: `0x00`, or `0x01` and a zigzag varint `i32`
+//! 7. `readings`: varint count, then per entry a name (as `device_uid`)
+//!    and an 8-byte little-endian `f64`
+//! 8. `firmware_version`: `0x00`, or `0x01` and a string as `device_uid`
+//!
+//! Buffers with another magic or layout version, or with bytes after the
+//! body, are rejected.  A decoded binary message carries every protocol
+//! version 2 field, so it is reported as version 2.
+//!
+//! [postcard]: https://postcard.jamesmunns.com/wire-format
+
+use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use uuid::Uuid;
 
 /// A raw telemetry message as received over UDP.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UdpTelemetryMessage {
-    /// Protocol version. Currently only `1` is accepted.
+    /// Protocol version: `1`, or `2`, which adds `firmware_version`.
     pub version: u8,
     /// Globally unique device identifier.
     pub device_uid: String,
@@ -23,33 +54,215 @@ pub struct UdpTelemetryMessage {
     pub ambient_light_lux:   Option<f64>,
     pub ambient_humidity_rh: Option<f64>,
     pub ambient_temp_c:      Option<f64>,
+
+    /// Battery voltage, if the device reports it.
+    pub battery_v:           Option<f64>,
+    /// Wi-Fi signal strength in dBm, if the device reports it.
+    pub rssi_dbm:            Option<i32>,
+
+    /// Further sensor readings by metric name, e.g. `{"co2_ppm": 650}`.
+    #[serde(default)]
+    pub readings:            HashMap<String, f64>,
+
+    /// Firmware the device runs (protocol version 2), e.g. `"1.4.2"`.
+    #[serde(default)]
+    pub firmware_version:    Option<String>,
+}
+
+/// Wire format of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// A JSON object with named fields.
+    Json,
+    /// The fixed binary layout described in the module docs.
+    Postcard,
+}
+
+impl Codec {
+    /// [`Codec::Postcard`] when `bytes` start with [`POSTCARD_MAGIC`],
+    /// otherwise [`Codec::Json`].
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&POSTCARD_MAGIC) {
+            Codec::Postcard
+        } else {
+            Codec::Json
+        }
+    }
+}
+
+/// First two bytes of a binary message; never the start of valid JSON.
+pub const POSTCARD_MAGIC: [u8; 2] = [0xA5, 0x17];
+
+/// Binary layout version [`decode_postcard`] accepts.
+pub const POSTCARD_VERSION: u8 = 1;
+
+/// Body of a binary message; field order is the wire layout.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct CompactMessage {
+    device_uid:          String,
+    plant_id:            [u8; 16],
+    seq:                 u32,
+    timestamp_ns:        i64,
+    soil_moisture:       Option<f64>,
+    ambient_light_lux:   Option<f64>,
+    ambient_humidity_rh: Option<f64>,
+    ambient_temp_c:      Option<f64>,
+    battery_v:           Option<f64>,
+    rssi_dbm:            Option<i32>,
+    readings:            Vec<(String, f64)>,
+    firmware_version:    Option<String>,
 }
 
 #[derive(Debug, Error)]
 pub enum DecodeError {
     #[error("JSON decode error: {0}")]
-    Json(#[from] serde_json::Error),
+    Json(#[from] JsonError),
+    #[error("postcard decode error: {0}")]
+    Postcard(#[from] postcard::Error),
+    #[error("binary payload does not start with the magic bytes a5 17")]
+    BadMagic,
+    #[error("binary payload has no layout version")]
+    MissingVersion,
+    #[error("{0} unexpected byte(s) after the binary payload")]
+    TrailingBytes(usize),
     #[error("unsupported protocol version {0}")]
     UnsupportedVersion(u8),
     #[error("device_uid is empty")]
     EmptyDeviceUid,
     #[error("plant_id is empty")]
     EmptyPlantId,
+    #[error("plant_id {0:?} is not a UUID")]
+    InvalidPlantId(String),
+}
+
+impl From<serde_json::Error> for DecodeError {
+    fn from(e: serde_json::Error) -> Self {
+        DecodeError::Json(e.into())
+    }
+}
+
+impl DecodeError {
+    /// A short, fixed name for the kind of failure, for aggregating decode
+    /// errors (e.g. as a log field) without parsing their messages.
+    pub fn class(&self) -> &'static str {
+        match self {
+            DecodeError::Json(JsonError::MissingField(_)) => "json_missing_field",
+            DecodeError::Json(JsonError::TypeMismatch(_)) => "json_type_mismatch",
+            DecodeError::Json(JsonError::Syntax(_))       => "json_syntax",
+            DecodeError::Postcard(_)                      => "postcard",
+            DecodeError::BadMagic                         => "bad_magic",
+            DecodeError::MissingVersion                   => "missing_version",
+            DecodeError::TrailingBytes(_)                 => "trailing_bytes",
+            DecodeError::UnsupportedVersion(_)            => "unsupported_version",
+            DecodeError::EmptyDeviceUid                   => "empty_device_uid",
+            DecodeError::EmptyPlantId                     => "empty_plant_id",
+            DecodeError::InvalidPlantId(_)                => "invalid_plant_id",
+        }
+    }
+}
+
+/// Why a JSON payload did not deserialize.
+#[derive(Debug, Error)]
+pub enum JsonError {
+    /// Well-formed JSON without a required field, e.g. `device_uid`.
+    #[error("missing field `{0}`")]
+    MissingField(String),
+    /// Well-formed JSON with a value of the wrong type or range, e.g.
+    /// `seq` as a string.
+    #[error("{0}")]
+    TypeMismatch(serde_json::Error),
+    /// Not well-formed JSON: bad syntax, truncated, or trailing data.
+    #[error("{0}")]
+    Syntax(serde_json::Error),
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.classify() {
+            serde_json::error::Category::Data => match missing_field(&e) {
+                Some(field) => JsonError::MissingField(field),
+                None => JsonError::TypeMismatch(e),
+            },
+            serde_json::error::Category::Syntax
+            | serde_json::error::Category::Eof
+            | serde_json::error::Category::Io => JsonError::Syntax(e),
+        }
+    }
 }
 
-/// Decode a UDP payload into a [`UdpTelemetryMessage`].
+/// The field named by serde's "missing field `x` at line L column C".
+fn missing_field(e: &serde_json::Error) -> Option<String> {
+    let message = e.to_string();
+    let rest = message.strip_prefix("missing field `")?;
+    let (field, _) = rest.split_once('`')?;
+    Some(field.to_string())
+}
+
+/// Protocol versions [`decode`] accepts.
+pub const SUPPORTED_VERSIONS: [u8; 2] = [1, 2];
+
+/// Decode a UDP payload into a [`UdpTelemetryMessage`], in whichever
+/// [`Codec`] it is.
 pub fn decode(bytes: &[u8]) -> Result<UdpTelemetryMessage, DecodeError> {
+    match Codec::detect(bytes) {
+        Codec::Json => decode_json(bytes),
+        Codec::Postcard => decode_postcard(bytes),
+    }
+}
+
+fn decode_json(bytes: &[u8]) -> Result<UdpTelemetryMessage, DecodeError> {
     let msg: UdpTelemetryMessage = serde_json::from_slice(bytes)?;
 
-    if msg.version != 1 {
+    if !SUPPORTED_VERSIONS.contains(&msg.version) {
         return Err(DecodeError::UnsupportedVersion(msg.version));
     }
+    validate(msg)
+}
+
+/// Decode a binary message (see the module docs for its layout).
+pub fn decode_postcard(bytes: &[u8]) -> Result<UdpTelemetryMessage, DecodeError> {
+    let Some(rest) = bytes.strip_prefix(&POSTCARD_MAGIC) else {
+        return Err(DecodeError::BadMagic);
+    };
+    let Some((&version, body)) = rest.split_first() else {
+        return Err(DecodeError::MissingVersion);
+    };
+    if version != POSTCARD_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let (msg, trailing) = postcard::take_from_bytes::<CompactMessage>(body)?;
+    if !trailing.is_empty() {
+        return Err(DecodeError::TrailingBytes(trailing.len()));
+    }
+
+    validate(UdpTelemetryMessage {
+        version:             2,
+        device_uid:          msg.device_uid,
+        plant_id:            Uuid::from_bytes(msg.plant_id).to_string(),
+        seq:                 msg.seq,
+        timestamp_ns:        msg.timestamp_ns,
+        soil_moisture:       msg.soil_moisture,
+        ambient_light_lux:   msg.ambient_light_lux,
+        ambient_humidity_rh: msg.ambient_humidity_rh,
+        ambient_temp_c:      msg.ambient_temp_c,
+        battery_v:           msg.battery_v,
+        rssi_dbm:            msg.rssi_dbm,
+        readings:            msg.readings.into_iter().collect(),
+        firmware_version:    msg.firmware_version,
+    })
+}
+
+/// Checks shared by both codecs.
+fn validate(msg: UdpTelemetryMessage) -> Result<UdpTelemetryMessage, DecodeError> {
     if msg.device_uid.trim().is_empty() {
         return Err(DecodeError::EmptyDeviceUid);
     }
     if msg.plant_id.trim().is_empty() {
         return Err(DecodeError::EmptyPlantId);
     }
+    if Uuid::parse_str(&msg.plant_id).is_err() {
+        return Err(DecodeError::InvalidPlantId(msg.plant_id));
+    }
 
     Ok(msg)
 }
@@ -79,6 +292,42 @@ mod tests {
         assert_eq!(msg.soil_moisture, Some(55.0));
         assert_eq!(msg.ambient_temp_c, Some(22.5));
         assert_eq!(msg.ambient_light_lux, None);
+        assert_eq!(msg.battery_v, None);
+        assert_eq!(msg.rssi_dbm, None);
+        assert_eq!(msg.firmware_version, None);
+    }
+
+    #[test]
+    fn decode_v2_firmware_version() {
+        let bytes = serde_json::to_vec(&serde_json::json!({
+            "version": 2,
+            "device_uid": "dev",
+            "plant_id": "550e8400-e29b-41d4-a716-446655440000",
+            "seq": 1,
+            "timestamp_ns": 0,
+            "firmware_version": "1.4.2"
+        }))
+        .unwrap();
+        let msg = decode(&bytes).unwrap();
+        assert_eq!(msg.version, 2);
+        assert_eq!(msg.firmware_version.as_deref(), Some("1.4.2"));
+    }
+
+    #[test]
+    fn decode_device_health_fields() {
+        let bytes = serde_json::to_vec(&serde_json::json!({
+            "version": 1,
+            "device_uid": "dev",
+            "plant_id": "550e8400-e29b-41d4-a716-446655440000",
+            "seq": 1,
+            "timestamp_ns": 0,
+            "battery_v": 3.42,
+            "rssi_dbm": -70
+        }))
+        .unwrap();
+        let msg = decode(&bytes).unwrap();
+        assert_eq!(msg.battery_v, Some(3.42));
+        assert_eq!(msg.rssi_dbm, Some(-70));
     }
 
     #[test]
@@ -86,6 +335,48 @@ mod tests {
         assert!(matches!(decode(b"not json"), Err(DecodeError::Json(_))));
     }
 
+    /// `valid_payload` with `key` removed, or set to `value` if given.
+    fn payload_with(key: &str, value: Option<serde_json::Value>) -> Vec<u8> {
+        let mut object: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_slice(&valid_payload()).unwrap();
+        match value {
+            Some(value) => object.insert(key.into(), value),
+            None => object.remove(key),
+        };
+        serde_json::to_vec(&object).unwrap()
+    }
+
+    #[test]
+    fn missing_field_is_named() {
+        let err = decode(&payload_with("device_uid", None)).unwrap_err();
+        match &err {
+            DecodeError::Json(JsonError::MissingField(field)) => assert_eq!(field, "device_uid"),
+            other => panic!("expected MissingField, got {other:?}"),
+        }
+        assert_eq!(err.class(), "json_missing_field");
+        assert_eq!(err.to_string(), "JSON decode error: missing field `device_uid`");
+    }
+
+    #[test]
+    fn wrong_type_is_a_type_mismatch() {
+        let err = decode(&payload_with("seq", Some("42".into()))).unwrap_err();
+        assert!(matches!(err, DecodeError::Json(JsonError::TypeMismatch(_))), "{err:?}");
+        assert_eq!(err.class(), "json_type_mismatch");
+        assert!(err.to_string().contains("line 1 column"), "{err}");
+    }
+
+    #[test]
+    fn broken_json_is_a_syntax_error() {
+        let mut trailing = valid_payload();
+        trailing.extend(b" {}");
+        let cases: [&[u8]; 3] = [br#"{"version": 1, "device_uid": "#, b"{,}", &trailing];
+        for bytes in cases {
+            let err = decode(bytes).unwrap_err();
+            assert!(matches!(err, DecodeError::Json(JsonError::Syntax(_))), "{err:?}");
+            assert_eq!(err.class(), "json_syntax");
+        }
+    }
+
     #[test]
     fn decode_wrong_version() {
         let bytes = serde_json::to_vec(&serde_json::json!({
@@ -123,5 +414,129 @@ mod tests {
         }))
         .unwrap();
         assert!(matches!(decode(&bytes), Err(DecodeError::EmptyPlantId)));
+
+        // Whitespace is still "empty", not an invalid UUID.
+        let bytes = serde_json::to_vec(&serde_json::json!({
+            "version": 1,
+            "device_uid": "dev",
+            "plant_id": "   ",
+            "seq": 1,
+            "timestamp_ns": 0
+        }))
+        .unwrap();
+        assert!(matches!(decode(&bytes), Err(DecodeError::EmptyPlantId)));
+    }
+
+    #[test]
+    fn decode_valid_plant_uuid() {
+        let msg = decode(&valid_payload()).unwrap();
+        assert_eq!(msg.plant_id, "550e8400-e29b-41d4-a716-446655440000");
+    }
+
+    #[test]
+    fn decode_non_uuid_plant_id() {
+        let bytes = serde_json::to_vec(&serde_json::json!({
+            "version": 1,
+            "device_uid": "free-form device id",
+            "plant_id": "plant-7",
+            "seq": 1,
+            "timestamp_ns": 0
+        }))
+        .unwrap();
+        match decode(&bytes) {
+            Err(DecodeError::InvalidPlantId(id)) => assert_eq!(id, "plant-7"),
+            other => panic!("expected InvalidPlantId, got {other:?}"),
+        }
+    }
+
+    fn compact() -> CompactMessage {
+        CompactMessage {
+            device_uid:          "esp32-c3".into(),
+            plant_id:            *Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap().as_bytes(),
+            seq:                 7,
+            timestamp_ns:        1_700_000_000_000_000_000,
+            soil_moisture:       Some(41.5),
+            ambient_light_lux:   None,
+            ambient_humidity_rh: None,
+            ambient_temp_c:      Some(-3.25),
+            battery_v:           Some(3.42),
+            rssi_dbm:            Some(-70),
+            readings:            vec![("co2_ppm".into(), 650.0)],
+            firmware_version:    Some("1.4.2".into()),
+        }
+    }
+
+    /// Magic, `version` and the postcard body of `msg`.
+    fn binary(version: u8, msg: &CompactMessage) -> Vec<u8> {
+        let mut bytes = POSTCARD_MAGIC.to_vec();
+        bytes.push(version);
+        bytes.extend(postcard::to_allocvec(msg).unwrap());
+        bytes
+    }
+
+    #[test]
+    fn postcard_round_trip() {
+        let bytes = binary(POSTCARD_VERSION, &compact());
+        assert_eq!(Codec::detect(&bytes), Codec::Postcard);
+
+        let msg = decode(&bytes).unwrap();
+        assert_eq!(msg.version, 2);
+        assert_eq!(msg.device_uid, "esp32-c3");
+        assert_eq!(msg.plant_id, "550e8400-e29b-41d4-a716-446655440000");
+        assert_eq!(msg.seq, 7);
+        assert_eq!(msg.timestamp_ns, 1_700_000_000_000_000_000);
+        assert_eq!(msg.soil_moisture, Some(41.5));
+        assert_eq!(msg.ambient_light_lux, None);
+        assert_eq!(msg.ambient_temp_c, Some(-3.25));
+        assert_eq!(msg.battery_v, Some(3.42));
+        assert_eq!(msg.rssi_dbm, Some(-70));
+        assert_eq!(msg.readings, HashMap::from([("co2_ppm".to_string(), 650.0)]));
+        assert_eq!(msg.firmware_version.as_deref(), Some("1.4.2"));
+    }
+
+    #[test]
+    fn postcard_is_smaller_than_json() {
+        let minimal = CompactMessage {
+            soil_moisture: Some(55.0),
+            ambient_temp_c: Some(22.5),
+            battery_v: None,
+            rssi_dbm: None,
+            readings: vec![],
+            firmware_version: None,
+            seq: 42,
+            device_uid: "esp32-abc".into(),
+            ..compact()
+        };
+        let bytes = binary(POSTCARD_VERSION, &minimal);
+        let msg = decode(&bytes).unwrap();
+        assert_eq!(msg.soil_moisture, Some(55.0));
+        assert!(msg.readings.is_empty());
+        assert!(bytes.len() * 2 < valid_payload().len(), "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn postcard_wrong_magic_is_rejected() {
+        let mut bytes = binary(POSTCARD_VERSION, &compact());
+        bytes[1] ^= 0xFF;
+        assert!(matches!(decode_postcard(&bytes), Err(DecodeError::BadMagic)));
+        // Without the magic it isn't taken for binary at all.
+        assert!(matches!(decode(&bytes), Err(DecodeError::Json(_))));
+    }
+
+    #[test]
+    fn postcard_wrong_version_or_trailing_bytes_is_rejected() {
+        let bytes = binary(2, &compact());
+        assert!(matches!(decode(&bytes), Err(DecodeError::UnsupportedVersion(2))));
+        assert!(matches!(decode(&POSTCARD_MAGIC), Err(DecodeError::MissingVersion)));
+
+        let mut bytes = binary(POSTCARD_VERSION, &compact());
+        bytes.push(0);
+        assert!(matches!(decode(&bytes), Err(DecodeError::TrailingBytes(1))));
+    }
+
+    #[test]
+    fn postcard_checks_device_uid() {
+        let bytes = binary(POSTCARD_VERSION, &CompactMessage { device_uid: " ".into(), ..compact() });
+        assert!(matches!(decode(&bytes), Err(DecodeError::EmptyDeviceUid)));
     }
 }
@@ -0,0 +1,79 @@
+//! Counting envelopes dropped because the forwarding channel was full.
+//!
+//! Logging each drop would flood the log exactly when the router is
+//! overloaded, so [`DropCounter::record`] only asks for a log line on the
+//! first drop, every [`REPORT_EVERY`] drops, and when [`REPORT_INTERVAL`]
+//! has passed since the last one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Drops between two reports while the channel stays full.
+pub const REPORT_EVERY: u64 = 1000;
+/// Longest gap between two reports while drops keep happening.
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Running total of dropped envelopes.
+#[derive(Debug, Default)]
+pub struct DropCounter {
+    total: AtomicU64,
+    last_report: Mutex<Option<Instant>>,
+}
+
+impl DropCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops counted so far.
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Count one drop at `now`; returns the running total when it is due
+    /// to be logged.
+    pub fn record(&self, now: Instant) -> Option<u64> {
+        let total = self.total.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut last = self.last_report.lock().unwrap_or_else(|e| e.into_inner());
+        let due = match *last {
+            None => true,
+            Some(at) => total.is_multiple_of(REPORT_EVERY) || now.duration_since(at) >= REPORT_INTERVAL,
+        };
+        if due {
+            *last = Some(now);
+        }
+        due.then_some(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn full_channel_drops_are_counted() {
+        let (tx, _rx) = mpsc::channel::<u32>(2);
+        let drops = DropCounter::new();
+        let now = Instant::now();
+        for i in 0..5 {
+            if tx.try_send(i).is_err() {
+                drops.record(now);
+            }
+        }
+        assert_eq!(drops.total(), 3);
+    }
+
+    #[test]
+    fn reports_first_every_thousandth_and_after_interval() {
+        let drops = DropCounter::new();
+        let start = Instant::now();
+        assert_eq!(drops.record(start), Some(1));
+        let reported: Vec<u64> = (2..=2000).filter_map(|_| drops.record(start)).collect();
+        assert_eq!(reported, vec![1000, 2000]);
+        assert_eq!(drops.record(start + Duration::from_secs(5)), None);
+        assert_eq!(drops.record(start + REPORT_INTERVAL), Some(2002));
+    }
+}
@@ -0,0 +1,90 @@
+//! Conversion of decoded UDP messages into supervisor envelopes.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use proto::supervisor_service::TelemetryEnvelope;
+
+use crate::codec::UdpTelemetryMessage;
+use crate::ingest_id;
+
+/// Build the [`TelemetryEnvelope`] forwarded to the supervisor, assigning its
+/// stable `ingest_id`.  `raw`, when given, is the datagram `msg` was decoded
+/// from and is forwarded base64-encoded.
+pub fn from_message(msg: UdpTelemetryMessage, raw: Option<&[u8]>) -> TelemetryEnvelope {
+    let id = ingest_id::compute(&msg.device_uid, &msg.plant_id, msg.seq, msg.timestamp_ns);
+
+    TelemetryEnvelope {
+        ingest_id:           id,
+        device_uid:          msg.device_uid,
+        plant_id:            msg.plant_id,
+        timestamp_ns:        msg.timestamp_ns,
+        seq:                 msg.seq,
+        soil_moisture:       msg.soil_moisture,
+        ambient_light_lux:   msg.ambient_light_lux,
+        ambient_humidity_rh: msg.ambient_humidity_rh,
+        ambient_temp_c:      msg.ambient_temp_c,
+        battery_v:           msg.battery_v,
+        rssi_dbm:            msg.rssi_dbm,
+        raw_payload_b64:     raw.map(|b| STANDARD.encode(b)).unwrap_or_default(),
+        readings:            msg.readings,
+        firmware_version:    msg.firmware_version,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec;
+
+    fn payload(extra: serde_json::Value) -> Vec<u8> {
+        let mut json = serde_json::json!({
+            "version": 1,
+            "device_uid": "esp32-abc",
+            "plant_id": "550e8400-e29b-41d4-a716-446655440000",
+            "seq": 7,
+            "timestamp_ns": 1_700_000_000_000_000_000_i64,
+            "soil_moisture": 40.0
+        });
+        json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::to_vec(&json).unwrap()
+    }
+
+    fn message(extra: serde_json::Value) -> UdpTelemetryMessage {
+        codec::decode(&payload(extra)).unwrap()
+    }
+
+    #[test]
+    fn carries_readings_and_device_health() {
+        let env = from_message(message(serde_json::json!({"battery_v": 3.7, "rssi_dbm": -61})), None);
+        assert_eq!(env.device_uid, "esp32-abc");
+        assert_eq!(env.seq, 7);
+        assert_eq!(env.soil_moisture, Some(40.0));
+        assert_eq!(env.battery_v, Some(3.7));
+        assert_eq!(env.rssi_dbm, Some(-61));
+        assert_eq!(
+            from_message(message(serde_json::json!({"version": 2, "firmware_version": "1.4.2"})), None)
+                .firmware_version
+                .as_deref(),
+            Some("1.4.2")
+        );
+        assert_eq!(
+            env.ingest_id,
+            ingest_id::compute("esp32-abc", "550e8400-e29b-41d4-a716-446655440000", 7, 1_700_000_000_000_000_000)
+        );
+    }
+
+    #[test]
+    fn device_health_is_optional() {
+        let env = from_message(message(serde_json::json!({})), None);
+        assert_eq!(env.battery_v, None);
+        assert_eq!(env.rssi_dbm, None);
+        assert_eq!(env.firmware_version, None);
+        assert!(env.raw_payload_b64.is_empty());
+    }
+
+    #[test]
+    fn captured_raw_bytes_round_trip() {
+        let bytes = payload(serde_json::json!({"unexpected": [1, 2, 3]}));
+        let env = from_message(codec::decode(&bytes).unwrap(), Some(&bytes));
+        assert_eq!(STANDARD.decode(&env.raw_payload_b64).unwrap(), bytes);
+    }
+}
@@ -0,0 +1,155 @@
+//! HTTP ingest, a fallback for devices that cannot send UDP.
+//!
+//! When `ROUTER_HTTP_ADDR` is set the router also serves `POST /ingest`,
+//! which takes the same JSON body as a UDP datagram.  Accepted messages get
+//! their ingest id and go onto the same channel as the UDP path, so both
+//! sources share the batch sender, its buffering and its drop counting.
+//!
+//! | Outcome                         | Status | Body                 |
+//! |---------------------------------|--------|----------------------|
+//! | Enqueued                        | 202    | `{"ingest_id": ...}` |
+//! | Does not decode                 | 400    | `{"error": ...}`     |
+//! | Channel full or router stopping | 503    | `{"error": ...}`     |
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use proto::supervisor_service::TelemetryEnvelope;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, warn};
+
+use crate::{codec, drops::DropCounter, envelope};
+
+/// What the `/ingest` handler shares with the UDP loop.
+#[derive(Clone)]
+pub struct IngestState {
+    pub tx: mpsc::Sender<TelemetryEnvelope>,
+    pub drops: Arc<DropCounter>,
+    pub capture_raw: bool,
+}
+
+/// `POST /ingest`, accepting bodies of up to `max_body_size` bytes.
+pub fn router(state: IngestState, max_body_size: usize) -> Router {
+    Router::new()
+        .route("/ingest", post(ingest))
+        .layer(DefaultBodyLimit::max(max_body_size))
+        .with_state(state)
+}
+
+async fn ingest(State(state): State<IngestState>, body: Bytes) -> Response {
+    let msg = match codec::decode(&body) {
+        Ok(msg) => msg,
+        Err(e) => {
+            debug!(class = e.class(), error = %e, "HTTP ingest decode error");
+            return error(StatusCode::BAD_REQUEST, e.to_string());
+        }
+    };
+    let envelope = envelope::from_message(msg, state.capture_raw.then_some(&body[..]));
+    let ingest_id = envelope.ingest_id.clone();
+
+    match state.tx.try_send(envelope) {
+        Ok(()) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "ingest_id": ingest_id })),
+        )
+            .into_response(),
+        Err(TrySendError::Full(_)) => {
+            if let Some(total) = state.drops.record(Instant::now()) {
+                warn!(dropped_total = total, "envelope channel full, dropping packets");
+            }
+            error(StatusCode::SERVICE_UNAVAILABLE, "router is overloaded; retry later")
+        }
+        Err(TrySendError::Closed(_)) => {
+            error(StatusCode::SERVICE_UNAVAILABLE, "router is shutting down")
+        }
+    }
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::ingest_id;
+
+    fn state(cap: usize) -> (IngestState, mpsc::Receiver<TelemetryEnvelope>) {
+        let (tx, rx) = mpsc::channel(cap);
+        let state = IngestState { tx, drops: Arc::new(DropCounter::new()), capture_raw: false };
+        (state, rx)
+    }
+
+    fn body(version: u32) -> serde_json::Value {
+        serde_json::json!({
+            "version": version,
+            "device_uid": "esp32-abc",
+            "plant_id": "550e8400-e29b-41d4-a716-446655440000",
+            "seq": 7,
+            "timestamp_ns": 1_700_000_000_000_000_000_i64,
+            "soil_moisture": 55.0
+        })
+    }
+
+    async fn post(app: Router, body: &serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let req = axum::http::Request::post("/ingest")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn valid_body_is_enqueued() {
+        let (state, mut rx) = state(4);
+        let (status, resp) = post(router(state, 4096), &body(1)).await;
+
+        let expected_id = ingest_id::compute(
+            "esp32-abc",
+            "550e8400-e29b-41d4-a716-446655440000",
+            7,
+            1_700_000_000_000_000_000,
+        );
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(resp["ingest_id"], expected_id.as_str());
+        let envelope = rx.try_recv().expect("envelope enqueued");
+        assert_eq!(envelope.ingest_id, expected_id);
+        assert_eq!(envelope.device_uid, "esp32-abc");
+        assert_eq!(envelope.soil_moisture, Some(55.0));
+    }
+
+    #[tokio::test]
+    async fn bad_version_is_rejected() {
+        let (state, mut rx) = state(4);
+        let (status, resp) = post(router(state, 4096), &body(3)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(resp["error"].as_str().unwrap().contains('3'), "{resp}");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn full_channel_answers_503_and_counts_the_drop() {
+        let (state, _rx) = state(1);
+        let drops = state.drops.clone();
+        let app = router(state, 4096);
+
+        assert_eq!(post(app.clone(), &body(1)).await.0, StatusCode::ACCEPTED);
+        assert_eq!(post(app, &body(1)).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(drops.total(), 1);
+    }
+}
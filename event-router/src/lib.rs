@@ -1,4 +1,11 @@
-//! Event Router library — UDP telemetry ingestion.
+//! Event Router library — UDP (and optional HTTP) telemetry ingestion.
 
+pub mod allowlist;
+pub mod batch;
+pub mod buffer;
 pub mod codec;
+pub mod drops;
+pub mod envelope;
+pub mod http;
 pub mod ingest_id;
+pub mod packet;
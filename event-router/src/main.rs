@@ -10,8 +10,32 @@
 //! | `ROUTER_UDP_ADDR`    | `0.0.0.0:7000`       |
 //! | `SUPERVISOR_ADDR`    | `http://[::1]:50053` |
 //! | `ROUTER_BATCH_SIZE`  | `64`                 |
+//! | `ROUTER_BATCH_INTERVAL_MS` | `100`          |
+//! | `ROUTER_MAX_BUFFERED`| `10000`              |
+//! | `ROUTER_CAPTURE_RAW` | `false`              |
+//! | `ROUTER_CHANNEL_CAP` | `1024`               |
+//! | `ROUTER_MAX_PACKET_SIZE` | `4096`           |
+//! | `ROUTER_HTTP_ADDR`   | unset (no HTTP)      |
+//! | `ROUTER_ALLOWED_CIDRS` | unset (any source) |
+//!
+//! While the supervisor answers `UNAVAILABLE` (e.g. maintenance mode) the
+//! router keeps up to `ROUTER_MAX_BUFFERED` envelopes and retries with
+//! exponential backoff; other transient failures are retried the same way
+//! a few times before the batch is dropped; see [`buffer`].  With
+//! `ROUTER_ALLOWED_CIDRS` set, UDP packets from other peers are dropped
+//! undecoded; see [`allowlist`].  Packets arriving while the
+//! channel to the batcher is full are dropped and counted; see [`drops`].
+//!
+//! With `ROUTER_HTTP_ADDR` set, `POST /ingest` accepts the same JSON over
+//! HTTP for devices that cannot use UDP, feeding the same channel; see
+//! [`http`].
+//!
+//! On SIGTERM or Ctrl-C the router stops reading UDP (and HTTP) and flushes everything
+//! still pending to the supervisor (for up to [`FLUSH_TIMEOUT`]) before
+//! exiting.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use proto::supervisor_service::{
@@ -21,12 +45,26 @@ use proto::supervisor_service::{
 use tokio::net::UdpSocket;
 use tokio::sync::mpsc;
 use tonic::transport::Channel;
-use tracing::{error, info, warn};
+use tonic::Code;
+use tracing::{debug, error, info, warn};
 
+mod allowlist;
+mod batch;
+mod buffer;
 mod codec;
+mod drops;
+mod envelope;
+mod http;
 mod ingest_id;
+mod packet;
+
+use buffer::{Backoff, PendingBuffer, RetryPolicy};
 
-const MAX_PACKET_SIZE: usize = 4096;
+const BACKOFF_MIN: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// Longest wait for the final flush on shutdown; below Kubernetes' default
+/// 30 s grace period.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(20);
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -47,7 +85,42 @@ async fn main() -> Result<()> {
     let batch_size: usize = std::env::var("ROUTER_BATCH_SIZE")
         .ok()
         .and_then(|s| s.parse().ok())
+        .filter(|&n: &usize| n > 0)
         .unwrap_or(64);
+    let batch_interval = std::env::var("ROUTER_BATCH_INTERVAL_MS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&ms: &u64| ms > 0)
+        .map_or(Duration::from_millis(100), Duration::from_millis);
+    let max_buffered: usize = std::env::var("ROUTER_MAX_BUFFERED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10_000);
+    let max_packet_size: usize = std::env::var("ROUTER_MAX_PACKET_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&n: &usize| n > 0)
+        .map_or(packet::DEFAULT_MAX_PACKET_SIZE, |n| n.min(packet::MAX_UDP_PAYLOAD));
+    let channel_cap: usize = std::env::var("ROUTER_CHANNEL_CAP")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&n: &usize| n > 0)
+        .unwrap_or(1024);
+    let http_addr = std::env::var("ROUTER_HTTP_ADDR")
+        .ok()
+        .filter(|v| !v.trim().is_empty());
+    let allowed_sources = match std::env::var("ROUTER_ALLOWED_CIDRS") {
+        Ok(spec) => allowlist::Allowlist::parse(&spec)?,
+        Err(_) => None,
+    };
+    if let Some(allowed) = &allowed_sources {
+        info!(?allowed, "UDP source allowlist enabled");
+    }
+    let capture_raw = std::env::var("ROUTER_CAPTURE_RAW")
+        .is_ok_and(|v| matches!(v.trim(), "1" | "true"));
+    if capture_raw {
+        info!("raw payload capture enabled");
+    }
 
     let socket = Arc::new(UdpSocket::bind(&udp_addr).await?);
     info!(addr = udp_addr, "UDP listener bound");
@@ -55,93 +128,199 @@ async fn main() -> Result<()> {
     let channel = Channel::from_shared(supervisor_addr)?.connect_lazy();
     let client = SupervisorServiceClient::new(channel);
 
-    let (tx, rx) = mpsc::channel::<TelemetryEnvelope>(1024);
+    let (tx, rx) = mpsc::channel::<TelemetryEnvelope>(channel_cap);
+    let drops = Arc::new(drops::DropCounter::new());
+    let rejected_sources = drops::DropCounter::new();
+
+    let sender = tokio::spawn(batch_sender(rx, client, batch_size, batch_interval, max_buffered));
 
-    tokio::spawn(batch_sender(rx, client, batch_size));
+    let mut http_server = None;
+    if let Some(addr) = http_addr {
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        info!(addr, "HTTP ingest listener bound");
+        let state = http::IngestState { tx: tx.clone(), drops: drops.clone(), capture_raw };
+        let app = http::router(state, max_packet_size);
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let serve = axum::serve(listener, app).with_graceful_shutdown(async {
+                let _ = stopped.await;
+            });
+            if let Err(e) = serve.await {
+                error!(error = %e, "HTTP ingest server failed");
+            }
+        });
+        http_server = Some((stop, server));
+    }
 
-    let mut buf = vec![0u8; MAX_PACKET_SIZE];
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let mut buf = packet::PacketBuffer::new(max_packet_size);
     loop {
-        let (len, peer) = match socket.recv_from(&mut buf).await {
-            Ok(v)  => v,
-            Err(e) => { error!(error = %e, "UDP recv_from error"); continue; }
+        let (len, peer) = tokio::select! {
+            received = socket.recv_from(buf.recv_buf()) => match received {
+                Ok(v)  => v,
+                Err(e) => { error!(error = %e, "UDP recv_from error"); continue; }
+            },
+            _ = &mut shutdown => break,
         };
 
-        let bytes = &buf[..len];
+        if allowed_sources.as_ref().is_some_and(|allowed| !allowed.allows(peer.ip())) {
+            debug!(peer = %peer, "packet from a source outside ROUTER_ALLOWED_CIDRS, dropping");
+            if let Some(total) = rejected_sources.record(std::time::Instant::now()) {
+                warn!(peer = %peer, rejected_total = total, "packets from sources outside ROUTER_ALLOWED_CIDRS dropped");
+            }
+            continue;
+        }
+
+        let bytes = match buf.datagram(len) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!(peer = %peer, max_packet_size, error = %e, "oversize datagram dropped");
+                continue;
+            }
+        };
 
         match codec::decode(bytes) {
             Ok(msg) => {
-                let id = ingest_id::compute(
-                    &msg.device_uid,
-                    &msg.plant_id,
-                    msg.seq,
-                    msg.timestamp_ns,
-                );
+                let envelope = envelope::from_message(msg, capture_raw.then_some(bytes));
 
-                let envelope = TelemetryEnvelope {
-                    ingest_id:           id,
-                    device_uid:          msg.device_uid,
-                    plant_id:            msg.plant_id,
-                    timestamp_ns:        msg.timestamp_ns,
-                    seq:                 msg.seq,
-                    soil_moisture:       msg.soil_moisture,
-                    ambient_light_lux:   msg.ambient_light_lux,
-                    ambient_humidity_rh: msg.ambient_humidity_rh,
-                    ambient_temp_c:      msg.ambient_temp_c,
-                };
-
-                if let Err(e) = tx.try_send(envelope) {
-                    warn!(peer = %peer, error = %e, "envelope channel full, dropping packet");
+                if tx.try_send(envelope).is_err() {
+                    debug!(peer = %peer, "envelope channel full, dropping packet");
+                    if let Some(total) = drops.record(std::time::Instant::now()) {
+                        warn!(dropped_total = total, capacity = channel_cap, "envelope channel full, dropping packets");
+                    }
                 }
             }
             Err(e) => {
-                warn!(peer = %peer, error = %e, "decode error");
+                warn!(peer = %peer, class = e.class(), error = %e, "decode error");
             }
         }
     }
+
+    // The HTTP server holds a sender too; stop it so the channel closes.
+    if let Some((stop, server)) = http_server {
+        let _ = stop.send(());
+        let _ = server.await;
+    }
+    // Closing the channel makes the sender flush what it holds and return.
+    drop(tx);
+    match tokio::time::timeout(FLUSH_TIMEOUT, sender).await {
+        Ok(_)  => info!(dropped_total = drops.total(), "event-router stopped"),
+        Err(_) => warn!("timed out flushing pending envelopes; exiting"),
+    }
+    Ok(())
 }
 
 async fn batch_sender(
     mut rx: mpsc::Receiver<TelemetryEnvelope>,
     mut client: SupervisorServiceClient<Channel>,
     batch_size: usize,
+    batch_interval: Duration,
+    max_buffered: usize,
 ) {
-    let mut batch = Vec::with_capacity(batch_size);
+    let mut pending = PendingBuffer::new(max_buffered);
+    let mut backoff = Backoff::new(BACKOFF_MIN, BACKOFF_MAX);
+    let mut retry = RetryPolicy::new();
+    // Delay before the next send; set only while backing off.
+    let mut retry_in: Option<Duration> = None;
 
     loop {
-        let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(100);
-
-        loop {
-            match tokio::time::timeout_at(deadline, rx.recv()).await {
-                Ok(Some(env)) => {
-                    batch.push(env);
-                    if batch.len() >= batch_size {
-                        break;
-                    }
-                }
-                Ok(None) => return,
-                Err(_)   => break,
-            }
+        // Keep draining the UDP channel while waiting, so a paused
+        // supervisor fills the bounded buffer rather than the mpsc channel.
+        let wait = retry_in.unwrap_or(batch_interval);
+        let deadline = tokio::time::Instant::now() + wait;
+        // While backing off only the delay ends the wait.
+        let limit = retry_in
+            .is_none()
+            .then(|| batch_size.saturating_sub(pending.len()));
+        let batch::Collected { items, closed } = batch::collect(&mut rx, deadline, limit).await;
+
+        let evicted = pending.extend(items);
+        if evicted > 0 {
+            warn!(evicted, buffered = pending.len(), "router buffer full, dropping oldest envelopes");
         }
 
-        if batch.is_empty() {
+        if closed {
+            flush(&mut pending, &mut client, batch_size).await;
+            return;
+        }
+
+        if pending.is_empty() {
             continue;
         }
 
+        let batch = pending.peek(batch_size);
         let req = IngestTelemetryRequest { envelopes: batch.clone() };
         match client.ingest_telemetry(req).await {
             Ok(resp) => {
                 let inner = resp.into_inner();
+                if retry_in.take().is_some() {
+                    info!(buffered = pending.len(), "supervisor available again, resuming");
+                    backoff.reset();
+                }
                 info!(
                     sent    = batch.len(),
                     changes = inner.status_changes.len(),
                     "batch forwarded"
                 );
+                pending.consume(batch.len());
+                retry.reset();
+            }
+            Err(e) if pending.fail_head(batch.len(), e.code(), &mut retry) => {
+                if retry_in.is_none() {
+                    if e.code() == Code::Unavailable {
+                        warn!(reason = %e.message(), buffered = pending.len(), "supervisor unavailable, buffering and backing off");
+                    } else {
+                        warn!(error = %e, buffered = pending.len(), "gRPC IngestTelemetry failed, retrying with backoff");
+                    }
+                }
+                retry_in = Some(backoff.next_delay());
             }
             Err(e) => {
-                error!(error = %e, count = batch.len(), "gRPC IngestTelemetry failed");
+                error!(error = %e, count = batch.len(), "gRPC IngestTelemetry failed, dropping batch");
             }
         }
+    }
+}
 
-        batch.clear();
+/// Send everything in `pending` once, without backoff, on shutdown.
+async fn flush(
+    pending: &mut PendingBuffer<TelemetryEnvelope>,
+    client: &mut SupervisorServiceClient<Channel>,
+    batch_size: usize,
+) {
+    while !pending.is_empty() {
+        let batch = pending.peek(batch_size);
+        let req = IngestTelemetryRequest { envelopes: batch.clone() };
+        match client.ingest_telemetry(req).await {
+            Ok(_) => {
+                info!(sent = batch.len(), remaining = pending.len() - batch.len(), "flushed batch on shutdown");
+                pending.consume(batch.len());
+            }
+            Err(e) => {
+                error!(error = %e, dropped = pending.len(), "flush on shutdown failed");
+                return;
+            }
+        }
+    }
+}
+
+/// Resolves on the first SIGTERM (sent by Kubernetes before SIGKILL) or
+/// Ctrl-C.  The SIGTERM handler is installed when this is called.
+fn shutdown_signal() -> impl std::future::Future<Output = ()> {
+    #[cfg(unix)]
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    async move {
+        #[cfg(unix)]
+        let term = term.recv();
+        #[cfg(not(unix))]
+        let term = std::future::pending::<Option<()>>();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term => {}
+        }
+        info!("shutdown signal received");
     }
 }
@@ -0,0 +1,98 @@
+//! Receiving datagrams of bounded size.
+//!
+//! `recv_from` silently cuts off a datagram longer than the buffer, which
+//! then fails to decode with a misleading JSON error.  [`PacketBuffer`]
+//! keeps one spare byte so such a datagram is recognised as [`Oversize`]
+//! and reported as such.
+
+use thiserror::Error;
+
+/// Default for `ROUTER_MAX_PACKET_SIZE`.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 4096;
+/// Largest payload a UDP datagram over IPv4 can carry.
+pub const MAX_UDP_PAYLOAD: usize = 65_507;
+
+/// A datagram longer than the configured maximum, truncated on receipt.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("datagram larger than {max_size} bytes was truncated; raise ROUTER_MAX_PACKET_SIZE")]
+pub struct Oversize {
+    pub max_size: usize,
+}
+
+/// Receive buffer for datagrams of up to `max_size` bytes.
+pub struct PacketBuffer {
+    buf: Vec<u8>,
+    max_size: usize,
+}
+
+impl PacketBuffer {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            buf: vec![0; max_size + 1],
+            max_size,
+        }
+    }
+
+    /// The buffer to pass to `recv_from`.
+    pub fn recv_buf(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+
+    /// The datagram `recv_from` reported as `len` bytes long, unless it
+    /// did not fit.
+    pub fn datagram(&self, len: usize) -> Result<&[u8], Oversize> {
+        if len > self.max_size {
+            return Err(Oversize { max_size: self.max_size });
+        }
+        Ok(&self.buf[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UdpSocket;
+
+    use super::*;
+    use crate::codec;
+
+    /// A valid telemetry message padded with JSON whitespace to `size` bytes.
+    fn payload_of_size(size: usize) -> Vec<u8> {
+        let mut payload = serde_json::to_vec(&serde_json::json!({
+            "version": 1,
+            "device_uid": "esp32-abc",
+            "plant_id": "550e8400-e29b-41d4-a716-446655440000",
+            "seq": 1,
+            "timestamp_ns": 1_700_000_000_000_000_000_i64,
+            "soil_moisture": 55.0
+        }))
+        .unwrap();
+        assert!(payload.len() <= size);
+        payload.resize(size, b' ');
+        payload
+    }
+
+    async fn receive(max_size: usize, payload: &[u8]) -> Result<Vec<u8>, Oversize> {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender.send_to(payload, receiver.local_addr().unwrap()).await.unwrap();
+
+        let mut buf = PacketBuffer::new(max_size);
+        let (len, _) = receiver.recv_from(buf.recv_buf()).await.unwrap();
+        buf.datagram(len).map(<[u8]>::to_vec)
+    }
+
+    #[tokio::test]
+    async fn datagram_at_the_limit_decodes() {
+        let payload = payload_of_size(512);
+        let received = receive(512, &payload).await.unwrap();
+        assert_eq!(received, payload);
+        assert_eq!(codec::decode(&received).unwrap().seq, 1);
+    }
+
+    #[tokio::test]
+    async fn datagram_over_the_limit_is_reported_as_oversize() {
+        let err = receive(512, &payload_of_size(513)).await.unwrap_err();
+        assert_eq!(err, Oversize { max_size: 512 });
+        assert!(err.to_string().contains("ROUTER_MAX_PACKET_SIZE"), "{err}");
+    }
+}
@@ -0,0 +1,79 @@
+//! Per-write bucket override.
+//!
+//! A `Write` may name a `bucket` (e.g. one per plant type) instead of the
+//! service's default.  Only configured buckets are accepted, the default and
+//! those in `INFLUXDB_ALLOWED_BUCKETS`, so a caller can't create or fill an
+//! arbitrary bucket the token happens to reach.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+/// A write named a bucket that isn't configured.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("bucket {0:?} is not configured; add it to INFLUXDB_ALLOWED_BUCKETS")]
+pub struct UnknownBucket(pub String);
+
+/// The default bucket and the others a write may name.
+#[derive(Debug, Clone)]
+pub struct Buckets {
+    default: String,
+    allowed: HashSet<String>,
+}
+
+impl Buckets {
+    pub fn new(default: &str, allowed: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            default: default.to_string(),
+            allowed: allowed.into_iter().collect(),
+        }
+    }
+
+    /// The bucket a write naming `requested` goes to: the default when
+    /// empty, else `requested` if it is configured.
+    pub fn resolve<'a>(&'a self, requested: &'a str) -> Result<&'a str, UnknownBucket> {
+        let requested = requested.trim();
+        if requested.is_empty() || requested == self.default {
+            Ok(&self.default)
+        } else if self.allowed.contains(requested) {
+            Ok(requested)
+        } else {
+            Err(UnknownBucket(requested.to_string()))
+        }
+    }
+}
+
+/// Parse a comma-separated bucket list, skipping empty entries.
+pub fn parse_list(spec: &str) -> Vec<String> {
+    spec.split(',').map(str::trim).filter(|b| !b.is_empty()).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buckets() -> Buckets {
+        Buckets::new("telemetry", parse_list("plants-tropical, plants-succulent,"))
+    }
+
+    #[test]
+    fn empty_bucket_uses_the_default() {
+        assert_eq!(buckets().resolve("").unwrap(), "telemetry");
+        assert_eq!(buckets().resolve("  ").unwrap(), "telemetry");
+        assert_eq!(buckets().resolve("telemetry").unwrap(), "telemetry");
+    }
+
+    #[test]
+    fn allowlisted_bucket_is_used() {
+        assert_eq!(buckets().resolve("plants-tropical").unwrap(), "plants-tropical");
+        assert_eq!(buckets().resolve("plants-succulent").unwrap(), "plants-succulent");
+    }
+
+    #[test]
+    fn other_bucket_is_rejected() {
+        assert_eq!(buckets().resolve("plants-cactus"), Err(UnknownBucket("plants-cactus".into())));
+        // Without an allowlist only the default is accepted.
+        let default_only = Buckets::new("telemetry", parse_list(""));
+        assert!(default_only.resolve("plants-tropical").is_err());
+    }
+}
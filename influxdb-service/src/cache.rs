@@ -0,0 +1,229 @@
+//! Short-lived cache of `Query` results.
+//!
+//! Dashboards re-issue the same queries on every refresh; within
+//! `INFLUX_QUERY_CACHE_TTL_MS` (default 2000, `0` disables) an identical
+//! Flux query is answered from memory.  Writes, deletes and tag renames
+//! evict the entries of the measurement they touch (raw line-protocol
+//! writes evict everything), and at most `INFLUX_QUERY_CACHE_CAPACITY`
+//! (default 256) results are kept, evicting the least recently used.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use proto::influxdb_service::DataPoint;
+
+pub const DEFAULT_TTL: Duration = Duration::from_millis(2000);
+pub const DEFAULT_CAPACITY: usize = 256;
+
+struct Entry {
+    /// Kept to rule out hash collisions.
+    flux: String,
+    measurements: Vec<String>,
+    points: Vec<DataPoint>,
+    stored_at: Instant,
+    /// Value of [`Inner::clock`] at the last hit, for LRU eviction.
+    used: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<u64, Entry>,
+    clock: u64,
+}
+
+/// Query results keyed by a hash of their Flux string.
+pub struct QueryCache {
+    ttl: Duration,
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl QueryCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Build from `INFLUX_QUERY_CACHE_TTL_MS` and
+    /// `INFLUX_QUERY_CACHE_CAPACITY`; unset or invalid values keep the
+    /// defaults.
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Self::new(
+            var("INFLUX_QUERY_CACHE_TTL_MS").map_or(DEFAULT_TTL, Duration::from_millis),
+            var("INFLUX_QUERY_CACHE_CAPACITY").map_or(DEFAULT_CAPACITY, |n| n as usize),
+        )
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The cached points for `flux`, if stored less than the TTL before `now`.
+    pub fn get(&self, flux: &str, now: Instant) -> Option<Vec<DataPoint>> {
+        if !self.enabled() {
+            return None;
+        }
+        let key = key(flux);
+        let mut inner = self.lock();
+        inner.clock += 1;
+        let clock = inner.clock;
+        match inner.entries.get_mut(&key) {
+            Some(entry) if entry.flux == flux && now.duration_since(entry.stored_at) < self.ttl => {
+                entry.used = clock;
+                Some(entry.points.clone())
+            }
+            Some(_) => {
+                inner.entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remember `points` as the result of `flux` (a query of `measurements`).
+    pub fn insert(&self, flux: &str, measurements: &[String], points: Vec<DataPoint>, now: Instant) {
+        if !self.enabled() {
+            return;
+        }
+        let key = key(flux);
+        let mut inner = self.lock();
+        inner.clock += 1;
+        let used = inner.clock;
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            // Expired entries go first; otherwise the least recently used.
+            let ttl = self.ttl;
+            inner.entries.retain(|_, e| now.duration_since(e.stored_at) < ttl);
+            if inner.entries.len() >= self.capacity {
+                if let Some(&lru) = inner.entries.iter().min_by_key(|(_, e)| e.used).map(|(k, _)| k) {
+                    inner.entries.remove(&lru);
+                }
+            }
+        }
+        inner.entries.insert(
+            key,
+            Entry {
+                flux: flux.to_string(),
+                measurements: measurements.to_vec(),
+                points,
+                stored_at: now,
+                used,
+            },
+        );
+    }
+
+    /// Drop every result involving `measurement`; an empty one drops
+    /// everything.
+    pub fn invalidate(&self, measurement: &str) {
+        let mut inner = self.lock();
+        if measurement.is_empty() {
+            inner.entries.clear();
+        } else {
+            inner.entries.retain(|_, e| !e.measurements.iter().any(|m| m == measurement));
+        }
+    }
+}
+
+fn key(flux: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    flux.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(measurement: &str) -> Vec<String> {
+        vec![measurement.to_string()]
+    }
+
+    fn point(measurement: &str, value: f64) -> DataPoint {
+        DataPoint {
+            measurement: measurement.to_string(),
+            fields: [("v".to_string(), value)].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hit_within_ttl() {
+        let cache = QueryCache::new(Duration::from_secs(2), 8);
+        let now = Instant::now();
+        assert!(cache.get("q1", now).is_none());
+        cache.insert("q1", &ms("m"), vec![point("m", 1.0)], now);
+        let hit = cache.get("q1", now + Duration::from_millis(1999)).unwrap();
+        assert_eq!(hit, vec![point("m", 1.0)]);
+        assert!(cache.get("q2", now).is_none());
+    }
+
+    #[test]
+    fn expires_after_ttl() {
+        let cache = QueryCache::new(Duration::from_secs(2), 8);
+        let now = Instant::now();
+        cache.insert("q1", &ms("m"), vec![point("m", 1.0)], now);
+        assert!(cache.get("q1", now + Duration::from_secs(2)).is_none());
+    }
+
+    #[test]
+    fn write_invalidates_only_its_measurement() {
+        let cache = QueryCache::new(Duration::from_secs(2), 8);
+        let now = Instant::now();
+        cache.insert("q1", &ms("soil"), vec![point("soil", 1.0)], now);
+        cache.insert("q2", &ms("air"), vec![point("air", 2.0)], now);
+        cache.invalidate("soil");
+        assert!(cache.get("q1", now).is_none());
+        assert!(cache.get("q2", now).is_some());
+        cache.invalidate("");
+        assert!(cache.get("q2", now).is_none());
+    }
+
+    #[test]
+    fn multi_measurement_result_invalidated_by_either() {
+        let cache = QueryCache::new(Duration::from_secs(2), 8);
+        let now = Instant::now();
+        let both = vec!["soil".to_string(), "air".to_string()];
+        cache.insert("q1", &both, vec![], now);
+        cache.insert("q2", &ms("air"), vec![], now);
+        cache.invalidate("air");
+        assert!(cache.get("q1", now).is_none());
+        cache.insert("q1", &both, vec![], now);
+        cache.invalidate("soil");
+        assert!(cache.get("q1", now).is_none());
+    }
+
+    #[test]
+    fn evicts_least_recently_used_at_capacity() {
+        let cache = QueryCache::new(Duration::from_secs(2), 2);
+        let now = Instant::now();
+        cache.insert("q1", &ms("m"), vec![], now);
+        cache.insert("q2", &ms("m"), vec![], now);
+        assert!(cache.get("q1", now).is_some());
+        cache.insert("q3", &ms("m"), vec![], now);
+        assert!(cache.get("q2", now).is_none());
+        assert!(cache.get("q1", now).is_some());
+        assert!(cache.get("q3", now).is_some());
+    }
+
+    #[test]
+    fn zero_ttl_disables() {
+        let cache = QueryCache::new(Duration::ZERO, 8);
+        let now = Instant::now();
+        cache.insert("q1", &ms("m"), vec![point("m", 1.0)], now);
+        assert!(cache.get("q1", now).is_none());
+    }
+}
@@ -1,9 +1,15 @@
 //! InfluxDB 2.x client wrapper.
 
-use anyhow::{Context, Result};
-use chrono::NaiveDateTime;
-use influxdb2::Client;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use influxdb2::api::write::TimestampPrecision;
 use influxdb2::models::Query;
+use influxdb2::{Client, RequestError};
+use influxdb2_structmap::value::Value;
+use proto::influxdb_service::ErrorCode;
+use tracing::warn;
+
+use crate::line_protocol::Precision;
 
 /// Thin wrapper around the [`influxdb2::Client`].
 pub struct Db {
@@ -22,14 +28,34 @@ impl Db {
         }
     }
 
+    /// Check that InfluxDB is up and ready to accept requests.
+    pub async fn ping(&self) -> Result<()> {
+        match self.client.ready().await.context("InfluxDB ready check failed")? {
+            true => Ok(()),
+            false => bail!("InfluxDB is not ready"),
+        }
+    }
+
     // ------------------------------------------------------------------ //
     //  Write                                                               //
     // ------------------------------------------------------------------ //
 
-    /// Write line-protocol data directly to InfluxDB.
-    pub async fn write_line_protocol(&self, data: String) -> Result<()> {
+    /// Write line-protocol data directly to the default bucket, its
+    /// timestamps in `precision`.
+    pub async fn write_line_protocol(&self, data: String, precision: Precision) -> Result<()> {
+        self.write_line_protocol_to(&self.bucket, data, precision).await
+    }
+
+    /// Like [`Db::write_line_protocol`], into `bucket`.
+    pub async fn write_line_protocol_to(&self, bucket: &str, data: String, precision: Precision) -> Result<()> {
+        let precision = match precision {
+            Precision::Ns => TimestampPrecision::Nanoseconds,
+            Precision::Us => TimestampPrecision::Microseconds,
+            Precision::Ms => TimestampPrecision::Milliseconds,
+            Precision::S => TimestampPrecision::Seconds,
+        };
         self.client
-            .write_line_protocol(&self.org, &self.bucket, data)
+            .write_line_protocol_with_precision(&self.org, bucket, data, precision)
             .await
             .context("InfluxDB write failed")
     }
@@ -53,42 +79,198 @@ impl Db {
     //  Delete                                                              //
     // ------------------------------------------------------------------ //
 
-    /// Delete points in the given time range / predicate.
+    /// Delete points in `[start, stop)` matching `predicate`, returning an
+    /// estimate of the number of series removed when `count_flux` is given
+    /// (0 otherwise).
     ///
-    /// `start` and `stop` must be RFC3339 strings, e.g. `"2024-01-01T00:00:00Z"`.
+    /// InfluxDB's delete API reports no counts, so `count_flux` (see
+    /// `flux::build_count_flux`) is run over the same scope first.  A failed
+    /// count is logged and reported as 0; it doesn't stop the delete.
+    /// `predicate` must already be escaped (see `flux::build_delete_predicate`).
     pub async fn delete(
         &self,
-        measurement: &str,
-        start: &str,
-        stop: &str,
-        extra_predicate: &str,
-    ) -> Result<()> {
-        let start_dt = parse_naive_dt(start)
-            .with_context(|| format!("Invalid start timestamp: {start}"))?;
-        let stop_dt = parse_naive_dt(stop)
-            .with_context(|| format!("Invalid stop timestamp: {stop}"))?;
-
-        let predicate = if extra_predicate.is_empty() {
-            format!("_measurement=\"{}\"", measurement)
-        } else {
-            format!("_measurement=\"{}\" AND {}", measurement, extra_predicate)
+        start: NaiveDateTime,
+        stop: NaiveDateTime,
+        predicate: &str,
+        count_flux: Option<&str>,
+    ) -> Result<i64> {
+        let estimate = match count_flux {
+            Some(count_flux) => match self.query_raw(count_flux).await {
+                Ok(records) => records
+                    .iter()
+                    .find_map(|r| match r.values.get("_value") {
+                        Some(Value::Long(n)) => Some(*n),
+                        _ => None,
+                    })
+                    .unwrap_or(0),
+                Err(e) => {
+                    warn!(error = %format!("{e:#}"), "delete count query failed; deleting anyway");
+                    0
+                }
+            },
+            None => 0,
         };
 
+        self.delete_range(start, stop, predicate).await?;
+        Ok(estimate)
+    }
+
+    /// Delete points in `[start, stop)` matching `predicate`, without any
+    /// count query.
+    pub async fn delete_range(
+        &self,
+        start: NaiveDateTime,
+        stop: NaiveDateTime,
+        predicate: &str,
+    ) -> Result<()> {
+        // An empty predicate deletes every series in the range.
+        let predicate = (!predicate.is_empty()).then(|| predicate.to_string());
         self.client
-            .delete(&self.bucket, start_dt, stop_dt, Some(predicate))
+            .delete(&self.bucket, start, stop, predicate)
             .await
             .context("InfluxDB delete failed")
     }
 }
 
-/// Parse an RFC3339 / ISO-8601 string into a `NaiveDateTime`.
-fn parse_naive_dt(s: &str) -> Result<NaiveDateTime> {
+/// Classify a [`Db`] error for the `error_code` response field.
+///
+/// Transport failures mean InfluxDB is unreachable; a 4xx answer means the
+/// query we sent was rejected; anything else is a backend error.
+pub fn classify_error(e: &anyhow::Error) -> ErrorCode {
+    match e.downcast_ref::<RequestError>() {
+        Some(RequestError::ReqwestProcessing { .. }) => ErrorCode::Unavailable,
+        Some(RequestError::Http { status, .. }) if status.is_client_error() => {
+            ErrorCode::InvalidArgument
+        }
+        _ => ErrorCode::Backend,
+    }
+}
+
+/// Parse the `start` / `stop` bounds of a delete request.
+///
+/// Relative bounds are resolved against a single `Utc::now()` so that e.g.
+/// `-1h` .. `now()` covers exactly one hour.
+pub fn parse_range(start: &str, stop: &str) -> Result<(NaiveDateTime, NaiveDateTime)> {
+    let now = Utc::now().naive_utc();
+    let start_dt = parse_naive_dt(start, now)
+        .with_context(|| format!("Invalid start timestamp: {start}"))?;
+    let stop_dt = parse_naive_dt(stop, now)
+        .with_context(|| format!("Invalid stop timestamp: {stop}"))?;
+    Ok((start_dt, stop_dt))
+}
+
+/// Parse a datetime bound into a `NaiveDateTime` (UTC).
+///
+/// Accepts RFC3339 / ISO-8601, `now()`, a signed duration relative to `now`
+/// (`-30m`, `+1h`, `-1d12h`) or a bare integer Unix epoch whose unit is
+/// inferred from its magnitude: seconds below 1e11, milliseconds below 1e14,
+/// microseconds below 1e17, nanoseconds above.
+fn parse_naive_dt(s: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
     // Try parsing common formats.
     if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
         return Ok(dt.naive_utc());
     }
     // Fallback: naive format without timezone.
-    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%SZ")
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%SZ")
         .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
-        .context("Failed to parse datetime")
+    {
+        return Ok(dt);
+    }
+    if s == "now()" {
+        return Ok(now);
+    }
+    if let Some((sign, rest)) = s.strip_prefix('-').map(|r| (-1, r)).or_else(|| s.strip_prefix('+').map(|r| (1, r))) {
+        if let Some(d) = parse_duration(rest) {
+            return now
+                .checked_add_signed(d * sign)
+                .with_context(|| format!("datetime {s:?} is out of range"));
+        }
+    }
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        if let Ok(n) = s.parse::<i64>() {
+            return epoch_to_naive(n).with_context(|| format!("epoch {s:?} is out of range"));
+        }
+    }
+    bail!("unrecognised datetime {s:?}: expected RFC3339, now(), a signed duration like -30m, or a Unix epoch")
+}
+
+fn epoch_to_naive(n: i64) -> Option<NaiveDateTime> {
+    let dt = match n {
+        n if n < 100_000_000_000 => DateTime::from_timestamp(n, 0),
+        n if n < 100_000_000_000_000 => DateTime::from_timestamp_millis(n),
+        n if n < 100_000_000_000_000_000 => DateTime::from_timestamp_micros(n),
+        n => Some(DateTime::from_timestamp_nanos(n)),
+    };
+    dt.map(|dt| dt.naive_utc())
+}
+
+/// Parse a duration such as `30m` or `1h30m`.  Calendar units (`mo`, `y`)
+/// have no fixed length and are not accepted.
+fn parse_duration(s: &str) -> Option<Duration> {
+    const UNITS: &[(&str, i64)] = &[
+        ("ns", 1),
+        ("us", 1_000),
+        ("ms", 1_000_000),
+        ("s", 1_000_000_000),
+        ("m", 60 * 1_000_000_000),
+        ("h", 3_600 * 1_000_000_000),
+        ("d", 86_400 * 1_000_000_000),
+        ("w", 7 * 86_400 * 1_000_000_000),
+    ];
+    let mut rest = s;
+    let mut total = Duration::zero();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        let n: i64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let (unit, ns) = UNITS.iter().find(|(u, _)| rest.starts_with(u) && !rest.starts_with("mo"))?;
+        total = total.checked_add(&Duration::nanoseconds(n.checked_mul(*ns)?))?;
+        rest = &rest[unit.len()..];
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn parses_rfc3339() {
+        let dt = parse_naive_dt("2024-01-01T06:00:00+02:00", now()).unwrap();
+        assert_eq!(dt.to_string(), "2024-01-01 04:00:00");
+    }
+
+    #[test]
+    fn parses_relative_durations() {
+        assert_eq!(parse_naive_dt("-30m", now()).unwrap(), now() - Duration::minutes(30));
+        assert_eq!(parse_naive_dt("+1h30m", now()).unwrap(), now() + Duration::minutes(90));
+        assert_eq!(parse_naive_dt("now()", now()).unwrap(), now());
+    }
+
+    #[test]
+    fn parses_epoch_by_magnitude() {
+        let expected = now();
+        assert_eq!(parse_naive_dt("1700000000", now()).unwrap(), expected);
+        assert_eq!(parse_naive_dt("1700000000000", now()).unwrap(), expected);
+        assert_eq!(parse_naive_dt("1700000000000000", now()).unwrap(), expected);
+        assert_eq!(parse_naive_dt("1700000000000000000", now()).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid_input_names_the_value() {
+        for bad in ["yesterday", "-30", "-1mo", "12:00", ""] {
+            let err = parse_naive_dt(bad, now()).unwrap_err();
+            assert!(err.to_string().contains(&format!("{bad:?}")), "{bad}: {err}");
+        }
+    }
 }
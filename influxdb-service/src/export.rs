@@ -0,0 +1,149 @@
+//! JSON-lines export of raw time-series records.
+//!
+//! `Export` runs a range query (optionally filtered by tags and limited)
+//! and streams every raw record as one JSON object:
+//!
+//! ```json
+//! {"measurement":"plant_telemetry","tags":{"plant_id":"p1"},"fields":{"soil_moisture":42.5},"time":"2024-01-01T00:00:01.500Z"}
+//! ```
+//!
+//! Records are un-pivoted, so each carries a single field; values keep their
+//! InfluxDB type (number, boolean or string).  Records without `_time`,
+//! `_field` or `_value` are skipped.
+
+use std::future::Future;
+
+use chrono::{DateTime, SecondsFormat};
+use influxdb2::api::query::FluxRecord;
+use influxdb2_structmap::value::Value;
+use proto::influxdb_service::{field_value::Kind, ExportLine, ExportRequest, QueryRequest};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+use crate::{rows, stream};
+
+pub type ExportStream = ReceiverStream<Result<ExportLine, Status>>;
+
+/// The query an export runs: `req`'s range, tag filters and limit, without
+/// aggregation.
+pub fn query_request(req: &ExportRequest) -> QueryRequest {
+    QueryRequest {
+        measurement: req.measurement.clone(),
+        start: req.start.clone(),
+        stop: req.stop.clone(),
+        tag_filters: req.tag_filters.clone(),
+        limit: req.limit,
+        ..Default::default()
+    }
+}
+
+/// Run `query` in the background and stream its records as JSON lines.
+pub fn spawn_export_stream<F>(query: F, measurement: String) -> ExportStream
+where
+    F: Future<Output = anyhow::Result<Vec<FluxRecord>>> + Send + 'static,
+{
+    stream::spawn_record_stream(query, move |r| {
+        to_json(r, &measurement).map(|json| ExportLine { json: json.to_string() })
+    })
+}
+
+/// One record as an export object.  The measurement is the record's
+/// `_measurement`, or `measurement` for records without one.
+pub fn to_json(record: &FluxRecord, measurement: &str) -> Option<serde_json::Value> {
+    let measurement = match record.values.get("_measurement") {
+        Some(Value::String(m)) => m.as_str(),
+        _ => measurement,
+    };
+    let point = rows::to_written_point(record, measurement)?;
+    let fields: serde_json::Map<String, serde_json::Value> = point
+        .typed_fields
+        .iter()
+        .filter_map(|(k, v)| Some((k.clone(), kind_to_json(v.kind.as_ref()?))))
+        .collect();
+    let time = DateTime::from_timestamp_nanos(point.timestamp_ns)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    Some(serde_json::json!({
+        "measurement": point.measurement,
+        "tags": point.tags,
+        "fields": fields,
+        "time": time,
+    }))
+}
+
+fn kind_to_json(kind: &Kind) -> serde_json::Value {
+    match kind {
+        Kind::DoubleValue(d) => serde_json::json!(d),
+        Kind::IntValue(i) => serde_json::json!(i),
+        Kind::UintValue(u) => serde_json::json!(u),
+        Kind::BoolValue(b) => serde_json::json!(b),
+        Kind::StringValue(s) => serde_json::json!(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    fn record(time: &str, value: Value) -> FluxRecord {
+        FluxRecord {
+            table: 0,
+            values: [
+                ("result", Value::String("_result".into())),
+                ("_time", Value::TimeRFC(chrono::DateTime::parse_from_rfc3339(time).unwrap())),
+                ("_measurement", Value::String("plant_telemetry".into())),
+                ("_field", Value::String("soil_moisture".into())),
+                ("_value", value),
+                ("plant_id", Value::String("p1".into())),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn one_line_per_record_with_expected_keys() {
+        let records = vec![
+            record("2024-01-01T00:00:01.5Z", Value::Double(42.5.into())),
+            record("2024-01-01T00:00:02Z", Value::Long(7)),
+            // No `_field`: not a raw record, skipped.
+            FluxRecord { table: 0, values: Default::default() },
+        ];
+        let fake_query_raw = async move { Ok(records) };
+        let lines: Vec<_> = spawn_export_stream(fake_query_raw, "plant_telemetry".into())
+            .map(|item| item.unwrap().json)
+            .collect()
+            .await;
+
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert!(!line.contains('\n'), "{line}");
+            let object: serde_json::Value = serde_json::from_str(line).unwrap();
+            let mut keys: Vec<_> = object.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            assert_eq!(keys, ["fields", "measurement", "tags", "time"]);
+        }
+        let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(
+            first,
+            serde_json::json!({
+                "measurement": "plant_telemetry",
+                "tags": {"plant_id": "p1"},
+                "fields": {"soil_moisture": 42.5},
+                "time": "2024-01-01T00:00:01.500Z",
+            })
+        );
+        let second: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(second["fields"]["soil_moisture"], 7);
+    }
+
+    #[test]
+    fn query_has_no_aggregation() {
+        let req = ExportRequest { measurement: "m".into(), limit: 10, ..Default::default() };
+        let query = query_request(&req);
+        assert_eq!((query.measurement.as_str(), query.limit), ("m", 10));
+        assert!(query.aggregate_fn.is_empty() && query.after_time_ns.is_none());
+    }
+}
@@ -0,0 +1,673 @@
+//! Flux query construction.
+//!
+//! Everything here is pure string building with no I/O, so the generated
+//! queries can be asserted on directly in tests.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use proto::influxdb_service::QueryRequest;
+use thiserror::Error;
+use tonic::Status;
+
+/// A request value that can't be turned into Flux.  The gRPC handlers
+/// answer it as `INVALID_ARGUMENT`.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("{0}")]
+pub struct FluxError(pub String);
+
+impl From<FluxError> for Status {
+    fn from(e: FluxError) -> Self {
+        Status::invalid_argument(e.0)
+    }
+}
+
+/// Aggregate functions accepted in `QueryRequest.aggregate_fn`.
+pub const AGGREGATE_FNS: &[&str] = &["mean", "max", "min", "sum", "last"];
+
+/// Check the optional aggregation fields before they reach [`build_flux`].
+pub fn validate_aggregate(req: &QueryRequest) -> Result<(), FluxError> {
+    match (req.aggregate_window.is_empty(), req.aggregate_fn.is_empty()) {
+        (true, true) => Ok(()),
+        (true, false) | (false, true) => Err(FluxError(
+            "aggregate_window and aggregate_fn must be set together".into(),
+        )),
+        (false, false) => {
+            if !AGGREGATE_FNS.contains(&req.aggregate_fn.as_str()) {
+                return Err(FluxError(format!(
+                    "unknown aggregate_fn '{}' (expected one of: {})",
+                    req.aggregate_fn,
+                    AGGREGATE_FNS.join(", ")
+                )));
+            }
+            if !is_flux_duration(&req.aggregate_window) {
+                return Err(FluxError(format!(
+                    "invalid aggregate_window '{}' (expected a duration like 5m)",
+                    req.aggregate_window
+                )));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Accept simple Flux duration literals such as `30s`, `5m` or `1h30m`.
+fn is_flux_duration(s: &str) -> bool {
+    const UNITS: &[&str] = &["ns", "us", "ms", "mo", "s", "m", "h", "d", "w", "y"];
+    let mut rest = s;
+    if rest.is_empty() {
+        return false;
+    }
+    while !rest.is_empty() {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        match UNITS.iter().find(|u| rest.starts_with(*u)) {
+            Some(unit) => rest = &rest[unit.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+// ------------------------------------------------------------------ //
+//  Escaping / validation of user input                                //
+// ------------------------------------------------------------------ //
+
+/// Escape `s` for use inside a double-quoted Flux string literal.
+///
+/// Handles `\`, `"`, `${` (string interpolation) and the `\n`/`\r`/`\t`
+/// whitespace escapes.  Any other control character has no Flux escape and is
+/// rejected rather than risk a malformed query.
+pub fn flux_escape(s: &str) -> Result<String, FluxError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c if c.is_control() => {
+                return Err(FluxError(format!("unsupported control character {c:?} in {s:?}")))
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Tag keys are interpolated as record properties, so only plain identifiers
+/// (`[A-Za-z_][A-Za-z0-9_]*`) are accepted.
+pub fn validate_tag_key(key: &str) -> Result<(), FluxError> {
+    let mut chars = key.chars();
+    let valid = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(FluxError(format!("invalid tag key {key:?}")))
+    }
+}
+
+/// `range()` bounds are emitted unquoted, so they must be one of: an RFC3339
+/// timestamp, `now()`, a signed duration such as `-1h`, or integer epoch seconds.
+fn validate_time_bound(name: &str, value: &str) -> Result<(), FluxError> {
+    let valid = value == "now()"
+        || chrono::DateTime::parse_from_rfc3339(value).is_ok()
+        || value
+            .strip_prefix('-')
+            .or_else(|| value.strip_prefix('+'))
+            .is_some_and(is_flux_duration)
+        || (!value.is_empty() && value.chars().all(|c| c.is_ascii_digit()));
+    if valid {
+        Ok(())
+    } else {
+        Err(FluxError(format!("invalid {name} bound {value:?}")))
+    }
+}
+
+// ------------------------------------------------------------------ //
+//  Builders                                                           //
+// ------------------------------------------------------------------ //
+
+/// Build the Flux query for a [`QueryRequest`], validating and escaping every
+/// user-supplied value.
+///
+/// Tag filters are emitted in key order so the same request always yields
+/// the same query text.
+pub fn build_flux(bucket: &str, req: &QueryRequest) -> Result<String, FluxError> {
+    validate_aggregate(req)?;
+    validate_time_bound("start", &req.start)?;
+    validate_time_bound("stop", &req.stop)?;
+
+    if req.measurements.iter().any(|m| m.is_empty()) {
+        return Err(FluxError("measurements must not contain empty names".into()));
+    }
+    let measurements = query_measurements(req);
+    let mut flux = select(bucket, &req.start, &req.stop, &measurements, &req.tag_filters)?;
+
+    if let Some(after) = req.after_time_ns.filter(|&ns| ns != 0) {
+        flux.push_str(&format!(
+            "\n  |> filter(fn: (r) => r._time > time(v: {after}))"
+        ));
+    }
+
+    if !req.aggregate_window.is_empty() && !req.aggregate_fn.is_empty() {
+        flux.push_str(&format!(
+            "\n  |> aggregateWindow(every: {}, fn: {})",
+            req.aggregate_window, req.aggregate_fn
+        ));
+    }
+
+    if req.limit > 0 {
+        flux.push_str(&format!("\n  |> limit(n: {})", req.limit));
+    }
+
+    Ok(flux)
+}
+
+/// Every measurement a [`QueryRequest`] selects: `measurement`, then any
+/// further `measurements` not already listed.
+pub fn query_measurements(req: &QueryRequest) -> Vec<&str> {
+    if req.measurements.is_empty() {
+        return vec![req.measurement.as_str()];
+    }
+    let mut all: Vec<&str> = Vec::with_capacity(req.measurements.len() + 1);
+    let first = Some(req.measurement.as_str()).filter(|m| !m.is_empty());
+    for m in first.into_iter().chain(req.measurements.iter().map(String::as_str)) {
+        if !all.contains(&m) {
+            all.push(m);
+        }
+    }
+    all
+}
+
+/// `from |> range |> filter(...)` shared by the query builders.  `start` and
+/// `stop` are emitted as-is and must already be valid range bounds.  Several
+/// `measurements` are `or`-combined; none selects every measurement.
+fn select(
+    bucket: &str,
+    start: &str,
+    stop: &str,
+    measurements: &[&str],
+    tag_filters: &HashMap<String, String>,
+) -> Result<String, FluxError> {
+    let mut flux = format!(
+        r#"from(bucket: "{}")
+  |> range(start: {}, stop: {})"#,
+        flux_escape(bucket)?,
+        start,
+        stop,
+    );
+    if !measurements.is_empty() {
+        let terms = measurements
+            .iter()
+            .map(|m| Ok(format!(r#"r._measurement == "{}""#, flux_escape(m)?)))
+            .collect::<Result<Vec<_>, FluxError>>()?;
+        flux.push_str(&format!(
+            "\n  |> filter(fn: (r) => {})",
+            terms.join(" or ")
+        ));
+    }
+
+    let mut tag_filters: Vec<(&String, &String)> = tag_filters.iter().collect();
+    tag_filters.sort();
+    for (k, v) in tag_filters {
+        validate_tag_key(k)?;
+        flux.push_str(&format!(
+            r#"
+  |> filter(fn: (r) => r["{}"] == "{}")"#,
+            k,
+            flux_escape(v)?
+        ));
+    }
+    Ok(flux)
+}
+
+/// Count the series a delete over the same range and predicate would touch.
+///
+/// Each input table is one series, so `count()` yields one row per series
+/// and the second `count()` (after `group()`) collapses them into a single
+/// `_value`.
+pub fn build_count_flux(
+    bucket: &str,
+    start: &NaiveDateTime,
+    stop: &NaiveDateTime,
+    measurement: &str,
+    tag_filters: &HashMap<String, String>,
+) -> Result<String, FluxError> {
+    let mut flux = build_range_flux(bucket, start, stop, measurement, tag_filters)?;
+    flux.push_str("\n  |> count()\n  |> group()\n  |> count()");
+    Ok(flux)
+}
+
+/// Select every raw record of `measurement` matching `tag_filters` in
+/// `[start, stop)`.  An empty `measurement` selects every measurement.
+pub fn build_range_flux(
+    bucket: &str,
+    start: &NaiveDateTime,
+    stop: &NaiveDateTime,
+    measurement: &str,
+    tag_filters: &HashMap<String, String>,
+) -> Result<String, FluxError> {
+    const RFC3339: &str = "%Y-%m-%dT%H:%M:%S%.fZ";
+    select(
+        bucket,
+        &start.format(RFC3339).to_string(),
+        &stop.format(RFC3339).to_string(),
+        if measurement.is_empty() { &[] } else { std::slice::from_ref(&measurement) },
+        tag_filters,
+    )
+}
+
+/// Build an InfluxDB delete predicate (`_measurement="m" AND key="value"`).
+///
+/// An empty `measurement` leaves out the `_measurement` term, so the delete
+/// spans every measurement; with no tag filters either the predicate is
+/// empty (see [`check_delete_scope`]).
+///
+/// The delete predicate grammar only understands `\"` and `\\` inside quoted
+/// values, so any control character (including newlines) is rejected.
+pub fn build_delete_predicate(
+    measurement: &str,
+    tag_filters: &HashMap<String, String>,
+) -> Result<String, FluxError> {
+    fn quote(s: &str) -> Result<String, FluxError> {
+        if s.chars().any(char::is_control) {
+            return Err(FluxError(format!(
+                "control characters are not allowed in delete predicates: {s:?}"
+            )));
+        }
+        Ok(format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")))
+    }
+
+    let mut parts = Vec::new();
+    if !measurement.is_empty() {
+        parts.push(format!("_measurement={}", quote(measurement)?));
+    }
+    let mut tags: Vec<(&String, &String)> = tag_filters.iter().collect();
+    tags.sort();
+    for (k, v) in tags {
+        validate_tag_key(k)?;
+        parts.push(format!("{}={}", k, quote(v)?));
+    }
+    Ok(parts.join(" AND "))
+}
+
+/// Refuse to wipe the whole bucket by accident: a delete with an empty
+/// `predicate` whose range runs from the Unix epoch (or earlier) to `now`
+/// (or later) needs `confirm_full_range`.  Bounded ranges, such as a
+/// retention delete of everything older than N days, need no flag.
+pub fn check_delete_scope(
+    predicate: &str,
+    start: &NaiveDateTime,
+    stop: &NaiveDateTime,
+    now: &NaiveDateTime,
+    confirm_full_range: bool,
+) -> Result<(), FluxError> {
+    let wide_open = start.and_utc().timestamp() <= 0 && stop >= now;
+    if predicate.is_empty() && wide_open && !confirm_full_range {
+        return Err(FluxError(
+            "delete without measurement or tag filters over the whole time range \
+             would empty the bucket; set confirm_full_range to proceed"
+                .into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "from(bucket: \"bucket\")\n  |> range(start: -1h, stop: now())\n  |> filter(fn: (r) => r._measurement == \"plant_telemetry\")";
+
+    fn query(aggregate_window: &str, aggregate_fn: &str) -> QueryRequest {
+        QueryRequest {
+            measurement: "plant_telemetry".into(),
+            start: "-1h".into(),
+            stop: "now()".into(),
+            aggregate_window: aggregate_window.into(),
+            aggregate_fn: aggregate_fn.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Name, tag filters, limit, expected Flux.
+    type BuilderCase = (&'static str, &'static [(&'static str, &'static str)], u32, String);
+
+    #[test]
+    fn builder_table() {
+        let cases: &[BuilderCase] = &[
+            ("no filters, no limit", &[], 0, BASE.to_string()),
+            (
+                "limit only",
+                &[],
+                25,
+                format!("{BASE}\n  |> limit(n: 25)"),
+            ),
+            (
+                "single tag",
+                &[("plant_id", "p1")],
+                0,
+                format!("{BASE}\n  |> filter(fn: (r) => r[\"plant_id\"] == \"p1\")"),
+            ),
+            (
+                "tags sorted by key",
+                &[("zone", "b"), ("device_uid", "d1"), ("plant_id", "p1")],
+                5,
+                format!(
+                    "{BASE}\n  |> filter(fn: (r) => r[\"device_uid\"] == \"d1\")\
+                     \n  |> filter(fn: (r) => r[\"plant_id\"] == \"p1\")\
+                     \n  |> filter(fn: (r) => r[\"zone\"] == \"b\")\
+                     \n  |> limit(n: 5)"
+                ),
+            ),
+        ];
+
+        for (name, tags, limit, expected) in cases {
+            let mut req = query("", "");
+            req.limit = *limit;
+            for (k, v) in tags.iter() {
+                req.tag_filters.insert(k.to_string(), v.to_string());
+            }
+            assert_eq!(&build_flux("bucket", &req).unwrap(), expected, "case: {name}");
+        }
+    }
+
+    #[test]
+    fn measurements_are_or_combined() {
+        let cases: &[(&str, &[&str], &str)] = &[
+            ("one", &[], r#"r._measurement == "soil""#),
+            ("one, repeated", &["soil"], r#"r._measurement == "soil""#),
+            ("two", &["temp"], r#"r._measurement == "soil" or r._measurement == "temp""#),
+            (
+                "three",
+                &["temp", "light"],
+                r#"r._measurement == "soil" or r._measurement == "temp" or r._measurement == "light""#,
+            ),
+        ];
+        for (name, more, filter) in cases {
+            let mut req = query("", "");
+            req.measurement = "soil".into();
+            req.measurements = more.iter().map(|m| m.to_string()).collect();
+            assert_eq!(
+                build_flux("bucket", &req).unwrap(),
+                format!(
+                    "from(bucket: \"bucket\")\n  |> range(start: -1h, stop: now())\n  |> filter(fn: (r) => {filter})"
+                ),
+                "case: {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn measurements_alone_and_escaped() {
+        let mut req = query("", "");
+        req.measurement = String::new();
+        req.measurements = vec!["a\"b".into(), "c".into()];
+        let flux = build_flux("bucket", &req).unwrap();
+        assert!(flux.contains(r#"filter(fn: (r) => r._measurement == "a\"b" or r._measurement == "c")"#), "{flux}");
+
+        req.measurements = vec!["c".into(), String::new()];
+        assert_eq!(Status::from(build_flux("bucket", &req).unwrap_err()).code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn output_is_stable_across_calls() {
+        let mut req = query("", "");
+        for i in 0..16 {
+            req.tag_filters.insert(format!("k{i}"), format!("v{i}"));
+        }
+        let first = build_flux("bucket", &req).unwrap();
+        for _ in 0..8 {
+            assert_eq!(build_flux("bucket", &req.clone()).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn no_aggregation_when_fields_empty() {
+        assert!(validate_aggregate(&query("", "")).is_ok());
+        assert_eq!(build_flux("bucket", &query("", "")).unwrap(), BASE);
+    }
+
+    #[test]
+    fn aggregate_window_appended_for_each_fn() {
+        for f in AGGREGATE_FNS {
+            let req = query("5m", f);
+            assert!(validate_aggregate(&req).is_ok(), "{f} should be accepted");
+            let flux = build_flux("bucket", &req).unwrap();
+            assert!(
+                flux.ends_with(&format!("|> aggregateWindow(every: 5m, fn: {f})")),
+                "unexpected flux for {f}: {flux}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_aggregate_fn_rejected() {
+        let err = validate_aggregate(&query("5m", "median")).unwrap_err();
+        assert!(err.0.contains("median"));
+        assert_eq!(Status::from(err).code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn aggregate_fields_must_be_set_together() {
+        assert!(validate_aggregate(&query("5m", "")).is_err());
+        assert!(validate_aggregate(&query("", "mean")).is_err());
+    }
+
+    #[test]
+    fn invalid_aggregate_window_rejected() {
+        assert!(validate_aggregate(&query("5 minutes", "mean")).is_err());
+        assert!(validate_aggregate(&query("m", "mean")).is_err());
+        assert!(validate_aggregate(&query("1h30m", "mean")).is_ok());
+    }
+
+    #[test]
+    fn tag_filter_and_aggregate_combined() {
+        let mut req = query("1h", "max");
+        req.tag_filters.insert("plant_id".into(), "abc".into());
+        req.limit = 10;
+        let flux = build_flux("bucket", &req).unwrap();
+
+        let filter = flux.find(r#"r["plant_id"] == "abc""#).unwrap();
+        let agg = flux.find("|> aggregateWindow(every: 1h, fn: max)").unwrap();
+        let limit = flux.find("|> limit(n: 10)").unwrap();
+        assert!(filter < agg && agg < limit, "unexpected ordering: {flux}");
+    }
+
+    #[test]
+    fn cursor_filter_added_only_when_non_zero() {
+        let mut req = query("", "");
+        assert_eq!(build_flux("bucket", &req).unwrap(), BASE);
+
+        req.after_time_ns = Some(0);
+        assert_eq!(build_flux("bucket", &req).unwrap(), BASE);
+
+        req.after_time_ns = Some(1_700_000_000_000_000_000);
+        assert_eq!(
+            build_flux("bucket", &req).unwrap(),
+            format!("{BASE}\n  |> filter(fn: (r) => r._time > time(v: 1700000000000000000))")
+        );
+    }
+
+    #[test]
+    fn cursor_composes_with_tag_filters_and_limit() {
+        let mut req = query("", "");
+        req.tag_filters.insert("plant_id".into(), "p1".into());
+        req.after_time_ns = Some(42);
+        req.limit = 100;
+        assert_eq!(
+            build_flux("bucket", &req).unwrap(),
+            format!(
+                "{BASE}\n  |> filter(fn: (r) => r[\"plant_id\"] == \"p1\")\
+                 \n  |> filter(fn: (r) => r._time > time(v: 42))\
+                 \n  |> limit(n: 100)"
+            )
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_backslashes_and_newlines() {
+        assert_eq!(flux_escape(r#"a"b"#).unwrap(), r#"a\"b"#);
+        assert_eq!(flux_escape(r"a\b").unwrap(), r"a\\b");
+        assert_eq!(flux_escape("a\nb").unwrap(), r"a\nb");
+        assert_eq!(flux_escape("${x}").unwrap(), r"\${x}");
+        assert_eq!(flux_escape("cost: $5").unwrap(), "cost: $5");
+        assert_eq!(flux_escape("plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn unescapable_control_characters_rejected() {
+        let err = flux_escape("a\u{0}b").unwrap_err();
+        assert_eq!(Status::from(err).code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn hostile_measurement_cannot_break_out_of_string() {
+        let mut req = query("", "");
+        req.measurement = r#"x") |> drop(columns: ["_value"]) //"#.into();
+        let flux = build_flux("bucket", &req).unwrap();
+        assert!(flux.contains(r#"r._measurement == "x\") |> drop(columns: [\"_value\"]) //""#));
+    }
+
+    #[test]
+    fn tag_values_are_escaped() {
+        let mut req = query("", "");
+        req.tag_filters.insert("location".into(), "shelf \"A\"\\\nrow 2".into());
+        let flux = build_flux("bucket", &req).unwrap();
+        assert!(flux.ends_with(r#"r["location"] == "shelf \"A\"\\\nrow 2")"#), "{flux}");
+    }
+
+    #[test]
+    fn invalid_tag_keys_rejected() {
+        for key in ["", "1abc", "plant id", r#"a"]"#, "a\nb"] {
+            let mut req = query("", "");
+            req.tag_filters.insert(key.into(), "v".into());
+            let err = build_flux("bucket", &req).unwrap_err();
+            assert_eq!(Status::from(err).code(), tonic::Code::InvalidArgument, "key {key:?}");
+        }
+    }
+
+    #[test]
+    fn time_bounds_validated() {
+        for (start, ok) in [
+            ("-1h", true),
+            ("2024-01-01T00:00:00Z", true),
+            ("1700000000", true),
+            ("now()", true),
+            ("-1h) |> drop()", false),
+            ("yesterday", false),
+        ] {
+            let mut req = query("", "");
+            req.start = start.into();
+            assert_eq!(build_flux("bucket", &req).is_ok(), ok, "start {start:?}");
+        }
+    }
+
+    #[test]
+    fn delete_predicate_escapes_and_sorts() {
+        let mut tags = HashMap::new();
+        tags.insert("zone".to_string(), r#"north "wing""#.to_string());
+        tags.insert("device_uid".to_string(), r"esp\32".to_string());
+        assert_eq!(
+            build_delete_predicate("plant_telemetry", &tags).unwrap(),
+            r#"_measurement="plant_telemetry" AND device_uid="esp\\32" AND zone="north \"wing\"""#
+        );
+    }
+
+    #[test]
+    fn count_query_matches_delete_scope() {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let stop = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(12, 30, 0).unwrap();
+        let mut tags = HashMap::new();
+        tags.insert("zone".to_string(), r#"north "wing""#.to_string());
+        tags.insert("device_uid".to_string(), "esp32".to_string());
+
+        assert_eq!(
+            build_count_flux("bucket", &start, &stop, "plant_telemetry", &tags).unwrap(),
+            "from(bucket: \"bucket\")\
+             \n  |> range(start: 2024-01-01T00:00:00Z, stop: 2024-01-02T12:30:00Z)\
+             \n  |> filter(fn: (r) => r._measurement == \"plant_telemetry\")\
+             \n  |> filter(fn: (r) => r[\"device_uid\"] == \"esp32\")\
+             \n  |> filter(fn: (r) => r[\"zone\"] == \"north \\\"wing\\\"\")\
+             \n  |> count()\
+             \n  |> group()\
+             \n  |> count()"
+        );
+    }
+
+    #[test]
+    fn delete_predicate_without_measurement() {
+        let mut tags = HashMap::new();
+        tags.insert("device_uid".to_string(), "esp32".to_string());
+        tags.insert("zone".to_string(), "north".to_string());
+        assert_eq!(
+            build_delete_predicate("", &tags).unwrap(),
+            r#"device_uid="esp32" AND zone="north""#
+        );
+        assert_eq!(build_delete_predicate("", &HashMap::new()).unwrap(), "");
+        assert_eq!(
+            build_delete_predicate("m", &HashMap::new()).unwrap(),
+            r#"_measurement="m""#
+        );
+    }
+
+    #[test]
+    fn count_query_without_measurement_spans_all() {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let stop = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(
+            build_count_flux("bucket", &start, &stop, "", &HashMap::new()).unwrap(),
+            "from(bucket: \"bucket\")\
+             \n  |> range(start: 2024-01-01T00:00:00Z, stop: 2024-01-02T00:00:00Z)\
+             \n  |> count()\
+             \n  |> group()\
+             \n  |> count()"
+        );
+    }
+
+    #[test]
+    fn full_range_delete_needs_confirmation() {
+        let epoch = chrono::DateTime::UNIX_EPOCH.naive_utc();
+        let now = chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let month_ago = now - chrono::Duration::days(30);
+
+        let err = check_delete_scope("", &epoch, &now, &now, false).unwrap_err();
+        assert_eq!(Status::from(err).code(), tonic::Code::InvalidArgument);
+        assert!(check_delete_scope("", &epoch, &now, &now, true).is_ok());
+        // Retention-style deletes of everything older than N days are bounded.
+        assert!(check_delete_scope("", &epoch, &month_ago, &now, false).is_ok());
+        // As are recent windows and predicate-scoped deletes.
+        assert!(check_delete_scope("", &month_ago, &now, &now, false).is_ok());
+        assert!(check_delete_scope(r#"zone="north""#, &epoch, &now, &now, false).is_ok());
+    }
+
+    #[test]
+    fn count_query_rejects_bad_tag_keys() {
+        let t = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let mut tags = HashMap::new();
+        tags.insert("bad key".to_string(), "v".to_string());
+        assert!(build_count_flux("bucket", &t, &t, "m", &tags).is_err());
+    }
+
+    #[test]
+    fn delete_predicate_rejects_newlines_and_bad_keys() {
+        let tags = HashMap::new();
+        assert!(build_delete_predicate("a\nb", &tags).is_err());
+
+        let mut tags = HashMap::new();
+        tags.insert("bad key".to_string(), "v".to_string());
+        assert!(build_delete_predicate("m", &tags).is_err());
+    }
+}
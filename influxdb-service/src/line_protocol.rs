@@ -0,0 +1,201 @@
+//! Line-protocol encoding of [`DataPoint`]s.
+//!
+//! `fields` are written as floats, as they always have been; `typed_fields`
+//! carry their own type and get the matching suffix (`i` / `u` for integers,
+//! `t` / `f` for booleans, double quotes for strings).  Timestamps are
+//! rendered in the write's [`Precision`].
+
+use std::collections::BTreeMap;
+
+use proto::influxdb_service::{field_value::Kind, DataPoint};
+use thiserror::Error;
+use tonic::Status;
+
+/// `WriteRequest.precision` named no known precision.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("precision must be one of ns, us, ms, s (got {0:?})")]
+pub struct UnknownPrecision(pub String);
+
+impl From<UnknownPrecision> for Status {
+    fn from(e: UnknownPrecision) -> Self {
+        Status::invalid_argument(e.to_string())
+    }
+}
+
+/// Timestamp precision of a write; InfluxDB must be told the same one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    #[default]
+    Ns,
+    Us,
+    Ms,
+    S,
+}
+
+impl Precision {
+    /// Parse `WriteRequest.precision`; empty means nanoseconds.
+    pub fn parse(s: &str) -> Result<Self, UnknownPrecision> {
+        match s.trim() {
+            "" | "ns" => Ok(Self::Ns),
+            "us" => Ok(Self::Us),
+            "ms" => Ok(Self::Ms),
+            "s" => Ok(Self::S),
+            other => Err(UnknownPrecision(other.to_string())),
+        }
+    }
+
+    /// `timestamp_ns` in this precision, truncated towards the past.
+    pub fn render(self, timestamp_ns: i64) -> i64 {
+        let per_unit = match self {
+            Self::Ns => 1,
+            Self::Us => 1_000,
+            Self::Ms => 1_000_000,
+            Self::S => 1_000_000_000,
+        };
+        timestamp_ns.div_euclid(per_unit)
+    }
+}
+
+/// Build one line of line protocol:
+/// `measurement,tag1=v1 field1=1.5,count=3i <timestamp>`.
+///
+/// Tags and fields are emitted in key order.
+pub fn to_line_protocol(pt: &DataPoint, precision: Precision) -> String {
+    let tags: BTreeMap<&String, &String> = pt.tags.iter().collect();
+    let tags: String = tags
+        .into_iter()
+        .map(|(k, v)| format!(",{}={}", escape_lp(k), escape_lp(v)))
+        .collect();
+
+    let mut fields: BTreeMap<&String, String> =
+        pt.fields.iter().map(|(k, v)| (k, v.to_string())).collect();
+    for (k, v) in &pt.typed_fields {
+        if let Some(kind) = &v.kind {
+            fields.insert(k, format_field(kind));
+        }
+    }
+    let fields: String = fields
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", escape_lp(k), v))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    if pt.timestamp_ns == 0 {
+        format!("{}{} {}", escape_lp(&pt.measurement), tags, fields)
+    } else {
+        format!(
+            "{}{} {} {}",
+            escape_lp(&pt.measurement),
+            tags,
+            fields,
+            precision.render(pt.timestamp_ns)
+        )
+    }
+}
+
+fn format_field(kind: &Kind) -> String {
+    match kind {
+        Kind::DoubleValue(d) => d.to_string(),
+        Kind::IntValue(i) => format!("{i}i"),
+        Kind::UintValue(u) => format!("{u}u"),
+        Kind::BoolValue(b) => (if *b { "t" } else { "f" }).to_string(),
+        Kind::StringValue(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+fn escape_lp(s: &str) -> String {
+    s.replace(' ', "\\ ").replace(',', "\\,").replace('=', "\\=")
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::influxdb_service::FieldValue;
+
+    use super::*;
+
+    fn point(typed: Vec<(&str, Kind)>, timestamp_ns: i64) -> DataPoint {
+        DataPoint {
+            measurement: "pump".into(),
+            tags: [("zone".to_string(), "a".to_string())].into(),
+            fields: Default::default(),
+            timestamp_ns,
+            typed_fields: typed
+                .into_iter()
+                .map(|(k, kind)| (k.to_string(), FieldValue { kind: Some(kind) }))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn each_type_gets_its_suffix() {
+        let cases = [
+            (Kind::DoubleValue(1.5), "v=1.5"),
+            (Kind::IntValue(-3), "v=-3i"),
+            (Kind::UintValue(7), "v=7u"),
+            (Kind::BoolValue(true), "v=t"),
+            (Kind::BoolValue(false), "v=f"),
+            (Kind::StringValue("ok".into()), "v=\"ok\""),
+        ];
+        for (kind, expected) in cases {
+            let line = to_line_protocol(&point(vec![("v", kind.clone())], 0), Precision::Ns);
+            assert_eq!(line, format!("pump,zone=a {expected}"), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn string_fields_escape_quotes_and_backslashes() {
+        let line = to_line_protocol(&point(vec![("msg", Kind::StringValue(r#"say "hi" \o/"#.into()))], 0), Precision::Ns);
+        assert_eq!(line, r#"pump,zone=a msg="say \"hi\" \\o/""#);
+    }
+
+    #[test]
+    fn timestamp_appended_when_set() {
+        let line = to_line_protocol(&point(vec![("count", Kind::IntValue(3))], 1_700_000_000_000_000_000), Precision::Ns);
+        assert_eq!(line, "pump,zone=a count=3i 1700000000000000000");
+    }
+
+    #[test]
+    fn timestamp_rendered_in_each_precision() {
+        let pt = point(vec![("count", Kind::IntValue(3))], 1_700_000_000_123_456_789);
+        let cases = [
+            (Precision::Ns, "1700000000123456789"),
+            (Precision::Us, "1700000000123456"),
+            (Precision::Ms, "1700000000123"),
+            (Precision::S, "1700000000"),
+        ];
+        for (precision, expected) in cases {
+            let line = to_line_protocol(&pt, precision);
+            assert_eq!(line, format!("pump,zone=a count=3i {expected}"), "{precision:?}");
+        }
+    }
+
+    #[test]
+    fn pre_epoch_timestamps_truncate_towards_the_past() {
+        assert_eq!(Precision::S.render(-1), -1);
+        assert_eq!(Precision::Ms.render(-1_000_000), -1);
+    }
+
+    #[test]
+    fn precision_names_are_validated() {
+        assert_eq!(Precision::parse("").unwrap(), Precision::Ns);
+        assert_eq!(Precision::parse("ms").unwrap(), Precision::Ms);
+        let err = Precision::parse("minutes").unwrap_err();
+        assert_eq!(err, UnknownPrecision("minutes".into()));
+        assert_eq!(Status::from(err).code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn double_fields_unchanged_and_merged_in_key_order() {
+        let mut pt = point(vec![("on", Kind::BoolValue(true))], 0);
+        pt.fields.insert("level".into(), 42.5);
+        pt.fields.insert("z".into(), 1.0);
+        assert_eq!(to_line_protocol(&pt, Precision::Ns), "pump,zone=a level=42.5,on=t,z=1");
+    }
+
+    #[test]
+    fn typed_value_wins_on_key_clash() {
+        let mut pt = point(vec![("count", Kind::IntValue(3))], 0);
+        pt.fields.insert("count".into(), 3.0);
+        assert_eq!(to_line_protocol(&pt, Precision::Ns), "pump,zone=a count=3i");
+    }
+}
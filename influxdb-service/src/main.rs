@@ -11,66 +11,59 @@
 //! | `INFLUXDB_TOKEN`               | `BWS_INFLUXDB_TOKEN_ID`            |
 //! | `INFLUXDB_ORG`                 | `BWS_INFLUXDB_ORG_ID`              |
 //! | `INFLUXDB_BUCKET`              | `BWS_INFLUXDB_BUCKET_ID`           |
+//!
+//! # Buckets
+//! Points go to `INFLUXDB_BUCKET` unless a `Write` names another of the
+//! buckets in `INFLUXDB_ALLOWED_BUCKETS`; see [`buckets`].
+//!
+//! # Query cache
+//! `Query` results are cached for `INFLUX_QUERY_CACHE_TTL_MS`; see [`cache`].
+//!
+//! # Export
+//! `Export` streams a range query's raw records as JSON objects; see
+//! [`export`].
+//!
+//! # Health
+//! Serves `grpc.health.v1.Health`, reporting SERVING if InfluxDB was ready
+//! at startup and NOT_SERVING otherwise.
+//!
+//! # Reflection
+//! Serves gRPC reflection for `grpcurl` unless `GRPC_REFLECTION=false`; see
+//! [`proto::reflection`].
 
+mod buckets;
+mod cache;
 mod db;
-mod secrets;
+mod export;
+mod flux;
+mod line_protocol;
+mod raw_write;
+mod retag;
+mod rows;
+mod stream;
 
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Result;
 use proto::influxdb_service::{
     influx_db_service_server::{InfluxDbService, InfluxDbServiceServer},
-    DataPoint, DeleteRequest, DeleteResponse, QueryRequest, QueryResponse, WriteRequest,
-    WriteResponse,
+    DeleteRequest, DeleteResponse, ErrorCode, ExportRequest, QueryRequest, QueryResponse,
+    QueryTypedResponse, RenameTagRequest, RenameTagResponse, WriteLineProtocolRequest,
+    WriteRequest, WriteResponse,
 };
+use tokio_stream::StreamExt;
 use tonic::{transport::Server, Request, Response, Status};
 use tracing::{error, info};
 
-// ------------------------------------------------------------------ //
-//  Helper: build line-protocol from a DataPoint                      //
-// ------------------------------------------------------------------ //
-
-fn to_line_protocol(pt: &DataPoint) -> String {
-    // measurement,tag1=v1,tag2=v2 field1=1.0,field2=2.0 <timestamp>
-    let tags: String = pt
-        .tags
-        .iter()
-        .map(|(k, v)| format!(",{}={}", escape_lp(k), escape_lp(v)))
-        .collect();
-
-    let fields: String = pt
-        .fields
-        .iter()
-        .enumerate()
-        .map(|(i, (k, v))| {
-            let sep = if i == 0 { "" } else { "," };
-            format!("{}{}={}", sep, escape_lp(k), v)
-        })
-        .collect();
-
-    if pt.timestamp_ns == 0 {
-        format!("{}{} {}", escape_lp(&pt.measurement), tags, fields)
-    } else {
-        format!(
-            "{}{} {} {}",
-            escape_lp(&pt.measurement),
-            tags,
-            fields,
-            pt.timestamp_ns
-        )
-    }
-}
-
-fn escape_lp(s: &str) -> String {
-    s.replace(' ', "\\ ").replace(',', "\\,").replace('=', "\\=")
-}
-
 // ------------------------------------------------------------------ //
 //  gRPC service implementation                                        //
 // ------------------------------------------------------------------ //
 
 pub struct InfluxDbServiceImpl {
     db: Arc<db::Db>,
+    buckets: buckets::Buckets,
+    cache: cache::QueryCache,
 }
 
 #[tonic::async_trait]
@@ -80,14 +73,22 @@ impl InfluxDbService for InfluxDbServiceImpl {
         request: Request<WriteRequest>,
     ) -> Result<Response<WriteResponse>, Status> {
         let req = request.into_inner();
+        let precision = line_protocol::Precision::parse(&req.precision)?;
+        let bucket = self
+            .buckets
+            .resolve(&req.bucket)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        for point in &req.points {
+            self.cache.invalidate(&point.measurement);
+        }
         let line_proto: String = req
             .points
             .iter()
-            .map(to_line_protocol)
+            .map(|pt| line_protocol::to_line_protocol(pt, precision))
             .collect::<Vec<_>>()
             .join("\n");
 
-        match self.db.write_line_protocol(line_proto).await {
+        match self.db.write_line_protocol_to(bucket, line_proto, precision).await {
             Ok(()) => Ok(Response::new(WriteResponse {
                 success: true,
                 error: String::new(),
@@ -102,84 +103,110 @@ impl InfluxDbService for InfluxDbServiceImpl {
         }
     }
 
+    async fn write_line_protocol(
+        &self,
+        request: Request<WriteLineProtocolRequest>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        let payload = request.into_inner().payload;
+        // Measurements are not parsed out of raw payloads.
+        self.cache.invalidate("");
+        Ok(Response::new(raw_write::write(&*self.db, payload).await?))
+    }
+
     async fn query(
         &self,
         request: Request<QueryRequest>,
     ) -> Result<Response<QueryResponse>, Status> {
         let req = request.into_inner();
+        let limit = req.limit;
 
-        // Build a basic Flux query.
-        let mut flux = format!(
-            r#"from(bucket: "{}")
-  |> range(start: {}, stop: {})
-  |> filter(fn: (r) => r._measurement == "{}")"#,
-            self.db.bucket, req.start, req.stop, req.measurement
-        );
-
-        for (k, v) in &req.tag_filters {
-            flux.push_str(&format!(
-                r#"
-  |> filter(fn: (r) => r["{}"] == "{}")"#,
-                k, v
-            ));
+        let flux = flux::build_flux(&self.db.bucket, &req)?;
+        if let Some(points) = self.cache.get(&flux, Instant::now()) {
+            return Ok(Response::new(query_response(limit, points)));
         }
-
-        if req.limit > 0 {
-            flux.push_str(&format!("\n  |> limit(n: {})", req.limit));
+        let measurements: Vec<String> =
+            flux::query_measurements(&req).into_iter().map(str::to_string).collect();
+
+        // Same query as QueryStream, collected into one response.
+        let mut point_stream = self.query_stream(Request::new(req)).await?.into_inner();
+        let mut points = Vec::new();
+        while let Some(item) = point_stream.next().await {
+            match item {
+                Ok(point) => points.push(point),
+                Err(status) => {
+                    return Ok(Response::new(QueryResponse {
+                        points: vec![],
+                        success: false,
+                        error: status.message().to_string(),
+                        error_code: stream::error_code(&status) as i32,
+                        next_cursor_ns: 0,
+                    }));
+                }
+            }
         }
 
+        self.cache.insert(&flux, &measurements, points.clone(), Instant::now());
+        Ok(Response::new(query_response(limit, points)))
+    }
+
+    type QueryStreamStream = stream::PointStream;
+
+    async fn query_stream(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<Self::QueryStreamStream>, Status> {
+        let req = request.into_inner();
+
+        let flux = flux::build_flux(&self.db.bucket, &req)?;
+
+        let db = Arc::clone(&self.db);
+        let query = async move { db.query_raw(&flux).await };
+        Ok(Response::new(stream::spawn_point_stream(query, req.measurement)))
+    }
+
+    type ExportStream = export::ExportStream;
+
+    async fn export(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<Self::ExportStream>, Status> {
+        let req = request.into_inner();
+
+        let flux = flux::build_flux(&self.db.bucket, &export::query_request(&req))?;
+
+        let db = Arc::clone(&self.db);
+        let query = async move { db.query_raw(&flux).await };
+        Ok(Response::new(export::spawn_export_stream(query, req.measurement)))
+    }
+
+    async fn query_typed(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<QueryTypedResponse>, Status> {
+        let req = request.into_inner();
+
+        let flux = flux::build_flux(&self.db.bucket, &req)?;
+
         match self.db.query_raw(&flux).await {
             Ok(records) => {
-                // Convert FluxRecord values into DataPoints.
-                let points: Vec<DataPoint> = records
-                    .into_iter()
-                    .map(|r| {
-                        let mut fields: std::collections::HashMap<String, f64> =
-                            std::collections::HashMap::new();
-                        let mut tags: std::collections::HashMap<String, String> =
-                            std::collections::HashMap::new();
-                        for (k, v) in &r.values {
-                            use influxdb2_structmap::value::Value;
-                            match v {
-                                Value::Double(d) => {
-                                    fields.insert(k.clone(), (*d).into());
-                                }
-                                Value::Long(l) => {
-                                    fields.insert(k.clone(), *l as f64);
-                                }
-                                Value::UnsignedLong(u) => {
-                                    fields.insert(k.clone(), *u as f64);
-                                }
-                                Value::Bool(b) => {
-                                    fields.insert(k.clone(), if *b { 1.0 } else { 0.0 });
-                                }
-                                Value::String(s) => {
-                                    tags.insert(k.clone(), s.clone());
-                                }
-                                _ => {}
-                            }
-                        }
-                        DataPoint {
-                            measurement: req.measurement.clone(),
-                            tags,
-                            fields,
-                            timestamp_ns: 0,
-                        }
-                    })
-                    .collect();
-
-                Ok(Response::new(QueryResponse {
-                    points,
+                let flux_rows: Vec<_> = records.iter().map(rows::to_flux_row).collect();
+                let times: Vec<i64> = flux_rows.iter().map(|r| r.timestamp_ns).collect();
+                Ok(Response::new(QueryTypedResponse {
+                    next_cursor_ns: rows::next_cursor_ns(req.limit, &times),
+                    rows: flux_rows,
                     success: true,
                     error: String::new(),
+                    error_code: ErrorCode::Unspecified as i32,
                 }))
             }
             Err(e) => {
-                error!(error = %e, "query failed");
-                Ok(Response::new(QueryResponse {
-                    points: vec![],
+                error!(error = %e, "typed query failed");
+                Ok(Response::new(QueryTypedResponse {
+                    rows: vec![],
                     success: false,
                     error: e.to_string(),
+                    error_code: db::classify_error(&e) as i32,
+                    next_cursor_ns: 0,
                 }))
             }
         }
@@ -190,34 +217,88 @@ impl InfluxDbService for InfluxDbServiceImpl {
         request: Request<DeleteRequest>,
     ) -> Result<Response<DeleteResponse>, Status> {
         let req = request.into_inner();
-
-        let predicate: String = req
-            .tag_filters
-            .iter()
-            .map(|(k, v)| format!("{}=\"{}\"", k, v))
-            .collect::<Vec<_>>()
-            .join(" AND ");
-
-        match self
-            .db
-            .delete(&req.measurement, &req.start, &req.stop, &predicate)
-            .await
-        {
-            Ok(()) => Ok(Response::new(DeleteResponse {
+        self.cache.invalidate(&req.measurement);
+
+        let predicate = flux::build_delete_predicate(&req.measurement, &req.tag_filters)?;
+        // Sampled before `parse_range` so a `stop` of "now()" counts as
+        // reaching the present.
+        let now = chrono::Utc::now().naive_utc();
+        let (start, stop) = db::parse_range(&req.start, &req.stop)
+            .map_err(|e| Status::invalid_argument(format!("{e:#}")))?;
+        flux::check_delete_scope(&predicate, &start, &stop, &now, req.confirm_full_range)?;
+        let count_flux = if req.with_count {
+            Some(flux::build_count_flux(
+                &self.db.bucket,
+                &start,
+                &stop,
+                &req.measurement,
+                &req.tag_filters,
+            )?)
+        } else {
+            None
+        };
+
+        match self.db.delete(start, stop, &predicate, count_flux.as_deref()).await {
+            Ok(deleted_estimate) => Ok(Response::new(DeleteResponse {
                 success: true,
                 error: String::new(),
+                deleted_estimate,
             })),
             Err(e) => {
                 error!(error = %e, "delete failed");
                 Ok(Response::new(DeleteResponse {
                     success: false,
                     error: e.to_string(),
+                    deleted_estimate: 0,
+                }))
+            }
+        }
+    }
+
+    async fn rename_tag(
+        &self,
+        request: Request<RenameTagRequest>,
+    ) -> Result<Response<RenameTagResponse>, Status> {
+        let plan = retag::RenamePlan::from_request(&request.into_inner())?;
+        info!(
+            measurement = %plan.measurement,
+            tag_key = %plan.tag_key,
+            old_value = %plan.old_value,
+            new_value = %plan.new_value,
+            "tag rename started"
+        );
+        self.cache.invalidate(&plan.measurement);
+
+        match retag::run(&*self.db, &self.db.bucket, &plan).await {
+            Ok(rewritten) => Ok(Response::new(RenameTagResponse {
+                success: true,
+                error: String::new(),
+                rewritten,
+            })),
+            Err(e) => {
+                error!(error = %e, "tag rename failed");
+                Ok(Response::new(RenameTagResponse {
+                    success: false,
+                    error: e.to_string(),
+                    rewritten: 0,
                 }))
             }
         }
     }
 }
 
+/// A successful `Query` answer carrying `points`.
+fn query_response(limit: u32, points: Vec<proto::influxdb_service::DataPoint>) -> QueryResponse {
+    let times: Vec<i64> = points.iter().map(|p| p.timestamp_ns).filter(|&t| t != 0).collect();
+    QueryResponse {
+        next_cursor_ns: rows::next_cursor_ns(limit, &times),
+        points,
+        success: true,
+        error: String::new(),
+        error_code: ErrorCode::Unspecified as i32,
+    }
+}
+
 // ------------------------------------------------------------------ //
 //  Entry point                                                        //
 // ------------------------------------------------------------------ //
@@ -262,19 +343,60 @@ async fn main() -> Result<()> {
     .await?;
 
     let db = db::Db::connect(&influx_url, &influx_token, &influx_org, &influx_bucket);
+    let buckets = buckets::Buckets::new(
+        &influx_bucket,
+        buckets::parse_list(&std::env::var("INFLUXDB_ALLOWED_BUCKETS").unwrap_or_default()),
+    );
 
     let addr = std::env::var("INFLUXDB_SERVICE_ADDR")
         .unwrap_or_else(|_| "[::1]:50052".to_string())
         .parse()?;
 
-    let svc = InfluxDbServiceImpl { db: Arc::new(db) };
+    let (mut reporter, health_service) = tonic_health::server::health_reporter();
+    match db.ping().await {
+        Ok(()) => reporter.set_serving::<InfluxDbServiceServer<InfluxDbServiceImpl>>().await,
+        Err(e) => {
+            error!(error = %e, "InfluxDB not reachable; reporting NOT_SERVING");
+            reporter.set_service_status("", tonic_health::ServingStatus::NotServing).await;
+            reporter.set_not_serving::<InfluxDbServiceServer<InfluxDbServiceImpl>>().await;
+        }
+    }
+
+    let svc = InfluxDbServiceImpl {
+        db: Arc::new(db),
+        buckets,
+        cache: cache::QueryCache::from_env(),
+    };
 
     info!(%addr, "influxdb-service listening");
 
     Server::builder()
+        .layer(request_id::RequestIdLayer)
+        .add_service(health_service)
         .add_service(InfluxDbServiceServer::new(svc))
-        .serve(addr)
+        .add_optional_service(proto::reflection::from_env()?)
+        .serve_with_shutdown(addr, shutdown_signal())
         .await?;
 
+    info!("influxdb-service stopped");
     Ok(())
 }
+
+/// Resolves on the first SIGTERM (sent by Kubernetes before SIGKILL) or
+/// Ctrl-C.  The SIGTERM handler is installed when this is called.
+fn shutdown_signal() -> impl std::future::Future<Output = ()> {
+    #[cfg(unix)]
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    async move {
+        #[cfg(unix)]
+        let term = term.recv();
+        #[cfg(not(unix))]
+        let term = std::future::pending::<Option<()>>();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term => {}
+        }
+        info!("shutdown signal received");
+    }
+}
@@ -0,0 +1,105 @@
+//! `WriteLineProtocol`: caller-supplied line protocol, written unchanged.
+
+use proto::influxdb_service::WriteResponse;
+use thiserror::Error;
+use tonic::Status;
+use tracing::error;
+
+use crate::retag::SeriesStore;
+
+/// The payload had nothing to write.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("payload is empty")]
+pub struct EmptyPayload;
+
+impl From<EmptyPayload> for Status {
+    fn from(e: EmptyPayload) -> Self {
+        Status::invalid_argument(e.to_string())
+    }
+}
+
+/// Reject payloads with nothing to write.
+///
+/// `payload` arrives as a proto `string`, so invalid UTF-8 has already been
+/// refused while decoding the request.
+pub fn validate(payload: &str) -> Result<(), EmptyPayload> {
+    if payload.trim().is_empty() {
+        return Err(EmptyPayload);
+    }
+    Ok(())
+}
+
+/// Forward `payload` to `store` as-is.  Storage failures are reported in
+/// the response, like `Write`.
+pub async fn write(store: &dyn SeriesStore, payload: String) -> Result<WriteResponse, Status> {
+    validate(&payload)?;
+    Ok(match store.write_line_protocol(payload).await {
+        Ok(()) => WriteResponse {
+            success: true,
+            error: String::new(),
+        },
+        Err(e) => {
+            error!(error = %e, "line protocol write failed");
+            WriteResponse {
+                success: false,
+                error: e.to_string(),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use chrono::NaiveDateTime;
+    use influxdb2::api::query::FluxRecord;
+
+    use super::*;
+
+    /// Records every write.
+    #[derive(Default)]
+    struct MockStore {
+        writes: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SeriesStore for MockStore {
+        async fn query_raw(&self, _: &str) -> Result<Vec<FluxRecord>> {
+            unreachable!("raw writes never query")
+        }
+
+        async fn write_line_protocol(&self, data: String) -> Result<()> {
+            self.writes.lock().unwrap().push(data);
+            Ok(())
+        }
+
+        async fn delete_range(&self, _: NaiveDateTime, _: NaiveDateTime, _: &str) -> Result<()> {
+            unreachable!("raw writes never delete")
+        }
+    }
+
+    #[tokio::test]
+    async fn payload_is_passed_through_verbatim() {
+        let store = MockStore::default();
+        let payload = "pump,zone=a\\ b on=t,count=3i 1700000000000000000\n\
+                       pump,zone=c level=42.5"
+            .to_string();
+
+        let resp = write(&store, payload.clone()).await.unwrap();
+        assert!(resp.success, "{}", resp.error);
+        assert_eq!(*store.writes.lock().unwrap(), vec![payload]);
+    }
+
+    #[tokio::test]
+    async fn empty_payload_is_invalid_argument() {
+        let store = MockStore::default();
+        for payload in ["", " \n\t"] {
+            let err = write(&store, payload.to_string()).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument, "{payload:?}");
+        }
+        assert!(store.writes.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! Renaming a tag value on historical points.
+//!
+//! InfluxDB cannot update tags in place, so each batch window of the range
+//! is read, written back with the new tag value, and only then deleted under
+//! the old value.  A failure mid-way leaves earlier windows renamed and the
+//! current one duplicated (old and new), never lost.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDateTime};
+use influxdb2::api::query::FluxRecord;
+use proto::influxdb_service::RenameTagRequest;
+use thiserror::Error;
+use tonic::Status;
+use tracing::info;
+
+use crate::{db, flux, line_protocol, rows};
+
+/// Batch window used when the request leaves `batch_window_s` at 0.
+pub const DEFAULT_BATCH_WINDOW_S: i64 = 3_600;
+/// Longest range a single rename may cover.
+pub const MAX_RANGE_DAYS: i64 = 366;
+/// A window returning more records than this aborts the rename; retry with a
+/// smaller `batch_window_s`.
+pub const MAX_BATCH_RECORDS: usize = 50_000;
+
+/// The storage operations a rename (and a raw write) needs; implemented by
+/// [`db::Db`].
+#[async_trait]
+pub trait SeriesStore: Send + Sync {
+    async fn query_raw(&self, flux: &str) -> Result<Vec<FluxRecord>>;
+    async fn write_line_protocol(&self, data: String) -> Result<()>;
+    async fn delete_range(&self, start: NaiveDateTime, stop: NaiveDateTime, predicate: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl SeriesStore for db::Db {
+    async fn query_raw(&self, flux: &str) -> Result<Vec<FluxRecord>> {
+        db::Db::query_raw(self, flux).await
+    }
+
+    async fn write_line_protocol(&self, data: String) -> Result<()> {
+        db::Db::write_line_protocol(self, data, line_protocol::Precision::Ns).await
+    }
+
+    async fn delete_range(&self, start: NaiveDateTime, stop: NaiveDateTime, predicate: &str) -> Result<()> {
+        db::Db::delete_range(self, start, stop, predicate).await
+    }
+}
+
+/// A validated [`RenameTagRequest`].
+#[derive(Debug, Clone)]
+pub struct RenamePlan {
+    pub measurement: String,
+    pub tag_key: String,
+    pub old_value: String,
+    pub new_value: String,
+    /// Further tag filters narrowing which series are renamed.
+    pub tag_filters: HashMap<String, String>,
+    pub start: NaiveDateTime,
+    pub stop: NaiveDateTime,
+    pub batch_window: Duration,
+}
+
+/// Why a [`RenameTagRequest`] was refused.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PlanError {
+    #[error("tag rename rewrites and deletes data; set confirm to proceed")]
+    NotConfirmed,
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl From<flux::FluxError> for PlanError {
+    fn from(e: flux::FluxError) -> Self {
+        PlanError::Invalid(e.0)
+    }
+}
+
+impl From<PlanError> for Status {
+    fn from(e: PlanError) -> Self {
+        match e {
+            PlanError::NotConfirmed => Status::failed_precondition(e.to_string()),
+            PlanError::Invalid(msg) => Status::invalid_argument(msg),
+        }
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> PlanError {
+    PlanError::Invalid(msg.into())
+}
+
+impl RenamePlan {
+    pub fn from_request(req: &RenameTagRequest) -> Result<Self, PlanError> {
+        if !req.confirm {
+            return Err(PlanError::NotConfirmed);
+        }
+        for (name, value) in [
+            ("measurement", &req.measurement),
+            ("tag_key", &req.tag_key),
+            ("old_value", &req.old_value),
+            ("new_value", &req.new_value),
+        ] {
+            if value.is_empty() {
+                return Err(invalid(format!("{name} is required")));
+            }
+        }
+        flux::validate_tag_key(&req.tag_key)?;
+        if req.tag_key.starts_with('_') {
+            return Err(invalid(format!("{:?} is a system column, not a tag", req.tag_key)));
+        }
+        if req.old_value == req.new_value {
+            return Err(invalid("old_value and new_value are the same"));
+        }
+        if req.tag_filters.contains_key(&req.tag_key) {
+            return Err(invalid("tag_filters must not include the renamed tag_key"));
+        }
+
+        let (start, stop) = db::parse_range(&req.start, &req.stop)
+            .map_err(|e| invalid(format!("{e:#}")))?;
+        if start >= stop {
+            return Err(invalid("start must be before stop"));
+        }
+        if stop - start > Duration::days(MAX_RANGE_DAYS) {
+            return Err(invalid(format!(
+                "range exceeds {MAX_RANGE_DAYS} days; split the rename"
+            )));
+        }
+        let batch_window = match req.batch_window_s {
+            0 => Duration::seconds(DEFAULT_BATCH_WINDOW_S),
+            s if s > 0 => Duration::seconds(s),
+            _ => return Err(invalid("batch_window_s must be positive")),
+        };
+
+        Ok(Self {
+            measurement: req.measurement.clone(),
+            tag_key: req.tag_key.clone(),
+            old_value: req.old_value.clone(),
+            new_value: req.new_value.clone(),
+            tag_filters: req.tag_filters.clone(),
+            start,
+            stop,
+            batch_window,
+        })
+    }
+
+    /// Tag filters selecting the series still carrying the old value.
+    fn old_series(&self) -> HashMap<String, String> {
+        let mut tags = self.tag_filters.clone();
+        tags.insert(self.tag_key.clone(), self.old_value.clone());
+        tags
+    }
+}
+
+/// Run `plan` window by window, returning the number of records rewritten.
+pub async fn run(store: &dyn SeriesStore, bucket: &str, plan: &RenamePlan) -> Result<i64> {
+    let old_series = plan.old_series();
+    let predicate = flux::build_delete_predicate(&plan.measurement, &old_series)?;
+    let mut rewritten = 0i64;
+
+    let mut window_start = plan.start;
+    while window_start < plan.stop {
+        let window_stop = (window_start + plan.batch_window).min(plan.stop);
+        let query = flux::build_range_flux(bucket, &window_start, &window_stop, &plan.measurement, &old_series)?;
+
+        let records = store.query_raw(&query).await?;
+        if records.len() > MAX_BATCH_RECORDS {
+            bail!(
+                "window starting {window_start} has {} records (max {MAX_BATCH_RECORDS}); use a smaller batch_window_s",
+                records.len()
+            );
+        }
+        if !records.is_empty() {
+            let lines: Vec<String> = records
+                .iter()
+                .filter_map(|r| rows::to_written_point(r, &plan.measurement))
+                .map(|mut pt| {
+                    pt.tags.insert(plan.tag_key.clone(), plan.new_value.clone());
+                    line_protocol::to_line_protocol(&pt, line_protocol::Precision::Ns)
+                })
+                .collect();
+            if lines.len() != records.len() {
+                bail!("window starting {window_start} has records that cannot be rewritten; nothing deleted");
+            }
+
+            store.write_line_protocol(lines.join("\n")).await?;
+            store.delete_range(window_start, window_stop, &predicate).await?;
+            rewritten += records.len() as i64;
+            info!(%window_start, records = records.len(), "tag rename window done");
+        }
+        window_start = window_stop;
+    }
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use influxdb2_structmap::value::Value;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Query(String),
+        Write(String),
+        Delete(NaiveDateTime, NaiveDateTime, String),
+    }
+
+    /// Records every call; each query returns the next canned batch.
+    #[derive(Default)]
+    struct MockStore {
+        batches: Mutex<Vec<Vec<FluxRecord>>>,
+        calls: Mutex<Vec<Call>>,
+    }
+
+    #[async_trait]
+    impl SeriesStore for MockStore {
+        async fn query_raw(&self, flux: &str) -> Result<Vec<FluxRecord>> {
+            self.calls.lock().unwrap().push(Call::Query(flux.to_string()));
+            let mut batches = self.batches.lock().unwrap();
+            Ok(if batches.is_empty() { vec![] } else { batches.remove(0) })
+        }
+
+        async fn write_line_protocol(&self, data: String) -> Result<()> {
+            self.calls.lock().unwrap().push(Call::Write(data));
+            Ok(())
+        }
+
+        async fn delete_range(&self, start: NaiveDateTime, stop: NaiveDateTime, predicate: &str) -> Result<()> {
+            self.calls.lock().unwrap().push(Call::Delete(start, stop, predicate.to_string()));
+            Ok(())
+        }
+    }
+
+    fn record(time: &str, value: f64) -> FluxRecord {
+        FluxRecord {
+            table: 0,
+            values: [
+                ("_time", Value::TimeRFC(chrono::DateTime::parse_from_rfc3339(time).unwrap())),
+                ("_measurement", Value::String("plant_telemetry".into())),
+                ("_field", Value::String("soil_moisture".into())),
+                ("_value", Value::Double(value.into())),
+                ("location", Value::String("bench".into())),
+                ("plant_id", Value::String("p1".into())),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        }
+    }
+
+    fn request() -> RenameTagRequest {
+        RenameTagRequest {
+            measurement: "plant_telemetry".into(),
+            start: "2024-01-01T00:00:00Z".into(),
+            stop: "2024-01-01T02:00:00Z".into(),
+            tag_key: "location".into(),
+            old_value: "bench".into(),
+            new_value: "greenhouse".into(),
+            confirm: true,
+            ..Default::default()
+        }
+    }
+
+    fn dt(s: &str) -> NaiveDateTime {
+        chrono::DateTime::parse_from_rfc3339(s).unwrap().naive_utc()
+    }
+
+    #[tokio::test]
+    async fn reads_rewrites_then_deletes_each_window() {
+        let store = MockStore::default();
+        *store.batches.lock().unwrap() = vec![
+            vec![record("2024-01-01T00:10:00Z", 41.0), record("2024-01-01T00:20:00Z", 42.0)],
+            vec![],
+        ];
+        let plan = RenamePlan::from_request(&request()).unwrap();
+
+        let rewritten = run(&store, "plants", &plan).await.unwrap();
+        assert_eq!(rewritten, 2);
+
+        let calls = store.calls.into_inner().unwrap();
+        assert_eq!(calls.len(), 4, "{calls:?}");
+        let Call::Query(q) = &calls[0] else { panic!("{calls:?}") };
+        assert!(q.contains(r#"range(start: 2024-01-01T00:00:00Z, stop: 2024-01-01T01:00:00Z)"#), "{q}");
+        assert!(q.contains(r#"r["location"] == "bench""#), "{q}");
+        assert_eq!(
+            calls[1],
+            Call::Write(
+                "plant_telemetry,location=greenhouse,plant_id=p1 soil_moisture=41 1704067800000000000\n\
+                 plant_telemetry,location=greenhouse,plant_id=p1 soil_moisture=42 1704068400000000000"
+                    .into()
+            )
+        );
+        assert_eq!(
+            calls[2],
+            Call::Delete(
+                dt("2024-01-01T00:00:00Z"),
+                dt("2024-01-01T01:00:00Z"),
+                r#"_measurement="plant_telemetry" AND location="bench""#.into()
+            )
+        );
+        // Empty second window: queried, nothing written or deleted.
+        assert!(matches!(&calls[3], Call::Query(q) if q.contains("stop: 2024-01-01T02:00:00Z")));
+    }
+
+    #[tokio::test]
+    async fn oversized_window_aborts_before_writing() {
+        let store = MockStore::default();
+        *store.batches.lock().unwrap() = vec![(0..=MAX_BATCH_RECORDS)
+            .map(|_| record("2024-01-01T00:10:00Z", 1.0))
+            .collect()];
+        let plan = RenamePlan::from_request(&request()).unwrap();
+
+        assert!(run(&store, "plants", &plan).await.is_err());
+        let calls = store.calls.into_inner().unwrap();
+        assert!(calls.iter().all(|c| matches!(c, Call::Query(_))), "{calls:?}");
+    }
+
+    #[test]
+    fn guards_reject_unsafe_requests() {
+        let cases = [
+            RenameTagRequest { confirm: false, ..request() },
+            RenameTagRequest { new_value: "bench".into(), ..request() },
+            RenameTagRequest { tag_key: "_measurement".into(), ..request() },
+            RenameTagRequest { tag_key: String::new(), ..request() },
+            RenameTagRequest { stop: "2023-12-31T00:00:00Z".into(), ..request() },
+            RenameTagRequest { stop: "2026-01-01T00:00:00Z".into(), ..request() },
+            RenameTagRequest { batch_window_s: -5, ..request() },
+            RenameTagRequest {
+                tag_filters: [("location".to_string(), "x".to_string())].into_iter().collect(),
+                ..request()
+            },
+        ];
+        for req in cases {
+            assert!(RenamePlan::from_request(&req).is_err(), "{req:?}");
+        }
+        let unconfirmed = RenameTagRequest { confirm: false, ..request() };
+        let err = RenamePlan::from_request(&unconfirmed).unwrap_err();
+        assert_eq!(Status::from(err).code(), tonic::Code::FailedPrecondition);
+    }
+}
@@ -0,0 +1,242 @@
+//! Conversion of raw Flux records into response messages.
+//!
+//! [`to_data_point`] is the legacy `query` shape, which folds every column
+//! into an f64 field or a string tag.  [`to_flux_row`] keeps each column
+//! under its original name with its InfluxDB type.  Both lift `_time` into
+//! `timestamp_ns`.
+
+use std::collections::HashMap;
+
+use influxdb2::api::query::FluxRecord;
+use influxdb2_structmap::value::Value;
+use proto::influxdb_service::{field_value::Kind, DataPoint, FieldValue, FluxRow};
+
+/// Column holding the record timestamp.
+const TIME_COLUMN: &str = "_time";
+
+/// Annotation columns Flux adds besides the `_`-prefixed ones.
+const NON_TAG_COLUMNS: &[&str] = &["result", "table"];
+
+/// `_time` of a record in Unix nanoseconds, if it has one.
+pub fn record_time_ns(record: &FluxRecord) -> Option<i64> {
+    match record.values.get(TIME_COLUMN) {
+        Some(Value::TimeRFC(t)) => t.timestamp_nanos_opt(),
+        _ => None,
+    }
+}
+
+/// Cursor for the next page: the latest timestamp when the page is full,
+/// 0 when fewer than `limit` rows came back (or no limit was set).
+pub fn next_cursor_ns(limit: u32, timestamps: &[i64]) -> i64 {
+    if limit == 0 || timestamps.len() < limit as usize {
+        return 0;
+    }
+    timestamps.iter().copied().max().unwrap_or(0)
+}
+
+/// Convert one Flux record into a legacy [`DataPoint`]: numbers and booleans
+/// become f64 fields, strings become tags.  The point's measurement is the
+/// record's `_measurement`, or `measurement` for records without one.
+pub fn to_data_point(record: &FluxRecord, measurement: &str) -> DataPoint {
+    let mut fields: HashMap<String, f64> = HashMap::new();
+    let mut tags: HashMap<String, String> = HashMap::new();
+    for (k, v) in &record.values {
+        match v {
+            Value::Double(d) => {
+                fields.insert(k.clone(), (*d).into());
+            }
+            Value::Long(l) => {
+                fields.insert(k.clone(), *l as f64);
+            }
+            Value::UnsignedLong(u) => {
+                fields.insert(k.clone(), *u as f64);
+            }
+            Value::Bool(b) => {
+                fields.insert(k.clone(), if *b { 1.0 } else { 0.0 });
+            }
+            Value::String(s) => {
+                tags.insert(k.clone(), s.clone());
+            }
+            _ => {}
+        }
+    }
+    let measurement = match record.values.get("_measurement") {
+        Some(Value::String(m)) => m.clone(),
+        _ => measurement.to_string(),
+    };
+    DataPoint {
+        measurement,
+        tags,
+        fields,
+        timestamp_ns: record_time_ns(record).unwrap_or(0),
+        typed_fields: Default::default(),
+    }
+}
+
+/// Rebuild the point a raw (un-pivoted) Flux record was written from: its
+/// tag columns plus `_field` = `_value` at `_time`, keeping the value's type.
+///
+/// Returns `None` for records without `_time`, `_field` or a `_value`.
+pub fn to_written_point(record: &FluxRecord, measurement: &str) -> Option<DataPoint> {
+    let timestamp_ns = record_time_ns(record)?;
+    let Some(Value::String(field)) = record.values.get("_field") else {
+        return None;
+    };
+    let value = to_kind(record.values.get("_value")?)?;
+
+    let tags = record
+        .values
+        .iter()
+        .filter(|(k, _)| !k.starts_with('_') && !NON_TAG_COLUMNS.contains(&k.as_str()))
+        .filter_map(|(k, v)| match v {
+            Value::String(s) => Some((k.clone(), s.clone())),
+            _ => None,
+        })
+        .collect();
+
+    Some(DataPoint {
+        measurement: measurement.to_string(),
+        tags,
+        fields: Default::default(),
+        timestamp_ns,
+        typed_fields: [(field.clone(), FieldValue { kind: Some(value) })].into_iter().collect(),
+    })
+}
+
+/// Convert one Flux record into a [`FluxRow`].
+pub fn to_flux_row(record: &FluxRecord) -> FluxRow {
+    let mut row = FluxRow::default();
+    for (k, v) in &record.values {
+        // influxdb2-structmap decodes every `dateTime:RFC3339*` column
+        // into `TimeRFC`.
+        if let (TIME_COLUMN, Value::TimeRFC(t)) = (k.as_str(), v) {
+            row.timestamp_ns = t.timestamp_nanos_opt().unwrap_or(0);
+            continue;
+        }
+        if let Some(kind) = to_kind(v) {
+            row.values.insert(k.clone(), FieldValue { kind: Some(kind) });
+        }
+    }
+    row
+}
+
+fn to_kind(v: &Value) -> Option<Kind> {
+    match v {
+        Value::Double(d) => Some(Kind::DoubleValue((*d).into())),
+        Value::Long(l) => Some(Kind::IntValue(*l)),
+        Value::UnsignedLong(u) => Some(Kind::UintValue(*u)),
+        Value::Bool(b) => Some(Kind::BoolValue(*b)),
+        Value::String(s) => Some(Kind::StringValue(s.clone())),
+        Value::TimeRFC(t) => Some(Kind::StringValue(t.to_rfc3339())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(values: Vec<(&str, Value)>) -> FluxRecord {
+        FluxRecord {
+            table: 0,
+            values: values.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+        }
+    }
+
+    fn time(s: &str) -> Value {
+        Value::TimeRFC(chrono::DateTime::parse_from_rfc3339(s).unwrap())
+    }
+
+    fn kind<'a>(row: &'a FluxRow, key: &str) -> &'a Kind {
+        row.values[key].kind.as_ref().unwrap()
+    }
+
+    #[test]
+    fn lifts_time_and_keeps_column_types() {
+        let row = to_flux_row(&record(vec![
+            ("_time", time("2024-01-01T00:00:01.5Z")),
+            ("_start", time("2024-01-01T00:00:00Z")),
+            ("_measurement", Value::String("plant_telemetry".into())),
+            ("_field", Value::String("soil_moisture".into())),
+            ("_value", Value::Double(42.5.into())),
+            ("plant_id", Value::String("p1".into())),
+        ]));
+
+        assert_eq!(row.timestamp_ns, 1_704_067_201_500_000_000);
+        assert!(!row.values.contains_key("_time"));
+        assert_eq!(kind(&row, "_field"), &Kind::StringValue("soil_moisture".into()));
+        assert_eq!(kind(&row, "_value"), &Kind::DoubleValue(42.5));
+        assert_eq!(kind(&row, "plant_id"), &Kind::StringValue("p1".into()));
+        assert_eq!(
+            kind(&row, "_start"),
+            &Kind::StringValue("2024-01-01T00:00:00+00:00".into())
+        );
+    }
+
+    #[test]
+    fn data_point_takes_the_record_measurement() {
+        let soil = to_data_point(&record(vec![("_measurement", Value::String("soil".into()))]), "air");
+        assert_eq!(soil.measurement, "soil");
+        let unnamed = to_data_point(&record(vec![("_value", Value::Double(1.0.into()))]), "air");
+        assert_eq!(unnamed.measurement, "air");
+    }
+
+    #[test]
+    fn integer_unsigned_and_bool_values_are_not_coerced() {
+        let rows: Vec<FluxRow> = [
+            record(vec![("_field", Value::String("count".into())), ("_value", Value::Long(-7))]),
+            record(vec![("_field", Value::String("seq".into())), ("_value", Value::UnsignedLong(u64::MAX))]),
+            record(vec![("_field", Value::String("pump_on".into())), ("_value", Value::Bool(true))]),
+            record(vec![("_field", Value::String("state".into())), ("_value", Value::String("ok".into()))]),
+        ]
+        .iter()
+        .map(to_flux_row)
+        .collect();
+
+        assert_eq!(kind(&rows[0], "_value"), &Kind::IntValue(-7));
+        assert_eq!(kind(&rows[1], "_value"), &Kind::UintValue(u64::MAX));
+        assert_eq!(kind(&rows[2], "_value"), &Kind::BoolValue(true));
+        assert_eq!(kind(&rows[3], "_value"), &Kind::StringValue("ok".into()));
+    }
+
+    #[test]
+    fn written_point_keeps_tags_field_type_and_time() {
+        let pt = to_written_point(
+            &record(vec![
+                ("_time", time("1970-01-01T00:00:02Z")),
+                ("_start", time("1970-01-01T00:00:00Z")),
+                ("_measurement", Value::String("plant_telemetry".into())),
+                ("_field", Value::String("seq".into())),
+                ("_value", Value::Long(3)),
+                ("result", Value::String("_result".into())),
+                ("location", Value::String("bench".into())),
+            ]),
+            "plant_telemetry",
+        )
+        .unwrap();
+
+        assert_eq!(pt.timestamp_ns, 2_000_000_000);
+        assert_eq!(pt.tags, [("location".to_string(), "bench".to_string())].into_iter().collect());
+        assert_eq!(pt.typed_fields["seq"].kind, Some(Kind::IntValue(3)));
+        assert!(to_written_point(&record(vec![("_value", Value::Long(3))]), "m").is_none());
+    }
+
+    #[test]
+    fn next_cursor_only_for_full_pages() {
+        assert_eq!(next_cursor_ns(0, &[5, 9]), 0);
+        assert_eq!(next_cursor_ns(3, &[5, 9]), 0);
+        assert_eq!(next_cursor_ns(2, &[9, 5]), 9);
+        assert_eq!(record_time_ns(&record(vec![("_time", time("1970-01-01T00:00:01Z"))])), Some(1_000_000_000));
+    }
+
+    #[test]
+    fn missing_time_leaves_timestamp_zero_and_unknown_is_dropped() {
+        let row = to_flux_row(&record(vec![
+            ("_value", Value::Double(1.0.into())),
+            ("weird", Value::Unknown),
+        ]));
+        assert_eq!(row.timestamp_ns, 0);
+        assert!(!row.values.contains_key("weird"));
+        assert_eq!(row.values.len(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+//! Server-streaming delivery of query results.
+//!
+//! Converted records (points for `QueryStream`, JSON lines for `Export`) are
+//! pushed through a bounded channel of [`STREAM_BUFFER`] items.
+//! When the client reads slower than records are converted, `send` waits for
+//! room, so at most that many converted points are queued at once; if the
+//! client goes away the producer stops at its next send.
+//!
+//! The InfluxDB client still returns each Flux response as one parsed batch,
+//! so the raw records are held in memory until they have been streamed out.
+
+use std::future::Future;
+
+use influxdb2::api::query::FluxRecord;
+use proto::influxdb_service::{DataPoint, ErrorCode};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Code, Status};
+use tracing::error;
+
+use crate::{db, rows};
+
+/// Converted records buffered ahead of the client.
+pub const STREAM_BUFFER: usize = 128;
+
+pub type PointStream = ReceiverStream<Result<DataPoint, Status>>;
+
+/// Run `query` in the background and stream its records as [`DataPoint`]s.
+///
+/// A failed query yields a single `Err` and ends the stream.
+pub fn spawn_point_stream<F>(query: F, measurement: String) -> PointStream
+where
+    F: Future<Output = anyhow::Result<Vec<FluxRecord>>> + Send + 'static,
+{
+    spawn_record_stream(query, move |r| Some(rows::to_data_point(r, &measurement)))
+}
+
+/// Run `query` in the background and stream each record `convert` maps to
+/// `Some`; records it maps to `None` are skipped.
+///
+/// A failed query yields a single `Err` and ends the stream.
+pub fn spawn_record_stream<T, F, C>(query: F, mut convert: C) -> ReceiverStream<Result<T, Status>>
+where
+    T: Send + 'static,
+    F: Future<Output = anyhow::Result<Vec<FluxRecord>>> + Send + 'static,
+    C: FnMut(&FluxRecord) -> Option<T> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        match query.await {
+            Ok(records) => {
+                for item in records.iter().filter_map(&mut convert) {
+                    if tx.send(Ok(item)).await.is_err() {
+                        return; // client disconnected
+                    }
+                }
+            }
+            Err(e) => {
+                error!(error = %e, "streaming query failed");
+                let status = Status::new(status_code(db::classify_error(&e)), e.to_string());
+                let _ = tx.send(Err(status)).await;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+fn status_code(code: ErrorCode) -> Code {
+    match code {
+        ErrorCode::InvalidArgument => Code::InvalidArgument,
+        ErrorCode::Unavailable => Code::Unavailable,
+        ErrorCode::Backend | ErrorCode::Unspecified => Code::Internal,
+    }
+}
+
+/// Inverse of the mapping used for stream errors, for the unary `query`
+/// response's `error_code`.
+pub fn error_code(status: &Status) -> ErrorCode {
+    match status.code() {
+        Code::InvalidArgument => ErrorCode::InvalidArgument,
+        Code::Unavailable => ErrorCode::Unavailable,
+        _ => ErrorCode::Backend,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use influxdb2_structmap::value::Value;
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    fn records(n: usize) -> Vec<FluxRecord> {
+        (0..n)
+            .map(|i| FluxRecord {
+                table: 0,
+                values: [
+                    ("_field".to_string(), Value::String("soil_moisture".into())),
+                    ("_value".to_string(), Value::Double((i as f64).into())),
+                ]
+                .into_iter()
+                .collect(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn yields_every_record_then_ends() {
+        let n = STREAM_BUFFER * 3 + 1;
+        let fake_query_raw = async move { Ok(records(n)) };
+        let mut stream = spawn_point_stream(fake_query_raw, "plant_telemetry".into());
+
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            let point = item.unwrap();
+            assert_eq!(point.measurement, "plant_telemetry");
+            assert_eq!(point.fields["_value"], count as f64);
+            count += 1;
+        }
+        assert_eq!(count, n);
+    }
+
+    #[tokio::test]
+    async fn query_error_ends_stream_with_status() {
+        let fake_query_raw = async { Err(anyhow::anyhow!("boom")) };
+        let items: Vec<_> = spawn_point_stream(fake_query_raw, "m".into()).collect().await;
+
+        assert_eq!(items.len(), 1);
+        let status = items[0].as_ref().unwrap_err();
+        assert_eq!(status.code(), Code::Internal);
+        assert!(status.message().contains("boom"));
+        assert_eq!(error_code(status), ErrorCode::Backend);
+    }
+}
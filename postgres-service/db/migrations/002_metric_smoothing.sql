@@ -0,0 +1,12 @@
+-- Optional per-metric smoothing applied before threshold evaluation.
+--   smoothing = 'sma' : simple moving average over the last `smoothing_window` readings
+--   smoothing = 'ema' : exponential moving average with factor `smoothing_alpha` (0, 1]
+ALTER TABLE plant_type_metric_threshold
+    ADD COLUMN IF NOT EXISTS smoothing        TEXT CHECK (smoothing IN ('sma', 'ema')),
+    ADD COLUMN IF NOT EXISTS smoothing_window INTEGER,
+    ADD COLUMN IF NOT EXISTS smoothing_alpha  DOUBLE PRECISION;
+
+-- Recent raw readings per metric, e.g. {"soil_moisture": [41.0, 40.5]},
+-- oldest first.  Only kept for metrics that have smoothing configured.
+ALTER TABLE plant_current_state
+    ADD COLUMN IF NOT EXISTS metric_history JSONB;
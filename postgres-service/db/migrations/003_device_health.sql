@@ -0,0 +1,5 @@
+-- Latest self-reported device health, updated on every accepted reading
+-- that carries the value.
+ALTER TABLE device
+    ADD COLUMN IF NOT EXISTS battery_v DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS rssi_dbm  INTEGER;
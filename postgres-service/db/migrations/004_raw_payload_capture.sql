@@ -0,0 +1,11 @@
+-- Original UDP datagrams forwarded by the router when ROUTER_CAPTURE_RAW is
+-- set.  Forensic only: rows older than a day are pruned on insert.
+CREATE TABLE IF NOT EXISTS raw_payload_capture (
+    ingest_id   TEXT        PRIMARY KEY,
+    device_uid  TEXT        NOT NULL,
+    captured_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    payload     BYTEA       NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_raw_payload_capture_captured_at
+    ON raw_payload_capture(captured_at);
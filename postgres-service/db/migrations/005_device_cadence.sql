@@ -0,0 +1,6 @@
+-- Expected reporting interval per device.  Readings arriving outside
+-- expected_interval_s ± interval_tolerance_pct raise a WARN ticker event.
+ALTER TABLE device
+    ADD COLUMN IF NOT EXISTS expected_interval_s    INTEGER CHECK (expected_interval_s > 0),
+    ADD COLUMN IF NOT EXISTS interval_tolerance_pct INTEGER NOT NULL DEFAULT 20
+        CHECK (interval_tolerance_pct BETWEEN 0 AND 100);
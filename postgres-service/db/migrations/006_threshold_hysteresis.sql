@@ -0,0 +1,5 @@
+-- Optional per-metric hysteresis margin, in the metric's own unit.  Once a
+-- metric is WARN/CRITICAL it only downgrades after the reading is back
+-- inside the band by at least this much.  NULL disables hysteresis.
+ALTER TABLE plant_type_metric_threshold
+    ADD COLUMN IF NOT EXISTS hysteresis DOUBLE PRECISION CHECK (hysteresis >= 0);
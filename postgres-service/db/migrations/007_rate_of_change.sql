@@ -0,0 +1,10 @@
+-- Optional per-metric rate-of-change limit: a reading that moved more than
+-- max_rate_per_min (in the metric's unit per minute) since the previous one
+-- is WARN, even when its value is in band.
+ALTER TABLE plant_type_metric_threshold
+    ADD COLUMN IF NOT EXISTS max_rate_per_min DOUBLE PRECISION CHECK (max_rate_per_min > 0);
+
+-- Last raw reading per metric with its envelope timestamp, e.g.
+-- {"soil_moisture": {"value": 41.0, "ts_ns": 1700000000000000000}}.
+ALTER TABLE plant_current_state
+    ADD COLUMN IF NOT EXISTS metric_last_reading JSONB;
@@ -0,0 +1,14 @@
+-- Append-only log of overall severity transitions per plant, for trend
+-- charts.  prev_severity is NULL for a plant's first reading;
+-- metric_severity is the per-metric snapshot at the transition.
+CREATE TABLE IF NOT EXISTS plant_severity_history (
+    id              BIGSERIAL   PRIMARY KEY,
+    plant_id        UUID        NOT NULL REFERENCES plant(id),
+    prev_severity   TEXT,
+    new_severity    TEXT        NOT NULL,
+    occurred_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    metric_severity JSONB
+);
+
+CREATE INDEX IF NOT EXISTS idx_plant_severity_history_plant_occurred_at
+    ON plant_severity_history(plant_id, occurred_at);
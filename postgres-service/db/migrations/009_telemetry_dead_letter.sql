@@ -0,0 +1,22 @@
+-- Envelopes the supervisor could not ingest (bad plant_id, unknown or
+-- inactive plant), kept field for field so ReplayDeadLetter can re-run
+-- them once the cause is fixed.  Rows are deleted when a replay succeeds.
+CREATE TABLE IF NOT EXISTS telemetry_dead_letter (
+    ingest_id           TEXT             PRIMARY KEY,
+    device_uid          TEXT             NOT NULL,
+    plant_id            TEXT             NOT NULL,   -- as sent; may not be a UUID
+    timestamp_ns        BIGINT           NOT NULL,
+    seq                 BIGINT           NOT NULL,
+    soil_moisture       DOUBLE PRECISION,
+    ambient_light_lux   DOUBLE PRECISION,
+    ambient_humidity_rh DOUBLE PRECISION,
+    ambient_temp_c      DOUBLE PRECISION,
+    battery_v           DOUBLE PRECISION,
+    rssi_dbm            INTEGER,
+    raw_payload_b64     TEXT             NOT NULL DEFAULT '',
+    error               TEXT             NOT NULL,
+    received_at         TIMESTAMPTZ      NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_telemetry_dead_letter_received_at
+    ON telemetry_dead_letter(received_at);
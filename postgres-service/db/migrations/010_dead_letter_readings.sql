@@ -0,0 +1,4 @@
+-- Readings beyond the four typed metrics (TelemetryEnvelope.readings), so a
+-- replayed envelope still carries them.
+ALTER TABLE telemetry_dead_letter
+    ADD COLUMN IF NOT EXISTS readings JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,19 @@
+-- Optional per-plant thresholds.  A row here replaces the plant type's
+-- plant_type_metric_threshold row for the same metric, e.g. for a plant of
+-- a common species kept in a shaded spot; metrics without a row keep the
+-- type's thresholds.
+CREATE TABLE IF NOT EXISTS plant_metric_threshold (
+    plant_id         UUID    NOT NULL REFERENCES plant(id) ON DELETE CASCADE,
+    metric           TEXT    NOT NULL,
+    warn_min         DOUBLE PRECISION,
+    warn_max         DOUBLE PRECISION,
+    crit_min         DOUBLE PRECISION,
+    crit_max         DOUBLE PRECISION,
+    unit             TEXT,
+    smoothing        TEXT CHECK (smoothing IN ('sma', 'ema')),
+    smoothing_window INTEGER,
+    smoothing_alpha  DOUBLE PRECISION,
+    hysteresis       DOUBLE PRECISION CHECK (hysteresis >= 0),
+    max_rate_per_min DOUBLE PRECISION CHECK (max_rate_per_min > 0),
+    PRIMARY KEY (plant_id, metric)
+);
@@ -0,0 +1,5 @@
+-- The supervisor only dedups against ledger rows inside its window
+-- (SUPERVISOR_DEDUP_WINDOW_HOURS) and periodically deletes older ones by
+-- reading time.
+CREATE INDEX IF NOT EXISTS idx_telemetry_ingest_ledger_timestamp_ns
+    ON telemetry_ingest_ledger(timestamp_ns);
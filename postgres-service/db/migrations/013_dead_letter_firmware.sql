@@ -0,0 +1,4 @@
+-- Firmware version reported in the envelope (TelemetryEnvelope.firmware_version),
+-- so a replayed envelope still updates the device row.
+ALTER TABLE telemetry_dead_letter
+    ADD COLUMN IF NOT EXISTS firmware_version TEXT;
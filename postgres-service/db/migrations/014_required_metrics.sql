@@ -0,0 +1,8 @@
+-- A required metric missing from an envelope (e.g. a dead soil-moisture
+-- sensor) escalates the plant, by default to CRITICAL
+-- (SUPERVISOR_MISSING_METRIC_SEVERITY).  Optional metrics are just absent.
+ALTER TABLE plant_type_metric_threshold
+    ADD COLUMN IF NOT EXISTS required BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE plant_metric_threshold
+    ADD COLUMN IF NOT EXISTS required BOOLEAN NOT NULL DEFAULT FALSE;
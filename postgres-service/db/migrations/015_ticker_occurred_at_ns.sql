@@ -0,0 +1,5 @@
+-- Device timestamp (Unix ns) of the reading that raised a ticker event,
+-- from the envelope's timestamp_ns.  occurred_at stays the insert time;
+-- events not raised by a reading (e.g. the stale sweep) leave this NULL.
+ALTER TABLE ticker_event
+    ADD COLUMN IF NOT EXISTS occurred_at_ns BIGINT;
@@ -0,0 +1,5 @@
+-- Consecutive non-NORMAL readings per metric, for escalating a long run of
+-- WARN readings to CRITICAL (SUPERVISOR_WARN_ESCALATION_COUNT), e.g.
+-- {"soil_moisture": 4}.  A NORMAL reading resets a metric to 0.
+ALTER TABLE plant_current_state
+    ADD COLUMN IF NOT EXISTS metric_warn_streak JSONB;
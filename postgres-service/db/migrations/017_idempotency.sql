@@ -0,0 +1,16 @@
+-- Responses kept for the coordinator's `Idempotency-Key` header, per
+-- endpoint ("POST /data").  A row with a NULL status is claimed by a request
+-- that has not finished yet.  Expired rows are pruned by the coordinator.
+CREATE TABLE IF NOT EXISTS idempotency (
+    key          TEXT        NOT NULL,
+    route        TEXT        NOT NULL,
+    status       SMALLINT,
+    content_type TEXT,
+    body         BYTEA,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at   TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (key, route)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_expires_at
+    ON idempotency (expires_at);
@@ -0,0 +1,8 @@
+-- Generic JSONB store for tables without a typed schema.
+CREATE TABLE IF NOT EXISTS records (
+    id         UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    table_name TEXT NOT NULL,
+    payload    JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,5 @@
+-- Optimistic concurrency and soft delete.  IF NOT EXISTS because databases
+-- created before versioned migrations already have these columns.
+ALTER TABLE records
+    ADD COLUMN IF NOT EXISTS version    BIGINT NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
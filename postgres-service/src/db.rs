@@ -1,16 +1,184 @@
 //! PostgreSQL database layer.
 //!
-//! Uses [`sqlx`] with a generic `records` table for demonstration.
-//! Real deployments should replace the raw-JSON approach with typed migrations
-//! and domain-specific tables.
+//! Uses [`sqlx`].  Tables declared in the [`Registry`] are real typed tables
+//! (see [`crate::schema`]); any other table name falls back to the generic
+//! `records` table, which stores the payload as JSONB.
+
+use std::collections::HashMap;
 
 use anyhow::{Context, Result};
-use sqlx::{postgres::PgPoolOptions, PgPool, Row};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgArguments, PgPoolOptions, PgRow};
+use sqlx::query::Query;
+use sqlx::{PgPool, Postgres, Row};
+use thiserror::Error;
+use tonic::Status;
 use uuid::Uuid;
 
+use crate::schema::{self, Registry, SqlValue, TableSpec};
+
+/// Most records accepted by one [`Db::batch_create`] call.  Keeps every
+/// multi-row INSERT well under Postgres' 65535 bind-parameter limit.
+pub const MAX_BATCH_CREATE: usize = 1_000;
+
+/// Versioned migrations of the generic `records` table, embedded from
+/// `migrations/` at build time.  Applied versions are recorded in
+/// `_sqlx_migrations`.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Why a [`Db::batch_create`] wrote nothing.
+#[derive(Debug, Error)]
+pub enum BatchCreateError {
+    /// The record at `index` was rejected before anything was written.
+    #[error("record {index}: {error:#}")]
+    Record { index: usize, error: anyhow::Error },
+    /// The batch as a whole failed and the transaction was rolled back.
+    #[error("{0:#}")]
+    Batch(anyhow::Error),
+}
+
+/// SQLSTATE of a unique constraint violation.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Why a CRUD operation failed.
+#[derive(Debug, Error)]
+pub enum DbError {
+    /// No live record matched.
+    #[error("record not found")]
+    NotFound,
+    /// The record id is not a UUID.
+    #[error("invalid id {0:?}: not a UUID")]
+    InvalidId(String),
+    /// The payload does not fit the table: not JSON, or a column value of
+    /// the wrong type.
+    #[error("invalid payload: {0:#}")]
+    InvalidPayload(anyhow::Error),
+    /// A list ordering outside [`ListOrder`]'s allowlist.
+    #[error("invalid order: {0}")]
+    InvalidOrder(String),
+    /// A unique constraint rejected the write.
+    #[error("conflict: {0}")]
+    Conflict(String),
+    /// PostgreSQL failed or could not be reached.
+    #[error("{0:#}")]
+    Backend(anyhow::Error),
+}
+
+impl From<DbError> for Status {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::NotFound           => Status::not_found(e.to_string()),
+            DbError::InvalidId(_)       => Status::invalid_argument(e.to_string()),
+            DbError::InvalidPayload(_)  => Status::invalid_argument(e.to_string()),
+            DbError::InvalidOrder(_)    => Status::invalid_argument(e.to_string()),
+            DbError::Conflict(_)        => Status::already_exists(e.to_string()),
+            DbError::Backend(_)         => Status::unavailable(e.to_string()),
+        }
+    }
+}
+
+/// Result of a CRUD operation.
+pub type DbResult<T> = std::result::Result<T, DbError>;
+
+/// Classify a failed `statement`: unique violations are conflicts, data
+/// exceptions (SQLSTATE class 22, e.g. a payload that is not JSON) and
+/// other integrity violations (class 23) are bad payloads, anything else
+/// is a backend failure.
+fn query_error(statement: &'static str) -> impl FnOnce(sqlx::Error) -> DbError {
+    move |e| {
+        let code = e.as_database_error().and_then(|d| d.code()).map(|c| c.into_owned());
+        match code.as_deref() {
+            Some(UNIQUE_VIOLATION) => DbError::Conflict(
+                e.as_database_error().map_or_else(String::new, |d| d.message().to_string()),
+            ),
+            Some(c) if c.starts_with("22") || c.starts_with("23") => {
+                DbError::InvalidPayload(anyhow::Error::new(e).context(statement))
+            }
+            _ => DbError::Backend(anyhow::Error::new(e).context(statement)),
+        }
+    }
+}
+
+fn parse_id(id: &str) -> DbResult<Uuid> {
+    Uuid::parse_str(id).map_err(|_| DbError::InvalidId(id.to_string()))
+}
+
+/// Check a list/count `filter`: blank means none, otherwise it must be a
+/// JSON object, which the payload has to contain (`@>`).
+fn parse_filter(filter: &str) -> DbResult<Option<&str>> {
+    let filter = filter.trim();
+    if filter.is_empty() {
+        return Ok(None);
+    }
+    match serde_json::from_str::<serde_json::Value>(filter) {
+        Ok(serde_json::Value::Object(_)) => Ok(Some(filter)),
+        _ => Err(DbError::InvalidPayload(anyhow::anyhow!("filter must be a JSON object"))),
+    }
+}
+
+/// Columns [`Db::list`] may sort by; every table has both.
+const ORDER_COLUMNS: &[&str] = &["created_at", "updated_at"];
+
+/// Sort order of [`Db::list`].  Only built by [`ListOrder::parse`], so the
+/// column is always one of [`ORDER_COLUMNS`] and safe to splice into SQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListOrder {
+    column: &'static str,
+    descending: bool,
+}
+
+impl Default for ListOrder {
+    /// Newest first.
+    fn default() -> Self {
+        Self { column: "created_at", descending: true }
+    }
+}
+
+impl ListOrder {
+    /// Check a `ListRequest`'s `order_by` / `order_dir`; blank values keep
+    /// the default (`created_at`, descending).
+    pub fn parse(order_by: &str, order_dir: &str) -> DbResult<Self> {
+        let default = Self::default();
+        let column = match order_by.trim() {
+            "" => default.column,
+            requested => ORDER_COLUMNS
+                .iter()
+                .copied()
+                .find(|&c| c.eq_ignore_ascii_case(requested))
+                .ok_or_else(|| {
+                    DbError::InvalidOrder(format!(
+                        "order_by must be one of {}, got {requested:?}",
+                        ORDER_COLUMNS.join(", ")
+                    ))
+                })?,
+        };
+        let descending = match order_dir.trim().to_ascii_lowercase().as_str() {
+            "" => default.descending,
+            "asc" => false,
+            "desc" => true,
+            _ => {
+                return Err(DbError::InvalidOrder(format!(
+                    "order_dir must be asc or desc, got {:?}",
+                    order_dir.trim()
+                )))
+            }
+        };
+        Ok(Self { column, descending })
+    }
+
+    /// The `ORDER BY` clause.
+    pub fn sql(&self) -> String {
+        format!("ORDER BY {} {}", self.column, if self.descending { "DESC" } else { "ASC" })
+    }
+}
+
 /// Shared connection pool.
 pub struct Db {
     pool: PgPool,
+    schema: Registry,
+    /// `delete` marks rows with `deleted_at` instead of removing them.
+    soft_delete: bool,
 }
 
 impl Db {
@@ -22,27 +190,59 @@ impl Db {
             .await
             .context("Failed to connect to PostgreSQL")?;
 
-        Ok(Self { pool })
+        Ok(Self { pool, schema: Registry::default(), soft_delete: false })
     }
 
-    /// Run any pending migrations located in the `migrations/` directory next
-    /// to the binary.  Creates the `records` table if it doesn't exist yet.
+    /// Route the tables declared in `schema` to typed tables.
+    pub fn with_schema(mut self, schema: Registry) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Make [`Db::delete`] a recoverable soft delete; see [`Db::restore`]
+    /// and [`Db::purge`].
+    pub fn with_soft_delete(mut self, soft_delete: bool) -> Self {
+        self.soft_delete = soft_delete;
+        self
+    }
+
+    /// Apply any pending migrations from `migrations/`, then create every
+    /// registered typed table if it doesn't exist yet.
     pub async fn migrate(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS records (
-                id         UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-                table_name TEXT NOT NULL,
-                payload    JSONB NOT NULL DEFAULT '{}',
-                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create records table")?;
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .context("Failed to apply migrations")?;
+
+        let mut tables = Vec::new();
+        for spec in self.schema.tables() {
+            sqlx::query(&spec.create_table_sql())
+                .execute(&self.pool)
+                .await
+                .with_context(|| format!("Failed to create table {}", spec.name))?;
+            tables.push(format!("\"{}\"", spec.name));
+        }
+        // Typed tables created before optimistic concurrency and soft delete.
+        for table in tables {
+            sqlx::query(&format!(
+                "ALTER TABLE {table} \
+                 ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1, \
+                 ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ"
+            ))
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("Failed to add columns to {table}"))?;
+        }
+
+        Ok(())
+    }
 
+    /// Round-trip a `SELECT 1`, for health checks.
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .context("SELECT 1 failed")?;
         Ok(())
     }
 
@@ -50,7 +250,17 @@ impl Db {
     //  CRUD operations                                                     //
     // ------------------------------------------------------------------ //
 
-    pub async fn create(&self, table_name: &str, payload: &str) -> Result<String> {
+    pub async fn create(&self, table_name: &str, payload: &str) -> DbResult<String> {
+        if let Some(spec) = self.schema.get(table_name) {
+            let values = spec.bind_values(payload).map_err(DbError::InvalidPayload)?;
+            let sql = spec.insert_sql();
+            let row = bind_all(sqlx::query(&sql), values)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(query_error("INSERT failed"))?;
+            return Ok(row.get::<Uuid, _>("id").to_string());
+        }
+
         let id: Uuid = sqlx::query_scalar(
             r#"
             INSERT INTO records (table_name, payload)
@@ -62,95 +272,465 @@ impl Db {
         .bind(payload)
         .fetch_one(&self.pool)
         .await
-        .context("INSERT failed")?;
+        .map_err(query_error("INSERT failed"))?;
 
         Ok(id.to_string())
     }
 
-    pub async fn read(&self, id: &str, table_name: &str) -> Result<Option<DbRecord>> {
-        let uuid = Uuid::parse_str(id).context("Invalid UUID")?;
+    /// Create a record under a client-supplied `id`, or, when `id` exists,
+    /// replace its payload (bumping `version` and reviving it if
+    /// soft-deleted).  An `id` already used by another table is a conflict.
+    pub async fn upsert(&self, table_name: &str, id: &str, payload: &str) -> DbResult<UpsertOutcome> {
+        let uuid = parse_id(id)?;
+
+        let inserted: Option<bool> = if let Some(spec) = self.schema.get(table_name) {
+            let values = spec.bind_values(payload).map_err(DbError::InvalidPayload)?;
+            let sql = spec.upsert_sql();
+            bind_all(sqlx::query(&sql).bind(uuid), values)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(query_error("UPSERT failed"))?
+                .map(|row| row.get("inserted"))
+        } else {
+            sqlx::query_scalar(
+                r#"
+                INSERT INTO records (id, table_name, payload)
+                VALUES ($1, $2, $3::jsonb)
+                ON CONFLICT (id) DO UPDATE
+                SET payload = EXCLUDED.payload, version = records.version + 1,
+                    updated_at = NOW(), deleted_at = NULL
+                WHERE records.table_name = EXCLUDED.table_name
+                RETURNING (xmax = 0) AS inserted
+                "#,
+            )
+            .bind(uuid)
+            .bind(table_name)
+            .bind(payload)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(query_error("UPSERT failed"))?
+        };
+
+        match inserted {
+            Some(true) => Ok(UpsertOutcome::Inserted),
+            Some(false) => Ok(UpsertOutcome::Updated),
+            None => Err(DbError::Conflict(format!("id {id} belongs to a record of another table"))),
+        }
+    }
+
+    /// Create `(table_name, payload)` records in one transaction, returning
+    /// their ids in input order.
+    ///
+    /// Records are validated up front so a bad one is reported by index, then
+    /// written with one multi-row INSERT per target table.  Ids are generated
+    /// here rather than relying on the order of `RETURNING` rows.
+    pub async fn batch_create(
+        &self,
+        records: &[(String, String)],
+    ) -> std::result::Result<Vec<String>, BatchCreateError> {
+        if records.len() > MAX_BATCH_CREATE {
+            return Err(BatchCreateError::Batch(anyhow::anyhow!(
+                "batch of {} records exceeds the limit of {MAX_BATCH_CREATE}",
+                records.len()
+            )));
+        }
+
+        // Validate, and group record indices by table in first-seen order.
+        let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+        let mut typed_values: Vec<Vec<SqlValue>> = Vec::with_capacity(records.len());
+        for (index, (table_name, payload)) in records.iter().enumerate() {
+            let values = match self.schema.get(table_name) {
+                Some(spec) => spec.bind_values(payload),
+                None => serde_json::from_str::<serde_json::Value>(payload)
+                    .map(|_| Vec::new())
+                    .context("payload is not valid JSON"),
+            }
+            .map_err(|error| BatchCreateError::Record { index, error })?;
+            typed_values.push(values);
+
+            match groups.iter_mut().find(|(t, _)| *t == table_name.as_str()) {
+                Some((_, indices)) => indices.push(index),
+                None => groups.push((table_name, vec![index])),
+            }
+        }
+
+        let ids: Vec<Uuid> = records.iter().map(|_| Uuid::new_v4()).collect();
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("BEGIN failed")
+            .map_err(BatchCreateError::Batch)?;
+
+        for (table_name, indices) in groups {
+            let spec = self.schema.get(table_name);
+            let sql = match spec {
+                Some(spec) => spec.insert_many_sql(indices.len()),
+                None => format!(
+                    "INSERT INTO records (id, table_name, payload) VALUES {} RETURNING id",
+                    schema::values_list(indices.len(), 3, &["", "", "::jsonb"])
+                ),
+            };
+            let mut query = sqlx::query(&sql);
+            for &i in &indices {
+                query = query.bind(ids[i]);
+                query = match spec {
+                    Some(_) => bind_all(query, std::mem::take(&mut typed_values[i])),
+                    None => query.bind(table_name).bind(&records[i].1),
+                };
+            }
+            let inserted = query
+                .fetch_all(&mut *tx)
+                .await
+                .with_context(|| format!("batch INSERT into {table_name} failed; no records were written"))
+                .map_err(BatchCreateError::Batch)?;
+            if inserted.len() != indices.len() {
+                return Err(BatchCreateError::Batch(anyhow::anyhow!(
+                    "batch INSERT into {table_name} wrote {} of {} records",
+                    inserted.len(),
+                    indices.len()
+                )));
+            }
+        }
+
+        tx.commit()
+            .await
+            .context("COMMIT failed")
+            .map_err(BatchCreateError::Batch)?;
+        Ok(ids.iter().map(Uuid::to_string).collect())
+    }
+
+    pub async fn read(&self, id: &str, table_name: &str) -> DbResult<Option<DbRecord>> {
+        let uuid = parse_id(id)?;
+
+        if let Some(spec) = self.schema.get(table_name) {
+            let sql = format!("{} WHERE id = $1 AND deleted_at IS NULL", spec.select_sql());
+            let row = sqlx::query(&sql)
+                .bind(uuid)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(query_error("SELECT failed"))?;
+            return Ok(row.map(|r| typed_record(spec, r)));
+        }
 
         let row = sqlx::query(
             r#"
-            SELECT id, table_name, payload::text, created_at::text, updated_at::text
+            SELECT id, table_name, payload::text, version, created_at, updated_at, deleted_at
             FROM records
-            WHERE id = $1 AND table_name = $2
+            WHERE id = $1 AND table_name = $2 AND deleted_at IS NULL
             "#,
         )
         .bind(uuid)
         .bind(table_name)
         .fetch_optional(&self.pool)
         .await
-        .context("SELECT failed")?;
-
-        Ok(row.map(|r| DbRecord {
-            id: r.get::<Uuid, _>("id").to_string(),
-            table_name: r.get("table_name"),
-            payload: r.get("payload"),
-            created_at: r.get("created_at"),
-            updated_at: r.get("updated_at"),
+        .map_err(query_error("SELECT failed"))?;
+
+        Ok(row.map(|r| {
+            let table_name = r.get("table_name");
+            db_record(r, table_name)
         }))
     }
 
+    /// Read the live records among `ids` with one `= ANY` query, returning
+    /// them in request order alongside the ids that have none.  Every id is
+    /// checked before querying; the first that is not a UUID fails the call.
+    pub async fn batch_read(&self, ids: &[String], table_name: &str) -> DbResult<(Vec<DbRecord>, Vec<String>)> {
+        let uuids = ids.iter().map(|id| parse_id(id)).collect::<DbResult<Vec<Uuid>>>()?;
+
+        let rows = if let Some(spec) = self.schema.get(table_name) {
+            let sql = format!("{} WHERE id = ANY($1) AND deleted_at IS NULL", spec.select_sql());
+            sqlx::query(&sql)
+                .bind(&uuids)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(query_error("SELECT failed"))?
+                .into_iter()
+                .map(|r| typed_record(spec, r))
+                .collect::<Vec<_>>()
+        } else {
+            sqlx::query(
+                r#"
+                SELECT id, table_name, payload::text, version, created_at, updated_at, deleted_at
+                FROM records
+                WHERE id = ANY($1) AND table_name = $2 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(&uuids)
+            .bind(table_name)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(query_error("SELECT failed"))?
+            .into_iter()
+            .map(|r| {
+                let table_name = r.get("table_name");
+                db_record(r, table_name)
+            })
+            .collect()
+        };
+
+        let by_id: HashMap<Uuid, DbRecord> =
+            rows.into_iter().map(|r| (Uuid::parse_str(&r.id).expect("id column is a UUID"), r)).collect();
+        let mut found = Vec::with_capacity(by_id.len());
+        let mut missing = Vec::new();
+        for (id, uuid) in ids.iter().zip(&uuids) {
+            match by_id.get(uuid) {
+                Some(record) => found.push(record.clone()),
+                None => missing.push(id.clone()),
+            }
+        }
+        Ok((found, missing))
+    }
+
+    /// List records in `order` whose payload contains `filter` (see
+    /// [`parse_filter`]); soft-deleted ones only with `include_deleted`.
     pub async fn list(
         &self,
         table_name: &str,
-        _filter: &str,
+        filter: &str,
         limit: u32,
         offset: u32,
-    ) -> Result<Vec<DbRecord>> {
-        let rows = sqlx::query(
+        include_deleted: bool,
+        order: ListOrder,
+    ) -> DbResult<Vec<DbRecord>> {
+        let filter = parse_filter(filter)?;
+
+        if let Some(spec) = self.schema.get(table_name) {
+            let sql = format!(
+                "{} WHERE ($3 OR deleted_at IS NULL) AND ($4::jsonb IS NULL OR to_jsonb(t) @> $4::jsonb) \
+                 {} LIMIT $1 OFFSET $2",
+                spec.select_sql(),
+                order.sql()
+            );
+            let rows = sqlx::query(&sql)
+                .bind(limit as i64)
+                .bind(offset as i64)
+                .bind(include_deleted)
+                .bind(filter)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(query_error("LIST query failed"))?;
+            return Ok(rows.into_iter().map(|r| typed_record(spec, r)).collect());
+        }
+
+        let sql = format!(
             r#"
-            SELECT id, table_name, payload::text, created_at::text, updated_at::text
+            SELECT id, table_name, payload::text, version, created_at, updated_at, deleted_at
             FROM records
-            WHERE table_name = $1
-            ORDER BY created_at DESC
+            WHERE table_name = $1 AND ($4 OR deleted_at IS NULL)
+              AND ($5::jsonb IS NULL OR payload @> $5::jsonb)
+            {}
             LIMIT $2 OFFSET $3
             "#,
-        )
+            order.sql()
+        );
+        let rows = sqlx::query(&sql)
         .bind(table_name)
         .bind(limit as i64)
         .bind(offset as i64)
+        .bind(include_deleted)
+        .bind(filter)
         .fetch_all(&self.pool)
         .await
-        .context("LIST query failed")?;
+        .map_err(query_error("LIST query failed"))?;
 
         Ok(rows
             .into_iter()
-            .map(|r| DbRecord {
-                id: r.get::<Uuid, _>("id").to_string(),
-                table_name: r.get("table_name"),
-                payload: r.get("payload"),
-                created_at: r.get("created_at"),
-                updated_at: r.get("updated_at"),
+            .map(|r| {
+                let table_name = r.get("table_name");
+                db_record(r, table_name)
             })
             .collect())
     }
 
-    pub async fn update(&self, id: &str, table_name: &str, payload: &str) -> Result<bool> {
-        let uuid = Uuid::parse_str(id).context("Invalid UUID")?;
+    /// Number of records [`Db::list`] would return with the same `filter`
+    /// and `include_deleted`, across all pages.
+    pub async fn count(&self, table_name: &str, filter: &str, include_deleted: bool) -> DbResult<u64> {
+        let filter = parse_filter(filter)?;
 
-        let affected = sqlx::query(
-            r#"
-            UPDATE records
-            SET payload    = $3::jsonb,
-                updated_at = NOW()
-            WHERE id = $1 AND table_name = $2
-            "#,
-        )
-        .bind(uuid)
-        .bind(table_name)
-        .bind(payload)
-        .execute(&self.pool)
-        .await
-        .context("UPDATE failed")?
-        .rows_affected();
+        let count: i64 = if let Some(spec) = self.schema.get(table_name) {
+            let sql = format!(
+                "SELECT COUNT(*) FROM \"{}\" t \
+                 WHERE ($1 OR deleted_at IS NULL) AND ($2::jsonb IS NULL OR to_jsonb(t) @> $2::jsonb)",
+                spec.name
+            );
+            sqlx::query_scalar(&sql)
+                .bind(include_deleted)
+                .bind(filter)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(query_error("COUNT query failed"))?
+        } else {
+            sqlx::query_scalar(
+                r#"
+                SELECT COUNT(*) FROM records
+                WHERE table_name = $1 AND ($2 OR deleted_at IS NULL)
+                  AND ($3::jsonb IS NULL OR payload @> $3::jsonb)
+                "#,
+            )
+            .bind(table_name)
+            .bind(include_deleted)
+            .bind(filter)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(query_error("COUNT query failed"))?
+        };
+        Ok(count as u64)
+    }
 
-        Ok(affected > 0)
+    /// Replace the payload of a record.  With `expected_version` set, only a
+    /// record still at that version is updated.
+    pub async fn update(
+        &self,
+        id: &str,
+        table_name: &str,
+        payload: &str,
+        expected_version: Option<i64>,
+    ) -> DbResult<UpdateOutcome> {
+        let uuid = parse_id(id)?;
+
+        let spec = self.schema.get(table_name);
+        let updated: Option<i64> = if let Some(spec) = spec {
+            let values = spec.bind_values(payload).map_err(DbError::InvalidPayload)?;
+            let sql = spec.update_sql();
+            bind_all(sqlx::query(&sql).bind(uuid).bind(expected_version), values)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(query_error("UPDATE failed"))?
+                .map(|r| r.get("version"))
+        } else {
+            sqlx::query_scalar(
+                r#"
+                UPDATE records
+                SET payload    = $3::jsonb,
+                    version    = version + 1,
+                    updated_at = NOW()
+                WHERE id = $1 AND table_name = $2 AND deleted_at IS NULL
+                  AND ($4::bigint IS NULL OR version = $4)
+                RETURNING version
+                "#,
+            )
+            .bind(uuid)
+            .bind(table_name)
+            .bind(payload)
+            .bind(expected_version)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(query_error("UPDATE failed"))?
+        };
+
+        self.update_outcome(uuid, table_name, updated).await
+    }
+
+    /// Merge `patch` into a record: keys it supplies override, omitted keys
+    /// are kept.  The merge is shallow — a nested object in `patch` replaces
+    /// the stored one wholesale — and a `null` value stores `null` rather
+    /// than removing the key.  On a typed table only the supplied columns are
+    /// set.  `expected_version` works as for [`Db::update`].
+    pub async fn update_partial(
+        &self,
+        id: &str,
+        table_name: &str,
+        patch: &str,
+        expected_version: Option<i64>,
+    ) -> DbResult<UpdateOutcome> {
+        let uuid = parse_id(id)?;
+
+        let updated: Option<i64> = if let Some(spec) = self.schema.get(table_name) {
+            let (columns, values) = spec.bind_partial(patch).map_err(DbError::InvalidPayload)?;
+            let sql = spec.update_partial_sql(&columns);
+            bind_all(sqlx::query(&sql).bind(uuid).bind(expected_version), values)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(query_error("UPDATE failed"))?
+                .map(|r| r.get("version"))
+        } else {
+            let value: serde_json::Value = serde_json::from_str(patch)
+                .context("patch is not valid JSON")
+                .map_err(DbError::InvalidPayload)?;
+            if !value.is_object() {
+                return Err(DbError::InvalidPayload(anyhow::anyhow!("patch must be a JSON object")));
+            }
+            sqlx::query_scalar(
+                r#"
+                UPDATE records
+                SET payload    = payload || $3::jsonb,
+                    version    = version + 1,
+                    updated_at = NOW()
+                WHERE id = $1 AND table_name = $2 AND deleted_at IS NULL
+                  AND ($4::bigint IS NULL OR version = $4)
+                RETURNING version
+                "#,
+            )
+            .bind(uuid)
+            .bind(table_name)
+            .bind(patch)
+            .bind(expected_version)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(query_error("UPDATE failed"))?
+        };
+
+        self.update_outcome(uuid, table_name, updated).await
+    }
+
+    /// The [`UpdateOutcome`] of an UPDATE that returned `updated`.
+    async fn update_outcome(
+        &self,
+        uuid: Uuid,
+        table_name: &str,
+        updated: Option<i64>,
+    ) -> DbResult<UpdateOutcome> {
+        if let Some(version) = updated {
+            return Ok(UpdateOutcome::Updated { version });
+        }
+        let spec = self.schema.get(table_name);
+        // Nothing matched: tell a stale version apart from a missing record.
+        let current_sql = match spec {
+            Some(spec) => format!("SELECT version FROM \"{}\" WHERE id = $1 AND deleted_at IS NULL", spec.name),
+            None => "SELECT version FROM records WHERE id = $1 AND table_name = $2 AND deleted_at IS NULL".to_string(),
+        };
+        let mut current = sqlx::query_scalar(&current_sql).bind(uuid);
+        if spec.is_none() {
+            current = current.bind(table_name);
+        }
+        let current: Option<i64> = current
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(query_error("SELECT failed"))?;
+        Ok(match current {
+            Some(version) => UpdateOutcome::Conflict { version },
+            None => UpdateOutcome::NotFound,
+        })
     }
 
-    pub async fn delete(&self, id: &str, table_name: &str) -> Result<bool> {
-        let uuid = Uuid::parse_str(id).context("Invalid UUID")?;
+    /// Delete a live record: soft (sets `deleted_at`) when enabled with
+    /// [`Db::with_soft_delete`], otherwise permanently.
+    pub async fn delete(&self, id: &str, table_name: &str) -> DbResult<bool> {
+        if !self.soft_delete {
+            return self.purge(id, table_name).await;
+        }
+        self.set_deleted_at(id, table_name, "NOW()", "deleted_at IS NULL").await
+    }
+
+    /// Bring back a soft-deleted record.
+    pub async fn restore(&self, id: &str, table_name: &str) -> DbResult<bool> {
+        self.set_deleted_at(id, table_name, "NULL", "deleted_at IS NOT NULL").await
+    }
+
+    /// Permanently remove a record, soft-deleted or not.
+    pub async fn purge(&self, id: &str, table_name: &str) -> DbResult<bool> {
+        let uuid = parse_id(id)?;
+
+        if let Some(spec) = self.schema.get(table_name) {
+            let affected = sqlx::query(&format!("DELETE FROM \"{}\" WHERE id = $1", spec.name))
+                .bind(uuid)
+                .execute(&self.pool)
+                .await
+                .map_err(query_error("DELETE failed"))?
+                .rows_affected();
+            return Ok(affected > 0);
+        }
 
         let affected = sqlx::query(
             r#"DELETE FROM records WHERE id = $1 AND table_name = $2"#,
@@ -159,18 +739,634 @@ impl Db {
         .bind(table_name)
         .execute(&self.pool)
         .await
-        .context("DELETE failed")?
+        .map_err(query_error("DELETE failed"))?
         .rows_affected();
 
         Ok(affected > 0)
     }
+
+    /// Set `deleted_at` to the SQL expression `value` on a record matching
+    /// `condition`.
+    async fn set_deleted_at(
+        &self,
+        id: &str,
+        table_name: &str,
+        value: &str,
+        condition: &str,
+    ) -> DbResult<bool> {
+        let uuid = parse_id(id)?;
+
+        let (sql, typed) = match self.schema.get(table_name) {
+            Some(spec) => (
+                format!("UPDATE \"{}\" SET deleted_at = {value} WHERE id = $1 AND {condition}", spec.name),
+                true,
+            ),
+            None => (
+                format!(
+                    "UPDATE records SET deleted_at = {value} \
+                     WHERE id = $1 AND table_name = $2 AND {condition}"
+                ),
+                false,
+            ),
+        };
+        let mut query = sqlx::query(&sql).bind(uuid);
+        if !typed {
+            query = query.bind(table_name);
+        }
+        let affected = query
+            .execute(&self.pool)
+            .await
+            .map_err(query_error("UPDATE deleted_at failed"))?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+}
+
+fn bind_all<'q>(
+    mut query: Query<'q, Postgres, PgArguments>,
+    values: Vec<SqlValue>,
+) -> Query<'q, Postgres, PgArguments> {
+    for v in values {
+        query = match v {
+            SqlValue::Text(v)      => query.bind(v),
+            SqlValue::Integer(v)   => query.bind(v),
+            SqlValue::Double(v)    => query.bind(v),
+            SqlValue::Boolean(v)   => query.bind(v),
+            SqlValue::Timestamp(v) => query.bind(v),
+            SqlValue::Json(v)      => query.bind(v),
+            SqlValue::Uuid(v)      => query.bind(v),
+        };
+    }
+    query
 }
 
-/// A row returned from the `records` table.
+fn typed_record(spec: &TableSpec, r: PgRow) -> DbRecord {
+    db_record(r, spec.name.clone())
+}
+
+/// Build a [`DbRecord`] from a row selecting `id`, `payload` (text),
+/// `version` and the `TIMESTAMPTZ` columns.
+fn db_record(r: PgRow, table_name: String) -> DbRecord {
+    DbRecord {
+        id: r.get::<Uuid, _>("id").to_string(),
+        table_name,
+        payload: r.get("payload"),
+        version: r.get("version"),
+        created_at: rfc3339(r.get("created_at")),
+        updated_at: rfc3339(r.get("updated_at")),
+        deleted_at: r.get::<Option<DateTime<Utc>>, _>("deleted_at").map(rfc3339),
+    }
+}
+
+/// Canonical RFC 3339 in UTC with a `Z` suffix, e.g.
+/// `2024-01-01T00:00:00Z` or `2024-01-01T00:00:00.250Z`.
+fn rfc3339(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Result of [`Db::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    /// Updated; `version` is the record's new version.
+    Updated { version: i64 },
+    /// The record exists but is at `version`, not the expected one.
+    Conflict { version: i64 },
+    NotFound,
+}
+
+/// Result of [`Db::upsert`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    /// No record had the id; one was created.
+    Inserted,
+    /// The record with the id was replaced.
+    Updated,
+}
+
+/// A row returned from the `records` table or a typed table.  Timestamps are
+/// RFC 3339 (see [`rfc3339`]).
+#[derive(Debug, Clone)]
 pub struct DbRecord {
     pub id: String,
     pub table_name: String,
     pub payload: String,
+    pub version: i64,
     pub created_at: String,
     pub updated_at: String,
+    /// Set once soft-deleted.
+    pub deleted_at: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    //! Exercise a real PostgreSQL at `TEST_DATABASE_URL`; each test returns
+    //! early when it is unset.
+
+    use super::*;
+
+    async fn test_db(schema: Registry) -> Option<Db> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let db = Db::connect(&url).await.expect("connect").with_schema(schema);
+        db.migrate().await.expect("migrate");
+        Some(db)
+    }
+
+    fn unique(prefix: &str) -> String {
+        format!("{prefix}_{}", Uuid::new_v4().simple())
+    }
+
+    /// A `Db` on a fresh, empty Postgres schema, for migration tests.
+    async fn empty_schema_db() -> Option<Db> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let schema = unique("migrate");
+        let search_path = format!("SET search_path TO {schema}, public");
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .after_connect(move |conn, _| {
+                let search_path = search_path.clone();
+                Box::pin(async move {
+                    sqlx::query(&search_path).execute(conn).await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await
+            .expect("connect");
+        sqlx::query(&format!("CREATE SCHEMA {schema}"))
+            .execute(&pool)
+            .await
+            .expect("create schema");
+        Some(Db { pool, schema: Registry::default(), soft_delete: false })
+    }
+
+    async fn applied_versions(db: &Db) -> Vec<i64> {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn migrate_is_idempotent() {
+        let Some(db) = empty_schema_db().await else { return };
+        db.migrate().await.unwrap();
+        db.migrate().await.unwrap();
+        assert_eq!(applied_versions(&db).await, vec![1, 2]);
+        let id = db.create("widgets", r#"{"n": 1}"#).await.unwrap();
+        assert_eq!(db.read(&id, "widgets").await.unwrap().unwrap().version, 1);
+    }
+
+    #[tokio::test]
+    async fn upgrade_applies_later_migrations() {
+        let Some(db) = empty_schema_db().await else { return };
+
+        // A database at version 1: only the first file is known.
+        let dir = std::env::temp_dir().join(unique("migrations"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("0001_init.sql"), include_str!("../migrations/0001_init.sql")).unwrap();
+        Migrator::new(dir.as_path()).await.unwrap().run(&db.pool).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(applied_versions(&db).await, vec![1]);
+        let old_id: Uuid =
+            sqlx::query_scalar("INSERT INTO records (table_name, payload) VALUES ('widgets', '{}') RETURNING id")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+
+        db.migrate().await.unwrap();
+        assert_eq!(applied_versions(&db).await, vec![1, 2]);
+        let old = db.read(&old_id.to_string(), "widgets").await.unwrap().unwrap();
+        assert_eq!(old.version, 1);
+        assert_eq!(old.deleted_at, None);
+    }
+
+    #[test]
+    fn db_errors_map_to_grpc_codes() {
+        use tonic::Code;
+        let cases = [
+            (DbError::NotFound, Code::NotFound),
+            (DbError::InvalidId("x".into()), Code::InvalidArgument),
+            (DbError::InvalidPayload(anyhow::anyhow!("bad")), Code::InvalidArgument),
+            (DbError::InvalidOrder("bad".into()), Code::InvalidArgument),
+            (DbError::Conflict("duplicate key".into()), Code::AlreadyExists),
+            (DbError::Backend(anyhow::anyhow!("connection reset")), Code::Unavailable),
+        ];
+        for (err, code) in cases {
+            let message = err.to_string();
+            let status = Status::from(err);
+            assert_eq!(status.code(), code, "{message}");
+            assert_eq!(status.message(), message);
+        }
+    }
+
+    #[test]
+    fn list_order_defaults_to_newest_first() {
+        assert_eq!(ListOrder::parse("", "").unwrap().sql(), "ORDER BY created_at DESC");
+        assert_eq!(ListOrder::parse(" ", " ").unwrap(), ListOrder::default());
+    }
+
+    #[test]
+    fn list_order_builds_the_requested_clause() {
+        assert_eq!(ListOrder::parse("updated_at", "asc").unwrap().sql(), "ORDER BY updated_at ASC");
+        assert_eq!(ListOrder::parse("UPDATED_AT", "DESC").unwrap().sql(), "ORDER BY updated_at DESC");
+        assert_eq!(ListOrder::parse("", "asc").unwrap().sql(), "ORDER BY created_at ASC");
+    }
+
+    #[test]
+    fn list_order_rejects_anything_off_the_allowlist() {
+        for (order_by, order_dir) in [
+            ("payload", ""),
+            ("id", "asc"),
+            ("created_at; DROP TABLE records", ""),
+            ("created_at", "sideways"),
+            ("created_at", "desc, id"),
+        ] {
+            let err = ListOrder::parse(order_by, order_dir).unwrap_err();
+            assert!(matches!(err, DbError::InvalidOrder(_)), "{order_by} {order_dir}: {err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_ids_and_payloads_are_classified() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("classified");
+        let err = db.read("not-a-uuid", &table).await.unwrap_err();
+        assert!(matches!(err, DbError::InvalidId(_)), "{err}");
+        let err = db.create(&table, "{not json").await.unwrap_err();
+        assert!(matches!(err, DbError::InvalidPayload(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn batch_create_returns_ids_in_input_order() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let (a, b) = (unique("batch_a"), unique("batch_b"));
+        let records: Vec<(String, String)> = (0..5)
+            .map(|i| {
+                let table = if i % 2 == 0 { &a } else { &b };
+                (table.clone(), format!(r#"{{"n":{i}}}"#))
+            })
+            .collect();
+
+        let ids = db.batch_create(&records).await.unwrap();
+        assert_eq!(ids.len(), 5);
+        for (i, id) in ids.iter().enumerate() {
+            let row = db.read(id, &records[i].0).await.unwrap().unwrap();
+            let payload: serde_json::Value = serde_json::from_str(&row.payload).unwrap();
+            assert_eq!(payload["n"], i);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_record_reports_index_and_writes_nothing() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("batch_bad");
+        let records = vec![
+            (table.clone(), r#"{"ok":1}"#.to_string()),
+            (table.clone(), "not json".to_string()),
+        ];
+
+        let err = db.batch_create(&records).await.unwrap_err();
+        assert!(matches!(err, BatchCreateError::Record { index: 1, .. }), "{err}");
+        assert!(db.list(&table, "", 10, 0, false, ListOrder::default()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failing_insert_rolls_back_earlier_tables() {
+        let schema = Registry::parse(
+            r#"{"tables":[{"name":"batch_typed","columns":[{"name":"code","type":"text","nullable":false}]}]}"#,
+        )
+        .unwrap();
+        let Some(db) = test_db(schema).await else { return };
+        sqlx::query(r#"CREATE UNIQUE INDEX IF NOT EXISTS batch_typed_code ON "batch_typed"(code)"#)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let loose = unique("batch_loose");
+        let code = unique("code");
+        let records = vec![
+            (loose.clone(), r#"{"first":true}"#.to_string()),
+            ("batch_typed".to_string(), format!(r#"{{"code":"{code}"}}"#)),
+            ("batch_typed".to_string(), format!(r#"{{"code":"{code}"}}"#)),
+        ];
+
+        let err = db.batch_create(&records).await.unwrap_err();
+        assert!(matches!(err, BatchCreateError::Batch(_)), "{err}");
+        assert!(db.list(&loose, "", 10, 0, false, ListOrder::default()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn batch_read_returns_records_in_request_order() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("batch_read");
+        let mut ids = Vec::new();
+        for n in 0..3 {
+            ids.push(db.create(&table, &format!(r#"{{"n":{n}}}"#)).await.unwrap());
+        }
+        ids.reverse();
+
+        let (records, missing) = db.batch_read(&ids, &table).await.unwrap();
+        let got: Vec<_> = records.iter().map(|r| (r.id.as_str(), payload(r)["n"].as_i64().unwrap())).collect();
+        assert_eq!(got, [(ids[0].as_str(), 2), (ids[1].as_str(), 1), (ids[2].as_str(), 0)]);
+        assert!(missing.is_empty());
+    }
+
+    #[tokio::test]
+    async fn batch_read_lists_missing_ids() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("batch_read");
+        let found = db.create(&table, "{}").await.unwrap();
+        let other_table = db.create(&unique("batch_read"), "{}").await.unwrap();
+        let unknown = Uuid::new_v4().to_string();
+
+        let ids = vec![unknown.clone(), found.clone(), other_table.clone()];
+        let (records, missing) = db.batch_read(&ids, &table).await.unwrap();
+        assert_eq!(records.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), [found.as_str()]);
+        assert_eq!(missing, [unknown, other_table]);
+    }
+
+    #[tokio::test]
+    async fn batch_read_rejects_a_bad_id() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let ids = vec![Uuid::new_v4().to_string(), "nope".to_string(), "also-bad".to_string()];
+        match db.batch_read(&ids, &unique("batch_read")).await {
+            Err(DbError::InvalidId(id)) => assert_eq!(id, "nope"),
+            other => panic!("expected InvalidId, got {:?}", other.map(|(_, missing)| missing)),
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_inserts_then_updates_by_client_id() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("upserted");
+        let id = Uuid::new_v4().to_string();
+
+        assert_eq!(db.upsert(&table, &id, r#"{"n":1}"#).await.unwrap(), UpsertOutcome::Inserted);
+        let row = db.read(&id, &table).await.unwrap().unwrap();
+        assert_eq!((row.id.as_str(), row.version), (id.as_str(), 1));
+
+        assert_eq!(db.upsert(&table, &id, r#"{"n":2}"#).await.unwrap(), UpsertOutcome::Updated);
+        let row = db.read(&id, &table).await.unwrap().unwrap();
+        assert_eq!(row.version, 2);
+        assert_eq!(payload(&row)["n"], 2);
+        assert_eq!(db.list(&table, "", 10, 0, false, ListOrder::default()).await.unwrap().len(), 1);
+
+        // Another table can't take over the id.
+        let err = db.upsert(&unique("upserted"), &id, "{}").await.unwrap_err();
+        assert!(matches!(err, DbError::Conflict(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn typed_table_upsert_replaces_columns() {
+        let schema = Registry::parse(
+            r#"{"tables":[{"name":"upserted_typed","columns":[{"name":"code","type":"text"}]}]}"#,
+        )
+        .unwrap();
+        let Some(db) = test_db(schema).await else { return };
+        let id = Uuid::new_v4().to_string();
+
+        assert_eq!(db.upsert("upserted_typed", &id, r#"{"code":"a"}"#).await.unwrap(), UpsertOutcome::Inserted);
+        assert_eq!(db.upsert("upserted_typed", &id, r#"{"code":"b"}"#).await.unwrap(), UpsertOutcome::Updated);
+        let row = db.read(&id, "upserted_typed").await.unwrap().unwrap();
+        assert_eq!((payload(&row)["code"].as_str(), row.version), (Some("b"), 2));
+    }
+
+    #[tokio::test]
+    async fn upsert_rejects_an_id_that_is_not_a_uuid() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let err = db.upsert(&unique("upserted"), "device-42", "{}").await.unwrap_err();
+        assert!(matches!(err, DbError::InvalidId(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn update_bumps_version_and_rejects_stale_writes() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("versioned");
+        let id = db.create(&table, r#"{"n":1}"#).await.unwrap();
+        assert_eq!(db.read(&id, &table).await.unwrap().unwrap().version, 1);
+
+        let outcome = db.update(&id, &table, r#"{"n":2}"#, Some(1)).await.unwrap();
+        assert_eq!(outcome, UpdateOutcome::Updated { version: 2 });
+
+        let stale = db.update(&id, &table, r#"{"n":3}"#, Some(1)).await.unwrap();
+        assert_eq!(stale, UpdateOutcome::Conflict { version: 2 });
+        let row = db.read(&id, &table).await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&row.payload).unwrap()["n"], 2);
+
+        // Without an expected version the write is unconditional.
+        let outcome = db.update(&id, &table, r#"{"n":4}"#, None).await.unwrap();
+        assert_eq!(outcome, UpdateOutcome::Updated { version: 3 });
+    }
+
+    #[tokio::test]
+    async fn versioned_update_of_missing_record_is_not_found() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let id = Uuid::new_v4().to_string();
+        let outcome = db.update(&id, &unique("versioned"), "{}", Some(1)).await.unwrap();
+        assert_eq!(outcome, UpdateOutcome::NotFound);
+    }
+
+    #[tokio::test]
+    async fn typed_table_update_checks_version() {
+        let schema = Registry::parse(
+            r#"{"tables":[{"name":"versioned_typed","columns":[{"name":"code","type":"text"}]}]}"#,
+        )
+        .unwrap();
+        let Some(db) = test_db(schema).await else { return };
+        let id = db.create("versioned_typed", r#"{"code":"a"}"#).await.unwrap();
+
+        let outcome = db.update(&id, "versioned_typed", r#"{"code":"b"}"#, Some(1)).await.unwrap();
+        assert_eq!(outcome, UpdateOutcome::Updated { version: 2 });
+        let stale = db.update(&id, "versioned_typed", r#"{"code":"c"}"#, Some(1)).await.unwrap();
+        assert_eq!(stale, UpdateOutcome::Conflict { version: 2 });
+        assert_eq!(db.read(&id, "versioned_typed").await.unwrap().unwrap().version, 2);
+    }
+
+    fn payload(db_record: &DbRecord) -> serde_json::Value {
+        serde_json::from_str(&db_record.payload).unwrap()
+    }
+
+    #[tokio::test]
+    async fn partial_update_merges_shallowly() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("patched");
+        let id = db
+            .create(&table, r#"{"name":"fern","status":"ok","pot":{"size":12,"color":"red"}}"#)
+            .await
+            .unwrap();
+
+        // New key added, existing key overridden, unrelated keys kept.
+        let outcome = db
+            .update_partial(&id, &table, r#"{"status":"thirsty","room":"kitchen"}"#, Some(1))
+            .await
+            .unwrap();
+        assert_eq!(outcome, UpdateOutcome::Updated { version: 2 });
+        let row = payload(&db.read(&id, &table).await.unwrap().unwrap());
+        assert_eq!(row["room"], "kitchen");
+        assert_eq!(row["status"], "thirsty");
+        assert_eq!(row["name"], "fern");
+        assert_eq!(row["pot"], serde_json::json!({"size": 12, "color": "red"}));
+
+        // Nested objects are replaced, not merged.
+        db.update_partial(&id, &table, r#"{"pot":{"size":14}}"#, None).await.unwrap();
+        let row = payload(&db.read(&id, &table).await.unwrap().unwrap());
+        assert_eq!(row["pot"], serde_json::json!({"size": 14}));
+        assert_eq!(row["name"], "fern");
+
+        let stale = db.update_partial(&id, &table, r#"{"name":"x"}"#, Some(1)).await.unwrap();
+        assert_eq!(stale, UpdateOutcome::Conflict { version: 3 });
+        assert!(db.update_partial(&id, &table, "[1]", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn typed_table_partial_update_keeps_other_columns() {
+        let schema = Registry::parse(
+            r#"{"tables":[{"name":"patched_typed","columns":[
+                {"name":"code","type":"text","nullable":false},
+                {"name":"qty","type":"integer"},
+                {"name":"note","type":"text"}]}]}"#,
+        )
+        .unwrap();
+        let Some(db) = test_db(schema).await else { return };
+        let id = db.create("patched_typed", r#"{"code":"a","qty":1,"note":"keep"}"#).await.unwrap();
+
+        let outcome = db.update_partial(&id, "patched_typed", r#"{"qty":5}"#, None).await.unwrap();
+        assert_eq!(outcome, UpdateOutcome::Updated { version: 2 });
+        let row = payload(&db.read(&id, "patched_typed").await.unwrap().unwrap());
+        assert_eq!(row, serde_json::json!({"code": "a", "qty": 5, "note": "keep"}));
+    }
+
+    async fn soft_db() -> Option<Db> {
+        Some(test_db(Registry::default()).await?.with_soft_delete(true))
+    }
+
+    #[tokio::test]
+    async fn soft_delete_hides_record_until_restored() {
+        let Some(db) = soft_db().await else { return };
+        let table = unique("soft");
+        let id = db.create(&table, r#"{"n":1}"#).await.unwrap();
+
+        assert!(db.delete(&id, &table).await.unwrap());
+        assert!(db.read(&id, &table).await.unwrap().is_none());
+        assert!(db.list(&table, "", 10, 0, false, ListOrder::default()).await.unwrap().is_empty());
+        let all = db.list(&table, "", 10, 0, true, ListOrder::default()).await.unwrap();
+        assert!(all[0].deleted_at.is_some());
+        assert_eq!(
+            db.update(&id, &table, "{}", None).await.unwrap(),
+            UpdateOutcome::NotFound
+        );
+        // Already deleted.
+        assert!(!db.delete(&id, &table).await.unwrap());
+
+        assert!(db.restore(&id, &table).await.unwrap());
+        let row = db.read(&id, &table).await.unwrap().unwrap();
+        assert!(row.deleted_at.is_none());
+        assert!(!db.restore(&id, &table).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn count_matches_list_with_and_without_filter() {
+        let Some(db) = soft_db().await else { return };
+        let table = unique("counted");
+        for status in ["active", "active", "retired"] {
+            db.create(&table, &format!(r#"{{"status":"{status}","n":1}}"#)).await.unwrap();
+        }
+        let deleted = db.create(&table, r#"{"status":"active"}"#).await.unwrap();
+        db.delete(&deleted, &table).await.unwrap();
+
+        assert_eq!(db.count(&table, "", false).await.unwrap(), 3);
+        assert_eq!(db.count(&table, "", true).await.unwrap(), 4);
+        let active = r#"{"status":"active"}"#;
+        assert_eq!(db.count(&table, active, false).await.unwrap(), 2);
+        assert_eq!(db.list(&table, active, 10, 0, false, ListOrder::default()).await.unwrap().len(), 2);
+        assert_eq!(db.count(&table, r#"{"status":"gone"}"#, false).await.unwrap(), 0);
+        assert!(matches!(
+            db.count(&table, "[1]", false).await,
+            Err(DbError::InvalidPayload(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn typed_table_count_applies_filter() {
+        let schema = Registry::parse(
+            r#"{"tables":[{"name":"counted_typed","columns":[{"name":"code","type":"text"}]}]}"#,
+        )
+        .unwrap();
+        let Some(db) = test_db(schema).await else { return };
+        let code = unique("code");
+        db.create("counted_typed", &format!(r#"{{"code":"{code}"}}"#)).await.unwrap();
+        db.create("counted_typed", r#"{"code":"other"}"#).await.unwrap();
+
+        let filter = format!(r#"{{"code":"{code}"}}"#);
+        assert_eq!(db.count("counted_typed", &filter, false).await.unwrap(), 1);
+        assert!(db.count("counted_typed", "", false).await.unwrap() >= 2);
+    }
+
+    #[tokio::test]
+    async fn purge_removes_soft_deleted_record_for_good() {
+        let Some(db) = soft_db().await else { return };
+        let table = unique("soft");
+        let id = db.create(&table, "{}").await.unwrap();
+
+        db.delete(&id, &table).await.unwrap();
+        assert!(db.purge(&id, &table).await.unwrap());
+        assert!(!db.restore(&id, &table).await.unwrap());
+        assert!(db.list(&table, "", 10, 0, true, ListOrder::default()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn hard_delete_is_the_default() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("hard");
+        let id = db.create(&table, "{}").await.unwrap();
+
+        assert!(db.delete(&id, &table).await.unwrap());
+        assert!(db.list(&table, "", 10, 0, true, ListOrder::default()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn typed_table_soft_delete_and_restore() {
+        let schema = Registry::parse(
+            r#"{"tables":[{"name":"soft_typed","columns":[{"name":"code","type":"text"}]}]}"#,
+        )
+        .unwrap();
+        let Some(db) = test_db(schema).await else { return };
+        let db = db.with_soft_delete(true);
+        let id = db.create("soft_typed", r#"{"code":"a"}"#).await.unwrap();
+
+        assert!(db.delete(&id, "soft_typed").await.unwrap());
+        assert!(db.read(&id, "soft_typed").await.unwrap().is_none());
+        assert!(db.restore(&id, "soft_typed").await.unwrap());
+        let row = db.read(&id, "soft_typed").await.unwrap().unwrap();
+        assert_eq!(row.payload, r#"{"code": "a"}"#);
+        assert!(db.purge(&id, "soft_typed").await.unwrap());
+    }
+
+    #[test]
+    fn formats_timestamps_as_rfc3339_utc() {
+        let t = DateTime::parse_from_rfc3339("2024-01-01T05:30:00+05:30").unwrap().with_timezone(&Utc);
+        assert_eq!(rfc3339(t), "2024-01-01T00:00:00Z");
+        let t = DateTime::parse_from_rfc3339("2024-01-01T00:00:00.250Z").unwrap().with_timezone(&Utc);
+        assert_eq!(rfc3339(t), "2024-01-01T00:00:00.250Z");
+    }
+
+    #[tokio::test]
+    async fn read_round_trips_stored_timestamps_as_rfc3339() {
+        let Some(db) = test_db(Registry::default()).await else { return };
+        let table = unique("stamped");
+        let id = db.create(&table, "{}").await.unwrap();
+        sqlx::query(
+            "UPDATE records SET created_at = '2024-01-01 00:00:00+00', \
+             updated_at = '2024-03-05 12:34:56.789+00' WHERE id = $1::uuid",
+        )
+        .bind(&id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let row = db.read(&id, &table).await.unwrap().unwrap();
+        assert_eq!(row.created_at, "2024-01-01T00:00:00Z");
+        assert_eq!(row.updated_at, "2024-03-05T12:34:56.789Z");
+        assert_eq!(row.deleted_at, None);
+    }
 }
@@ -0,0 +1,86 @@
+//! gRPC health reporting (`grpc.health.v1.Health`).
+//!
+//! The service reports SERVING once the database is connected and migrated,
+//! and NOT_SERVING while a periodic `SELECT 1` fails.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use proto::postgres_service::postgres_service_server::PostgresServiceServer;
+use tokio::task::JoinHandle;
+use tonic::server::NamedService;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tracing::{info, warn};
+
+use crate::{db, PostgresServiceImpl};
+
+/// How often [`spawn_probe`] checks the database.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+type Service = PostgresServiceServer<PostgresServiceImpl>;
+
+/// Report the overall server (`""`) and `PostgresService` as serving or not.
+pub async fn set(reporter: &mut HealthReporter, serving: bool) {
+    let status = if serving { ServingStatus::Serving } else { ServingStatus::NotServing };
+    reporter.set_service_status("", status).await;
+    reporter.set_service_status(Service::NAME, status).await;
+}
+
+/// Ping the database every [`PROBE_INTERVAL`], flipping the reported status
+/// when the result changes.  Assumes the service starts out SERVING.
+pub fn spawn_probe(mut reporter: HealthReporter, db: Arc<db::Db>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut serving = true;
+        let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let ok = match db.ping().await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!(error = %e, "database health probe failed");
+                    false
+                }
+            };
+            if ok != serving {
+                info!(serving = ok, "health status changed");
+                set(&mut reporter, ok).await;
+                serving = ok;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::{Channel, Server};
+    use tonic_health::pb::health_check_response::ServingStatus as PbStatus;
+    use tonic_health::pb::health_client::HealthClient;
+    use tonic_health::pb::HealthCheckRequest;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn health_service_reports_status_changes() {
+        let (mut reporter, health_service) = tonic_health::server::health_reporter();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(Server::builder().add_service(health_service).serve_with_incoming(incoming));
+
+        let channel = Channel::from_shared(format!("http://{addr}")).unwrap().connect().await.unwrap();
+        let mut client = HealthClient::new(channel);
+        let check = |service: &str| HealthCheckRequest { service: service.to_string() };
+
+        set(&mut reporter, false).await;
+        let resp = client.check(check(Service::NAME)).await.unwrap().into_inner();
+        assert_eq!(resp.status(), PbStatus::NotServing);
+
+        set(&mut reporter, true).await;
+        for service in ["", Service::NAME] {
+            let resp = client.check(check(service)).await.unwrap().into_inner();
+            assert_eq!(resp.status(), PbStatus::Serving, "{service:?}");
+        }
+    }
+}
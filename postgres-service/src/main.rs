@@ -8,17 +8,48 @@
 //! The `DATABASE_URL` is resolved via Bitwarden Secrets Manager
 //! (`BWS_ACCESS_TOKEN` + `BWS_POSTGRES_DATABASE_URL_ID`) with a fallback to
 //! the `DATABASE_URL` environment variable for local development.
+//!
+//! # Startup
+//! The initial connect is retried with backoff while Postgres is not yet
+//! accepting connections, `DB_CONNECT_ATTEMPTS` times (default 10); see
+//! [`connect_retry`].
+//!
+//! # Typed tables
+//! `POSTGRES_SCHEMA_FILE` optionally names a JSON file of table specs (see
+//! [`schema`]); those tables are created on startup and used instead of the
+//! generic `records` table.
+//!
+//! # Soft delete
+//! With `POSTGRES_SOFT_DELETE=true`, `Delete` only sets `deleted_at`; such
+//! records are hidden from reads until `Restore`d, and `Purge` removes them
+//! for good.  Hard delete is the default.
+//!
+//! # Errors
+//! CRUD failures are returned as gRPC statuses: `NOT_FOUND`,
+//! `INVALID_ARGUMENT` (bad id or payload), `ALREADY_EXISTS` (unique
+//! violation) or `UNAVAILABLE` (database failure); see [`db::DbError`].  A
+//! stale `version` on update is still answered with `conflict: true`.
+//!
+//! # Health
+//! Serves `grpc.health.v1.Health`; see [`health`].
+//!
+//! # Reflection
+//! Serves gRPC reflection for `grpcurl` unless `GRPC_REFLECTION=false`; see
+//! [`proto::reflection`].
 
 mod db;
-mod secrets;
+mod health;
+mod schema;
 
 use std::sync::Arc;
 
 use anyhow::Result;
 use proto::postgres_service::{
     postgres_service_server::{PostgresService, PostgresServiceServer},
-    CreateRequest, CreateResponse, DeleteRequest, DeleteResponse, ListRequest, ListResponse,
-    ReadRequest, ReadResponse, Record, UpdateRequest, UpdateResponse,
+    BatchCreateRequest, BatchCreateResponse, BatchReadRequest, BatchReadResponse, CountRequest, CountResponse, CreateRequest, CreateResponse, DeleteRequest,
+    DeleteResponse, ListRequest, ListResponse,
+    PurgeRequest, PurgeResponse, ReadRequest, ReadResponse, Record, RestoreRequest, RestoreResponse, UpdateRequest,
+    UpdateResponse,
 };
 use tonic::{transport::Server, Request, Response, Status};
 use tracing::{error, info};
@@ -38,18 +69,58 @@ impl PostgresService for PostgresServiceImpl {
         request: Request<CreateRequest>,
     ) -> Result<Response<CreateResponse>, Status> {
         let req = request.into_inner();
-        match self.db.create(&req.table_name, &req.payload).await {
-            Ok(id) => Ok(Response::new(CreateResponse {
+        let created = if req.id.is_empty() {
+            self.db.create(&req.table_name, &req.payload).await.map(|id| (id, false))
+        } else {
+            self.db
+                .upsert(&req.table_name, &req.id, &req.payload)
+                .await
+                .map(|outcome| (req.id, outcome == db::UpsertOutcome::Updated))
+        };
+        match created {
+            Ok((id, updated)) => Ok(Response::new(CreateResponse {
                 id,
                 success: true,
                 error: String::new(),
+                updated,
+            })),
+            Err(e) => Err(failed(e, "create")),
+        }
+    }
+
+    async fn batch_create(
+        &self,
+        request: Request<BatchCreateRequest>,
+    ) -> Result<Response<BatchCreateResponse>, Status> {
+        let requests = request.into_inner().requests;
+        if let Some(index) = requests.iter().position(|r| !r.id.is_empty()) {
+            return Ok(Response::new(BatchCreateResponse {
+                ids: vec![],
+                success: false,
+                error: "client-supplied ids are only supported by Create".to_string(),
+                failed_index: Some(index as u32),
+            }));
+        }
+        let records: Vec<(String, String)> =
+            requests.into_iter().map(|r| (r.table_name, r.payload)).collect();
+        match self.db.batch_create(&records).await {
+            Ok(ids) => Ok(Response::new(BatchCreateResponse {
+                ids,
+                success: true,
+                error: String::new(),
+                failed_index: None,
             })),
             Err(e) => {
-                error!(error = %e, "create failed");
-                Ok(Response::new(CreateResponse {
-                    id: String::new(),
+                error!(error = %e, "batch create failed");
+                let failed_index = match &e {
+                    db::BatchCreateError::Record { index, .. } => Some(*index as u32),
+                    db::BatchCreateError::Batch(_) => None,
+                };
+                Ok(Response::new(BatchCreateResponse {
+                    ids: vec![],
                     success: false,
                     error: e.to_string(),
+                    failed_index,
                 }))
             }
         }
@@ -66,25 +137,43 @@ impl PostgresService for PostgresServiceImpl {
                     id: row.id,
                     table_name: row.table_name,
                     payload: row.payload,
+                    version: row.version,
                     created_at: row.created_at,
                     updated_at: row.updated_at,
+                    deleted_at: row.deleted_at.unwrap_or_default(),
                 }),
                 success: true,
                 error: String::new(),
             })),
-            Ok(None) => Ok(Response::new(ReadResponse {
-                record: None,
-                success: false,
-                error: "record not found".to_string(),
+            Ok(None) => Err(db::DbError::NotFound.into()),
+            Err(e) => Err(failed(e, "read")),
+        }
+    }
+
+    async fn batch_read(
+        &self,
+        request: Request<BatchReadRequest>,
+    ) -> Result<Response<BatchReadResponse>, Status> {
+        let req = request.into_inner();
+        match self.db.batch_read(&req.ids, &req.table_name).await {
+            Ok((rows, missing)) => Ok(Response::new(BatchReadResponse {
+                records: rows
+                    .into_iter()
+                    .map(|r| Record {
+                        id: r.id,
+                        table_name: r.table_name,
+                        payload: r.payload,
+                        version: r.version,
+                        created_at: r.created_at,
+                        updated_at: r.updated_at,
+                        deleted_at: r.deleted_at.unwrap_or_default(),
+                    })
+                    .collect(),
+                missing,
+                success: true,
+                error: String::new(),
             })),
-            Err(e) => {
-                error!(error = %e, "read failed");
-                Ok(Response::new(ReadResponse {
-                    record: None,
-                    success: false,
-                    error: e.to_string(),
-                }))
-            }
+            Err(e) => Err(failed(e, "batch read")),
         }
     }
 
@@ -94,7 +183,12 @@ impl PostgresService for PostgresServiceImpl {
     ) -> Result<Response<ListResponse>, Status> {
         let req = request.into_inner();
         let limit = if req.limit == 0 { 100 } else { req.limit };
-        match self.db.list(&req.table_name, &req.filter, limit, req.offset).await {
+        let order = db::ListOrder::parse(&req.order_by, &req.order_dir).map_err(|e| failed(e, "list"))?;
+        match self
+            .db
+            .list(&req.table_name, &req.filter, limit, req.offset, req.include_deleted, order)
+            .await
+        {
             Ok(rows) => Ok(Response::new(ListResponse {
                 records: rows
                     .into_iter()
@@ -102,21 +196,31 @@ impl PostgresService for PostgresServiceImpl {
                         id: r.id,
                         table_name: r.table_name,
                         payload: r.payload,
+                        version: r.version,
                         created_at: r.created_at,
                         updated_at: r.updated_at,
+                        deleted_at: r.deleted_at.unwrap_or_default(),
                     })
                     .collect(),
                 success: true,
                 error: String::new(),
             })),
-            Err(e) => {
-                error!(error = %e, "list failed");
-                Ok(Response::new(ListResponse {
-                    records: vec![],
-                    success: false,
-                    error: e.to_string(),
-                }))
-            }
+            Err(e) => Err(failed(e, "list")),
+        }
+    }
+
+    async fn count(
+        &self,
+        request: Request<CountRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        match self.db.count(&req.table_name, &req.filter, req.include_deleted).await {
+            Ok(count) => Ok(Response::new(CountResponse {
+                count,
+                success: true,
+                error: String::new(),
+            })),
+            Err(e) => Err(failed(e, "count")),
         }
     }
 
@@ -125,23 +229,24 @@ impl PostgresService for PostgresServiceImpl {
         request: Request<UpdateRequest>,
     ) -> Result<Response<UpdateResponse>, Status> {
         let req = request.into_inner();
-        match self.db.update(&req.id, &req.table_name, &req.payload).await {
-            Ok(found) => Ok(Response::new(UpdateResponse {
-                success: found,
-                error: if found {
-                    String::new()
-                } else {
-                    "record not found".to_string()
-                },
-            })),
-            Err(e) => {
-                error!(error = %e, "update failed");
-                Ok(Response::new(UpdateResponse {
-                    success: false,
-                    error: e.to_string(),
-                }))
-            }
-        }
+        let outcome = self.db.update(&req.id, &req.table_name, &req.payload, req.version).await;
+        update_response(outcome)
+            .map(Response::new)
+            .map_err(|e| failed(e, "update"))
+    }
+
+    async fn update_partial(
+        &self,
+        request: Request<UpdateRequest>,
+    ) -> Result<Response<UpdateResponse>, Status> {
+        let req = request.into_inner();
+        let outcome = self
+            .db
+            .update_partial(&req.id, &req.table_name, &req.payload, req.version)
+            .await;
+        update_response(outcome)
+            .map(Response::new)
+            .map_err(|e| failed(e, "partial update"))
     }
 
     async fn delete(
@@ -150,23 +255,75 @@ impl PostgresService for PostgresServiceImpl {
     ) -> Result<Response<DeleteResponse>, Status> {
         let req = request.into_inner();
         match self.db.delete(&req.id, &req.table_name).await {
-            Ok(found) => Ok(Response::new(DeleteResponse {
-                success: found,
-                error: if found {
-                    String::new()
-                } else {
-                    "record not found".to_string()
-                },
+            Ok(true) => Ok(Response::new(DeleteResponse {
+                success: true,
+                error: String::new(),
             })),
-            Err(e) => {
-                error!(error = %e, "delete failed");
-                Ok(Response::new(DeleteResponse {
-                    success: false,
-                    error: e.to_string(),
-                }))
-            }
+            Ok(false) => Err(db::DbError::NotFound.into()),
+            Err(e) => Err(failed(e, "delete")),
         }
     }
+
+    async fn restore(
+        &self,
+        request: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, Status> {
+        let req = request.into_inner();
+        match self.db.restore(&req.id, &req.table_name).await {
+            Ok(true) => Ok(Response::new(RestoreResponse {
+                success: true,
+                error: String::new(),
+            })),
+            Ok(false) => Err(Status::not_found("no soft-deleted record found")),
+            Err(e) => Err(failed(e, "restore")),
+        }
+    }
+
+    async fn purge(
+        &self,
+        request: Request<PurgeRequest>,
+    ) -> Result<Response<PurgeResponse>, Status> {
+        let req = request.into_inner();
+        match self.db.purge(&req.id, &req.table_name).await {
+            Ok(true) => Ok(Response::new(PurgeResponse {
+                success: true,
+                error: String::new(),
+            })),
+            Ok(false) => Err(db::DbError::NotFound.into()),
+            Err(e) => Err(failed(e, "purge")),
+        }
+    }
+}
+
+/// Log a failed `action` (database failures as errors, rejected requests
+/// at info) and turn it into its gRPC status.
+fn failed(e: db::DbError, action: &str) -> Status {
+    if matches!(e, db::DbError::Backend(_)) {
+        error!(error = %e, "{action} failed");
+    } else {
+        info!(error = %e, "{action} rejected");
+    }
+    e.into()
+}
+
+/// The response for a [`db::Db::update`] / [`db::Db::update_partial`]
+/// outcome; a missing record is [`db::DbError::NotFound`].
+fn update_response(outcome: db::DbResult<db::UpdateOutcome>) -> db::DbResult<UpdateResponse> {
+    match outcome? {
+        db::UpdateOutcome::Updated { version } => Ok(UpdateResponse {
+            success: true,
+            error: String::new(),
+            conflict: false,
+            version,
+        }),
+        db::UpdateOutcome::Conflict { version } => Ok(UpdateResponse {
+            success: false,
+            error: format!("version conflict: record is at version {version}"),
+            conflict: true,
+            version,
+        }),
+        db::UpdateOutcome::NotFound => Err(db::DbError::NotFound),
+    }
 }
 
 // ------------------------------------------------------------------ //
@@ -194,21 +351,62 @@ async fn main() -> Result<()> {
     )
     .await?;
 
-    let db = db::Db::connect(&database_url).await?;
+    let schema = match std::env::var("POSTGRES_SCHEMA_FILE").ok() {
+        Some(path) => schema::Registry::load(&path)?,
+        None => schema::Registry::default(),
+    };
+    let soft_delete = std::env::var("POSTGRES_SOFT_DELETE")
+        .map(|v| matches!(v.trim(), "1" | "true"))
+        .unwrap_or(false);
+    // Postgres may still be starting (e.g. under docker-compose).
+    let db = connect_retry::retry("Postgres", &connect_retry::Backoff::from_env(), || {
+        db::Db::connect(&database_url)
+    })
+    .await?
+        .with_schema(schema)
+        .with_soft_delete(soft_delete);
     db.migrate().await?;
 
     let addr = std::env::var("POSTGRES_SERVICE_ADDR")
         .unwrap_or_else(|_| "[::1]:50051".to_string())
         .parse()?;
 
-    let svc = PostgresServiceImpl { db: Arc::new(db) };
+    let db = Arc::new(db);
+    let (mut reporter, health_service) = tonic_health::server::health_reporter();
+    health::set(&mut reporter, true).await;
+    health::spawn_probe(reporter, db.clone());
+
+    let svc = PostgresServiceImpl { db };
 
     info!(%addr, "postgres-service listening");
 
     Server::builder()
+        .layer(request_id::RequestIdLayer)
+        .add_service(health_service)
         .add_service(PostgresServiceServer::new(svc))
-        .serve(addr)
+        .add_optional_service(proto::reflection::from_env()?)
+        .serve_with_shutdown(addr, shutdown_signal())
         .await?;
 
+    info!("postgres-service stopped");
     Ok(())
 }
+
+/// Resolves on the first SIGTERM (sent by Kubernetes before SIGKILL) or
+/// Ctrl-C.  The SIGTERM handler is installed when this is called.
+fn shutdown_signal() -> impl std::future::Future<Output = ()> {
+    #[cfg(unix)]
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    async move {
+        #[cfg(unix)]
+        let term = term.recv();
+        #[cfg(not(unix))]
+        let term = std::future::pending::<Option<()>>();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term => {}
+        }
+        info!("shutdown signal received");
+    }
+}
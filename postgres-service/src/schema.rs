@@ -0,0 +1,483 @@
+//! Declared domain tables.
+//!
+//! A schema file lists [`TableSpec`]s; each becomes a real table with typed
+//! columns instead of rows in the generic `records` table.  Every typed table
+//! also gets the `id`, `version`, `created_at`, `updated_at` and `deleted_at`
+//! columns that `records` has, so reads return the same shape.
+//!
+//! ```json
+//! { "tables": [
+//!     { "name": "customer",
+//!       "columns": [
+//!         { "name": "email", "type": "text", "nullable": false },
+//!         { "name": "age",   "type": "integer" } ] } ] }
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Columns every typed table carries; not declarable in a spec.
+const RESERVED_COLUMNS: &[&str] = &["id", "version", "created_at", "updated_at", "deleted_at"];
+
+/// Column type of a declared table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Double,
+    Boolean,
+    Timestamp,
+    Json,
+    Uuid,
+}
+
+impl ColumnType {
+    fn sql(self) -> &'static str {
+        match self {
+            ColumnType::Text      => "TEXT",
+            ColumnType::Integer   => "BIGINT",
+            ColumnType::Double    => "DOUBLE PRECISION",
+            ColumnType::Boolean   => "BOOLEAN",
+            ColumnType::Timestamp => "TIMESTAMPTZ",
+            ColumnType::Json      => "JSONB",
+            ColumnType::Uuid      => "UUID",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColumnSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TableSpec {
+    pub name: String,
+    pub columns: Vec<ColumnSpec>,
+}
+
+/// A payload value converted for binding to a typed column.  `None` binds
+/// SQL NULL of the column's type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Text(Option<String>),
+    Integer(Option<i64>),
+    Double(Option<f64>),
+    Boolean(Option<bool>),
+    Timestamp(Option<DateTime<Utc>>),
+    Json(Option<Value>),
+    Uuid(Option<Uuid>),
+}
+
+impl TableSpec {
+    /// `CREATE TABLE IF NOT EXISTS` for this table.
+    pub fn create_table_sql(&self) -> String {
+        let mut cols = vec!["id UUID PRIMARY KEY DEFAULT gen_random_uuid()".to_string()];
+        for c in &self.columns {
+            let not_null = if c.nullable { "" } else { " NOT NULL" };
+            cols.push(format!("\"{}\" {}{not_null}", c.name, c.column_type.sql()));
+        }
+        cols.push("version BIGINT NOT NULL DEFAULT 1".to_string());
+        cols.push("created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()".to_string());
+        cols.push("updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()".to_string());
+        cols.push("deleted_at TIMESTAMPTZ".to_string());
+        format!(
+            "CREATE TABLE IF NOT EXISTS \"{}\" (\n    {}\n)",
+            self.name,
+            cols.join(",\n    ")
+        )
+    }
+
+    /// `INSERT` binding one parameter per column, in declaration order.
+    pub fn insert_sql(&self) -> String {
+        let names: Vec<String> = self.columns.iter().map(|c| format!("\"{}\"", c.name)).collect();
+        let params: Vec<String> = (1..=self.columns.len()).map(|i| format!("${i}")).collect();
+        format!(
+            "INSERT INTO \"{}\" ({}) VALUES ({}) RETURNING id",
+            self.name,
+            names.join(", "),
+            params.join(", ")
+        )
+    }
+
+    /// Multi-row `INSERT` of `rows` rows, each binding `id` then one
+    /// parameter per column.
+    pub fn insert_many_sql(&self, rows: usize) -> String {
+        let names: Vec<String> = self.columns.iter().map(|c| format!("\"{}\"", c.name)).collect();
+        format!(
+            "INSERT INTO \"{}\" (id, {}) VALUES {} RETURNING id",
+            self.name,
+            names.join(", "),
+            values_list(rows, self.columns.len() + 1, &[])
+        )
+    }
+
+    /// `INSERT` binding `id` then one parameter per column that, when `id`
+    /// already exists, replaces the row's columns instead, bumping `version`
+    /// and reviving it if soft-deleted.  Returns `inserted`, false for an
+    /// update.
+    pub fn upsert_sql(&self) -> String {
+        let names: Vec<String> = self.columns.iter().map(|c| format!("\"{}\"", c.name)).collect();
+        let sets: String = names.iter().map(|n| format!("{n} = EXCLUDED.{n}, ")).collect();
+        format!(
+            "INSERT INTO \"{0}\" (id, {1}) VALUES {2} \
+             ON CONFLICT (id) DO UPDATE SET {sets}version = \"{0}\".version + 1, updated_at = NOW(), deleted_at = NULL \
+             RETURNING (xmax = 0) AS inserted",
+            self.name,
+            names.join(", "),
+            values_list(1, self.columns.len() + 1, &[])
+        )
+    }
+
+    /// `UPDATE` of a live row by `id` (`$1`) and optional expected version
+    /// (`$2`), then one parameter per column.  Bumps and returns `version`.
+    pub fn update_sql(&self) -> String {
+        let sets: Vec<String> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("\"{}\" = ${}", c.name, i + 3))
+            .collect();
+        format!(
+            "UPDATE \"{}\" SET {}, version = version + 1, updated_at = NOW() \
+             WHERE id = $1 AND deleted_at IS NULL AND ($2::bigint IS NULL OR version = $2) RETURNING version",
+            self.name,
+            sets.join(", ")
+        )
+    }
+
+    /// Like [`TableSpec::update_sql`], but setting only `columns` (as
+    /// returned by [`TableSpec::bind_partial`]).
+    pub fn update_partial_sql(&self, columns: &[&str]) -> String {
+        let sets: String = columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("\"{name}\" = ${}, ", i + 3))
+            .collect();
+        format!(
+            "UPDATE \"{}\" SET {sets}version = version + 1, updated_at = NOW() \
+             WHERE id = $1 AND deleted_at IS NULL AND ($2::bigint IS NULL OR version = $2) RETURNING version",
+            self.name
+        )
+    }
+
+    /// Column list for reads: declared columns folded back into a JSON
+    /// `payload`, as the `records` table returns them.
+    pub fn select_sql(&self) -> String {
+        format!(
+            "SELECT id, (to_jsonb(t) - 'id' - 'version' - 'created_at' - 'updated_at' - 'deleted_at')::text AS payload, \
+             version, created_at, updated_at, deleted_at FROM \"{}\" t",
+            self.name
+        )
+    }
+
+    /// Convert a JSON object payload into one [`SqlValue`] per column.
+    ///
+    /// Missing keys and `null` bind NULL; unknown keys are rejected.
+    pub fn bind_values(&self, payload: &str) -> Result<Vec<SqlValue>> {
+        let value: Value = serde_json::from_str(payload).context("payload is not valid JSON")?;
+        let Value::Object(obj) = value else {
+            bail!("payload for table {:?} must be a JSON object", self.name);
+        };
+        if let Some(unknown) = obj.keys().find(|k| !self.columns.iter().any(|c| &c.name == *k)) {
+            bail!("unknown column {unknown:?} for table {:?}", self.name);
+        }
+        self.columns
+            .iter()
+            .map(|c| {
+                let v = obj.get(&c.name).filter(|v| !v.is_null());
+                if v.is_none() && !c.nullable {
+                    bail!("column {:?} is required", c.name);
+                }
+                to_sql_value(c, v)
+            })
+            .collect()
+    }
+
+    /// Convert a JSON object patch into the columns it names and their
+    /// values, in declaration order.
+    ///
+    /// Omitted columns are left out; `null` binds NULL; unknown keys are
+    /// rejected, as is `null` for a required column.
+    pub fn bind_partial(&self, patch: &str) -> Result<(Vec<&str>, Vec<SqlValue>)> {
+        let value: Value = serde_json::from_str(patch).context("patch is not valid JSON")?;
+        let Value::Object(obj) = value else {
+            bail!("patch for table {:?} must be a JSON object", self.name);
+        };
+        if let Some(unknown) = obj.keys().find(|k| !self.columns.iter().any(|c| &c.name == *k)) {
+            bail!("unknown column {unknown:?} for table {:?}", self.name);
+        }
+        let mut columns = Vec::new();
+        let mut values = Vec::new();
+        for c in &self.columns {
+            let Some(v) = obj.get(&c.name) else { continue };
+            if v.is_null() && !c.nullable {
+                bail!("column {:?} is required", c.name);
+            }
+            columns.push(c.name.as_str());
+            values.push(to_sql_value(c, Some(v).filter(|v| !v.is_null()))?);
+        }
+        Ok((columns, values))
+    }
+}
+
+/// `($1, $2), ($3, $4), ...` for `rows` rows of `width` parameters.  `casts`
+/// optionally suffixes parameters by position, e.g. `::jsonb`.
+pub fn values_list(rows: usize, width: usize, casts: &[&str]) -> String {
+    (0..rows)
+        .map(|r| {
+            let params: Vec<String> = (0..width)
+                .map(|c| format!("${}{}", r * width + c + 1, casts.get(c).copied().unwrap_or("")))
+                .collect();
+            format!("({})", params.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn to_sql_value(col: &ColumnSpec, v: Option<&Value>) -> Result<SqlValue> {
+    let mismatch = || anyhow::anyhow!("column {:?} expects {:?}", col.name, col.column_type);
+    Ok(match col.column_type {
+        ColumnType::Text => SqlValue::Text(v.map(|v| v.as_str().map(str::to_string).ok_or_else(mismatch)).transpose()?),
+        ColumnType::Integer => SqlValue::Integer(v.map(|v| v.as_i64().ok_or_else(mismatch)).transpose()?),
+        ColumnType::Double => SqlValue::Double(v.map(|v| v.as_f64().ok_or_else(mismatch)).transpose()?),
+        ColumnType::Boolean => SqlValue::Boolean(v.map(|v| v.as_bool().ok_or_else(mismatch)).transpose()?),
+        ColumnType::Timestamp => SqlValue::Timestamp(
+            v.map(|v| {
+                v.as_str()
+                    .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .ok_or_else(mismatch)
+            })
+            .transpose()?,
+        ),
+        ColumnType::Json => SqlValue::Json(v.cloned()),
+        ColumnType::Uuid => SqlValue::Uuid(
+            v.map(|v| v.as_str().and_then(|s| Uuid::parse_str(s).ok()).ok_or_else(mismatch))
+                .transpose()?,
+        ),
+    })
+}
+
+/// Registered typed tables, keyed by name.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    tables: HashMap<String, TableSpec>,
+}
+
+#[derive(Deserialize)]
+struct SchemaFile {
+    tables: Vec<TableSpec>,
+}
+
+impl Registry {
+    /// Read and validate a schema file.
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read schema file {path}"))?;
+        Self::parse(&json).with_context(|| format!("Invalid schema file {path}"))
+    }
+
+    pub fn parse(json: &str) -> Result<Self> {
+        let file: SchemaFile = serde_json::from_str(json)?;
+        let mut tables = HashMap::new();
+        for spec in file.tables {
+            validate(&spec)?;
+            if tables.contains_key(&spec.name) {
+                bail!("table {:?} declared twice", spec.name);
+            }
+            tables.insert(spec.name.clone(), spec);
+        }
+        Ok(Self { tables })
+    }
+
+    pub fn get(&self, table_name: &str) -> Option<&TableSpec> {
+        self.tables.get(table_name)
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &TableSpec> {
+        self.tables.values()
+    }
+}
+
+fn validate(spec: &TableSpec) -> Result<()> {
+    if !is_identifier(&spec.name) {
+        bail!("invalid table name {:?}", spec.name);
+    }
+    if spec.name == "records" {
+        bail!("table name \"records\" is reserved");
+    }
+    if spec.columns.is_empty() {
+        bail!("table {:?} has no columns", spec.name);
+    }
+    for (i, c) in spec.columns.iter().enumerate() {
+        if !is_identifier(&c.name) {
+            bail!("invalid column name {:?} in table {:?}", c.name, spec.name);
+        }
+        if RESERVED_COLUMNS.contains(&c.name.as_str()) {
+            bail!("column {:?} in table {:?} is reserved", c.name, spec.name);
+        }
+        if spec.columns[..i].iter().any(|p| p.name == c.name) {
+            bail!("column {:?} declared twice in table {:?}", c.name, spec.name);
+        }
+    }
+    Ok(())
+}
+
+/// Lower-case SQL identifier that needs no escaping beyond quoting.
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && s.len() <= 63
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"{ "tables": [
+        { "name": "customer",
+          "columns": [
+            { "name": "email",   "type": "text", "nullable": false },
+            { "name": "age",     "type": "integer" },
+            { "name": "joined",  "type": "timestamp" },
+            { "name": "prefs",   "type": "json" } ] } ] }"#;
+
+    fn customer() -> TableSpec {
+        Registry::parse(SCHEMA).unwrap().get("customer").unwrap().clone()
+    }
+
+    #[test]
+    fn parses_spec() {
+        let spec = customer();
+        assert_eq!(spec.columns.len(), 4);
+        assert_eq!(spec.columns[0].column_type, ColumnType::Text);
+        assert!(!spec.columns[0].nullable);
+        assert!(spec.columns[1].nullable);
+        assert!(Registry::parse(SCHEMA).unwrap().get("records").is_none());
+    }
+
+    #[test]
+    fn generates_ddl() {
+        assert_eq!(
+            customer().create_table_sql(),
+            "CREATE TABLE IF NOT EXISTS \"customer\" (\n    \
+             id UUID PRIMARY KEY DEFAULT gen_random_uuid(),\n    \
+             \"email\" TEXT NOT NULL,\n    \
+             \"age\" BIGINT,\n    \
+             \"joined\" TIMESTAMPTZ,\n    \
+             \"prefs\" JSONB,\n    \
+             version BIGINT NOT NULL DEFAULT 1,\n    \
+             created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),\n    \
+             updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),\n    \
+             deleted_at TIMESTAMPTZ\n)"
+        );
+    }
+
+    #[test]
+    fn generates_dml() {
+        let spec = customer();
+        assert_eq!(
+            spec.insert_sql(),
+            "INSERT INTO \"customer\" (\"email\", \"age\", \"joined\", \"prefs\") VALUES ($1, $2, $3, $4) RETURNING id"
+        );
+        assert_eq!(
+            spec.update_sql(),
+            "UPDATE \"customer\" SET \"email\" = $3, \"age\" = $4, \"joined\" = $5, \"prefs\" = $6, \
+             version = version + 1, updated_at = NOW() \
+             WHERE id = $1 AND deleted_at IS NULL AND ($2::bigint IS NULL OR version = $2) RETURNING version"
+        );
+    }
+
+    #[test]
+    fn generates_upsert() {
+        assert_eq!(
+            customer().upsert_sql(),
+            "INSERT INTO \"customer\" (id, \"email\", \"age\", \"joined\", \"prefs\") VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (id) DO UPDATE SET \"email\" = EXCLUDED.\"email\", \"age\" = EXCLUDED.\"age\", \
+             \"joined\" = EXCLUDED.\"joined\", \"prefs\" = EXCLUDED.\"prefs\", \
+             version = \"customer\".version + 1, updated_at = NOW(), deleted_at = NULL \
+             RETURNING (xmax = 0) AS inserted"
+        );
+    }
+
+    #[test]
+    fn generates_multi_row_insert() {
+        assert_eq!(
+            customer().insert_many_sql(2),
+            "INSERT INTO \"customer\" (id, \"email\", \"age\", \"joined\", \"prefs\") \
+             VALUES ($1, $2, $3, $4, $5), ($6, $7, $8, $9, $10) RETURNING id"
+        );
+        assert_eq!(values_list(2, 2, &["", "::jsonb"]), "($1, $2::jsonb), ($3, $4::jsonb)");
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        for bad in [
+            r#"{"tables":[{"name":"Bad-Name","columns":[{"name":"a","type":"text"}]}]}"#,
+            r#"{"tables":[{"name":"records","columns":[{"name":"a","type":"text"}]}]}"#,
+            r#"{"tables":[{"name":"t","columns":[]}]}"#,
+            r#"{"tables":[{"name":"t","columns":[{"name":"id","type":"uuid"}]}]}"#,
+            r#"{"tables":[{"name":"t","columns":[{"name":"a","type":"text"},{"name":"a","type":"text"}]}]}"#,
+            r#"{"tables":[{"name":"t","columns":[{"name":"a","type":"money"}]}]}"#,
+        ] {
+            assert!(Registry::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn binds_typed_values() {
+        let values = customer()
+            .bind_values(r#"{"email":"a@b.c","age":41,"joined":"2024-01-01T00:00:00Z"}"#)
+            .unwrap();
+        assert_eq!(values[0], SqlValue::Text(Some("a@b.c".into())));
+        assert_eq!(values[1], SqlValue::Integer(Some(41)));
+        assert!(matches!(values[2], SqlValue::Timestamp(Some(_))));
+        assert_eq!(values[3], SqlValue::Json(None));
+    }
+
+    #[test]
+    fn rejects_bad_payloads() {
+        let spec = customer();
+        assert!(spec.bind_values(r#"{"age":41}"#).is_err()); // email required
+        assert!(spec.bind_values(r#"{"email":"x","age":"old"}"#).is_err());
+        assert!(spec.bind_values(r#"{"email":"x","nickname":"y"}"#).is_err());
+        assert!(spec.bind_values(r#"[1]"#).is_err());
+    }
+
+    #[test]
+    fn partial_binds_only_supplied_columns() {
+        let spec = customer();
+        let (columns, values) = spec.bind_partial(r#"{"prefs":{"theme":"dark"},"age":null}"#).unwrap();
+        assert_eq!(columns, ["age", "prefs"]);
+        assert_eq!(values[0], SqlValue::Integer(None));
+        assert_eq!(values[1], SqlValue::Json(Some(serde_json::json!({"theme": "dark"}))));
+        assert_eq!(
+            spec.update_partial_sql(&columns),
+            "UPDATE \"customer\" SET \"age\" = $3, \"prefs\" = $4, \
+             version = version + 1, updated_at = NOW() \
+             WHERE id = $1 AND deleted_at IS NULL AND ($2::bigint IS NULL OR version = $2) RETURNING version"
+        );
+
+        // A required column may be left out, but not nulled.
+        assert!(spec.bind_partial(r#"{"age":41}"#).is_ok());
+        assert!(spec.bind_partial(r#"{"email":null}"#).is_err());
+        assert!(spec.bind_partial(r#"{"nickname":"y"}"#).is_err());
+        assert!(spec.bind_partial(r#"[1]"#).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let proto_files = &[
         "../protos/postgres_service.proto",
@@ -5,6 +7,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "../protos/supervisor_service.proto",
     ];
     let include_dirs = &["../protos"];
+    // Served by gRPC reflection (see `src/reflection.rs`).
+    let descriptor_path = PathBuf::from(std::env::var("OUT_DIR")?).join("descriptor.bin");
 
     tonic_build::configure()
         .build_server(true)
@@ -12,6 +16,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Add serde derives to every generated message so they can be
         // serialised directly to JSON in HTTP responses.
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+        .file_descriptor_set_path(descriptor_path)
         .compile_protos(proto_files, include_dirs)?;
 
     // Re-run if any proto file changes.
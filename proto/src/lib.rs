@@ -3,6 +3,12 @@
 //! All client and server stubs are generated at build time from the
 //! `.proto` files in the workspace-level `protos/` directory.
 
+pub mod reflection;
+
+/// Encoded `FileDescriptorSet` of every compiled `.proto`, for
+/// [`reflection`].
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("descriptor");
+
 /// gRPC types and stubs for the PostgreSQL CRUD service.
 pub mod postgres_service {
     tonic::include_proto!("postgres_service");
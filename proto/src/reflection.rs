@@ -0,0 +1,75 @@
+//! gRPC server reflection, so `grpcurl` can list and call the services
+//! without their `.proto` files.
+//!
+//! Serves `grpc.reflection.v1.ServerReflection` over [`FILE_DESCRIPTOR_SET`]
+//! (every service in this crate).  On by default; set `GRPC_REFLECTION=false`
+//! to turn it off, e.g. in production.
+
+use tonic_reflection::server::{Error, ServerReflection, ServerReflectionServer};
+
+use crate::FILE_DESCRIPTOR_SET;
+
+/// Whether `GRPC_REFLECTION` leaves reflection on (anything but `0` /
+/// `false`, including unset).
+pub fn enabled_from_env() -> bool {
+    std::env::var("GRPC_REFLECTION").map_or(true, |v| !matches!(v.trim(), "0" | "false"))
+}
+
+/// The reflection service over all compiled protos.
+pub fn service() -> Result<ServerReflectionServer<impl ServerReflection>, Error> {
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build_v1()
+}
+
+/// [`service`], or `None` when [`enabled_from_env`] says it is off; meant
+/// for `Router::add_optional_service`.
+pub fn from_env() -> Result<Option<ServerReflectionServer<impl ServerReflection>>, Error> {
+    enabled_from_env().then(service).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::transport::{server::TcpIncoming, Channel, Server};
+    use tonic_reflection::pb::v1::{
+        server_reflection_client::ServerReflectionClient,
+        server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+        ServerReflectionRequest,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn lists_every_service() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(Server::builder().add_service(service().unwrap()).serve_with_incoming(incoming));
+
+        let channel = Channel::from_shared(format!("http://{addr}")).unwrap().connect().await.unwrap();
+        let mut client = ServerReflectionClient::new(channel);
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses = client
+            .server_reflection_info(tokio_stream::iter([request]))
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(MessageResponse::ListServicesResponse(list)) =
+            responses.message().await.unwrap().unwrap().message_response
+        else {
+            panic!("expected a list of services");
+        };
+
+        let names: Vec<_> = list.service.iter().map(|s| s.name.as_str()).collect();
+        for expected in [
+            "postgres_service.PostgresService",
+            "influxdb_service.InfluxDbService",
+            "supervisor_service.SupervisorService",
+        ] {
+            assert!(names.contains(&expected), "{expected} missing from {names:?}");
+        }
+    }
+}
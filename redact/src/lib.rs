@@ -0,0 +1,146 @@
+//! Masking of sensitive JSON keys before payloads are logged.
+//!
+//! The set of keys comes from `COORDINATOR_REDACT_KEYS` (comma-separated,
+//! matched case-insensitively at any nesting depth).  The coordinator masks
+//! request payloads at the handler boundary and the supervisor its ledger
+//! and ticker payloads.  Only the copy written to the log is masked; what is
+//! forwarded or stored is never modified.
+
+use std::collections::HashSet;
+
+use serde_json::Value;
+use tracing::debug;
+
+/// Replacement written in place of a redacted value.
+pub const MASK: &str = "***";
+
+/// Env var listing the keys to mask.
+pub const REDACT_KEYS_VAR: &str = "COORDINATOR_REDACT_KEYS";
+
+/// Masks configured JSON keys in payloads destined for the log.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    keys: HashSet<String>,
+}
+
+impl Redactor {
+    /// Build a redactor for the given key names.
+    pub fn new<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|k| k.as_ref().trim().to_ascii_lowercase())
+                .filter(|k| !k.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Build a redactor from `COORDINATOR_REDACT_KEYS`.  Unset means no redaction.
+    pub fn from_env() -> Self {
+        let raw = std::env::var(REDACT_KEYS_VAR).unwrap_or_default();
+        Self::new(raw.split(','))
+    }
+
+    /// Return a copy of `value` with every configured key masked.
+    pub fn redact(&self, value: &Value) -> Value {
+        if self.keys.is_empty() {
+            return value.clone();
+        }
+        match value {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| {
+                        if self.keys.contains(&k.to_ascii_lowercase()) {
+                            (k.clone(), Value::String(MASK.to_string()))
+                        } else {
+                            (k.clone(), self.redact(v))
+                        }
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.redact(v)).collect()),
+            other => other.clone(),
+        }
+    }
+
+    /// Log an inbound request payload at debug level, after redaction.
+    pub fn log_payload(&self, route: &str, payload: &Value) {
+        debug!(route, payload = %self.redact(payload), "request payload");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CaptureWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn masks_configured_keys_at_any_depth() {
+        let r = Redactor::new(["password", "Token"]);
+        let out = r.redact(&serde_json::json!({
+            "user": "alice",
+            "password": "hunter2",
+            "nested": {"token": "abc", "keep": 1},
+            "list": [{"PASSWORD": "x", "n": 2}]
+        }));
+        assert_eq!(
+            out,
+            serde_json::json!({
+                "user": "alice",
+                "password": MASK,
+                "nested": {"token": MASK, "keep": 1},
+                "list": [{"PASSWORD": MASK, "n": 2}]
+            })
+        );
+    }
+
+    #[test]
+    fn no_keys_leaves_payload_untouched() {
+        let payload = serde_json::json!({"password": "hunter2"});
+        assert_eq!(Redactor::new(Vec::<String>::new()).redact(&payload), payload);
+    }
+
+    #[test]
+    fn logged_event_is_redacted() {
+        let writer = CaptureWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .finish();
+
+        let r = Redactor::new(["api_key"]);
+        tracing::subscriber::with_default(subscriber, || {
+            r.log_payload(
+                "POST /data",
+                &serde_json::json!({"api_key": "s3cr3t", "table": "plants"}),
+            );
+        });
+
+        let logged = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(!logged.contains("s3cr3t"), "secret leaked: {logged}");
+        assert!(logged.contains(MASK));
+        assert!(logged.contains("plants"));
+    }
+}
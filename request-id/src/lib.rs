@@ -0,0 +1,164 @@
+//! `x-request-id` correlation shared by the services.
+//!
+//! The coordinator assigns each HTTP request an id (the client's
+//! `x-request-id` if it sent a usable one, a fresh UUID otherwise) and
+//! forwards it as gRPC metadata on every backend call.  Backends wrap their
+//! tonic server in [`RequestIdLayer`], which puts the id into each call's
+//! extensions and log span, generating one when the header is absent.
+
+use std::fmt;
+use std::task::{Context, Poll};
+
+use tonic::codegen::http;
+use tonic::metadata::MetadataValue;
+use tower::{Layer, Service};
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header (HTTP) and metadata key (gRPC) carrying the id.
+pub const HEADER: &str = "x-request-id";
+
+/// Longest client-supplied id accepted; longer ones are replaced.
+pub const MAX_LEN: usize = 128;
+
+/// Correlation id of one request, as seen by every service it touches.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// A fresh random id.
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    /// `value` as an id, if it is 1–[`MAX_LEN`] visible ASCII characters.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let usable = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value.bytes().all(|b| b.is_ascii_graphic());
+        usable.then(|| Self(value.to_string()))
+    }
+
+    /// The id in `headers`, or a fresh one when it is missing or unusable.
+    pub fn from_headers(headers: &http::HeaderMap) -> Self {
+        headers
+            .get(HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::parse)
+            .unwrap_or_else(Self::generate)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Send this id with an outgoing gRPC call.
+    pub fn attach<T>(&self, request: &mut tonic::Request<T>) {
+        // `parse`/`generate` only admit visible ASCII, which is always a
+        // valid metadata value.
+        if let Ok(value) = MetadataValue::try_from(self.as_str()) {
+            request.metadata_mut().insert(HEADER, value);
+        }
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Tower layer for a tonic server: reads (or generates) each call's
+/// request id, stores it in the request extensions, where handlers find
+/// it via `request.extensions().get::<RequestId>()`, and runs the call in
+/// a `grpc` span carrying it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+/// Service produced by [`RequestIdLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for RequestIdService<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        let id = RequestId::from_headers(req.headers());
+        let span = tracing::info_span!("grpc", request_id = %id, path = %req.uri().path());
+        req.extensions_mut().insert(id);
+        self.inner.call(req).instrument(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    /// A backend that answers with the id it found in its extensions.
+    async fn seen_by_backend(req: http::Request<()>) -> Option<RequestId> {
+        RequestIdLayer
+            .layer(service_fn(|req: http::Request<()>| async move {
+                Ok::<_, Infallible>(req.extensions().get::<RequestId>().cloned())
+            }))
+            .oneshot(req)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn provided_id_reaches_backend_extensions() {
+        let req = http::Request::builder()
+            .header(HEADER, "req-1234")
+            .body(())
+            .unwrap();
+        assert_eq!(seen_by_backend(req).await.unwrap().as_str(), "req-1234");
+    }
+
+    #[tokio::test]
+    async fn missing_or_unusable_id_is_generated() {
+        let too_long = "x".repeat(MAX_LEN + 1);
+        for header in [None, Some(""), Some("has space"), Some(too_long.as_str())] {
+            let mut req = http::Request::builder();
+            if let Some(value) = header {
+                req = req.header(HEADER, value);
+            }
+            let id = seen_by_backend(req.body(()).unwrap()).await.unwrap();
+            assert!(Uuid::parse_str(id.as_str()).is_ok(), "{header:?} -> {id}");
+        }
+    }
+
+    #[test]
+    fn attach_sets_grpc_metadata() {
+        let mut request = tonic::Request::new(());
+        RequestId::parse("abc-123").unwrap().attach(&mut request);
+        assert_eq!(request.metadata().get(HEADER).unwrap(), "abc-123");
+        // And a backend reading that metadata sees the same id.
+        let headers = request.into_parts().0.into_headers();
+        assert_eq!(RequestId::from_headers(&headers).as_str(), "abc-123");
+    }
+}
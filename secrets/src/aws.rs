@@ -0,0 +1,65 @@
+//! AWS Secrets Manager backend.
+//!
+//! Credentials and region come from the standard AWS chain (`AWS_REGION`,
+//! `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`, profiles, instance or task
+//! roles).  `secret_id` may be a secret name or ARN; only string secrets are
+//! supported.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_secretsmanager::error::DisplayErrorContext;
+use aws_sdk_secretsmanager::Client;
+use tokio::sync::OnceCell;
+
+use crate::provider::SecretsProvider;
+
+/// Client for AWS Secrets Manager.
+///
+/// The SDK client is built on first use, since loading the AWS config is
+/// async.
+#[derive(Default)]
+pub struct AwsProvider {
+    client: OnceCell<Client>,
+}
+
+impl AwsProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn client(&self) -> &Client {
+        self.client
+            .get_or_init(|| async {
+                Client::new(&aws_config::load_defaults(BehaviorVersion::latest()).await)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for AwsProvider {
+    fn name(&self) -> &'static str {
+        "AWS Secrets Manager"
+    }
+
+    async fn fetch(&self, secret_id: &str) -> Result<String> {
+        let resp = self
+            .client()
+            .await
+            .get_secret_value()
+            .secret_id(secret_id)
+            .send()
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "AWS Secrets Manager request failed: {}",
+                    DisplayErrorContext(e)
+                )
+            })?;
+
+        resp.secret_string()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("AWS secret '{secret_id}' has no string value"))
+    }
+}
@@ -0,0 +1,71 @@
+//! Bitwarden Secrets Manager backend.
+//!
+//! Authenticates with the machine-account access token in
+//! `BWS_ACCESS_TOKEN`; `BWS_API_URL` overrides the API base URL.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::provider::SecretsProvider;
+
+/// Client for Bitwarden Secrets Manager.
+pub struct BitwardenProvider {
+    /// BWS machine-account access token.
+    access_token: String,
+    /// Base URL for the Bitwarden Secrets Manager API.
+    api_url: String,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct BwsSecretResponse {
+    value: String,
+}
+
+impl BitwardenProvider {
+    pub fn new(access_token: String, api_url: String) -> Self {
+        Self {
+            access_token,
+            api_url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Configure from the environment; `None` when `BWS_ACCESS_TOKEN` is
+    /// absent.
+    pub fn from_env() -> Option<Self> {
+        let access_token = std::env::var("BWS_ACCESS_TOKEN").ok()?;
+        let api_url = std::env::var("BWS_API_URL")
+            .unwrap_or_else(|_| "https://api.bitwarden.com".to_string());
+        Some(Self::new(access_token, api_url))
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for BitwardenProvider {
+    fn name(&self) -> &'static str {
+        "Bitwarden"
+    }
+
+    async fn fetch(&self, secret_id: &str) -> Result<String> {
+        let url = format!("{}/secrets/{}", self.api_url, secret_id);
+        let resp = self
+            .http
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .context("HTTP request to Bitwarden Secrets Manager failed")?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!("Bitwarden API returned status {}", resp.status()));
+        }
+
+        let body: BwsSecretResponse = resp
+            .json()
+            .await
+            .context("Failed to parse Bitwarden response")?;
+        Ok(body.value)
+    }
+}
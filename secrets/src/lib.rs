@@ -0,0 +1,375 @@
+//! Secrets client shared by the services.
+//!
+//! Fetches secrets from the backend chosen by `SECRETS_PROVIDER`:
+//!
+//! | Value                 | Backend                                          |
+//! |-----------------------|--------------------------------------------------|
+//! | `bitwarden` (default) | Bitwarden Secrets Manager, if `BWS_ACCESS_TOKEN` is set |
+//! | `aws`                 | AWS Secrets Manager                              |
+//! | `env`                 | none                                             |
+//!
+//! Every lookup falls back to a plain environment variable when the backend
+//! is absent or fails (useful for local development / CI).
+//!
+//! Values fetched from the backend are cached process-wide for
+//! `BWS_CACHE_TTL_SECONDS` (default 300; 0 disables caching).  The env
+//! fallback is read fresh on every lookup, as before.
+
+mod aws;
+mod bitwarden;
+mod provider;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::future::join_all;
+
+pub use aws::AwsProvider;
+pub use bitwarden::BitwardenProvider;
+pub use provider::{ProviderKind, SecretsProvider};
+
+/// Cache TTL used when `BWS_CACHE_TTL_SECONDS` is unset or invalid.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Resolves secrets from the configured backend with an env fallback.
+pub struct SecretsClient {
+    /// `None` when only env vars are consulted.
+    provider: Option<Box<dyn SecretsProvider>>,
+    cache: Arc<SecretCache>,
+}
+
+impl SecretsClient {
+    /// Create a new [`SecretsClient`] for the backend named by
+    /// `SECRETS_PROVIDER`.
+    ///
+    /// If the backend is not configured (e.g. no `BWS_ACCESS_TOKEN`) the
+    /// client silently falls back to plain environment-variable lookup so
+    /// that local development works without a secrets manager.  All clients
+    /// share one process-wide cache.
+    pub fn new() -> Self {
+        let kind = ProviderKind::from_env().unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Invalid SECRETS_PROVIDER, using env vars only");
+            ProviderKind::Env
+        });
+        Self::with_cache(kind.provider(), global_cache())
+    }
+
+    /// Create a client for an explicit backend, with its own cache.
+    pub fn with_provider(provider: Option<Box<dyn SecretsProvider>>) -> Self {
+        Self::with_cache(provider, Arc::new(SecretCache::new(cache_ttl_from_env())))
+    }
+
+    fn with_cache(provider: Option<Box<dyn SecretsProvider>>, cache: Arc<SecretCache>) -> Self {
+        Self { provider, cache }
+    }
+
+    /// Retrieve a secret value.
+    ///
+    /// Resolution order:
+    /// 1. The cache, for a backend value fetched within the TTL
+    /// 2. The configured backend, if any
+    /// 3. Plain environment variable named `env_fallback`
+    pub async fn get_secret(&self, secret_id: &str, env_fallback: &str) -> Result<String> {
+        if let Some(provider) = &self.provider {
+            if let Some(value) = self.cache.get(secret_id) {
+                return Ok(value);
+            }
+            match provider.fetch(secret_id).await {
+                Ok(value) => {
+                    self.cache.insert(secret_id, &value);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        secret_id,
+                        provider = provider.name(),
+                        error = %e,
+                        "Failed to fetch secret, falling back to env var"
+                    );
+                }
+            }
+        }
+
+        let source = self
+            .provider
+            .as_ref()
+            .map_or("the secrets backend", |p| p.name());
+        std::env::var(env_fallback).with_context(|| {
+            format!("Secret '{secret_id}' not found in {source} and env var '{env_fallback}' is not set")
+        })
+    }
+
+    /// Retrieve several secrets at once, each given as
+    /// `(secret_id, env_fallback)`.
+    ///
+    /// Backend lookups run concurrently and each falls back to its env var
+    /// independently; results are returned in input order, so one missing
+    /// secret does not prevent the others from resolving.
+    pub async fn get_secrets(&self, pairs: &[(&str, &str)]) -> Vec<Result<String>> {
+        join_all(
+            pairs
+                .iter()
+                .map(|(secret_id, env_fallback)| self.get_secret(secret_id, env_fallback)),
+        )
+        .await
+    }
+}
+
+impl Default for SecretsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convenience wrapper: build a [`SecretsClient`] and fetch a secret.
+pub async fn get_secret(secret_id: &str, env_fallback: &str) -> Result<String> {
+    SecretsClient::new()
+        .get_secret(secret_id, env_fallback)
+        .await
+}
+
+/// Convenience wrapper: build a [`SecretsClient`] and fetch several secrets.
+pub async fn get_secrets(pairs: &[(&str, &str)]) -> Vec<Result<String>> {
+    SecretsClient::new().get_secrets(pairs).await
+}
+
+// ------------------------------------------------------------------ //
+//  Cache                                                              //
+// ------------------------------------------------------------------ //
+
+/// Backend values by secret id, each valid for `ttl` after it was fetched.
+struct SecretCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl SecretCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, secret_id: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        let (value, fetched_at) = entries.get(secret_id)?;
+        (fetched_at.elapsed() < self.ttl).then(|| value.clone())
+    }
+
+    fn insert(&self, secret_id: &str, value: &str) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(secret_id.to_string(), (value.to_string(), Instant::now()));
+    }
+}
+
+fn global_cache() -> Arc<SecretCache> {
+    static CACHE: OnceLock<Arc<SecretCache>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| Arc::new(SecretCache::new(cache_ttl_from_env())))
+        .clone()
+}
+
+fn cache_ttl_from_env() -> Duration {
+    std::env::var("BWS_CACHE_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CACHE_TTL)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// A stand-in for the Bitwarden API serving `GET /secrets/{id}` from
+    /// `secrets` (404 for unknown ids).  Returns its base URL and a request
+    /// counter.
+    async fn mock_bitwarden(
+        secrets: &'static [(&'static str, &'static str)],
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut buf = [0u8; 4096];
+                let n = sock.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let id = request
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|path| path.strip_prefix("/secrets/"))
+                    .unwrap_or_default();
+                let (status, body) = match secrets.iter().find(|(k, _)| *k == id) {
+                    Some((_, value)) => (200, format!(r#"{{"value":"{value}"}}"#)),
+                    None => (404, String::new()),
+                };
+                let resp = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = sock.write_all(resp.as_bytes()).await;
+            }
+        });
+        (url, hits)
+    }
+
+    fn client(api_url: String, ttl: Duration) -> SecretsClient {
+        let provider = BitwardenProvider::new("token".into(), api_url);
+        SecretsClient::with_cache(Some(Box::new(provider)), Arc::new(SecretCache::new(ttl)))
+    }
+
+    #[tokio::test]
+    async fn bitwarden_value_is_returned() {
+        let (url, hits) = mock_bitwarden(&[("db-url", "from-bitwarden")]).await;
+        std::env::set_var("SECRETS_TEST_SUCCESS", "from-env");
+        let value = client(url, DEFAULT_CACHE_TTL)
+            .get_secret("db-url", "SECRETS_TEST_SUCCESS")
+            .await
+            .unwrap();
+        assert_eq!(value, "from-bitwarden");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn bitwarden_failure_falls_back_to_env() {
+        let (url, _) = mock_bitwarden(&[]).await;
+        std::env::set_var("SECRETS_TEST_FALLBACK", "from-env");
+        let value = client(url, DEFAULT_CACHE_TTL)
+            .get_secret("db-url", "SECRETS_TEST_FALLBACK")
+            .await
+            .unwrap();
+        assert_eq!(value, "from-env");
+    }
+
+    #[tokio::test]
+    async fn missing_everywhere_is_an_error() {
+        let (url, _) = mock_bitwarden(&[]).await;
+        let err = client(url, DEFAULT_CACHE_TTL)
+            .get_secret("db-url", "SECRETS_TEST_UNSET")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("SECRETS_TEST_UNSET"), "{err}");
+
+        let env_only = SecretsClient::with_provider(None);
+        assert!(env_only
+            .get_secret("db-url", "SECRETS_TEST_UNSET")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn env_provider_reads_env_vars_only() {
+        std::env::set_var("SECRETS_TEST_ENV_ONLY", "from-env");
+        let client = SecretsClient::with_provider(ProviderKind::Env.provider());
+        assert_eq!(
+            client
+                .get_secret("db-url", "SECRETS_TEST_ENV_ONLY")
+                .await
+                .unwrap(),
+            "from-env"
+        );
+    }
+
+    #[tokio::test]
+    async fn second_lookup_within_ttl_is_served_from_cache() {
+        let (url, hits) = mock_bitwarden(&[("db-url", "s3cret"), ("other", "x")]).await;
+        let client = client(url, Duration::from_secs(300));
+
+        assert_eq!(
+            client
+                .get_secret("db-url", "UNSET_SECRET_ENV")
+                .await
+                .unwrap(),
+            "s3cret"
+        );
+        assert_eq!(
+            client
+                .get_secret("db-url", "UNSET_SECRET_ENV")
+                .await
+                .unwrap(),
+            "s3cret"
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Different ids are cached separately.
+        client
+            .get_secret("other", "UNSET_SECRET_ENV")
+            .await
+            .unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_disables_cache() {
+        let (url, hits) = mock_bitwarden(&[("db-url", "s3cret"), ("other", "x")]).await;
+        let client = client(url, Duration::ZERO);
+
+        client
+            .get_secret("db-url", "UNSET_SECRET_ENV")
+            .await
+            .unwrap();
+        client
+            .get_secret("db-url", "UNSET_SECRET_ENV")
+            .await
+            .unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_is_not_cached() {
+        let (url, hits) = mock_bitwarden(&[]).await;
+        let client = client(url, Duration::from_secs(300));
+
+        assert!(client
+            .get_secret("db-url", "UNSET_SECRET_ENV")
+            .await
+            .is_err());
+        assert!(client
+            .get_secret("db-url", "UNSET_SECRET_ENV")
+            .await
+            .is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn batch_resolves_each_item_independently_in_order() {
+        let (url, hits) =
+            mock_bitwarden(&[("pg-addr", "http://pg"), ("sup-addr", "http://sup")]).await;
+        std::env::set_var("SECRETS_TEST_BATCH_INFLUX", "http://influx-from-env");
+        std::env::set_var("SECRETS_TEST_BATCH_SUP", "http://sup-from-env");
+
+        let results = client(url, DEFAULT_CACHE_TTL)
+            .get_secrets(&[
+                ("pg-addr", "SECRETS_TEST_BATCH_UNSET"),
+                ("influx-addr", "SECRETS_TEST_BATCH_INFLUX"),
+                ("missing", "SECRETS_TEST_BATCH_UNSET"),
+                ("sup-addr", "SECRETS_TEST_BATCH_SUP"),
+            ])
+            .await;
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), "http://pg");
+        assert_eq!(results[1].as_ref().unwrap(), "http://influx-from-env");
+        assert!(results[2].is_err());
+        assert_eq!(results[3].as_ref().unwrap(), "http://sup");
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+}
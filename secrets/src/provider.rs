@@ -0,0 +1,85 @@
+//! Secret backends and their selection via `SECRETS_PROVIDER`.
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+
+use crate::aws::AwsProvider;
+use crate::bitwarden::BitwardenProvider;
+
+/// A remote store secrets can be fetched from by id.
+#[async_trait]
+pub trait SecretsProvider: Send + Sync {
+    /// Backend name used in log and error messages.
+    fn name(&self) -> &'static str;
+
+    /// Fetch the current value of `secret_id`.
+    async fn fetch(&self, secret_id: &str) -> Result<String>;
+}
+
+/// Which backend to consult before the env fallback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    /// Bitwarden Secrets Manager; the default.
+    Bitwarden,
+    /// AWS Secrets Manager.
+    Aws,
+    /// Environment variables only.
+    Env,
+}
+
+impl ProviderKind {
+    /// Parse a `SECRETS_PROVIDER` value; empty means the default.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "bitwarden" => Ok(Self::Bitwarden),
+            "aws" => Ok(Self::Aws),
+            "env" => Ok(Self::Env),
+            other => bail!("unknown SECRETS_PROVIDER '{other}' (expected bitwarden, aws or env)"),
+        }
+    }
+
+    /// Read `SECRETS_PROVIDER`.
+    pub fn from_env() -> Result<Self> {
+        Self::parse(&std::env::var("SECRETS_PROVIDER").unwrap_or_default())
+    }
+
+    /// Build the backend for this kind from its environment configuration.
+    ///
+    /// `None` means secrets come from env vars only: either `Env` was chosen
+    /// or Bitwarden was chosen without a `BWS_ACCESS_TOKEN`.
+    pub fn provider(self) -> Option<Box<dyn SecretsProvider>> {
+        match self {
+            Self::Bitwarden => {
+                BitwardenProvider::from_env().map(|p| Box::new(p) as Box<dyn SecretsProvider>)
+            }
+            Self::Aws => Some(Box::new(AwsProvider::new())),
+            Self::Env => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_provider_names() {
+        assert_eq!(ProviderKind::parse("").unwrap(), ProviderKind::Bitwarden);
+        assert_eq!(
+            ProviderKind::parse("bitwarden").unwrap(),
+            ProviderKind::Bitwarden
+        );
+        assert_eq!(ProviderKind::parse(" AWS ").unwrap(), ProviderKind::Aws);
+        assert_eq!(ProviderKind::parse("env").unwrap(), ProviderKind::Env);
+        assert!(ProviderKind::parse("vault").is_err());
+    }
+
+    #[test]
+    fn builds_the_selected_backend() {
+        assert!(ProviderKind::Env.provider().is_none());
+        assert_eq!(
+            ProviderKind::Aws.provider().unwrap().name(),
+            "AWS Secrets Manager"
+        );
+    }
+}